@@ -0,0 +1,67 @@
+//! the per request hot path: uri, formalized parameters and signature payload, and the
+//! buffers of the client which `FxdxClient` encodes every request into
+//!
+//! `cargo bench --bench encoding`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fxdx_rs::encoding::{self, Encoded};
+use fxdx_rs::request::{NewOrder, Page, PrivPub, Request, Side};
+use fxdx_rs::wire::WireProfile;
+use std::hint::black_box;
+
+fn encoding(c: &mut Criterion) {
+    let order = Request::PendingOrder(
+        NewOrder::builder()
+            .symbol("BTC-USDT")
+            .side(Side::Bid)
+            .price("30000.125".parse().unwrap())
+            .amount("0.5".parse().unwrap())
+            .build(),
+    );
+    let page = Request::OrderByPage {
+        symbol: String::from("BTC-USDT"),
+        page: Page::new(3, 50).unwrap(),
+        pending: true,
+        filter: Default::default(),
+    };
+
+    for (name, req) in [("order", &order), ("order page", &page)] {
+        c.bench_function(&format!("{} allocating", name), |b| {
+            b.iter(|| {
+                let uri = req.uri::<PrivPub>();
+                let formalized = req.formalize().unwrap();
+                black_box(encoding::signature_payload(
+                    "1650000000",
+                    &uri,
+                    formalized.as_deref(),
+                ))
+            })
+        });
+
+        let mut payload = String::new();
+        c.bench_function(&format!("{} reused buffer", name), |b| {
+            b.iter(|| {
+                payload.clear();
+                payload.push_str("1650000000,");
+                req.write_uri::<PrivPub>(&mut payload);
+                let len = payload.len();
+                payload.push(',');
+                if !req.write_formalized(&mut payload).unwrap() {
+                    payload.truncate(len);
+                }
+                black_box(&payload);
+            })
+        });
+
+        let (wire, mut encoded) = (WireProfile::default(), Encoded::default());
+        c.bench_function(&format!("{} client buffers", name), |b| {
+            b.iter(|| {
+                req.encode_with::<PrivPub>(&wire, &mut encoded).unwrap();
+                black_box(&encoded);
+            })
+        });
+    }
+}
+
+criterion_group!(benches, encoding);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! encoding an order into the buffers of the client and signing it, what an order placement
+//! costs before it is sent, with both halves shown on their own
+//!
+//! `cargo bench --bench signing`; the one microsecond budget of the whole is held by
+//! `cargo test --release budget`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fxdx_rs::encoding::Encoded;
+use fxdx_rs::request::{NewOrder, PrivPub, Request, Side};
+use fxdx_rs::signing::{SignatureEncoding, Signer};
+use fxdx_rs::wire::WireProfile;
+use secrecy::SecretString;
+use std::hint::black_box;
+
+fn signing(c: &mut Criterion) {
+    let order = Request::PendingOrder(
+        NewOrder::builder()
+            .symbol("BTC-USDT")
+            .side(Side::Bid)
+            .price("30000.125".parse().unwrap())
+            .amount("0.5".parse().unwrap())
+            .build(),
+    );
+    let secret = "a-maker-secret-of-the-usual-length-0123456789";
+    let signer = Signer::new(SecretString::from(secret), SignatureEncoding::Hex);
+    let wire = WireProfile::default();
+    let mut buffers = Encoded::default();
+
+    c.bench_function("order encoded and signed", |b| {
+        b.iter(|| {
+            order.encode_with::<PrivPub>(&wire, &mut buffers).unwrap();
+            black_box(signer.sign(&buffers, "1650000000", "0xabc").unwrap())
+        })
+    });
+
+    c.bench_function("order encoded", |b| {
+        b.iter(|| {
+            order.encode_with::<PrivPub>(&wire, &mut buffers).unwrap();
+            black_box(&buffers);
+        })
+    });
+
+    let encoded = order.encode::<PrivPub>().unwrap();
+    c.bench_function("order signed", |b| {
+        b.iter(|| black_box(signer.sign(&encoded, "1650000000", "0xabc").unwrap()))
+    });
+
+    c.bench_function("order signed, key per call", |b| {
+        b.iter(|| {
+            let signer = Signer::new(SecretString::from(secret), SignatureEncoding::Hex);
+            black_box(signer.sign(&encoded, "1650000000", "0xabc").unwrap())
+        })
+    });
+}
+
+criterion_group!(benches, signing);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! decodes arbitrary bytes as every response of the api, a panic is a finding
+//!
+//! `cargo +nightly fuzz run responses fixtures/responses` from the crate root, seeded with
+//! the recorded responses
+
+#![no_main]
+
+use fxdx_rs::response::*;
+use libfuzzer_sys::fuzz_target;
+
+fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) {
+    if let Ok(resp) = serde_json::from_slice::<ApiResponse<T>>(body) {
+        let _ = resp.into_result();
+    }
+}
+
+fuzz_target!(|body: &[u8]| {
+    decode::<SessionToken>(body);
+    decode::<Vec<Option<String>>>(body);
+    decode::<BatchCancelData>(body);
+    decode::<Vec<Trade>>(body);
+    decode::<Vec<PublicTrade>>(body);
+    decode::<DepositAddress>(body);
+    decode::<Vec<Transfer>>(body);
+    decode::<QueryOrder>(body);
+    decode::<Vec<QueryOrder>>(body);
+    decode::<Balance>(body);
+    decode::<AccountInfo>(body);
+    decode::<Depth>(body);
+    decode::<Vec<Kline>>(body);
+    decode::<Vec<Symbol>>(body);
+});
@@ -0,0 +1,156 @@
+use crate::response::Symbol;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// an asset of the exchange with the identifiers other venues and data vendors know it by
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    /// numeric id used by `Trade` and `Symbol`
+    pub id: i32,
+    /// name as listed by the exchange
+    pub exchange_code: String,
+    /// upper case code shared across venues, e.g. `BTC`
+    pub code: String,
+    /// ids keyed by source, e.g. `coingecko` => `bitcoin`
+    #[serde(default)]
+    pub external_ids: BTreeMap<String, String>,
+}
+
+/// a venue independent pair of normalized asset codes
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NormalizedSymbol {
+    pub base: String,
+    pub quote: String,
+}
+
+impl std::fmt::Display for NormalizedSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
+
+/// maps exchange asset ids and symbol strings to normalized identifiers and back
+#[derive(Debug, Clone, Default)]
+pub struct AssetRegistry {
+    aliases: HashMap<String, String>,
+    assets: BTreeMap<i32, Asset>,
+    symbols: BTreeMap<String, NormalizedSymbol>,
+}
+
+impl AssetRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// normalize `exchange_code` to `code` instead of its upper cased name, e.g. `XBT` to `BTC`;
+    /// only affects assets loaded afterwards
+    pub fn alias(mut self, exchange_code: &str, code: &str) -> Self {
+        self.aliases
+            .insert(exchange_code.to_uppercase(), code.to_uppercase());
+        self
+    }
+
+    fn normalize(&self, exchange_code: &str) -> String {
+        let upper = exchange_code.to_uppercase();
+        self.aliases.get(&upper).cloned().unwrap_or(upper)
+    }
+
+    /// register the assets and symbols listed by the exchange, keeping known external ids
+    pub fn load_symbols(&mut self, symbols: &[Symbol]) {
+        for symbol in symbols {
+            for (id, name) in [
+                (symbol.base, &symbol.base_name),
+                (symbol.quote, &symbol.quote_name),
+            ] {
+                let code = self.normalize(name);
+                let asset = self.assets.entry(id).or_insert_with(|| Asset {
+                    id,
+                    exchange_code: name.clone(),
+                    code: code.clone(),
+                    external_ids: BTreeMap::new(),
+                });
+                asset.exchange_code = name.clone();
+                asset.code = code;
+            }
+            self.symbols.insert(
+                symbol.pair(),
+                NormalizedSymbol {
+                    base: self.normalize(&symbol.base_name),
+                    quote: self.normalize(&symbol.quote_name),
+                },
+            );
+        }
+    }
+
+    /// attach an external id, e.g. `external_id("BTC", "coingecko", "bitcoin")`, false for an unknown code
+    pub fn external_id(&mut self, code: &str, source: &str, id: &str) -> bool {
+        let code = code.to_uppercase();
+        match self.assets.values_mut().find(|a| a.code == code) {
+            Some(asset) => {
+                asset
+                    .external_ids
+                    .insert(source.to_string(), id.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn asset(&self, id: i32) -> Option<&Asset> {
+        self.assets.get(&id)
+    }
+
+    pub fn by_code(&self, code: &str) -> Option<&Asset> {
+        let code = code.to_uppercase();
+        self.assets.values().find(|a| a.code == code)
+    }
+
+    pub fn by_external_id(&self, source: &str, id: &str) -> Option<&Asset> {
+        self.assets
+            .values()
+            .find(|a| a.external_ids.get(source).map(String::as_str) == Some(id))
+    }
+
+    pub fn assets(&self) -> impl Iterator<Item = &Asset> {
+        self.assets.values()
+    }
+
+    pub fn normalize_symbol(&self, exchange_symbol: &str) -> Option<&NormalizedSymbol> {
+        self.symbols.get(exchange_symbol)
+    }
+
+    pub fn exchange_symbol(&self, symbol: &NormalizedSymbol) -> Option<&str> {
+        self.symbols
+            .iter()
+            .find(|(_, s)| *s == symbol)
+            .map(|(pair, _)| pair.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let symbol = serde_json::from_str::<Symbol>(
+            r#"{"base":1,"quote":0,"base_name":"xbt","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0.001","make_fee":"0.001","min_amount":"0.0001",
+            "min_vol":"1","enable_marker_order":true}"#,
+        )
+        .unwrap();
+        let mut registry = AssetRegistry::new().alias("XBT", "BTC");
+        registry.load_symbols(&[symbol]);
+        assert!(registry.external_id("btc", "coingecko", "bitcoin"));
+        assert!(!registry.external_id("ETH", "coingecko", "ethereum"));
+
+        assert_eq!(registry.asset(1).unwrap().code, "BTC");
+        assert_eq!(
+            registry.by_external_id("coingecko", "bitcoin").unwrap().id,
+            1
+        );
+        let normalized = registry.normalize_symbol("xbt-USDT").unwrap().clone();
+        assert_eq!(normalized.to_string(), "BTC/USDT");
+        assert_eq!(registry.exchange_symbol(&normalized), Some("xbt-USDT"));
+    }
+}
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// how much of a request `AuditRecord::summary` keeps
+pub const MAX_SUMMARY: usize = 256;
+
+/// one signed request for compliance review, without the secret parts: the signature
+/// is only kept as a hash and no response body is recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// unix seconds the request was signed at
+    pub timestamp: i64,
+    pub endpoint: String,
+    pub method: String,
+    pub uri: String,
+    /// the signed query or payload, cut to `MAX_SUMMARY` bytes
+    pub summary: String,
+    /// hex SHA-256 of the signature header
+    pub signature_hash: String,
+    /// `None` when no response arrived
+    pub status: Option<u16>,
+}
+
+impl AuditRecord {
+    pub fn summarize(formalized: Option<&str>, payload: Option<&str>) -> String {
+        let mut summary = payload.or(formalized).unwrap_or_default().to_string();
+        if summary.len() > MAX_SUMMARY {
+            let mut end = MAX_SUMMARY;
+            while !summary.is_char_boundary(end) {
+                end -= 1;
+            }
+            summary.truncate(end);
+            summary.push_str("...");
+        }
+        summary
+    }
+
+    pub fn hash_signature(signature: &str) -> String {
+        hex::encode(openssl::sha::sha256(signature.as_bytes()))
+    }
+}
+
+/// where the audit lines go, every line is one JSON encoded `AuditRecord`
+pub trait AuditSink: Send + Sync {
+    fn write_line(&self, line: &str) -> std::io::Result<()>;
+}
+
+impl<S: AuditSink + ?Sized> AuditSink for std::sync::Arc<S> {
+    fn write_line(&self, line: &str) -> std::io::Result<()> {
+        (**self).write_line(line)
+    }
+}
+
+/// appends to a file, never truncating it
+#[derive(Debug)]
+pub struct FileSink {
+    file: Mutex<std::fs::File>,
+}
+
+impl FileSink {
+    pub fn open<T: AsRef<Path>>(path: T) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(FileSink {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for FileSink {
+    fn write_line(&self, line: &str) -> std::io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // one write per line, so concurrent appenders never interleave
+        file.write_all(format!("{}\n", line).as_bytes())
+    }
+}
+
+/// any writer, e.g. stdout or a pipe to a log shipper
+#[derive(Debug)]
+pub struct WriterSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        WriterSink {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send> AuditSink for WriterSink<W> {
+    fn write_line(&self, line: &str) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(writer, "{}", line)?;
+        writer.flush()
+    }
+}
+
+/// the local syslog daemon, facility `user` and severity `info`
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct SyslogSink {
+    socket: std::os::unix::net::UnixDatagram,
+    tag: String,
+}
+
+#[cfg(unix)]
+impl SyslogSink {
+    /// `/dev/log`, where most daemons listen
+    pub fn connect(tag: &str) -> std::io::Result<Self> {
+        Self::connect_to("/dev/log", tag)
+    }
+
+    pub fn connect_to<T: AsRef<Path>>(path: T, tag: &str) -> std::io::Result<Self> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(SyslogSink {
+            socket,
+            tag: tag.to_string(),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl AuditSink for SyslogSink {
+    fn write_line(&self, line: &str) -> std::io::Result<()> {
+        // <PRI> is facility * 8 + severity, user is 1 and info is 6
+        let message = format!("<14>{}[{}]: {}", self.tag, std::process::id(), line);
+        self.socket.send(message.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// the append-only JSON lines audit trail of `FxdxBuilder::audit`
+pub struct AuditLog {
+    sink: Box<dyn AuditSink>,
+}
+
+impl AuditLog {
+    pub fn new<S: AuditSink + 'static>(sink: S) -> Self {
+        AuditLog {
+            sink: Box::new(sink),
+        }
+    }
+
+    pub fn record(&self, record: &AuditRecord) -> anyhow::Result<()> {
+        self.sink.write_line(&serde_json::to_string(record)?)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_are_json_lines() {
+        let summary = AuditRecord::summarize(Some("symbol=BTC-USDT"), None);
+        let record = AuditRecord {
+            timestamp: 1,
+            endpoint: String::from("https://fxdx"),
+            method: String::from("GET"),
+            uri: String::from("/maker/order"),
+            summary,
+            signature_hash: AuditRecord::hash_signature("dead"),
+            status: Some(200),
+        };
+        let sink = std::sync::Arc::new(WriterSink::new(vec![]));
+        let log = AuditLog::new(sink.clone());
+        log.record(&record).unwrap();
+        log.record(&record).unwrap();
+        drop(log);
+
+        let written = std::sync::Arc::into_inner(sink).unwrap().into_inner();
+        let lines: Vec<AuditRecord> = String::from_utf8(written)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, vec![record.clone(), record]);
+        assert_eq!(lines[0].summary, "symbol=BTC-USDT");
+        assert_eq!(lines[0].signature_hash.len(), 64);
+        assert!(AuditRecord::summarize(None, Some(&"x".repeat(300))).ends_with("..."));
+    }
+}
@@ -0,0 +1,647 @@
+//! a simulated venue fed with historical klines and depth, behind the same
+//! `exchange::ExchangeClient` as `FxdxClient` so a strategy runs unchanged in a backtest
+//!
+//! the clock only moves with the data: `on_depth`, `on_book`, `on_kline` and `advance` take
+//! unix milliseconds, fills are stamped in unix seconds like the exchange does
+
+use crate::exchange::{
+    convert, AssetBalance, Book, Candle, ExchangeClient, ExchangeFuture, Market, Order,
+    OrderRequest,
+};
+use crate::orderbook::OrderBook;
+use crate::paper;
+use crate::request::{NewOrder, OrderKind, OrderStatus, Scale, Side};
+use crate::response::{Balance, Depth, Kline, PriceLevel, QueryOrder, Symbol, Trade};
+use crate::symbols::SymbolRegistry;
+use crate::Error;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct BacktestOptions {
+    /// how long an order or a cancel takes to reach the matching engine, the book may move
+    /// in the meantime
+    pub latency: Duration,
+    /// the share of a candle's volume a resting order may fill when only klines are fed,
+    /// and the size of the one level book made from the close when no depth is fed
+    pub kline_participation: BigDecimal,
+}
+
+impl Default for BacktestOptions {
+    fn default() -> Self {
+        BacktestOptions {
+            latency: Duration::from_millis(50),
+            kline_participation: BigDecimal::new(1.into(), 1),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SimOrder {
+    order: QueryOrder,
+    kind: OrderKind,
+    /// reached the matching engine, until then it can neither fill nor be listed as open
+    live: bool,
+}
+
+impl SimOrder {
+    fn is_open(&self) -> bool {
+        matches!(
+            self.order.status,
+            OrderStatus::Undeal | OrderStatus::PartialDealed
+        )
+    }
+
+    fn remaining(&self) -> BigDecimal {
+        &self.order.amount - &self.order.filled_base
+    }
+}
+
+#[derive(Debug)]
+enum Action {
+    Place(u64),
+    Cancel(u64),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    now: i64,
+    next_id: u64,
+    /// totals of every asset, frozen amounts are derived from the open orders
+    assets: BTreeMap<String, BigDecimal>,
+    orders: BTreeMap<u64, SimOrder>,
+    /// due time and action, in the order they were sent
+    in_flight: VecDeque<(i64, Action)>,
+    books: HashMap<String, Depth>,
+    /// symbols with real depth, the others get a book made from their klines
+    depth_fed: HashSet<String>,
+    klines: HashMap<(String, Scale), Vec<Kline>>,
+}
+
+/// matches orders against the fed books with the fees of the symbol: taker fees for what
+/// an order takes on arrival, maker fees for what fills while it rests
+///
+/// a resting order fills at its own price once the book crosses it, limited by the crossing
+/// amounts, or by `kline_participation` of the volume of a candle trading through it
+pub struct SimulatedExchange {
+    options: BacktestOptions,
+    symbols: Vec<Symbol>,
+    registry: SymbolRegistry,
+    state: Mutex<State>,
+}
+
+impl SimulatedExchange {
+    /// a venue listing `symbols` with the starting `balances` of every asset by name
+    pub fn new(
+        symbols: Vec<Symbol>,
+        balances: impl IntoIterator<Item = (String, BigDecimal)>,
+        options: BacktestOptions,
+    ) -> Self {
+        let registry = SymbolRegistry::new(Duration::MAX);
+        registry.load(symbols.clone());
+        SimulatedExchange {
+            options,
+            symbols,
+            registry,
+            state: Mutex::new(State {
+                assets: balances.into_iter().collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// the current time of the simulation in unix milliseconds
+    pub fn now(&self) -> i64 {
+        self.state().now
+    }
+
+    /// move the clock to `timestamp` and deliver the orders and cancels due by then
+    pub fn advance(&self, timestamp: i64) {
+        self.advance_state(&mut self.state(), timestamp);
+    }
+
+    /// the depth of `symbol` at `timestamp`, resting orders it crosses fill as maker
+    pub fn on_depth(&self, timestamp: i64, symbol: &str, depth: Depth) {
+        let mut state = self.state();
+        self.advance_state(&mut state, timestamp);
+        for id in resting(&state, symbol) {
+            let sim = &state.orders[&id];
+            let limit = sim.order.price.clone();
+            let amount = paper::crossing(&sim.order, Some(&limit), &depth)
+                .into_iter()
+                .fold(BigDecimal::zero(), |acc, (_, amount)| acc + amount);
+            self.fill(&mut state, id, limit, amount, false);
+        }
+        state.books.insert(symbol.to_string(), depth);
+        state.depth_fed.insert(symbol.to_string());
+    }
+
+    /// a book rebuilt by `booklog::BookReplayer`, stale books are skipped
+    pub fn on_book(&self, timestamp: i64, book: &OrderBook) {
+        if book.stale {
+            self.advance(timestamp);
+            return;
+        }
+        let depth = Depth {
+            depth: 0,
+            seq: book.seq(),
+            checksum: None,
+            bids: book.bids.clone(),
+            asks: book.asks.clone(),
+        };
+        self.on_depth(timestamp, &book.symbol, depth);
+    }
+
+    /// a finished candle of `symbol`, the clock moves to its close
+    ///
+    /// resting orders its range trades through fill as maker, and without fed depth the
+    /// book becomes a single level at the close on each side
+    pub fn on_kline(&self, symbol: &str, scale: Scale, kline: Kline) {
+        let close = kline
+            .close_time
+            .unwrap_or(kline.id + scale.duration().as_secs() as i64);
+        let mut state = self.state();
+        self.advance_state(&mut state, close * 1000);
+        let mut volume = &kline.vol * &self.options.kline_participation;
+        for id in resting(&state, symbol) {
+            let sim = &state.orders[&id];
+            let crossed = match sim.order.direction {
+                Side::Bid => kline.low <= sim.order.price,
+                Side::Ask => kline.high >= sim.order.price,
+            };
+            if crossed {
+                let amount = sim.remaining().min(volume.clone());
+                volume -= &amount;
+                let price = sim.order.price.clone();
+                self.fill(&mut state, id, price, amount, false);
+            }
+        }
+        if !state.depth_fed.contains(symbol) {
+            let level = PriceLevel::new(
+                kline.close.clone(),
+                &kline.vol * &self.options.kline_participation,
+            );
+            let book = Depth {
+                depth: 1,
+                seq: None,
+                checksum: None,
+                bids: vec![level.clone()],
+                asks: vec![level],
+            };
+            state.books.insert(symbol.to_string(), book);
+        }
+        state
+            .klines
+            .entry((symbol.to_string(), scale))
+            .or_default()
+            .push(kline);
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn advance_state(&self, state: &mut State, timestamp: i64) {
+        while state
+            .in_flight
+            .front()
+            .is_some_and(|(due, _)| *due <= timestamp)
+        {
+            let (due, action) = state.in_flight.pop_front().unwrap();
+            state.now = state.now.max(due);
+            match action {
+                Action::Place(id) => self.arrive(state, id),
+                Action::Cancel(id) => {
+                    if let Some(sim) = state.orders.get_mut(&id).filter(|sim| sim.is_open()) {
+                        sim.order.status = OrderStatus::Cancel;
+                        sim.order.updated_at = Some(due / 1000);
+                    }
+                }
+            }
+        }
+        state.now = state.now.max(timestamp);
+    }
+
+    /// an order reaches the matching engine and takes what the book offers
+    fn arrive(&self, state: &mut State, id: u64) {
+        let sim = &state.orders[&id];
+        if !sim.is_open() {
+            return;
+        }
+        let kind = sim.kind;
+        let limit = (sim.order.price > BigDecimal::zero()).then(|| sim.order.price.clone());
+        let fills = match state.books.get(&sim.order.symbol) {
+            Some(depth) => paper::crossing(&sim.order, limit.as_ref(), depth),
+            None => vec![],
+        };
+        let fillable = fills
+            .iter()
+            .fold(BigDecimal::zero(), |acc, (_, amount)| acc + amount);
+        let rejected = match kind {
+            OrderKind::PostOnly => !fills.is_empty(),
+            OrderKind::FOK => fillable < sim.order.amount,
+            _ => false,
+        };
+        if !rejected {
+            for (price, amount) in fills {
+                self.fill(state, id, price, amount, true);
+            }
+        }
+        let sim = state.orders.get_mut(&id).unwrap();
+        sim.live = true;
+        let takes_only = matches!(kind, OrderKind::Market | OrderKind::IOC | OrderKind::FOK);
+        if rejected || (sim.is_open() && takes_only) {
+            sim.order.status = OrderStatus::Cancel;
+            sim.order.updated_at = Some(state.now / 1000);
+        }
+    }
+
+    fn fill(&self, state: &mut State, id: u64, price: BigDecimal, amount: BigDecimal, taker: bool) {
+        if amount <= BigDecimal::zero() {
+            return;
+        }
+        let now = state.now;
+        let sim = state.orders.get_mut(&id).unwrap();
+        let Some(symbol) = self.registry.get(&sim.order.symbol) else {
+            return;
+        };
+        let rate = if taker {
+            &symbol.taker_fee
+        } else {
+            &symbol.make_fee
+        };
+        let quote = &price * &amount;
+        // the fee is charged in the asset received
+        let (base_fee, quote_fee) = match sim.order.direction {
+            Side::Bid => (&amount * rate, BigDecimal::zero()),
+            Side::Ask => (BigDecimal::zero(), &quote * rate),
+        };
+        let order = &mut sim.order;
+        order.filled_base += &amount;
+        order.filled_quote += &quote;
+        order.avg_price = &order.filled_quote / &order.filled_base;
+        order.status = if order.filled_base >= order.amount {
+            OrderStatus::Dealed
+        } else {
+            OrderStatus::PartialDealed
+        };
+        order.trades.push(Trade {
+            base: symbol.base,
+            quote: symbol.quote,
+            ask_or_bid: order.direction,
+            price,
+            amount: amount.clone(),
+            quote_amount: quote.clone(),
+            quote_fee: quote_fee.clone(),
+            base_fee: base_fee.clone(),
+            timestamp: now / 1000,
+        });
+        order.updated_at = Some(now / 1000);
+        let (base_change, quote_change) = match order.direction {
+            Side::Bid => (amount - base_fee, -quote),
+            Side::Ask => (-amount, quote - quote_fee),
+        };
+        *state.assets.entry(symbol.base_name).or_default() += base_change;
+        *state.assets.entry(symbol.quote_name).or_default() += quote_change;
+    }
+
+    /// what the open orders hold back of each asset
+    fn frozen(&self, state: &State) -> HashMap<String, BigDecimal> {
+        let mut frozen: HashMap<String, BigDecimal> = HashMap::new();
+        for sim in state.orders.values().filter(|sim| sim.is_open()) {
+            let Some(symbol) = self.registry.get(&sim.order.symbol) else {
+                continue;
+            };
+            let (asset, amount) = match sim.order.direction {
+                Side::Bid => (symbol.quote_name, sim.remaining() * &sim.order.price),
+                Side::Ask => (symbol.base_name, sim.remaining()),
+            };
+            *frozen.entry(asset).or_default() += amount;
+        }
+        frozen
+    }
+
+    /// accept `order` and send it towards the matching engine, returns its id
+    pub fn place(&self, order: NewOrder) -> Result<String> {
+        order.validate()?;
+        self.registry.check_order(&order)?;
+        let side = order
+            .side()
+            .ok_or_else(|| Error::InvalidRequest(format!("unknown order type {}", order.r#type)))?;
+        let symbol = self.registry.get(&order.symbol).unwrap();
+        let mut state = self.state();
+        let (asset, needed) = match side {
+            // market bids are not held against the quote balance, their cost is unknown
+            Side::Bid => (
+                symbol.quote_name,
+                &order.amount * order.price.clone().unwrap_or_default(),
+            ),
+            Side::Ask => (symbol.base_name, order.amount.clone()),
+        };
+        let total = state.assets.get(&asset).cloned().unwrap_or_default();
+        let frozen = self.frozen(&state).remove(&asset).unwrap_or_default();
+        if total - frozen < needed {
+            return Err(Error::InvalidRequest(format!("insufficient {} balance", asset)).into());
+        }
+        state.next_id += 1;
+        let id = state.next_id;
+        let now = state.now / 1000;
+        state.orders.insert(
+            id,
+            SimOrder {
+                order: QueryOrder {
+                    symbol: order.symbol,
+                    order_id: format!("sim-{}", id),
+                    order_type: side,
+                    direction: side,
+                    amount: order.amount,
+                    price: order.price.unwrap_or_default(),
+                    filled_base: BigDecimal::zero(),
+                    filled_quote: BigDecimal::zero(),
+                    avg_price: BigDecimal::zero(),
+                    status: OrderStatus::Undeal,
+                    trades: vec![],
+                    created_at: Some(now),
+                    updated_at: Some(now),
+                },
+                kind: order.kind,
+                live: false,
+            },
+        );
+        self.send(&mut state, Action::Place(id));
+        Ok(format!("sim-{}", id))
+    }
+
+    /// send a cancel towards the matching engine, the order may still fill until it arrives
+    pub fn cancel(&self, symbol: &str, order_id: &str) -> Result<String> {
+        let mut state = self.state();
+        let id = find(&state, symbol, order_id)?;
+        self.send(&mut state, Action::Cancel(id));
+        Ok(order_id.to_string())
+    }
+
+    fn send(&self, state: &mut State, action: Action) {
+        let due = state.now + self.options.latency.as_millis() as i64;
+        state.in_flight.push_back((due, action));
+        let now = state.now;
+        self.advance_state(state, now);
+    }
+
+    pub fn order(&self, symbol: &str, order_id: &str) -> Result<QueryOrder> {
+        let state = self.state();
+        let id = find(&state, symbol, order_id)?;
+        Ok(state.orders[&id].order.clone())
+    }
+
+    /// the orders of `symbol` the matching engine holds
+    pub fn open_orders(&self, symbol: &str) -> Vec<QueryOrder> {
+        let state = self.state();
+        resting(&state, symbol)
+            .into_iter()
+            .map(|id| state.orders[&id].order.clone())
+            .collect()
+    }
+
+    pub fn balances(&self) -> Vec<Balance> {
+        let state = self.state();
+        let mut frozen = self.frozen(&state);
+        let code = |asset: &str| {
+            self.symbols.iter().find_map(|s| {
+                if s.base_name == asset {
+                    Some(s.base)
+                } else if s.quote_name == asset {
+                    Some(s.quote)
+                } else {
+                    None
+                }
+            })
+        };
+        state
+            .assets
+            .iter()
+            .map(|(name, total)| {
+                let frozen = frozen.remove(name).unwrap_or_default();
+                Balance {
+                    code: code(name).unwrap_or_default(),
+                    name: name.clone(),
+                    available: total - &frozen,
+                    frozen,
+                }
+            })
+            .collect()
+    }
+
+    /// the last fed book of `symbol`, empty before any data
+    pub fn depth(&self, symbol: &str, limit: Option<u32>) -> Depth {
+        let state = self.state();
+        let mut depth = match state.books.get(symbol) {
+            Some(book) => Depth {
+                depth: book.depth,
+                seq: book.seq,
+                checksum: None,
+                bids: book.bids.clone(),
+                asks: book.asks.clone(),
+            },
+            None => Depth {
+                depth: 0,
+                seq: None,
+                checksum: None,
+                bids: vec![],
+                asks: vec![],
+            },
+        };
+        if let Some(limit) = limit {
+            depth.bids.truncate(limit as usize);
+            depth.asks.truncate(limit as usize);
+        }
+        depth
+    }
+
+    /// the candles fed so far, oldest first
+    pub fn klines(&self, symbol: &str, scale: Scale) -> Vec<Kline> {
+        self.state()
+            .klines
+            .get(&(symbol.to_string(), scale))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// the ids of the open orders of `symbol` the matching engine holds, oldest first
+fn resting(state: &State, symbol: &str) -> Vec<u64> {
+    state
+        .orders
+        .iter()
+        .filter(|(_, sim)| sim.live && sim.is_open() && sim.order.symbol == symbol)
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+fn find(state: &State, symbol: &str, order_id: &str) -> Result<u64> {
+    order_id
+        .strip_prefix("sim-")
+        .and_then(|id| id.parse().ok())
+        .filter(|id| {
+            state
+                .orders
+                .get(id)
+                .is_some_and(|sim| sim.order.symbol == symbol)
+        })
+        .ok_or_else(|| Error::InvalidRequest(format!("unknown order {}", order_id)).into())
+}
+
+impl ExchangeClient for SimulatedExchange {
+    fn venue(&self) -> &str {
+        "backtest"
+    }
+
+    fn place_order(&self, order: OrderRequest) -> ExchangeFuture<'_, String> {
+        Box::pin(async move { self.place(order.try_into()?) })
+    }
+
+    fn cancel_order<'a>(
+        &'a self,
+        symbol: &'a str,
+        order_id: &'a str,
+    ) -> ExchangeFuture<'a, String> {
+        Box::pin(async move { self.cancel(symbol, order_id) })
+    }
+
+    fn order<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> ExchangeFuture<'a, Order> {
+        Box::pin(async move { Ok(SimulatedExchange::order(self, symbol, order_id)?.into()) })
+    }
+
+    fn open_orders<'a>(&'a self, symbol: &'a str) -> ExchangeFuture<'a, Vec<Order>> {
+        Box::pin(async move { Ok(convert(SimulatedExchange::open_orders(self, symbol))) })
+    }
+
+    fn balances(&self) -> ExchangeFuture<'_, Vec<AssetBalance>> {
+        Box::pin(async move { Ok(convert(SimulatedExchange::balances(self))) })
+    }
+
+    fn depth<'a>(&'a self, symbol: &'a str, limit: Option<u32>) -> ExchangeFuture<'a, Book> {
+        Box::pin(async move { Ok(SimulatedExchange::depth(self, symbol, limit).into()) })
+    }
+
+    fn klines<'a>(&'a self, symbol: &'a str, scale: Scale) -> ExchangeFuture<'a, Vec<Candle>> {
+        Box::pin(async move { Ok(convert(SimulatedExchange::klines(self, symbol, scale))) })
+    }
+
+    fn markets(&self) -> ExchangeFuture<'_, Vec<Market>> {
+        Box::pin(async move { Ok(convert(self.symbols.clone())) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol() -> Symbol {
+        serde_json::from_str(
+            r#"{"base":1,"quote":0,"base_name":"BTC","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0.002","make_fee":"0.001","min_amount":"0.0001",
+            "min_vol":"1","enable_marker_order":true}"#,
+        )
+        .unwrap()
+    }
+
+    fn depth(bid: i64, ask: i64, amount: i64) -> Depth {
+        Depth {
+            depth: 0,
+            seq: Some(1),
+            checksum: None,
+            bids: vec![PriceLevel::new(bid.into(), amount.into())],
+            asks: vec![PriceLevel::new(ask.into(), amount.into())],
+        }
+    }
+
+    fn bid(price: Option<i64>, amount: i64) -> NewOrder {
+        let kind = match price {
+            Some(_) => OrderKind::Limit,
+            None => OrderKind::Market,
+        };
+        NewOrder::new(
+            Side::Bid,
+            kind,
+            String::from("BTC-USDT"),
+            price.map(BigDecimal::from),
+            amount.into(),
+        )
+        .unwrap()
+    }
+
+    /// a strategy only knowing the trait
+    async fn buy_the_dip(venue: &dyn ExchangeClient) -> Result<String> {
+        let depth = venue.depth("BTC-USDT", Some(1)).await?;
+        let price = &depth.bids[0].price - BigDecimal::from(1);
+        venue
+            .place_order(OrderRequest {
+                symbol: String::from("BTC-USDT"),
+                side: Side::Bid,
+                kind: OrderKind::Limit,
+                price: Some(price),
+                amount: 2.into(),
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_latency_fees_and_partial_fills() {
+        let venue = SimulatedExchange::new(
+            vec![symbol()],
+            [(String::from("USDT"), BigDecimal::from(1000))],
+            BacktestOptions::default(),
+        );
+        venue.on_depth(1_000, "BTC-USDT", depth(99, 101, 1));
+
+        let market = venue.place(bid(None, 1)).unwrap();
+        // still on its way, the book moves before it arrives
+        assert_eq!(
+            venue.order("BTC-USDT", &market).unwrap().filled_base,
+            0.into()
+        );
+        venue.on_depth(1_040, "BTC-USDT", depth(100, 102, 1));
+        venue.advance(1_050);
+        let market = venue.order("BTC-USDT", &market).unwrap();
+        assert_eq!(market.status, OrderStatus::Dealed);
+        assert_eq!(market.avg_price, 102.into());
+        assert_eq!(market.trades[0].base_fee, "0.002".parse().unwrap());
+
+        let id = buy_the_dip(&venue).await.unwrap();
+        venue.advance(1_100);
+        let open = ExchangeClient::open_orders(&venue, "BTC-USDT")
+            .await
+            .unwrap();
+        assert_eq!(open[0].state, crate::exchange::OrderState::Open);
+        venue.on_depth(2_000, "BTC-USDT", depth(97, 99, 1));
+        let resting = venue.order("BTC-USDT", &id).unwrap();
+        assert_eq!(resting.status, OrderStatus::PartialDealed);
+        assert_eq!(resting.trades[0].price, 99.into());
+        assert_eq!(resting.trades[0].base_fee, "0.001".parse().unwrap());
+
+        let balances = venue.balances();
+        let btc = balances.iter().find(|b| b.name == "BTC").unwrap();
+        let usdt = balances.iter().find(|b| b.name == "USDT").unwrap();
+        assert_eq!(btc.available, "1.997".parse().unwrap());
+        assert_eq!(usdt.available, BigDecimal::from(1000 - 102 - 99 - 99));
+        assert_eq!(usdt.frozen, 99.into());
+
+        venue.cancel("BTC-USDT", &id).unwrap();
+        venue.on_kline(
+            "BTC-USDT",
+            Scale::Minute,
+            serde_json::from_str(
+                r#"{"id": 0, "open": "99", "close": "98", "high": "99", "low": "98", "vol": "50"}"#,
+            )
+            .unwrap(),
+        );
+        // the candle closes after the cancel arrived
+        let cancelled = venue.order("BTC-USDT", &id).unwrap();
+        assert_eq!(cancelled.status, OrderStatus::Cancel);
+        assert_eq!(cancelled.filled_base, 1.into());
+        assert!(venue.place(bid(Some(100), 100)).is_err());
+    }
+}
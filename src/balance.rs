@@ -0,0 +1,195 @@
+use crate::response::{Balance, Side, Symbol, Trade};
+use crate::sequence::SequenceGuard;
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::HashMap;
+
+/// last known balance of every asset, a balance read by an older request never overwrites a newer one
+#[derive(Debug, Default)]
+pub struct BalanceCache {
+    balances: HashMap<String, (SequenceGuard, Balance)>,
+}
+
+impl BalanceCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// store `balance` read by the request with sequence `seq`, false if it was outdated
+    pub fn update(&mut self, seq: u64, balance: Balance) -> bool {
+        let entry = self
+            .balances
+            .entry(balance.name.clone())
+            .or_insert_with(|| (SequenceGuard::default(), balance.clone()));
+        if !entry.0.accept(seq) {
+            return false;
+        }
+        entry.1 = balance;
+        true
+    }
+
+    pub fn get(&self, asset: &str) -> Option<&Balance> {
+        self.balances.get(asset).map(|(_, b)| b)
+    }
+
+    pub fn assets(&self) -> impl Iterator<Item = &str> {
+        self.balances.keys().map(|k| k.as_str())
+    }
+}
+
+/// an asset whose exchange balance differs from the one predicted by the fills
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub asset: String,
+    pub expected: BigDecimal,
+    pub actual: BigDecimal,
+}
+
+impl Drift {
+    pub fn diff(&self) -> BigDecimal {
+        &self.actual - &self.expected
+    }
+}
+
+/// predicts balances from a baseline plus every fill since, and compares them against the exchange
+///
+/// fees are always deducted from the asset they are charged in, a drift beyond
+/// `tolerance` points at a missed fill, a wrong fee model or an exchange discrepancy
+#[derive(Debug, Clone, Default)]
+pub struct BalanceReconciler {
+    tolerance: BigDecimal,
+    names: HashMap<i32, String>,
+    baseline: HashMap<String, BigDecimal>,
+    deltas: HashMap<String, BigDecimal>,
+}
+
+impl BalanceReconciler {
+    pub fn new(tolerance: BigDecimal) -> Self {
+        BalanceReconciler {
+            tolerance,
+            ..Default::default()
+        }
+    }
+
+    /// learn the asset names behind the numeric ids used by `Trade`
+    pub fn with_symbols(mut self, symbols: &[Symbol]) -> Self {
+        for symbol in symbols {
+            self.names.insert(symbol.base, symbol.base_name.clone());
+            self.names.insert(symbol.quote, symbol.quote_name.clone());
+        }
+        self
+    }
+
+    pub fn asset(mut self, id: i32, name: String) -> Self {
+        self.names.insert(id, name);
+        self
+    }
+
+    fn name(&self, id: i32) -> String {
+        self.names
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// start predicting from these balances, forgetting every fill recorded so far
+    pub fn rebase(&mut self, balances: &[Balance]) {
+        self.baseline = balances
+            .iter()
+            .map(|b| (b.name.clone(), &b.available + &b.frozen))
+            .collect();
+        self.deltas.clear();
+    }
+
+    pub fn record_fill(&mut self, trade: &Trade) {
+        let (base, quote) = match trade.ask_or_bid {
+            Side::Bid => (trade.amount.clone(), -trade.quote_amount.clone()),
+            Side::Ask => (-trade.amount.clone(), trade.quote_amount.clone()),
+        };
+        *self.deltas.entry(self.name(trade.base)).or_default() += base - &trade.base_fee;
+        *self.deltas.entry(self.name(trade.quote)).or_default() += quote - &trade.quote_fee;
+    }
+
+    pub fn expected(&self, asset: &str) -> BigDecimal {
+        self.baseline.get(asset).cloned().unwrap_or_default()
+            + self.deltas.get(asset).cloned().unwrap_or_default()
+    }
+
+    /// every asset of `actual` or of the prediction whose total balance drifted beyond the tolerance
+    pub fn reconcile(&self, actual: &[Balance]) -> Vec<Drift> {
+        let mut totals = actual
+            .iter()
+            .map(|b| (b.name.clone(), &b.available + &b.frozen))
+            .collect::<HashMap<_, _>>();
+        for asset in self.baseline.keys().chain(self.deltas.keys()) {
+            totals.entry(asset.clone()).or_insert_with(BigDecimal::zero);
+        }
+        let mut drifts = totals
+            .into_iter()
+            .map(|(asset, actual)| Drift {
+                expected: self.expected(&asset),
+                asset,
+                actual,
+            })
+            .filter(|d| d.diff().abs() > self.tolerance)
+            .collect::<Vec<_>>();
+        drifts.sort_by(|a, b| a.asset.cmp(&b.asset));
+        drifts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(available: i64) -> Balance {
+        named("USDT", available)
+    }
+
+    fn named(name: &str, available: i64) -> Balance {
+        Balance {
+            code: 0,
+            name: String::from(name),
+            available: BigDecimal::from(available),
+            frozen: BigDecimal::from(0),
+        }
+    }
+
+    #[test]
+    fn test_late_balance_ignored() {
+        let mut cache = BalanceCache::new();
+        assert!(cache.update(2, balance(20)));
+        assert!(!cache.update(1, balance(10)));
+        assert_eq!(cache.get("USDT").unwrap().available, BigDecimal::from(20));
+    }
+
+    #[test]
+    fn test_reconcile_fills() {
+        let mut reconciler = BalanceReconciler::new(BigDecimal::from(0))
+            .asset(1, String::from("BTC"))
+            .asset(0, String::from("USDT"));
+        reconciler.rebase(&[named("USDT", 1000)]);
+        reconciler.record_fill(&Trade {
+            base: 1,
+            quote: 0,
+            ask_or_bid: Side::Bid,
+            price: BigDecimal::from(100),
+            amount: BigDecimal::from(2),
+            quote_amount: BigDecimal::from(200),
+            quote_fee: BigDecimal::from(1),
+            base_fee: BigDecimal::from(0),
+            timestamp: 0,
+        });
+        assert_eq!(reconciler.expected("USDT"), BigDecimal::from(799));
+        assert!(reconciler
+            .reconcile(&[named("USDT", 799), named("BTC", 2)])
+            .is_empty());
+        assert_eq!(
+            reconciler.reconcile(&[named("USDT", 799), named("BTC", 1)]),
+            vec![Drift {
+                asset: String::from("BTC"),
+                expected: BigDecimal::from(2),
+                actual: BigDecimal::from(1),
+            }]
+        );
+    }
+}
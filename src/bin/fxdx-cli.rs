@@ -0,0 +1,279 @@
+//! manual operations against the exchange, built with `--features cli`
+//!
+//! credentials come from the file given by `--config` or `FXDX_CONFIG`, see
+//! `config::ClientConfig`, and otherwise from `FXDX_ENDPOINT`, `FXDX_ADDRESS` and `FXDX_SECRET`
+
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use clap::{Parser, Subcommand, ValueEnum};
+use fxdx_rs::request::{OrderKind, PrivPub, Request, Scale, Side};
+use fxdx_rs::{response, FxdxBuilder, FxdxClient};
+use serde::Serialize;
+
+#[derive(Debug, Parser)]
+#[command(name = "fxdx-cli", about = "poke the fxdx exchange from a shell")]
+struct Cli {
+    /// TOML or YAML client settings, the environment is used when absent
+    #[arg(long, env = "FXDX_CONFIG", global = true)]
+    config: Option<std::path::PathBuf>,
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Json,
+    Table,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(subcommand)]
+    Order(OrderCommand),
+    /// the balances of the key
+    Balances,
+    /// the order book of a symbol
+    Depth {
+        symbol: String,
+        /// top levels of each side only
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// candles of a symbol
+    Kline {
+        symbol: String,
+        /// e.g. `1m`, `4h` or `DAY`
+        #[arg(long, default_value = "1m")]
+        scale: Scale,
+    },
+    /// every listed symbol
+    Symbols,
+}
+
+#[derive(Debug, Subcommand)]
+enum OrderCommand {
+    /// place an order and print its id
+    Place {
+        symbol: String,
+        /// `bid`/`buy` or `ask`/`sell`
+        side: Side,
+        amount: BigDecimal,
+        /// leave out for a market order
+        #[arg(long)]
+        price: Option<BigDecimal>,
+        /// `LIMIT`, `POST_ONLY`, `IOC` or `FOK`, defaults to `LIMIT` with a price
+        #[arg(long)]
+        kind: Option<OrderKind>,
+    },
+    /// cancel an order and print its id
+    Cancel { symbol: String, order_id: String },
+    /// the open orders of a symbol
+    List { symbol: String },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let builder = match &cli.config {
+        Some(path) => FxdxBuilder::<PrivPub>::from_config(path)?,
+        None => FxdxBuilder::<PrivPub>::from_env()?,
+    };
+    let client = builder.build().await?;
+    run(&client, cli.command, cli.format).await
+}
+
+async fn run(client: &FxdxClient<PrivPub>, command: Command, format: Format) -> Result<()> {
+    match command {
+        Command::Order(OrderCommand::Place {
+            symbol,
+            side,
+            amount,
+            price,
+            kind,
+        }) => {
+            let kind = kind.unwrap_or(match price {
+                Some(_) => OrderKind::Limit,
+                None => OrderKind::Market,
+            });
+            let order = Request::order(side, kind, symbol, price, amount)?;
+            print_id(format, &client.pending_order(order).await?)
+        }
+        Command::Order(OrderCommand::Cancel { symbol, order_id }) => {
+            let id = client
+                .cancel_order(Request::CancelOrder { symbol, order_id })
+                .await?;
+            print_id(format, &id)
+        }
+        Command::Order(OrderCommand::List { symbol }) => {
+            let orders = client.open_orders(&symbol).await?;
+            output(format, orders.as_slice(), orders_table)
+        }
+        Command::Balances => {
+            let balance = client.query_account_balance(Request::Balances).await?;
+            output(format, &balance, |b| {
+                balances_table(std::slice::from_ref(b))
+            })
+        }
+        Command::Depth { symbol, limit } => {
+            let depth = client.query_depth(Request::Depth { symbol, limit }).await?;
+            output(format, &depth, depth_table)
+        }
+        Command::Kline { symbol, scale } => {
+            let klines = client.query_kline(Request::Kline { symbol, scale }).await?;
+            output(format, klines.as_slice(), klines_table)
+        }
+        Command::Symbols => {
+            let symbols = client.query_symbols(Request::Symbols).await?;
+            output(format, symbols.as_slice(), symbols_table)
+        }
+    }
+}
+
+fn print_id(format: Format, id: &str) -> Result<()> {
+    match format {
+        Format::Json => println!("{}", serde_json::json!({ "order_id": id })),
+        Format::Table => println!("{}", id),
+    }
+    Ok(())
+}
+
+fn output<T, F>(format: Format, value: &T, table: F) -> Result<()>
+where
+    T: Serialize + ?Sized,
+    F: FnOnce(&T) -> Table,
+{
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Format::Table => print!("{}", table(value)),
+    }
+    Ok(())
+}
+
+/// columns padded to their widest cell
+struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut widths: Vec<_> = self.header.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let header = self.header.iter().map(|h| h.to_string()).collect();
+        for row in std::iter::once(&header).chain(&self.rows) {
+            let cells: Vec<_> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+fn orders_table(orders: &[response::QueryOrder]) -> Table {
+    Table {
+        header: vec![
+            "ORDER", "SYMBOL", "SIDE", "PRICE", "AMOUNT", "FILLED", "STATUS",
+        ],
+        rows: orders
+            .iter()
+            .map(|o| {
+                vec![
+                    o.order_id.clone(),
+                    o.symbol.clone(),
+                    o.direction.to_string(),
+                    o.price.to_string(),
+                    o.amount.to_string(),
+                    o.filled_base.to_string(),
+                    format!("{:?}", o.status),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn balances_table(balances: &[response::Balance]) -> Table {
+    Table {
+        header: vec!["ASSET", "AVAILABLE", "FROZEN"],
+        rows: balances
+            .iter()
+            .map(|b| {
+                vec![
+                    b.name.clone(),
+                    b.available.to_string(),
+                    b.frozen.to_string(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn depth_table(depth: &response::Depth) -> Table {
+    let level = |level: Option<&response::PriceLevel>| match level {
+        Some(level) => (level.price.to_string(), level.amount.to_string()),
+        None => Default::default(),
+    };
+    Table {
+        header: vec!["BID AMOUNT", "BID", "ASK", "ASK AMOUNT"],
+        rows: (0..depth.bids.len().max(depth.asks.len()))
+            .map(|i| {
+                let (bid, bid_amount) = level(depth.bids.get(i));
+                let (ask, ask_amount) = level(depth.asks.get(i));
+                vec![bid_amount, bid, ask, ask_amount]
+            })
+            .collect(),
+    }
+}
+
+fn klines_table(klines: &[response::Kline]) -> Table {
+    Table {
+        header: vec!["ID", "OPEN", "HIGH", "LOW", "CLOSE", "VOLUME"],
+        rows: klines
+            .iter()
+            .map(|k| {
+                vec![
+                    k.id.to_string(),
+                    k.open.to_string(),
+                    k.high.to_string(),
+                    k.low.to_string(),
+                    k.close.to_string(),
+                    k.vol.to_string(),
+                ]
+            })
+            .collect(),
+    }
+}
+
+fn symbols_table(symbols: &[response::Symbol]) -> Table {
+    Table {
+        header: vec![
+            "SYMBOL",
+            "PRICE TICK",
+            "AMOUNT STEP",
+            "MIN AMOUNT",
+            "TAKER",
+            "MAKER",
+        ],
+        rows: symbols
+            .iter()
+            .map(|s| {
+                vec![
+                    s.pair(),
+                    s.price_tick().to_string(),
+                    s.amount_step().to_string(),
+                    s.min_amount.to_string(),
+                    s.taker_fee.to_string(),
+                    s.make_fee.to_string(),
+                ]
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,426 @@
+//! depth snapshots and deltas recorded to a compact binary log, and replayed into the same
+//! `OrderBook` the live path keeps, e.g. for backtests
+//!
+//! the log starts with `MAGIC` and holds length prefixed records: a tag, the unix milliseconds
+//! the update was received at, the symbol, the sequence numbers and the levels, prices and
+//! amounts as a zigzag varint scale followed by the little endian digits
+
+use crate::depthsync::{DeltaOutcome, DepthDelta, DepthSync};
+use crate::orderbook::{BookValidator, OrderBook};
+use crate::response::{Depth, PriceLevel};
+use crate::Error;
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// the first bytes of a book log, the last one is the format version
+pub const MAGIC: [u8; 5] = *b"FXBL\x01";
+
+const SNAPSHOT: u8 = 0;
+const DELTA: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookRecord {
+    /// a REST snapshot taken at `seq`
+    Snapshot {
+        timestamp: i64,
+        symbol: String,
+        seq: u64,
+        bids: Vec<PriceLevel>,
+        asks: Vec<PriceLevel>,
+    },
+    Delta {
+        timestamp: i64,
+        delta: DepthDelta,
+    },
+}
+
+impl BookRecord {
+    /// unix milliseconds the update was received at
+    pub fn timestamp(&self) -> i64 {
+        match self {
+            BookRecord::Snapshot { timestamp, .. } | BookRecord::Delta { timestamp, .. } => {
+                *timestamp
+            }
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            BookRecord::Snapshot { symbol, .. } => symbol,
+            BookRecord::Delta { delta, .. } => &delta.symbol,
+        }
+    }
+}
+
+/// appends records to a book log, see `BookReplayer` to read it back
+#[derive(Debug)]
+pub struct BookRecorder<W: Write> {
+    writer: W,
+}
+
+impl BookRecorder<BufWriter<std::fs::File>> {
+    /// start a new log at `path`, replacing any file there
+    pub fn create<T: AsRef<Path>>(path: T) -> Result<Self> {
+        Self::new(BufWriter::new(std::fs::File::create(path)?))
+    }
+}
+
+impl<W: Write> BookRecorder<W> {
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&MAGIC)?;
+        Ok(BookRecorder { writer })
+    }
+
+    /// the snapshot `depth` of `symbol`, read by request `seq`
+    pub fn record_snapshot(
+        &mut self,
+        timestamp: i64,
+        symbol: &str,
+        seq: u64,
+        depth: &Depth,
+    ) -> Result<()> {
+        let mut record = vec![SNAPSHOT];
+        put_varint(&mut record, zigzag(timestamp));
+        put_bytes(&mut record, symbol.as_bytes());
+        put_varint(&mut record, seq);
+        put_levels(&mut record, &depth.bids);
+        put_levels(&mut record, &depth.asks);
+        self.write(&record)
+    }
+
+    pub fn record_delta(&mut self, timestamp: i64, delta: &DepthDelta) -> Result<()> {
+        let mut record = vec![DELTA];
+        put_varint(&mut record, zigzag(timestamp));
+        put_bytes(&mut record, delta.symbol.as_bytes());
+        put_varint(&mut record, delta.prev_seq);
+        put_varint(&mut record, delta.seq);
+        put_levels(&mut record, &delta.bids);
+        put_levels(&mut record, &delta.asks);
+        self.write(&record)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// flush and hand back the writer
+    pub fn into_inner(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, record: &[u8]) -> Result<()> {
+        let mut len = vec![];
+        put_varint(&mut len, record.len() as u64);
+        self.writer.write_all(&len)?;
+        self.writer.write_all(record)?;
+        Ok(())
+    }
+}
+
+/// the records of a book log in the order they were written, a torn last record ends it
+#[derive(Debug)]
+pub struct BookReader<R: Read> {
+    reader: R,
+}
+
+impl BookReader<BufReader<std::fs::File>> {
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self> {
+        Self::new(BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+impl<R: Read> BookReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(corrupt("not a book log"));
+        }
+        Ok(BookReader { reader })
+    }
+
+    /// the next record, `None` at the end of the log
+    pub fn read(&mut self) -> Result<Option<BookRecord>> {
+        let mut len = 0u64;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            len |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut record = vec![];
+        (&mut self.reader).take(len).read_to_end(&mut record)?;
+        if record.len() as u64 != len {
+            return Ok(None);
+        }
+        decode(&record).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for BookReader<R> {
+    type Item = Result<BookRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// rebuilds the books of every symbol in a log one record at a time, through the same
+/// `DepthSync` as the live stream so gaps and corrupt snapshots behave as they did live
+pub struct BookReplayer<R: Read> {
+    reader: BookReader<R>,
+    validator: BookValidator,
+    books: HashMap<String, DepthSync>,
+}
+
+impl BookReplayer<BufReader<std::fs::File>> {
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self> {
+        Ok(Self::new(BookReader::open(path)?))
+    }
+}
+
+impl<R: Read> BookReplayer<R> {
+    pub fn new(reader: BookReader<R>) -> Self {
+        BookReplayer {
+            reader,
+            validator: BookValidator::new(),
+            books: HashMap::new(),
+        }
+    }
+
+    /// check snapshots with `validator`, e.g. to be told about the ones that were rejected
+    pub fn validator(mut self, validator: BookValidator) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// apply the next record, returns its timestamp and the book it changed, `None` at the end
+    pub fn step(&mut self) -> Result<Option<(i64, &OrderBook)>> {
+        let Some(record) = self.reader.read()? else {
+            return Ok(None);
+        };
+        let timestamp = record.timestamp();
+        let sync = self
+            .books
+            .entry(record.symbol().to_string())
+            .or_insert_with(|| DepthSync::new(record.symbol().to_string()));
+        match record {
+            BookRecord::Snapshot {
+                seq, bids, asks, ..
+            } => {
+                let depth = Depth {
+                    depth: 0,
+                    seq: Some(seq),
+                    checksum: None,
+                    bids,
+                    asks,
+                };
+                // a rejected snapshot leaves the book stale, just as it did when recorded
+                let _ = sync.on_snapshot(seq, depth, &self.validator);
+            }
+            BookRecord::Delta { delta, .. } => {
+                if let DeltaOutcome::Gap { expected, got } = sync.on_delta(delta) {
+                    log::debug!("book log gap after {}, next starts at {}", expected, got);
+                }
+            }
+        }
+        Ok(Some((timestamp, sync.book())))
+    }
+
+    /// the book of `symbol` as of the last applied record
+    pub fn book(&self, symbol: &str) -> Option<&OrderBook> {
+        self.books.get(symbol).map(DepthSync::book)
+    }
+}
+
+fn corrupt(reason: &str) -> anyhow::Error {
+    Error::InvalidRequest(format!("corrupt book log: {}", reason)).into()
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+fn put_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn put_decimal(out: &mut Vec<u8>, value: &BigDecimal) {
+    let (digits, scale) = value.as_bigint_and_exponent();
+    put_varint(out, zigzag(scale));
+    put_bytes(out, &digits.to_signed_bytes_le());
+}
+
+fn put_levels(out: &mut Vec<u8>, levels: &[PriceLevel]) {
+    put_varint(out, levels.len() as u64);
+    for level in levels {
+        put_decimal(out, &level.price);
+        put_decimal(out, &level.amount);
+    }
+}
+
+/// reads the fields of one record in order
+struct Cursor<'a>(&'a [u8]);
+
+impl Cursor<'_> {
+    fn byte(&mut self) -> Result<u8> {
+        let (&byte, rest) = self.0.split_first().ok_or_else(|| corrupt("truncated"))?;
+        self.0 = rest;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(corrupt("varint too long"))
+    }
+
+    fn bytes(&mut self) -> Result<&[u8]> {
+        let len = self.varint()? as usize;
+        if len > self.0.len() {
+            return Err(corrupt("truncated"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| corrupt("symbol is not utf-8"))
+    }
+
+    fn decimal(&mut self) -> Result<BigDecimal> {
+        let scale = unzigzag(self.varint()?);
+        Ok(BigDecimal::new(
+            BigInt::from_signed_bytes_le(self.bytes()?),
+            scale,
+        ))
+    }
+
+    fn levels(&mut self) -> Result<Vec<PriceLevel>> {
+        let count = self.varint()?;
+        let mut levels = Vec::with_capacity(count.min(1024) as usize);
+        for _ in 0..count {
+            levels.push(PriceLevel::new(self.decimal()?, self.decimal()?));
+        }
+        Ok(levels)
+    }
+}
+
+fn decode(record: &[u8]) -> Result<BookRecord> {
+    let mut cursor = Cursor(record);
+    let tag = cursor.byte()?;
+    let timestamp = unzigzag(cursor.varint()?);
+    let symbol = cursor.string()?;
+    match tag {
+        SNAPSHOT => Ok(BookRecord::Snapshot {
+            timestamp,
+            symbol,
+            seq: cursor.varint()?,
+            bids: cursor.levels()?,
+            asks: cursor.levels()?,
+        }),
+        DELTA => Ok(BookRecord::Delta {
+            timestamp,
+            delta: DepthDelta {
+                symbol,
+                prev_seq: cursor.varint()?,
+                seq: cursor.varint()?,
+                checksum: None,
+                bids: cursor.levels()?,
+                asks: cursor.levels()?,
+            },
+        }),
+        _ => Err(corrupt("unknown record")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(price: &str, amount: &str) -> PriceLevel {
+        PriceLevel::new(price.parse().unwrap(), amount.parse().unwrap())
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut recorder = BookRecorder::new(vec![]).unwrap();
+        let depth = Depth {
+            depth: 0,
+            seq: Some(10),
+            checksum: None,
+            bids: vec![level("100.25", "1.5"), level("99", "0.001")],
+            asks: vec![level("101", "2")],
+        };
+        let delta = |prev_seq, seq, bids| DepthDelta {
+            symbol: String::from("BTC-USDT"),
+            prev_seq,
+            seq,
+            checksum: None,
+            bids,
+            asks: vec![],
+        };
+        recorder
+            .record_snapshot(1_000, "BTC-USDT", 10, &depth)
+            .unwrap();
+        recorder
+            .record_delta(1_250, &delta(10, 11, vec![level("99", "0")]))
+            .unwrap();
+        recorder
+            .record_delta(-5, &delta(11, 12, vec![level("100.5", "3")]))
+            .unwrap();
+        let mut log = recorder.into_inner().unwrap();
+
+        let records: Vec<_> = BookReader::new(log.as_slice())
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(matches!(
+            &records[0],
+            BookRecord::Snapshot { seq: 10, bids, .. } if bids == &depth.bids
+        ));
+
+        // a torn record at the end is dropped
+        log.extend_from_slice(&[40, DELTA, 2]);
+        let mut replayer = BookReplayer::new(BookReader::new(log.as_slice()).unwrap());
+        let mut steps = vec![];
+        while let Some((timestamp, book)) = replayer.step().unwrap() {
+            steps.push((timestamp, book.bids.len()));
+        }
+        assert_eq!(steps, vec![(1_000, 2), (1_250, 1), (-5, 2)]);
+        let book = replayer.book("BTC-USDT").unwrap();
+        assert_eq!(book.bids, vec![level("100.5", "3"), level("100.25", "1.5")]);
+        assert!(!book.stale);
+        assert!(BookReader::new(&b"FXDX"[..]).is_err());
+    }
+}
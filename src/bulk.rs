@@ -0,0 +1,237 @@
+use crate::priority::{self, Priority};
+use crate::request::Request;
+use crate::Error;
+use anyhow::Result;
+use futures_util::stream::{self, FuturesUnordered, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct BulkOptions {
+    /// requests in flight at once
+    pub concurrency: usize,
+    /// sends of one request before a 429 is reported as its failure
+    pub max_attempts: u32,
+    /// how long to hold off new requests after a 429 without `Retry-After`
+    pub rate_limit_pause: Duration,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        BulkOptions {
+            concurrency: 4,
+            max_attempts: 3,
+            rate_limit_pause: Duration::from_secs(1),
+        }
+    }
+}
+
+/// one finished request, `index` is its position in the submitted list
+#[derive(Debug)]
+pub struct BulkItem<T> {
+    pub index: usize,
+    pub request: Request,
+    pub result: Result<T>,
+}
+
+#[derive(Debug)]
+pub struct BulkFailure {
+    pub index: usize,
+    pub request: Request,
+    pub error: anyhow::Error,
+}
+
+/// everything a bulk download returned, ordered by index
+#[derive(Debug)]
+pub struct BulkReport<T> {
+    pub results: Vec<(usize, T)>,
+    pub failures: Vec<BulkFailure>,
+}
+
+impl<T> BulkReport<T> {
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// wait for every item of `items` and split them into results and failures
+pub async fn collect<T>(items: impl Stream<Item = BulkItem<T>>) -> BulkReport<T> {
+    let mut report = BulkReport {
+        results: vec![],
+        failures: vec![],
+    };
+    let mut items = std::pin::pin!(items);
+    while let Some(item) = items.next().await {
+        match item.result {
+            Ok(value) => report.results.push((item.index, value)),
+            Err(error) => report.failures.push(BulkFailure {
+                index: item.index,
+                request: item.request,
+                error,
+            }),
+        }
+    }
+    report.results.sort_by_key(|(index, _)| *index);
+    report.failures.sort_by_key(|failure| failure.index);
+    report
+}
+
+/// send `requests` through `call` with at most `options.concurrency` in flight, yielding each
+/// as it completes
+///
+/// the calls run at `Priority::Bulk` so orders and cancels overtake them on the rate limiter,
+/// a 429 pauses new sends for the `Retry-After` wait and queues the request again, and anything
+/// but a GET is refused as bulk downloads must not change state
+pub fn fan_out<T, F, Fut>(
+    requests: Vec<Request>,
+    options: BulkOptions,
+    call: F,
+) -> impl Stream<Item = BulkItem<T>>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let queue: VecDeque<_> = requests
+        .into_iter()
+        .enumerate()
+        .map(|(index, request)| (index, request, 1))
+        .collect();
+    let state = (queue, FuturesUnordered::new(), None::<Instant>, call);
+    stream::unfold(
+        state,
+        move |(mut queue, mut running, mut paused_until, call)| {
+            let options = options.clone();
+            async move {
+                loop {
+                    if paused_until.is_none_or(|until| until <= Instant::now()) {
+                        while running.len() < options.concurrency.max(1) {
+                            let Some((index, request, attempts)) = queue.pop_front() else {
+                                break;
+                            };
+                            if request.method() != reqwest::Method::GET {
+                                let error = Error::InvalidRequest(String::from(
+                                    "bulk requests must be read-only",
+                                ));
+                                let item = BulkItem {
+                                    index,
+                                    request,
+                                    result: Err(error.into()),
+                                };
+                                return Some((item, (queue, running, paused_until, call)));
+                            }
+                            let response =
+                                priority::with_priority(Priority::Bulk, call(request.clone()));
+                            running.push(async move { (index, request, attempts, response.await) });
+                        }
+                    }
+                    let Some((index, request, attempts, result)) = running.next().await else {
+                        match paused_until {
+                            Some(until) if !queue.is_empty() => {
+                                tokio::time::sleep_until(until).await;
+                                continue;
+                            }
+                            _ => return None,
+                        }
+                    };
+                    match rate_limited(&result) {
+                        Some(after) if attempts < options.max_attempts => {
+                            let wait = after.unwrap_or(options.rate_limit_pause);
+                            let until = Instant::now() + wait;
+                            paused_until = paused_until.max(Some(until));
+                            queue.push_front((index, request, attempts + 1));
+                        }
+                        _ => {
+                            let item = BulkItem {
+                                index,
+                                request,
+                                result,
+                            };
+                            return Some((item, (queue, running, paused_until, call)));
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// `Some` with the `Retry-After` wait if `result` is a 429
+fn rate_limited<T>(result: &Result<T>) -> Option<Option<Duration>> {
+    match result.as_ref().map_err(|e| e.downcast_ref::<Error>()) {
+        Err(Some(Error::RateLimited(after))) => Some(*after),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    fn trades(symbol: &str) -> Request {
+        Request::Trades {
+            symbol: symbol.to_string(),
+            limit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fan_out() {
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let limited = Mutex::new(false);
+        let requests = vec![
+            trades("BTC-USDT"),
+            trades("ETH-USDT"),
+            Request::CancelOrder {
+                symbol: String::from("BTC-USDT"),
+                order_id: String::from("1"),
+            },
+            trades("BAD-USDT"),
+            trades("SOL-USDT"),
+        ];
+        let options = BulkOptions {
+            concurrency: 2,
+            max_attempts: 2,
+            rate_limit_pause: Duration::from_millis(5),
+        };
+        let items = fan_out(requests, options, |req| {
+            let (in_flight, peak, limited) = (&in_flight, &peak, &limited);
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                assert_eq!(priority::current(), Some(Priority::Bulk));
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                let Request::Trades { symbol, .. } = req else {
+                    unreachable!()
+                };
+                match symbol.as_str() {
+                    "BAD-USDT" => Err(Error::Rejected(100).into()),
+                    "ETH-USDT" if !std::mem::replace(&mut *limited.lock().unwrap(), true) => {
+                        Err(Error::RateLimited(None).into())
+                    }
+                    _ => Ok(symbol),
+                }
+            }
+        });
+        let report = collect(items).await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        let results: Vec<_> = report
+            .results
+            .iter()
+            .map(|(i, s)| (*i, s.as_str()))
+            .collect();
+        assert_eq!(
+            results,
+            vec![(0, "BTC-USDT"), (1, "ETH-USDT"), (4, "SOL-USDT")]
+        );
+        let failed: Vec<_> = report.failures.iter().map(|f| f.index).collect();
+        assert_eq!(failed, vec![2, 3]);
+        assert!(!report.is_complete());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::request::Scale;
+use crate::response::Kline;
+use anyhow::Result;
+use futures_util::Stream;
+use std::future::Future;
+use std::time::Duration;
+
+/// how long after a close the candle is asked for, the exchange needs a moment to seal it
+pub const CLOSE_GRACE: Duration = Duration::from_millis(500);
+
+/// how often a closed candle missing from the response is asked for again before it is skipped,
+/// a candle without trades may never show up
+const CLOSE_RETRIES: u32 = 3;
+const CLOSE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// turns the updates of the forming candle, e.g. `marketdata::MarketEvent::Kline`, into closes:
+/// a candle is closed once the first update of a later one arrives
+#[derive(Debug, Default)]
+pub struct CloseDetector {
+    forming: Option<Kline>,
+}
+
+impl CloseDetector {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// the candle `kline` closed, if it is the first update of a new candle
+    pub fn update(&mut self, kline: Kline) -> Option<Kline> {
+        match &self.forming {
+            Some(forming) if kline.id < forming.id => None,
+            Some(forming) if kline.id == forming.id => {
+                self.forming = Some(kline);
+                None
+            }
+            _ => self.forming.replace(kline),
+        }
+    }
+
+    /// the candle still forming
+    pub fn forming(&self) -> Option<&Kline> {
+        self.forming.as_ref()
+    }
+}
+
+/// the candle of `klines` opening at `open_time`
+pub fn find_candle(klines: Vec<Kline>, open_time: i64) -> Option<Kline> {
+    klines.into_iter().find(|kline| kline.id == open_time)
+}
+
+/// wakes up right after every close of a `scale` candle and fetches it, see
+/// `FxdxClient::candle_close_stream`
+///
+/// `now_ms` is the clock of the exchange in unix milliseconds
+pub(crate) fn poll_closes<'a, N, F, Fut>(
+    scale: Scale,
+    now_ms: N,
+    fetch: F,
+) -> impl Stream<Item = Result<Kline>> + 'a
+where
+    N: Fn() -> i64 + 'a,
+    F: Fn() -> Fut + 'a,
+    Fut: Future<Output = Result<Vec<Kline>>> + 'a,
+{
+    let seconds = scale.duration().as_secs() as i64;
+    futures_util::stream::unfold((now_ms, fetch), move |(now_ms, fetch)| async move {
+        loop {
+            let now = now_ms();
+            let open_time = scale.candle_start(now.div_euclid(1000));
+            let close_ms = (open_time + seconds) * 1000;
+            tokio::time::sleep(Duration::from_millis((close_ms - now).max(0) as u64) + CLOSE_GRACE)
+                .await;
+            for retry in 0..=CLOSE_RETRIES {
+                if retry > 0 {
+                    tokio::time::sleep(CLOSE_RETRY_DELAY).await;
+                }
+                match fetch().await {
+                    Ok(klines) => {
+                        if let Some(kline) = find_candle(klines, open_time) {
+                            return Some((Ok(kline), (now_ms, fetch)));
+                        }
+                    }
+                    Err(e) => return Some((Err(e), (now_ms, fetch))),
+                }
+            }
+            log::debug!("no {} candle opening at {}, skipped", scale, open_time);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(id: i64, close: i64) -> Kline {
+        serde_json::from_str(&format!(
+            r#"{{"id":{},"open":"1","close":"{}","high":"1","low":"1","vol":"1"}}"#,
+            id, close
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_close_on_next_candle() {
+        let mut detector = CloseDetector::new();
+        assert!(detector.update(kline(60, 1)).is_none());
+        assert!(detector.update(kline(60, 2)).is_none());
+        // a late update of an older candle changes nothing
+        assert!(detector.update(kline(0, 9)).is_none());
+        let closed = detector.update(kline(120, 3)).unwrap();
+        assert_eq!((closed.id, closed.close), (60, 2.into()));
+        assert_eq!(detector.forming().unwrap().id, 120);
+
+        let klines = vec![kline(60, 2), kline(120, 3)];
+        assert_eq!(find_candle(klines.clone(), 120).unwrap().close, 3.into());
+        assert!(find_candle(klines, 180).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_poll_after_close() {
+        use futures_util::StreamExt;
+        // 10ms before the first minute closes
+        let closes = poll_closes(
+            Scale::Minute,
+            || 59_990,
+            || async { Ok(vec![kline(0, 4), kline(60, 5)]) },
+        );
+        futures_util::pin_mut!(closes);
+        let closed = closes.next().await.unwrap().unwrap();
+        assert_eq!((closed.id, closed.close), (0, 4.into()));
+    }
+}
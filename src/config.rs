@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// client settings read from a TOML or YAML file, see `FxdxBuilder::from_config`
+///
+/// the file never holds the secret itself, only the name of the environment variable it is read from
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+    /// the endpoints of the preset are used when `endpoints` is empty
+    pub environment: Option<crate::environment::Environment>,
+    /// primary first, the others take over in order
+    pub endpoints: Vec<String>,
+    pub address: Option<String>,
+    /// environment variable holding the secret key
+    pub secret_env: Option<String>,
+    pub failover_threshold: Option<u32>,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub timeout_ms: Option<u64>,
+    pub connect_timeout_ms: Option<u64>,
+    pub retry: Option<RetryPolicy>,
+    /// the symbols a deployment trades, available as `FxdxClient::default_symbols`
+    pub symbols: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub requests: u32,
+    pub per_ms: u64,
+}
+
+/// how often a failed query is sent again, orders and cancels are never retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// delay before the first retry, doubled for every further one
+    pub backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 2,
+            backoff_ms: 100,
+        }
+    }
+}
+
+/// the longest a rate limited request waits before it is sent again, whatever the
+/// `Retry-After` header of the response asked for
+pub const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+
+impl RetryPolicy {
+    /// delay before retry number `retry`, counting from 0
+    pub fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << retry.min(16)))
+    }
+
+    /// delay before retry number `retry` of a rate limited request, the `Retry-After` of its
+    /// response if any, capped at `MAX_RETRY_WAIT`
+    pub fn rate_limited(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        retry_after
+            .unwrap_or_else(|| self.backoff(retry))
+            .min(MAX_RETRY_WAIT)
+    }
+}
+
+impl ClientConfig {
+    /// parse `path` as TOML or YAML by its extension
+    pub fn load<T: AsRef<Path>>(path: T) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("yaml") | Some("yml") => Self::from_yaml(&content),
+            _ => Err(crate::Error::InvalidRequest(format!(
+                "unknown config format of {}, expect .toml, .yaml or .yml",
+                path.display()
+            ))
+            .into()),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn from_yaml(content: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_and_yaml_agree() {
+        let toml = r#"
+endpoints = ["https://a.fxdx", "https://b.fxdx"]
+address = "0xabc"
+secret_env = "FXDX_SECRET"
+timeout_ms = 5000
+symbols = ["BTC-USDT", "ETH-USDT"]
+
+[rate_limit]
+requests = 10
+per_ms = 1000
+
+[retry]
+max_retries = 3
+"#;
+        let yaml = r#"
+endpoints: [https://a.fxdx, https://b.fxdx]
+address: "0xabc"
+secret_env: FXDX_SECRET
+timeout_ms: 5000
+symbols: [BTC-USDT, ETH-USDT]
+rate_limit:
+  requests: 10
+  per_ms: 1000
+retry:
+  max_retries: 3
+"#;
+        let config = ClientConfig::from_toml(toml).unwrap();
+        assert_eq!(config, ClientConfig::from_yaml(yaml).unwrap());
+        assert_eq!(config.endpoints.len(), 2);
+        assert_eq!(
+            config.retry,
+            Some(RetryPolicy {
+                max_retries: 3,
+                backoff_ms: 100
+            })
+        );
+        let retry = config.retry.unwrap();
+        assert_eq!(retry.backoff(2), Duration::from_millis(400));
+        assert_eq!(retry.rate_limited(2, None), Duration::from_millis(400));
+        assert_eq!(
+            retry.rate_limited(0, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            retry.rate_limited(0, Some(Duration::from_secs(86400))),
+            MAX_RETRY_WAIT
+        );
+    }
+
+    #[test]
+    fn test_unknown_field_rejected() {
+        assert!(ClientConfig::from_toml("secret = \"plain\"").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// client certificate presented to endpoints which require mutual TLS
+#[derive(Clone)]
+pub enum ClientIdentity {
+    /// DER encoded PKCS#12 archive holding the certificate chain and the key
+    Pkcs12 { der: Vec<u8>, password: String },
+    /// PEM encoded certificate chain and PKCS#8 key
+    Pem { cert: Vec<u8>, key: Vec<u8> },
+}
+
+impl std::fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientIdentity::Pkcs12 { .. } => f.write_str("Pkcs12(..)"),
+            ClientIdentity::Pem { .. } => f.write_str("Pem(..)"),
+        }
+    }
+}
+
+impl ClientIdentity {
+    fn to_reqwest(&self) -> reqwest::Result<reqwest::Identity> {
+        match self {
+            ClientIdentity::Pkcs12 { der, password } => {
+                reqwest::Identity::from_pkcs12_der(der, password)
+            }
+            ClientIdentity::Pem { cert, key } => reqwest::Identity::from_pkcs8_pem(cert, key),
+        }
+    }
+}
+
+/// which encodings responses may come in and which requests go out gzipped, built with
+/// `--features compression`
+///
+/// every response encoding is accepted unless turned off; request bodies are only compressed
+/// when `request_above` is set, as not every gateway decodes them
+#[cfg(feature = "compression")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Compression {
+    pub gzip: bool,
+    pub deflate: bool,
+    pub brotli: bool,
+    /// gzip bodies of at least this many bytes, e.g. large batch orders
+    pub request_above: Option<usize>,
+}
+
+#[cfg(feature = "compression")]
+impl Default for Compression {
+    fn default() -> Self {
+        Compression {
+            gzip: true,
+            deflate: true,
+            brotli: true,
+            request_above: None,
+        }
+    }
+}
+
+/// transport knobs of the underlying `reqwest::Client`, unset values keep the reqwest defaults
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionOptions {
+    pub pool_max_idle_per_host: Option<usize>,
+    pub pool_idle_timeout: Option<Duration>,
+    pub tcp_keepalive: Option<Duration>,
+    pub tcp_nodelay: Option<bool>,
+    /// speak HTTP/2 right away instead of negotiating it, only for endpoints known to support it
+    pub http2_prior_knowledge: bool,
+    pub connect_timeout: Option<Duration>,
+    /// deadline of a whole request, from connecting until the body is read
+    pub timeout: Option<Duration>,
+    /// resolve these domains to fixed addresses instead of asking DNS
+    pub resolve: Vec<(String, SocketAddr)>,
+    /// `http://`, `https://` or `socks5://` proxy every request goes through
+    pub proxy: Option<String>,
+    pub proxy_basic_auth: Option<(String, String)>,
+    /// PEM encoded CA certificates trusted on top of the system ones
+    pub root_certificates: Vec<Vec<u8>>,
+    pub identity: Option<ClientIdentity>,
+    /// skip certificate verification, only ever meant for self-signed staging deployments
+    pub danger_accept_invalid_certs: bool,
+    #[cfg(feature = "compression")]
+    pub compression: Compression,
+}
+
+impl ConnectionOptions {
+    pub fn apply(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> reqwest::Result<reqwest::ClientBuilder> {
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+        if let Some(nodelay) = self.tcp_nodelay {
+            builder = builder.tcp_nodelay(nodelay);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        for (domain, addr) in &self.resolve {
+            builder = builder.resolve(domain, *addr);
+        }
+        if let Some(url) = &self.proxy {
+            let mut proxy = reqwest::Proxy::all(url)?;
+            if let Some((username, password)) = &self.proxy_basic_auth {
+                proxy = proxy.basic_auth(username, password);
+            }
+            builder = builder.proxy(proxy);
+        }
+        for pem in &self.root_certificates {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.to_reqwest()?);
+        }
+        if self.danger_accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        #[cfg(feature = "compression")]
+        {
+            builder = builder
+                .gzip(self.compression.gzip)
+                .deflate(self.compression.deflate)
+                .brotli(self.compression.brotli);
+        }
+        Ok(builder)
+    }
+
+    pub fn build(&self) -> reqwest::Result<reqwest::Client> {
+        self.apply(reqwest::Client::builder())?.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_and_certificates() {
+        let options = ConnectionOptions {
+            proxy: Some(String::from("socks5://127.0.0.1:1080")),
+            proxy_basic_auth: Some((String::from("user"), String::from("pass"))),
+            ..Default::default()
+        };
+        assert!(options.build().is_ok());
+
+        let options = ConnectionOptions {
+            root_certificates: vec![b"not a certificate".to_vec()],
+            ..Default::default()
+        };
+        assert!(options.build().is_err());
+
+        let identity = ClientIdentity::Pkcs12 {
+            der: vec![],
+            password: String::from("secret"),
+        };
+        assert!(!format!("{:?}", identity).contains("secret"));
+    }
+}
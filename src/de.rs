@@ -0,0 +1,132 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString<T> {
+    Number(T),
+    String(String),
+}
+
+/// a number sent as a number or as a string
+///
+/// the response types read their fields through these, the exchange moves between `1` and
+/// `"1"` or sends `null` for an empty list without notice
+pub fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    match NumberOrString::<T>::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s.trim().parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// like `number`, with `null` and an empty string read as `None`; needs `#[serde(default)]`
+/// as well for the field to be optional
+pub fn option_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    match Option::<NumberOrString<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NumberOrString::Number(n)) => Ok(Some(n)),
+        Some(NumberOrString::String(s)) if s.trim().is_empty() => Ok(None),
+        Some(NumberOrString::String(s)) => {
+            s.trim().parse().map(Some).map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Integer(i64),
+    Unsigned(u64),
+}
+
+/// an id sent as a string or as a number
+pub fn string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Integer(n) => n.to_string(),
+        StringOrNumber::Unsigned(n) => n.to_string(),
+    })
+}
+
+/// `null` read as the default, e.g. an empty list; needs `#[serde(default)]` as well for a
+/// missing field
+pub fn null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawBool {
+    Bool(bool),
+    Number(i64),
+    String(String),
+}
+
+/// `true`, `1` or `"true"`, and their opposites
+pub fn boolean<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match RawBool::deserialize(deserializer)? {
+        RawBool::Bool(b) => Ok(b),
+        RawBool::Number(n) => Ok(n != 0),
+        RawBool::String(s) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" | "" => Ok(false),
+            _ => Err(serde::de::Error::custom(format!("invalid boolean {}", s))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Fields {
+        #[serde(deserialize_with = "number")]
+        n: i64,
+        #[serde(default, deserialize_with = "option_number")]
+        o: Option<u64>,
+        #[serde(deserialize_with = "string")]
+        id: String,
+        #[serde(default, deserialize_with = "null_default")]
+        list: Vec<i32>,
+        #[serde(deserialize_with = "boolean")]
+        b: bool,
+    }
+
+    #[test]
+    fn test_lenient_fields() {
+        let f: Fields =
+            serde_json::from_str(r#"{"n":"-7","o":"","id":42,"list":null,"b":"true"}"#).unwrap();
+        assert_eq!(
+            (f.n, f.o, f.id.as_str(), f.list.len(), f.b),
+            (-7, None, "42", 0, true)
+        );
+
+        let f: Fields = serde_json::from_str(r#"{"n":7,"o":"3","id":"a","b":0}"#).unwrap();
+        assert_eq!((f.n, f.o, f.id.as_str(), f.b), (7, Some(3), "a", false));
+
+        assert!(serde_json::from_str::<Fields>(r#"{"n":"x","id":"a","b":true}"#).is_err());
+    }
+}
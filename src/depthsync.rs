@@ -0,0 +1,262 @@
+use crate::orderbook::{BookValidator, BookViolation, OrderBook};
+use crate::response::{Depth, PriceLevel};
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// deltas kept while waiting for a snapshot, the oldest are dropped beyond this
+pub const MAX_BUFFERED_DELTAS: usize = 1000;
+
+/// an incremental depth update of the websocket stream, levels with a zero amount are removed
+///
+/// `prev_seq` is the `seq` of the update before, a delta only applies on top of exactly that one
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DepthDelta {
+    pub symbol: String,
+    pub prev_seq: u64,
+    pub seq: u64,
+    #[serde(default)]
+    pub bids: Vec<PriceLevel>,
+    #[serde(default)]
+    pub asks: Vec<PriceLevel>,
+    /// CRC-32 of the book after the delta if the gateway sends one, see `orderbook::checksum`
+    #[serde(default)]
+    pub checksum: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaOutcome {
+    Applied,
+    /// no snapshot yet, the delta is replayed once one lands
+    Buffered,
+    /// already contained in the book
+    Outdated,
+    /// an update was missed, the book is stale until `DepthSync::on_snapshot`
+    Gap {
+        expected: u64,
+        got: u64,
+    },
+    /// the book after the delta fails its checksum, it is stale until `DepthSync::on_snapshot`
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+}
+
+/// keeps an `OrderBook` in sync from a REST snapshot and the deltas following it
+///
+/// deltas arriving before the snapshot, or after a gap until the refetched snapshot lands,
+/// are buffered and replayed on top of it, so the book never silently diverges
+#[derive(Debug)]
+pub struct DepthSync {
+    book: OrderBook,
+    seq: Option<u64>,
+    buffer: VecDeque<DepthDelta>,
+}
+
+impl DepthSync {
+    pub fn new(symbol: String) -> Self {
+        DepthSync {
+            book: OrderBook::new(symbol),
+            seq: None,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    /// the `seq` of the last update in the book
+    pub fn seq(&self) -> Option<u64> {
+        self.seq
+    }
+
+    /// true until a snapshot was applied, and again after a gap
+    pub fn needs_snapshot(&self) -> bool {
+        self.seq.is_none()
+    }
+
+    pub fn on_delta(&mut self, delta: DepthDelta) -> DeltaOutcome {
+        match self.seq {
+            None => {
+                self.buffer(delta);
+                DeltaOutcome::Buffered
+            }
+            Some(seq) if delta.seq <= seq => DeltaOutcome::Outdated,
+            Some(seq) if delta.prev_seq != seq => {
+                let got = delta.prev_seq;
+                self.invalidate();
+                self.buffer(delta);
+                DeltaOutcome::Gap { expected: seq, got }
+            }
+            Some(_) => match self.apply(&delta) {
+                Ok(()) => DeltaOutcome::Applied,
+                Err(outcome) => outcome,
+            },
+        }
+    }
+
+    /// load the snapshot taken at `seq` and replay the buffered deltas after it, returns
+    /// how many were replayed; a gap within the buffer leaves the sync waiting for another snapshot
+    pub fn on_snapshot(
+        &mut self,
+        seq: u64,
+        depth: Depth,
+        validator: &BookValidator,
+    ) -> Result<usize, Vec<BookViolation>> {
+        if let Err(violations) = validator.check(&self.book.symbol, &depth) {
+            self.invalidate();
+            return Err(violations);
+        }
+        self.book.set_levels(depth.bids, depth.asks);
+        self.book.stale = false;
+        self.seq = Some(seq);
+        let mut replayed = 0;
+        while let Some(delta) = self.buffer.pop_front() {
+            let current = self.seq.unwrap_or(seq);
+            if delta.seq <= current {
+                continue;
+            }
+            // the first delta after the snapshot may start before it
+            let continues = if replayed == 0 {
+                delta.prev_seq <= current
+            } else {
+                delta.prev_seq == current
+            };
+            if !continues {
+                self.invalidate();
+                self.buffer.push_front(delta);
+                break;
+            }
+            if self.apply(&delta).is_err() {
+                // the deltas after it build on a corrupt book, the next snapshot replaces them
+                self.buffer.clear();
+                break;
+            }
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+
+    /// a book failing the checksum of the delta is invalidated, so it gets a fresh snapshot
+    fn apply(&mut self, delta: &DepthDelta) -> Result<(), DeltaOutcome> {
+        self.book.apply_changes(&delta.bids, &delta.asks);
+        self.seq = Some(delta.seq);
+        if let Some(expected) = delta.checksum {
+            if let Err(BookViolation::Checksum { expected, actual }) =
+                crate::orderbook::verify_checksum(expected, self.book.checksum())
+            {
+                self.invalidate();
+                return Err(DeltaOutcome::ChecksumMismatch { expected, actual });
+            }
+        }
+        Ok(())
+    }
+
+    fn buffer(&mut self, delta: DepthDelta) {
+        if self.buffer.len() >= MAX_BUFFERED_DELTAS {
+            self.buffer.pop_front();
+        }
+        self.buffer.push_back(delta);
+    }
+
+    fn invalidate(&mut self) {
+        self.seq = None;
+        self.book.stale = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    fn level(price: i64, amount: i64) -> PriceLevel {
+        PriceLevel::new(BigDecimal::from(price), BigDecimal::from(amount))
+    }
+
+    fn delta(prev_seq: u64, seq: u64, bids: Vec<PriceLevel>) -> DepthDelta {
+        DepthDelta {
+            symbol: String::from("BTC-USDT"),
+            prev_seq,
+            seq,
+            checksum: None,
+            bids,
+            asks: vec![],
+        }
+    }
+
+    fn snapshot() -> Depth {
+        Depth {
+            depth: 0,
+            seq: Some(10),
+            checksum: None,
+            bids: vec![level(10, 1), level(9, 1)],
+            asks: vec![level(11, 1)],
+        }
+    }
+
+    #[test]
+    fn test_buffered_deltas_replayed_on_snapshot() {
+        let validator = BookValidator::new();
+        let mut sync = DepthSync::new(String::from("BTC-USDT"));
+        assert_eq!(
+            sync.on_delta(delta(8, 9, vec![level(8, 1)])),
+            DeltaOutcome::Buffered
+        );
+        sync.on_delta(delta(9, 11, vec![level(10, 0)]));
+        sync.on_delta(delta(11, 12, vec![level(10, 3)]));
+        assert_eq!(sync.on_snapshot(10, snapshot(), &validator), Ok(2));
+        assert_eq!(sync.seq(), Some(12));
+        assert_eq!(sync.book().bids, vec![level(10, 3), level(9, 1)]);
+        assert!(!sync.book().stale);
+    }
+
+    #[test]
+    fn test_gap_waits_for_snapshot() {
+        let validator = BookValidator::new();
+        let mut sync = DepthSync::new(String::from("BTC-USDT"));
+        sync.on_snapshot(10, snapshot(), &validator).unwrap();
+        assert_eq!(
+            sync.on_delta(delta(10, 11, vec![level(9, 0)])),
+            DeltaOutcome::Applied
+        );
+        assert_eq!(sync.on_delta(delta(10, 11, vec![])), DeltaOutcome::Outdated);
+        assert_eq!(
+            sync.on_delta(delta(12, 13, vec![level(8, 2)])),
+            DeltaOutcome::Gap {
+                expected: 11,
+                got: 12
+            }
+        );
+        assert!(sync.needs_snapshot());
+        assert!(sync.book().stale);
+
+        let mut refetched = snapshot();
+        refetched.bids = vec![level(10, 1)];
+        assert_eq!(sync.on_snapshot(12, refetched, &validator), Ok(1));
+        assert_eq!(sync.book().bids, vec![level(10, 1), level(8, 2)]);
+        assert_eq!(sync.seq(), Some(13));
+    }
+
+    #[test]
+    fn test_checksum_mismatch_invalidates() {
+        let validator = BookValidator::new();
+        let mut sync = DepthSync::new(String::from("BTC-USDT"));
+        sync.on_snapshot(10, snapshot(), &validator).unwrap();
+        let mut good = delta(10, 11, vec![level(9, 2)]);
+        good.checksum = Some(i64::from(crate::orderbook::checksum(
+            &[level(10, 1), level(9, 2)],
+            &[level(11, 1)],
+        )));
+        assert_eq!(sync.on_delta(good), DeltaOutcome::Applied);
+
+        let mut bad = delta(11, 12, vec![level(9, 0)]);
+        bad.checksum = Some(1);
+        assert!(matches!(
+            sync.on_delta(bad),
+            DeltaOutcome::ChecksumMismatch { expected: 1, .. }
+        ));
+        assert!(sync.needs_snapshot());
+    }
+}
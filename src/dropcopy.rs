@@ -0,0 +1,366 @@
+//! a drop copy of every order placement, cancel and fill in one stable schema, modelled on the
+//! FIX execution report, for middle-office systems which should not scrape logs
+//!
+//! fills are found when an order is read back, by `query_order_by_id`, `open_orders` or any
+//! other order query, and reported once with the quantity filled since the last read
+//!
+//! Kafka, NATS or any other bus plugs in as a `DropCopySink`, `LineSink` writes JSON lines to
+//! the sinks of `audit` and `channel` hands the reports to a task of the caller
+
+use crate::audit::AuditSink;
+use crate::request::{NewOrder, OrderStatus};
+use crate::response::QueryOrder;
+use bigdecimal::{BigDecimal, Signed, Zero};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+/// the version of `ExecutionReport`, bumped only when a field changes its meaning or goes away
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// FIX tag 150
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecType {
+    New,
+    PartialFill,
+    Fill,
+    Canceled,
+    Rejected,
+}
+
+impl ExecType {
+    /// the value of FIX 4.2 tag 150
+    pub fn fix_code(&self) -> char {
+        match self {
+            ExecType::New => '0',
+            ExecType::PartialFill => '1',
+            ExecType::Fill => '2',
+            ExecType::Canceled => '4',
+            ExecType::Rejected => '8',
+        }
+    }
+}
+
+/// one order event; quantities and prices are decimal strings, absent fields are left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub schema: u32,
+    /// gapless per client from 1, a gap means a report was lost by the sink
+    pub seq: u64,
+    /// unix milliseconds the client saw the event at
+    pub transact_time: i64,
+    pub exec_type: ExecType,
+    pub symbol: String,
+    /// absent on a rejection, which never got one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<String>,
+    /// `BID` or `ASK`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<BigDecimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_qty: Option<BigDecimal>,
+    /// base filled by this fill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_qty: Option<BigDecimal>,
+    /// mean price of this fill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_px: Option<BigDecimal>,
+    /// base filled in total
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cum_qty: Option<BigDecimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_px: Option<BigDecimal>,
+    /// why an order was rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl ExecutionReport {
+    fn new(exec_type: ExecType, symbol: &str) -> Self {
+        ExecutionReport {
+            schema: SCHEMA_VERSION,
+            seq: 0,
+            transact_time: 0,
+            exec_type,
+            symbol: symbol.to_string(),
+            order_id: None,
+            side: None,
+            price: None,
+            order_qty: None,
+            last_qty: None,
+            last_px: None,
+            cum_qty: None,
+            avg_px: None,
+            text: None,
+        }
+    }
+
+    fn order(exec_type: ExecType, order: &NewOrder, order_id: Option<&str>) -> Self {
+        ExecutionReport {
+            order_id: order_id.map(str::to_string),
+            side: order.side().map(|side| side.to_string()),
+            price: order.price.clone(),
+            order_qty: Some(order.amount.clone()),
+            ..ExecutionReport::new(exec_type, &order.symbol)
+        }
+    }
+}
+
+/// where the drop copy goes, see `FxdxBuilder::drop_copy`
+///
+/// called on the task of the request, so a slow sink should hand the report on rather than
+/// block; a failed report is logged and not retried
+pub trait DropCopySink: Send + Sync {
+    fn send(&self, report: &ExecutionReport) -> std::io::Result<()>;
+}
+
+/// every report as one JSON line to an `audit::AuditSink`, e.g. a file or syslog
+pub struct LineSink<S> {
+    sink: S,
+}
+
+impl<S: AuditSink> LineSink<S> {
+    pub fn new(sink: S) -> Self {
+        LineSink { sink }
+    }
+}
+
+impl<S: AuditSink> DropCopySink for LineSink<S> {
+    fn send(&self, report: &ExecutionReport) -> std::io::Result<()> {
+        self.sink.write_line(&serde_json::to_string(report)?)
+    }
+}
+
+/// the sending half of `channel`
+#[derive(Debug, Clone)]
+pub struct ChannelSink {
+    sender: mpsc::UnboundedSender<ExecutionReport>,
+}
+
+impl DropCopySink for ChannelSink {
+    fn send(&self, report: &ExecutionReport) -> std::io::Result<()> {
+        self.sender
+            .send(report.clone())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "receiver dropped"))
+    }
+}
+
+/// a sink whose reports arrive at the receiver, e.g. for a task publishing them to a bus
+pub fn channel() -> (ChannelSink, mpsc::UnboundedReceiver<ExecutionReport>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (ChannelSink { sender }, receiver)
+}
+
+/// numbers the reports and remembers how much of every open order was already reported filled
+pub(crate) struct DropCopy {
+    sink: Box<dyn DropCopySink>,
+    seq: AtomicU64,
+    /// every order placed or seen open, until it is read back closed
+    filled: Mutex<HashMap<String, Watermark>>,
+}
+
+/// what was reported of an order so far
+#[derive(Debug, Default)]
+struct Watermark {
+    base: BigDecimal,
+    quote: BigDecimal,
+    /// by `cancelled`, the order may still have filled before the cancel went through
+    cancelled: bool,
+}
+
+impl DropCopy {
+    pub(crate) fn new<S: DropCopySink + 'static>(sink: S) -> Self {
+        DropCopy {
+            sink: Box::new(sink),
+            seq: AtomicU64::new(0),
+            filled: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn emit(&self, mut report: ExecutionReport) {
+        report.seq = self.seq.fetch_add(1, Ordering::SeqCst) + 1;
+        report.transact_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+        if let Err(e) = self.sink.send(&report) {
+            log::error!(
+                "drop copy {} of order {:?} lost: {}",
+                report.seq,
+                report.order_id,
+                e
+            );
+        }
+    }
+
+    pub(crate) fn placed(&self, order: &NewOrder, order_id: &str) {
+        self.filled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(order_id.to_string(), Default::default());
+        self.emit(ExecutionReport::order(ExecType::New, order, Some(order_id)));
+    }
+
+    pub(crate) fn rejected(&self, order: &NewOrder, reason: String) {
+        self.emit(ExecutionReport {
+            text: Some(reason),
+            ..ExecutionReport::order(ExecType::Rejected, order, None)
+        });
+    }
+
+    /// the watermark of the order is kept, a fill between its last read and the cancel is
+    /// reported when it is read back closed
+    pub(crate) fn cancelled(&self, symbol: &str, order_id: &str) {
+        if let Some(watermark) = self
+            .filled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(order_id)
+        {
+            watermark.cancelled = true;
+        }
+        self.emit(ExecutionReport {
+            order_id: Some(order_id.to_string()),
+            ..ExecutionReport::new(ExecType::Canceled, symbol)
+        });
+    }
+
+    /// report what `order` filled since it was last seen and its cancel by someone else; an
+    /// order placed by another client or before a restart is only followed from the first
+    /// time it is seen open, so its earlier fills are not reported twice
+    pub(crate) fn observe(&self, order: &QueryOrder) {
+        let open = matches!(
+            order.status,
+            OrderStatus::Undeal | OrderStatus::PartialDealed
+        );
+        let mut filled = self.filled.lock().unwrap_or_else(|e| e.into_inner());
+        let before = if open {
+            let watermark = filled
+                .entry(order.order_id.clone())
+                .or_insert_with(|| Watermark {
+                    base: order.filled_base.clone(),
+                    quote: order.filled_quote.clone(),
+                    cancelled: false,
+                });
+            Watermark {
+                base: std::mem::replace(&mut watermark.base, order.filled_base.clone()),
+                quote: std::mem::replace(&mut watermark.quote, order.filled_quote.clone()),
+                cancelled: watermark.cancelled,
+            }
+        } else {
+            match filled.remove(&order.order_id) {
+                Some(watermark) => watermark,
+                None => return,
+            }
+        };
+        drop(filled);
+        if order.filled_base > before.base {
+            self.filled_since(order, before.base, before.quote);
+        }
+        if order.status == OrderStatus::Cancel && !before.cancelled {
+            self.emit(ExecutionReport {
+                order_id: Some(order.order_id.clone()),
+                ..ExecutionReport::new(ExecType::Canceled, &order.symbol)
+            });
+        }
+    }
+
+    fn filled_since(&self, order: &QueryOrder, base_before: BigDecimal, quote_before: BigDecimal) {
+        let last_qty = &order.filled_base - &base_before;
+        let last_quote = &order.filled_quote - &quote_before;
+        let last_px = if last_quote.is_positive() {
+            Some(last_quote / &last_qty)
+        } else if base_before.is_zero() && !order.avg_price.is_zero() {
+            Some(order.avg_price.clone())
+        } else {
+            None
+        };
+        let exec_type = match order.status {
+            OrderStatus::Dealed => ExecType::Fill,
+            _ => ExecType::PartialFill,
+        };
+        self.emit(ExecutionReport {
+            order_id: Some(order.order_id.clone()),
+            side: Some(order.direction.to_string()),
+            price: (!order.price.is_zero()).then(|| order.price.clone()),
+            order_qty: Some(order.amount.clone()),
+            last_qty: Some(last_qty),
+            last_px,
+            cum_qty: Some(order.filled_base.clone()),
+            avg_px: (!order.avg_price.is_zero()).then(|| order.avg_price.clone()),
+            ..ExecutionReport::new(exec_type, &order.symbol)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(status: i32, filled_base: &str, filled_quote: &str) -> QueryOrder {
+        serde_json::from_str(&format!(
+            r#"{{"symbol":"BTC-USDT","order_id":"7","order_type":1,"direction":1,"amount":"2","price":"10","filled_base":"{}","filled_quote":"{}","status":{}}}"#,
+            filled_base, filled_quote, status
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fills_reported_once() {
+        let (sink, mut reports) = channel();
+        let copy = DropCopy::new(sink);
+        // first seen open with a fill from before, only followed from here on
+        copy.observe(&order(4, "0.5", "5"));
+        copy.observe(&order(4, "0.5", "5"));
+        assert!(reports.try_recv().is_err());
+
+        copy.observe(&order(4, "1.5", "14.5"));
+        let fill = reports.try_recv().unwrap();
+        assert_eq!(
+            (fill.seq, fill.exec_type, fill.exec_type.fix_code()),
+            (1, ExecType::PartialFill, '1')
+        );
+        assert_eq!(fill.last_qty, Some("1".parse().unwrap()));
+        assert_eq!(fill.last_px, Some("9.5".parse().unwrap()));
+        assert_eq!(fill.cum_qty, Some("1.5".parse().unwrap()));
+
+        copy.observe(&order(3, "2", "19.5"));
+        let fill = reports.try_recv().unwrap();
+        assert_eq!((fill.seq, fill.exec_type), (2, ExecType::Fill));
+        assert_eq!(fill.last_qty, Some("0.5".parse().unwrap()));
+        // a closed order is forgotten, reading it again reports nothing
+        copy.observe(&order(3, "2", "19.5"));
+        assert!(reports.try_recv().is_err());
+
+        let line = serde_json::to_value(&fill).unwrap();
+        assert_eq!(line["exec_type"], "fill");
+        assert_eq!(line["side"], "BID");
+        assert!(line.get("text").is_none());
+    }
+
+    #[test]
+    fn test_fill_before_cancel_reported() {
+        let (sink, mut reports) = channel();
+        let copy = DropCopy::new(sink);
+        copy.observe(&order(4, "0.5", "5"));
+        copy.cancelled("BTC-USDT", "7");
+        let cancelled = reports.try_recv().unwrap();
+        assert_eq!(cancelled.exec_type, ExecType::Canceled);
+
+        // filled some more between the last read and the cancel, found by the terminal read
+        copy.observe(&order(2, "1.25", "12.5"));
+        let fill = reports.try_recv().unwrap();
+        assert_eq!(fill.exec_type, ExecType::PartialFill);
+        assert_eq!(fill.last_qty, Some("0.75".parse().unwrap()));
+        assert_eq!(fill.cum_qty, Some("1.25".parse().unwrap()));
+        // the cancel itself was already reported
+        assert!(reports.try_recv().is_err());
+
+        copy.observe(&order(2, "1.25", "12.5"));
+        assert!(reports.try_recv().is_err());
+    }
+}
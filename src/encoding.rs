@@ -0,0 +1,231 @@
+//! the parts of a signed request that need no transport: uri paths, the formalized parameter
+//! string, the signature payload and the signature headers
+//!
+//! only `core` and `alloc` are used here, so an enclave or HSM that holds the secret can build
+//! and sign requests with its own MAC and hand them to whatever transport it has, see `sign`
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Write};
+
+pub const TIMESTAMP_HEADER: &str = "X-Timestamp";
+pub const ADDRESS_HEADER: &str = "X-Address";
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+/// the session token of keys with a handshake
+pub const TOKEN_HEADER: &str = "X-Token";
+/// the increasing nonce of a request of a token session, see `nonce`
+pub const NONCE_HEADER: &str = "X-Nonce";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Method {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
+            Method::Delete => "DELETE",
+        }
+    }
+}
+
+/// how the HMAC digest is written into the `X-Signature` header
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+impl SignatureEncoding {
+    pub fn encode(&self, digest: &[u8]) -> String {
+        match self {
+            SignatureEncoding::Hex => hex(digest),
+            SignatureEncoding::Base64 => base64(digest),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        out.push(char::from(DIGITS[usize::from(byte >> 4)]));
+        out.push(char::from(DIGITS[usize::from(byte & 0xf)]));
+    }
+    out
+}
+
+/// standard alphabet with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 63]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// `/{prefix}/{segment}/..`, the way every endpoint but the nonce is addressed
+pub fn path(prefix: &str, segments: &[&str]) -> String {
+    let mut uri = String::from("/");
+    uri.push_str(prefix);
+    for segment in segments {
+        uri.push('/');
+        uri.push_str(segment);
+    }
+    uri
+}
+
+/// `path` appended to `out`, numbers are written in place instead of through `to_string`, so
+/// nothing is allocated once `out` has grown to the longest uri
+pub fn write_path(out: &mut String, prefix: &str, segments: &[&dyn Display]) {
+    out.push('/');
+    out.push_str(prefix);
+    for segment in segments {
+        let _ = write!(out, "/{}", segment);
+    }
+}
+
+/// the formalized parameters of a request, its fields joined by commas in signing order
+pub fn fields(values: &[&str]) -> String {
+    values.join(",")
+}
+
+/// `fields` appended to `out`, see `write_path`
+pub fn write_fields(out: &mut String, values: &[&dyn Display]) {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{}", value);
+    }
+}
+
+/// the formalized order: `amount,kind,price,symbol,side`, without the price for market orders
+pub fn order_fields(
+    amount: &str,
+    kind: &str,
+    price: Option<&str>,
+    symbol: &str,
+    side: &str,
+) -> String {
+    match price {
+        Some(price) => fields(&[amount, kind, price, symbol, side]),
+        None => fields(&[amount, kind, symbol, side]),
+    }
+}
+
+/// the string a request is signed over: `timestamp,uri` followed by `,formalized`
+/// when the request has parameters
+pub fn signature_payload(timestamp: &str, uri: &str, formalized: Option<&str>) -> String {
+    match formalized {
+        Some(formalized) => fields(&[timestamp, uri, formalized]),
+        None => fields(&[timestamp, uri]),
+    }
+}
+
+/// everything of a request that goes into its signature, see `Request::encode`
+///
+/// the default is an empty GET with no buffers allocated, see `Request::encode_with`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Encoded {
+    pub method: Method,
+    pub uri: String,
+    pub formalized: Option<String>,
+    /// the JSON body, if any
+    pub payload: Option<String>,
+}
+
+/// a request ready for the transport: send `method` to the endpoint followed by `uri`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedRequest {
+    pub method: Method,
+    pub uri: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Option<String>,
+}
+
+/// sign `request` with `mac`, which computes the HMAC-SHA1 of the payload with the secret;
+/// `request` is only borrowed, so its buffers can be signed again or reused for the next one
+pub fn sign<E>(
+    request: &Encoded,
+    timestamp: &str,
+    address: &str,
+    encoding: SignatureEncoding,
+    mac: impl FnOnce(&[u8]) -> Result<Vec<u8>, E>,
+) -> Result<SignedRequest, E> {
+    let payload = signature_payload(timestamp, &request.uri, request.formalized.as_deref());
+    let signature = encoding.encode(&mac(payload.as_bytes())?);
+    Ok(SignedRequest {
+        method: request.method,
+        uri: request.uri.clone(),
+        headers: alloc::vec![
+            (TIMESTAMP_HEADER, String::from(timestamp)),
+            (ADDRESS_HEADER, String::from(address)),
+            (SIGNATURE_HEADER, signature),
+        ],
+        body: request.payload.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encodings() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(SignatureEncoding::Base64.encode(bytes), encoded);
+        }
+        assert_eq!(SignatureEncoding::Hex.encode(&[0, 0xab, 0x1f]), "00ab1f");
+    }
+
+    #[test]
+    fn test_sign_with_own_mac() {
+        let request = Encoded {
+            method: Method::Get,
+            uri: path("/maker", &["depth", "BTC-USDT"]),
+            formalized: Some(fields(&["BTC-USDT"])),
+            payload: None,
+        };
+        let signed = sign(
+            &request,
+            "1650000000",
+            "0xabc",
+            SignatureEncoding::Hex,
+            |payload| {
+                assert_eq!(payload, b"1650000000,//maker/depth/BTC-USDT,BTC-USDT");
+                Ok::<_, ()>(alloc::vec![0xde, 0xad])
+            },
+        )
+        .unwrap();
+        assert_eq!(signed.uri, "//maker/depth/BTC-USDT");
+        assert_eq!(signed.headers[2], (SIGNATURE_HEADER, String::from("dead")));
+    }
+}
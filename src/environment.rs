@@ -0,0 +1,34 @@
+use serde::Deserialize;
+
+/// the deployments of fxdx, `FxdxBuilder::environment` takes the endpoints and quirks from here
+/// so switching between them does not mean editing URLs
+///
+/// both serve the same prefixes, `/maker` for `request::PrivPub` keys and `/api` for the others
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    #[default]
+    Mainnet,
+    Testnet,
+}
+
+impl Environment {
+    /// the gateways of the environment, primary first
+    pub fn endpoints(&self) -> Vec<String> {
+        let endpoints: &[&str] = match self {
+            Environment::Mainnet => &["https://api.fxdx.finance"],
+            Environment::Testnet => &["https://test-api.fxdx.finance"],
+        };
+        endpoints.iter().map(|e| e.to_string()).collect()
+    }
+
+    /// the testnet lists and delists pairs without notice, its `/symbols` often lags behind
+    /// what it trades, so `symbols::SymbolRegistry::check_order` lets unknown pairs through
+    pub fn relaxed_symbols(&self) -> bool {
+        matches!(self, Environment::Testnet)
+    }
+
+    pub fn is_testnet(&self) -> bool {
+        matches!(self, Environment::Testnet)
+    }
+}
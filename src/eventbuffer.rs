@@ -0,0 +1,240 @@
+use crate::marketdata::MarketEvent;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// what happens when a subscriber reads slower than its events arrive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BufferPolicy {
+    /// wait for room, which stalls the whole session until the subscriber caught up
+    Block(usize),
+    /// drop the oldest queued event to make room
+    DropOldest(usize),
+    /// keep only the latest queued book of a symbol, other events drop the oldest beyond the capacity
+    CoalesceDepth(usize),
+}
+
+impl Default for BufferPolicy {
+    fn default() -> Self {
+        BufferPolicy::Block(1024)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    queue: VecDeque<MarketEvent>,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    policy: BufferPolicy,
+    state: Mutex<State>,
+    dropped: AtomicU64,
+    readable: Notify,
+    writable: Notify,
+}
+
+impl Shared {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// a queue of market events between one producer and one subscriber, bounded by its policy
+pub fn event_buffer(policy: BufferPolicy) -> (EventSender, EventReceiver) {
+    let shared = Arc::new(Shared {
+        policy,
+        state: Default::default(),
+        dropped: AtomicU64::new(0),
+        readable: Notify::new(),
+        writable: Notify::new(),
+    });
+    (
+        EventSender {
+            shared: shared.clone(),
+        },
+        EventReceiver { shared },
+    )
+}
+
+#[derive(Debug)]
+pub struct EventSender {
+    shared: Arc<Shared>,
+}
+
+impl EventSender {
+    /// queue `event` according to the policy, false once the receiver is gone
+    pub async fn send(&self, event: MarketEvent) -> bool {
+        let mut event = Some(event);
+        loop {
+            {
+                let mut state = self.shared.state();
+                if state.receiver_closed {
+                    return false;
+                }
+                let dropped = match self.shared.policy {
+                    BufferPolicy::Block(capacity) if state.queue.len() >= capacity.max(1) => None,
+                    BufferPolicy::Block(_) => Some(0),
+                    BufferPolicy::DropOldest(capacity) => {
+                        Some(make_room(&mut state.queue, capacity.max(1)))
+                    }
+                    BufferPolicy::CoalesceDepth(capacity) => {
+                        let replaced = match &event {
+                            Some(MarketEvent::Book(book)) => state.queue.iter().position(
+                                |queued| matches!(queued, MarketEvent::Book(b) if b.symbol == book.symbol),
+                            ),
+                            _ => None,
+                        };
+                        match replaced {
+                            Some(at) => {
+                                state.queue.remove(at);
+                                Some(1)
+                            }
+                            None => Some(make_room(&mut state.queue, capacity.max(1))),
+                        }
+                    }
+                };
+                if let Some(dropped) = dropped {
+                    self.shared.dropped.fetch_add(dropped, Ordering::Relaxed);
+                    state.queue.extend(event.take());
+                    self.shared.readable.notify_one();
+                    return true;
+                }
+            }
+            self.shared.writable.notified().await;
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.state().receiver_closed
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        self.shared.state().sender_closed = true;
+        self.shared.readable.notify_one();
+    }
+}
+
+fn make_room(queue: &mut VecDeque<MarketEvent>, capacity: usize) -> u64 {
+    let mut dropped = 0;
+    while queue.len() >= capacity {
+        queue.pop_front();
+        dropped += 1;
+    }
+    dropped
+}
+
+#[derive(Debug)]
+pub struct EventReceiver {
+    shared: Arc<Shared>,
+}
+
+impl EventReceiver {
+    /// the next event, `None` once the session ended and the queue is drained
+    pub async fn recv(&mut self) -> Option<MarketEvent> {
+        loop {
+            {
+                let mut state = self.shared.state();
+                if let Some(event) = state.queue.pop_front() {
+                    self.shared.writable.notify_one();
+                    return Some(event);
+                }
+                if state.sender_closed {
+                    return None;
+                }
+            }
+            self.shared.readable.notified().await;
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Option<MarketEvent> {
+        let event = self.shared.state().queue.pop_front();
+        if event.is_some() {
+            self.shared.writable.notify_one();
+        }
+        event
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.state().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// events discarded or coalesced away so far
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.shared.state().receiver_closed = true;
+        self.shared.writable.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::OrderBook;
+    use std::time::Duration;
+
+    fn gap(symbol: &str) -> MarketEvent {
+        MarketEvent::Gap {
+            symbol: symbol.to_string(),
+        }
+    }
+
+    fn book(symbol: &str, stale: bool) -> MarketEvent {
+        let mut book = OrderBook::new(symbol.to_string());
+        book.stale = stale;
+        MarketEvent::Book(book)
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let (tx, mut rx) = event_buffer(BufferPolicy::DropOldest(2));
+        for symbol in ["A", "B", "C"] {
+            assert!(tx.send(gap(symbol)).await);
+        }
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(rx.recv().await.unwrap().symbol(), "B");
+        assert_eq!(rx.recv().await.unwrap().symbol(), "C");
+        drop(tx);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_depth() {
+        let (tx, mut rx) = event_buffer(BufferPolicy::CoalesceDepth(8));
+        tx.send(book("A", true)).await;
+        tx.send(gap("B")).await;
+        tx.send(book("A", false)).await;
+        assert_eq!(rx.len(), 2);
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(rx.try_recv().unwrap().symbol(), "B");
+        assert!(matches!(rx.try_recv(), Some(MarketEvent::Book(b)) if !b.stale));
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let (tx, mut rx) = event_buffer(BufferPolicy::Block(1));
+        tx.send(gap("A")).await;
+        let blocked = tokio::time::timeout(Duration::from_millis(50), tx.send(gap("B"))).await;
+        assert!(blocked.is_err());
+        let (sent, received) = tokio::join!(tx.send(gap("B")), rx.recv());
+        assert!(sent);
+        assert_eq!(received.unwrap().symbol(), "A");
+        assert_eq!(rx.dropped(), 0);
+        drop(rx);
+        assert!(!tx.send(gap("C")).await);
+    }
+}
@@ -0,0 +1,358 @@
+//! a venue neutral client trait, so fxdx is one adapter among several in a multi-exchange
+//! system; symbols are `BASE-QUOTE` and every type here converts from the fxdx responses
+
+use crate::fees::Fee;
+use crate::request::{self, NewOrder, OrderKind, OrderStatus, Request, Scale, Side};
+use crate::response;
+use crate::FxdxClient;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use std::future::Future;
+use std::pin::Pin;
+
+pub type ExchangeFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    pub symbol: String,
+    pub side: Side,
+    pub kind: OrderKind,
+    /// `None` for market orders only
+    pub price: Option<BigDecimal>,
+    pub amount: BigDecimal,
+}
+
+impl TryFrom<OrderRequest> for NewOrder {
+    type Error = anyhow::Error;
+
+    fn try_from(order: OrderRequest) -> Result<Self> {
+        NewOrder::new(
+            order.side,
+            order.kind,
+            order.symbol,
+            order.price,
+            order.amount,
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OrderState {
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+}
+
+impl OrderState {
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderState::Open | OrderState::PartiallyFilled)
+    }
+}
+
+impl From<OrderStatus> for OrderState {
+    fn from(status: OrderStatus) -> Self {
+        match status {
+            OrderStatus::Undeal => OrderState::Open,
+            OrderStatus::PartialDealed => OrderState::PartiallyFilled,
+            OrderStatus::Dealed => OrderState::Filled,
+            OrderStatus::Cancel => OrderState::Cancelled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+    pub fee: Fee,
+    /// unix seconds
+    pub timestamp: i64,
+}
+
+impl From<response::Trade> for Fill {
+    fn from(trade: response::Trade) -> Self {
+        Fill {
+            fee: crate::fees::actual_fees(&trade),
+            price: trade.price,
+            amount: trade.amount,
+            timestamp: trade.timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub id: String,
+    pub symbol: String,
+    pub side: Side,
+    /// `None` for market orders
+    pub price: Option<BigDecimal>,
+    pub amount: BigDecimal,
+    pub filled: BigDecimal,
+    /// the quote value of what filled
+    pub filled_quote: BigDecimal,
+    /// `None` until the first fill
+    pub avg_price: Option<BigDecimal>,
+    pub state: OrderState,
+    pub fills: Vec<Fill>,
+}
+
+impl From<response::QueryOrder> for Order {
+    fn from(order: response::QueryOrder) -> Self {
+        let nonzero = |value: BigDecimal| (!value.is_zero()).then_some(value);
+        Order {
+            id: order.order_id,
+            symbol: order.symbol,
+            side: order.direction,
+            price: nonzero(order.price),
+            amount: order.amount,
+            filled: order.filled_base,
+            filled_quote: order.filled_quote,
+            avg_price: nonzero(order.avg_price),
+            state: order.status.into(),
+            fills: order.trades.into_iter().map(Fill::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetBalance {
+    pub asset: String,
+    pub available: BigDecimal,
+    /// held by open orders
+    pub locked: BigDecimal,
+}
+
+impl AssetBalance {
+    pub fn total(&self) -> BigDecimal {
+        &self.available + &self.locked
+    }
+}
+
+impl From<response::Balance> for AssetBalance {
+    fn from(balance: response::Balance) -> Self {
+        AssetBalance {
+            asset: balance.name,
+            available: balance.available,
+            locked: balance.frozen,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+impl From<response::PriceLevel> for Level {
+    fn from(level: response::PriceLevel) -> Self {
+        Level {
+            price: level.price,
+            amount: level.amount,
+        }
+    }
+}
+
+/// bids best first descending, asks best first ascending
+#[derive(Debug, Clone, PartialEq)]
+pub struct Book {
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+    /// the venue's update sequence if it has one
+    pub seq: Option<u64>,
+}
+
+impl From<response::Depth> for Book {
+    fn from(depth: response::Depth) -> Self {
+        Book {
+            bids: depth.bids.into_iter().map(Level::from).collect(),
+            asks: depth.asks.into_iter().map(Level::from).collect(),
+            seq: depth.seq,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candle {
+    /// unix seconds
+    pub open_time: i64,
+    pub open: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub close: BigDecimal,
+    /// in base
+    pub volume: BigDecimal,
+    pub quote_volume: Option<BigDecimal>,
+}
+
+impl From<response::Kline> for Candle {
+    fn from(kline: response::Kline) -> Self {
+        Candle {
+            open_time: kline.id,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.vol,
+            quote_volume: kline.turnover,
+        }
+    }
+}
+
+/// a tradable pair and its trading rules
+#[derive(Debug, Clone, PartialEq)]
+pub struct Market {
+    pub symbol: String,
+    pub base: String,
+    pub quote: String,
+    pub price_tick: BigDecimal,
+    pub amount_step: BigDecimal,
+    pub min_amount: BigDecimal,
+    /// the smallest `price * amount`
+    pub min_notional: BigDecimal,
+    pub maker_fee: BigDecimal,
+    pub taker_fee: BigDecimal,
+}
+
+impl From<response::Symbol> for Market {
+    fn from(symbol: response::Symbol) -> Self {
+        Market {
+            symbol: symbol.pair(),
+            price_tick: symbol.price_tick(),
+            amount_step: symbol.amount_step(),
+            base: symbol.base_name,
+            quote: symbol.quote_name,
+            min_amount: symbol.min_amount,
+            min_notional: symbol.min_vol,
+            maker_fee: symbol.make_fee,
+            taker_fee: symbol.taker_fee,
+        }
+    }
+}
+
+/// what a strategy needs from a venue, implemented by `FxdxClient` and by
+/// `backtest::SimulatedExchange`, so the same strategy code runs live and in a backtest
+pub trait ExchangeClient: Send + Sync {
+    /// the name of the venue, e.g. to key the clients of a multi-exchange system
+    fn venue(&self) -> &str;
+
+    /// returns the id of the order
+    fn place_order(&self, order: OrderRequest) -> ExchangeFuture<'_, String>;
+
+    /// returns the id of the cancelled order
+    fn cancel_order<'a>(&'a self, symbol: &'a str, order_id: &'a str)
+        -> ExchangeFuture<'a, String>;
+
+    fn order<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> ExchangeFuture<'a, Order>;
+
+    fn open_orders<'a>(&'a self, symbol: &'a str) -> ExchangeFuture<'a, Vec<Order>>;
+
+    fn balances(&self) -> ExchangeFuture<'_, Vec<AssetBalance>>;
+
+    /// `limit` levels of each side, `None` for the full book
+    fn depth<'a>(&'a self, symbol: &'a str, limit: Option<u32>) -> ExchangeFuture<'a, Book>;
+
+    fn klines<'a>(&'a self, symbol: &'a str, scale: Scale) -> ExchangeFuture<'a, Vec<Candle>>;
+
+    fn markets(&self) -> ExchangeFuture<'_, Vec<Market>>;
+}
+
+/// convert every item of a response list
+pub(crate) fn convert<T, U: From<T>>(items: Vec<T>) -> Vec<U> {
+    items.into_iter().map(U::from).collect()
+}
+
+impl<P> ExchangeClient for FxdxClient<P>
+where
+    P: request::Prefix + Send + Sync,
+{
+    fn venue(&self) -> &str {
+        "fxdx"
+    }
+
+    fn place_order(&self, order: OrderRequest) -> ExchangeFuture<'_, String> {
+        Box::pin(async move {
+            self.pending_order(Request::PendingOrder(order.try_into()?))
+                .await
+        })
+    }
+
+    fn cancel_order<'a>(
+        &'a self,
+        symbol: &'a str,
+        order_id: &'a str,
+    ) -> ExchangeFuture<'a, String> {
+        Box::pin(FxdxClient::cancel_order(
+            self,
+            Request::CancelOrder {
+                symbol: symbol.to_string(),
+                order_id: order_id.to_string(),
+            },
+        ))
+    }
+
+    fn order<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> ExchangeFuture<'a, Order> {
+        Box::pin(async move { Ok(self.order_by_id(symbol, order_id).await?.into()) })
+    }
+
+    fn open_orders<'a>(&'a self, symbol: &'a str) -> ExchangeFuture<'a, Vec<Order>> {
+        Box::pin(async move { Ok(convert(FxdxClient::open_orders(self, symbol).await?)) })
+    }
+
+    fn balances(&self) -> ExchangeFuture<'_, Vec<AssetBalance>> {
+        Box::pin(async move { Ok(convert(FxdxClient::balances(self).await?)) })
+    }
+
+    fn depth<'a>(&'a self, symbol: &'a str, limit: Option<u32>) -> ExchangeFuture<'a, Book> {
+        Box::pin(async move {
+            let depth = self
+                .query_depth(Request::Depth {
+                    symbol: symbol.to_string(),
+                    limit,
+                })
+                .await?;
+            Ok(depth.into())
+        })
+    }
+
+    fn klines<'a>(&'a self, symbol: &'a str, scale: Scale) -> ExchangeFuture<'a, Vec<Candle>> {
+        Box::pin(async move {
+            let klines = self
+                .query_kline(Request::Kline {
+                    symbol: symbol.to_string(),
+                    scale,
+                })
+                .await?;
+            Ok(convert(klines))
+        })
+    }
+
+    fn markets(&self) -> ExchangeFuture<'_, Vec<Market>> {
+        Box::pin(async move { Ok(convert(self.query_symbols(Request::Symbols).await?)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_order_converts() {
+        let order: response::QueryOrder = serde_json::from_str(
+            r#"{"symbol":"BTC-USDT","order_id":7,"order_type":1,"direction":1,"amount":"2",
+            "price":"0","filled_base":"1","filled_quote":"100","avg_price":"100","status":4,
+            "trades":[{"base":1,"quote":0,"ask_or_bid":1,"price":"100","amount":"1",
+            "quote_amount":"100","base_fee":"0.001","quote_fee":null,"timestamp":1650000000}]}"#,
+        )
+        .unwrap();
+        let order = Order::from(order);
+        assert_eq!(order.id, "7");
+        assert_eq!(order.price, None);
+        assert_eq!(order.avg_price, Some(BigDecimal::from(100)));
+        assert_eq!(order.state, OrderState::PartiallyFilled);
+        assert!(order.state.is_open());
+        assert_eq!(order.fills[0].fee.base, "0.001".parse().unwrap());
+    }
+}
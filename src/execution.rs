@@ -0,0 +1,500 @@
+//! TWAP execution: a parent order sent as evenly sized children over a schedule
+//!
+//! children go through `exchange::ExchangeClient`, so the same schedule runs against
+//! `FxdxClient`, whose rate limiter they pass like every other order, and in a backtest;
+//! they are kept in the `reconcile::OrderTracker` of the strategy while they work
+
+use crate::exchange::{ExchangeClient, OrderRequest, OrderState};
+use crate::reconcile::OrderTracker;
+use crate::request::{NewOrder, OrderKind, Side};
+use crate::rounding;
+use crate::Error;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// wait after a 429 without `Retry-After` before the child is sent again
+const RATE_LIMIT_PAUSE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct TwapPlan {
+    pub symbol: String,
+    pub side: Side,
+    /// the parent amount in base
+    pub amount: BigDecimal,
+    pub slices: u32,
+    /// between two children, each child works for one interval before the rest is cancelled
+    pub interval: Duration,
+    /// children rest at this price, `None` sends market children
+    pub price: Option<BigDecimal>,
+    /// child amounts are rounded down to it, e.g. `exchange::Market::amount_step`
+    pub amount_step: Option<BigDecimal>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Control {
+    Run,
+    Pause,
+    Cancel,
+}
+
+/// steers a running `Twap` from another task
+#[derive(Debug, Clone)]
+pub struct TwapHandle {
+    control: Arc<watch::Sender<Control>>,
+}
+
+impl TwapHandle {
+    /// no further children until `resume`, the working child keeps working
+    pub fn pause(&self) {
+        self.set(Control::Pause);
+    }
+
+    /// continue the schedule, a slice that fell due while paused is sent at once
+    pub fn resume(&self) {
+        self.set(Control::Run);
+    }
+
+    /// drop the rest of the schedule and cancel the working child
+    pub fn cancel(&self) {
+        self.set(Control::Cancel);
+    }
+
+    fn set(&self, control: Control) {
+        self.control.send_if_modified(|current| {
+            let changed = *current != control && *current != Control::Cancel;
+            if changed {
+                *current = control;
+            }
+            changed
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildOrder {
+    pub id: String,
+    pub amount: BigDecimal,
+    pub filled: BigDecimal,
+    pub state: OrderState,
+}
+
+#[derive(Debug, Clone)]
+pub struct TwapReport {
+    /// the parent amount
+    pub amount: BigDecimal,
+    pub children: Vec<ChildOrder>,
+    /// set when the schedule was cut short by `TwapHandle::cancel`
+    pub cancelled: bool,
+}
+
+/// a schedule stopped by a venue error, with what it did up to then; the children still open
+/// were cancelled and read back as far as the venue let them
+#[derive(Debug)]
+pub struct TwapError {
+    pub report: TwapReport,
+    pub error: anyhow::Error,
+}
+
+impl std::fmt::Display for TwapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "twap stopped with {} of {} filled: {:#}",
+            self.report.filled(),
+            self.report.amount,
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for TwapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl TwapReport {
+    pub fn filled(&self) -> BigDecimal {
+        self.children
+            .iter()
+            .fold(BigDecimal::zero(), |acc, child| acc + &child.filled)
+    }
+
+    pub fn remaining(&self) -> BigDecimal {
+        &self.amount - self.filled()
+    }
+}
+
+/// sends the slices of a `TwapPlan`, each one sized to spread what is still unfilled over
+/// the slices left, so a child that did not fill completely is made up later
+pub struct Twap<'a> {
+    venue: &'a dyn ExchangeClient,
+    plan: TwapPlan,
+    control: watch::Receiver<Control>,
+}
+
+impl<'a> Twap<'a> {
+    pub fn new(venue: &'a dyn ExchangeClient, plan: TwapPlan) -> (Self, TwapHandle) {
+        let (sender, control) = watch::channel(Control::Run);
+        let handle = TwapHandle {
+            control: Arc::new(sender),
+        };
+        (
+            Twap {
+                venue,
+                plan,
+                control,
+            },
+            handle,
+        )
+    }
+
+    /// run the schedule to its end, or until cancelled; children still open at the end, or
+    /// when a venue error stops the schedule, are cancelled
+    ///
+    /// every child is tracked in `tracker` while it works, with the base it filled, and
+    /// removed once it is read back closed
+    pub async fn run(mut self, tracker: &mut OrderTracker) -> Result<TwapReport, TwapError> {
+        let mut report = TwapReport {
+            amount: self.plan.amount.clone(),
+            children: vec![],
+            cancelled: false,
+        };
+        let scheduled = self.schedule(&mut report, tracker).await;
+        let settled = self.settle(&mut report, tracker).await;
+        match scheduled.and(settled) {
+            Ok(()) => Ok(report),
+            Err(error) => Err(TwapError { report, error }),
+        }
+    }
+
+    async fn schedule(
+        &mut self,
+        report: &mut TwapReport,
+        tracker: &mut OrderTracker,
+    ) -> Result<()> {
+        let mut next = Instant::now();
+        for slice in 0..self.plan.slices {
+            if !self.wait_until(next).await {
+                report.cancelled = true;
+                return Ok(());
+            }
+            self.settle(report, tracker).await?;
+            let amount = self.slice_amount(report, self.plan.slices - slice);
+            if amount > BigDecimal::zero() {
+                let Some(id) = self.place(amount.clone(), tracker).await? else {
+                    report.cancelled = true;
+                    return Ok(());
+                };
+                report.children.push(ChildOrder {
+                    id,
+                    amount,
+                    filled: BigDecimal::zero(),
+                    state: OrderState::Open,
+                });
+            }
+            next = Instant::now() + self.plan.interval;
+        }
+        if !self.wait_until(next).await {
+            report.cancelled = true;
+        }
+        Ok(())
+    }
+
+    /// false once cancelled, waits out a pause even past `deadline`
+    async fn wait_until(&mut self, deadline: Instant) -> bool {
+        loop {
+            let control = *self.control.borrow_and_update();
+            match control {
+                Control::Cancel => return false,
+                Control::Pause => {
+                    if self.control.changed().await.is_err() {
+                        // the handle is gone, nobody can resume
+                        return false;
+                    }
+                }
+                Control::Run => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deadline) => return true,
+                        changed = self.control.changed() => {
+                            if changed.is_err() {
+                                tokio::time::sleep_until(deadline).await;
+                                return true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// what is neither filled nor working, spread over `slices_left`
+    fn slice_amount(&self, report: &TwapReport, slices_left: u32) -> BigDecimal {
+        let committed = report
+            .children
+            .iter()
+            .fold(BigDecimal::zero(), |acc, child| {
+                acc + if child.state.is_open() {
+                    &child.amount
+                } else {
+                    &child.filled
+                }
+            });
+        let amount = (&self.plan.amount - committed) / BigDecimal::from(slices_left.max(1));
+        match &self.plan.amount_step {
+            Some(step) => rounding::floor_to_tick(&amount, step),
+            None => amount,
+        }
+    }
+
+    /// send one child and track it, `None` if cancelled while waiting out a 429
+    async fn place(
+        &mut self,
+        amount: BigDecimal,
+        tracker: &mut OrderTracker,
+    ) -> Result<Option<String>> {
+        let order = OrderRequest {
+            symbol: self.plan.symbol.clone(),
+            side: self.plan.side,
+            kind: match self.plan.price {
+                Some(_) => OrderKind::Limit,
+                None => OrderKind::Market,
+            },
+            price: self.plan.price.clone(),
+            amount,
+        };
+        let tracked = NewOrder::try_from(order.clone())?;
+        loop {
+            match self.venue.place_order(order.clone()).await {
+                Ok(id) => {
+                    tracker.track(id.clone(), &tracked);
+                    return Ok(Some(id));
+                }
+                Err(e) => match e.downcast_ref::<Error>() {
+                    Some(Error::RateLimited(after)) => {
+                        let wait = after.unwrap_or(RATE_LIMIT_PAUSE);
+                        if !self.wait_until(Instant::now() + wait).await {
+                            return Ok(None);
+                        }
+                    }
+                    _ => return Err(e),
+                },
+            }
+        }
+    }
+
+    /// cancel the open children and read them back into `tracker`, the schedule has moved on;
+    /// a child that can not be read back stays open and the first such error is returned once
+    /// every child was tried
+    async fn settle(&self, report: &mut TwapReport, tracker: &mut OrderTracker) -> Result<()> {
+        let symbol = &self.plan.symbol;
+        let mut failed = None;
+        for child in report.children.iter_mut().filter(|c| c.state.is_open()) {
+            if let Err(e) = self.venue.cancel_order(symbol, &child.id).await {
+                // it may have filled in the meantime, the refresh tells
+                log::debug!("cancel of twap child {} failed: {}", child.id, e);
+            }
+            match self.venue.order(symbol, &child.id).await {
+                Ok(order) => {
+                    tracker.fill(&child.id, order.filled.clone());
+                    if !order.state.is_open() {
+                        tracker.remove(&child.id);
+                    }
+                    child.filled = order.filled;
+                    child.state = order.state;
+                }
+                Err(e) => {
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+        failed.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{BacktestOptions, SimulatedExchange};
+    use crate::exchange::{AssetBalance, Book, Candle, ExchangeFuture, Market, Order};
+    use crate::request::Scale;
+    use crate::response::{Depth, PriceLevel};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn venue() -> SimulatedExchange {
+        let symbol = serde_json::from_str(
+            r#"{"base":1,"quote":0,"base_name":"BTC","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0","make_fee":"0","min_amount":"0.0001",
+            "min_vol":"0","enable_marker_order":true}"#,
+        )
+        .unwrap();
+        let venue = SimulatedExchange::new(
+            vec![symbol],
+            [(String::from("USDT"), BigDecimal::from(10_000))],
+            BacktestOptions {
+                latency: Duration::ZERO,
+                ..Default::default()
+            },
+        );
+        let level = |price: i64| PriceLevel::new(price.into(), 10.into());
+        let depth = Depth {
+            depth: 0,
+            seq: Some(1),
+            checksum: None,
+            bids: vec![level(99)],
+            asks: vec![level(101)],
+        };
+        venue.on_depth(0, "BTC-USDT", depth);
+        venue
+    }
+
+    fn plan(price: Option<i64>) -> TwapPlan {
+        TwapPlan {
+            symbol: String::from("BTC-USDT"),
+            side: Side::Bid,
+            amount: 1.into(),
+            slices: 3,
+            interval: Duration::from_millis(5),
+            price: price.map(BigDecimal::from),
+            amount_step: Some("0.0001".parse().unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_twap_slices() {
+        let venue = venue();
+        let mut tracker = OrderTracker::new();
+        let (twap, _handle) = Twap::new(&venue, plan(None));
+        let report = twap.run(&mut tracker).await.unwrap();
+        let amounts: Vec<_> = report
+            .children
+            .iter()
+            .map(|c| c.amount.to_string())
+            .collect();
+        assert_eq!(amounts, vec!["0.3333", "0.3333", "0.3334"]);
+        assert_eq!(report.filled(), 1.into());
+        assert!(!report.cancelled);
+        assert!(tracker.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_twap_cancel() {
+        let venue = venue();
+        // rests below the ask and never fills
+        let (twap, handle) = Twap::new(&venue, plan(Some(100)));
+        handle.pause();
+        let run = tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                handle.cancel();
+            }
+        });
+        let report = twap.run(&mut OrderTracker::new()).await.unwrap();
+        run.await.unwrap();
+        assert!(report.cancelled);
+        assert!(report.children.is_empty());
+
+        let plan = TwapPlan {
+            interval: Duration::from_millis(20),
+            ..plan(Some(100))
+        };
+        let (twap, handle) = Twap::new(&venue, plan);
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            handle.cancel();
+        };
+        let mut tracker = OrderTracker::new();
+        let (report, _) = tokio::join!(twap.run(&mut tracker), cancel);
+        let report = report.unwrap();
+        assert!(report.cancelled);
+        assert_eq!(report.children.len(), 2);
+        assert!(report
+            .children
+            .iter()
+            .all(|c| c.state == OrderState::Cancelled));
+        assert_eq!(report.remaining(), 1.into());
+        assert!(tracker.is_empty());
+    }
+
+    /// the simulated venue, refusing every order after the first `places`
+    struct Refusing {
+        venue: SimulatedExchange,
+        places: AtomicUsize,
+    }
+
+    impl ExchangeClient for Refusing {
+        fn venue(&self) -> &str {
+            "refusing"
+        }
+
+        fn place_order(&self, order: OrderRequest) -> ExchangeFuture<'_, String> {
+            let left = self
+                .places
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                    left.checked_sub(1)
+                });
+            if left.is_err() {
+                return Box::pin(async { anyhow::bail!("venue unavailable") });
+            }
+            ExchangeClient::place_order(&self.venue, order)
+        }
+
+        fn cancel_order<'a>(
+            &'a self,
+            symbol: &'a str,
+            order_id: &'a str,
+        ) -> ExchangeFuture<'a, String> {
+            ExchangeClient::cancel_order(&self.venue, symbol, order_id)
+        }
+
+        fn order<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> ExchangeFuture<'a, Order> {
+            ExchangeClient::order(&self.venue, symbol, order_id)
+        }
+
+        fn open_orders<'a>(&'a self, symbol: &'a str) -> ExchangeFuture<'a, Vec<Order>> {
+            ExchangeClient::open_orders(&self.venue, symbol)
+        }
+
+        fn balances(&self) -> ExchangeFuture<'_, Vec<AssetBalance>> {
+            ExchangeClient::balances(&self.venue)
+        }
+
+        fn depth<'a>(&'a self, symbol: &'a str, limit: Option<u32>) -> ExchangeFuture<'a, Book> {
+            ExchangeClient::depth(&self.venue, symbol, limit)
+        }
+
+        fn klines<'a>(&'a self, symbol: &'a str, scale: Scale) -> ExchangeFuture<'a, Vec<Candle>> {
+            ExchangeClient::klines(&self.venue, symbol, scale)
+        }
+
+        fn markets(&self) -> ExchangeFuture<'_, Vec<Market>> {
+            ExchangeClient::markets(&self.venue)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_twap_venue_error() {
+        let venue = Refusing {
+            venue: venue(),
+            places: AtomicUsize::new(2),
+        };
+        // the children rest below the ask, the third is refused while the second works
+        let (twap, _handle) = Twap::new(&venue, plan(Some(100)));
+        let mut tracker = OrderTracker::new();
+        let err = twap.run(&mut tracker).await.unwrap_err();
+        assert_eq!(err.error.to_string(), "venue unavailable");
+        let states: Vec<_> = err.report.children.iter().map(|c| c.state).collect();
+        assert_eq!(states, vec![OrderState::Cancelled, OrderState::Cancelled]);
+        assert_eq!(err.report.remaining(), 1.into());
+        assert_eq!(
+            err.to_string(),
+            "twap stopped with 0 of 1 filled: venue unavailable"
+        );
+        assert!(tracker.is_empty());
+    }
+}
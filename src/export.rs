@@ -0,0 +1,315 @@
+//! klines and fills as CSV or, with the `parquet` feature, as Parquet files that pandas and
+//! Polars load as they are
+//!
+//! the columns are the fields of the response structs under the same names, decimals are
+//! written as text in CSV and as `Float64` in Parquet
+
+use crate::response::{Kline, Trade};
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use std::io::Write;
+use std::marker::PhantomData;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColumnType {
+    Int,
+    Decimal,
+    Text,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnType,
+    /// the field is an `Option`
+    pub nullable: bool,
+}
+
+const fn column(name: &'static str, kind: ColumnType) -> Column {
+    Column {
+        name,
+        kind,
+        nullable: false,
+    }
+}
+
+const fn nullable(name: &'static str, kind: ColumnType) -> Column {
+    Column {
+        name,
+        kind,
+        nullable: true,
+    }
+}
+
+/// one value of a row, of the type of its column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Int(Option<i64>),
+    Decimal(Option<BigDecimal>),
+    Text(String),
+}
+
+impl Cell {
+    fn decimal(value: &BigDecimal) -> Cell {
+        Cell::Decimal(Some(value.clone()))
+    }
+
+    /// the CSV form, empty for a missing value
+    fn text(&self) -> String {
+        match self {
+            Cell::Int(value) => value.map(|v| v.to_string()).unwrap_or_default(),
+            Cell::Decimal(value) => value.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+            Cell::Text(value) => value.clone(),
+        }
+    }
+}
+
+/// a response struct that can be written as a row
+pub trait Record {
+    /// in file order
+    const COLUMNS: &'static [Column];
+
+    /// one cell for each of `COLUMNS`
+    fn cells(&self) -> Vec<Cell>;
+}
+
+impl Record for Kline {
+    const COLUMNS: &'static [Column] = &[
+        column("id", ColumnType::Int),
+        column("open", ColumnType::Decimal),
+        column("close", ColumnType::Decimal),
+        column("high", ColumnType::Decimal),
+        column("low", ColumnType::Decimal),
+        column("vol", ColumnType::Decimal),
+        nullable("close_time", ColumnType::Int),
+        nullable("turnover", ColumnType::Decimal),
+        nullable("count", ColumnType::Int),
+    ];
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            Cell::Int(Some(self.id)),
+            Cell::decimal(&self.open),
+            Cell::decimal(&self.close),
+            Cell::decimal(&self.high),
+            Cell::decimal(&self.low),
+            Cell::decimal(&self.vol),
+            Cell::Int(self.close_time),
+            Cell::Decimal(self.turnover.clone()),
+            Cell::Int(self.count.and_then(|count| i64::try_from(count).ok())),
+        ]
+    }
+}
+
+/// a fill of the key, the side is written as `ASK` or `BID`
+impl Record for Trade {
+    const COLUMNS: &'static [Column] = &[
+        column("base", ColumnType::Int),
+        column("quote", ColumnType::Int),
+        column("ask_or_bid", ColumnType::Text),
+        column("price", ColumnType::Decimal),
+        column("amount", ColumnType::Decimal),
+        column("quote_amount", ColumnType::Decimal),
+        column("quote_fee", ColumnType::Decimal),
+        column("base_fee", ColumnType::Decimal),
+        column("timestamp", ColumnType::Int),
+    ];
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            Cell::Int(Some(self.base.into())),
+            Cell::Int(Some(self.quote.into())),
+            Cell::Text(self.ask_or_bid.to_string()),
+            Cell::decimal(&self.price),
+            Cell::decimal(&self.amount),
+            Cell::decimal(&self.quote_amount),
+            Cell::decimal(&self.quote_fee),
+            Cell::decimal(&self.base_fee),
+            Cell::Int(Some(self.timestamp)),
+        ]
+    }
+}
+
+/// where pages of records are written as they arrive, see `TradePages::export`
+pub trait Sink<T: Record> {
+    fn write(&mut self, records: &[T]) -> Result<()>;
+}
+
+/// CSV with a header row of the column names
+pub struct CsvWriter<T, W: Write> {
+    writer: csv::Writer<W>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Record, W: Write> CsvWriter<T, W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(T::COLUMNS.iter().map(|c| c.name))?;
+        Ok(CsvWriter {
+            writer,
+            _marker: PhantomData,
+        })
+    }
+
+    /// flush what was written and hand back the writer
+    pub fn finish(self) -> Result<W> {
+        Ok(self.writer.into_inner().map_err(|e| e.into_error())?)
+    }
+}
+
+impl<T: Record, W: Write> Sink<T> for CsvWriter<T, W> {
+    fn write(&mut self, records: &[T]) -> Result<()> {
+        for record in records {
+            self.writer
+                .write_record(record.cells().iter().map(Cell::text))?;
+        }
+        Ok(())
+    }
+}
+
+/// the Arrow schema of `T`
+#[cfg(feature = "parquet")]
+pub fn schema<T: Record>() -> arrow_schema::Schema {
+    use arrow_schema::{DataType, Field};
+    arrow_schema::Schema::new(
+        T::COLUMNS
+            .iter()
+            .map(|column| {
+                let kind = match column.kind {
+                    ColumnType::Int => DataType::Int64,
+                    ColumnType::Decimal => DataType::Float64,
+                    ColumnType::Text => DataType::Utf8,
+                };
+                Field::new(column.name, kind, column.nullable)
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// `records` as one Arrow batch of `schema::<T>()`
+#[cfg(feature = "parquet")]
+pub fn record_batch<T: Record>(records: &[T]) -> Result<arrow_array::RecordBatch> {
+    use arrow_array::{ArrayRef, Float64Array, Int64Array, StringArray};
+    use std::sync::Arc;
+
+    let rows: Vec<_> = records.iter().map(Record::cells).collect();
+    let columns = T::COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(i, column)| -> ArrayRef {
+            let cells = rows.iter().map(|row| &row[i]);
+            match column.kind {
+                ColumnType::Int => Arc::new(Int64Array::from_iter(cells.map(|cell| match cell {
+                    Cell::Int(value) => *value,
+                    _ => None,
+                }))),
+                ColumnType::Decimal => {
+                    Arc::new(Float64Array::from_iter(cells.map(|cell| match cell {
+                        Cell::Decimal(value) => {
+                            value.as_ref().and_then(|v| crate::numeric::to_f64(v).ok())
+                        }
+                        _ => None,
+                    })))
+                }
+                ColumnType::Text => {
+                    Arc::new(StringArray::from_iter(cells.map(|cell| match cell {
+                        Cell::Text(value) => Some(value.as_str()),
+                        _ => None,
+                    })))
+                }
+            }
+        })
+        .collect();
+    Ok(arrow_array::RecordBatch::try_new(
+        Arc::new(schema::<T>()),
+        columns,
+    )?)
+}
+
+/// Parquet, rows are buffered into row groups and `finish` writes the footer
+#[cfg(feature = "parquet")]
+pub struct ParquetWriter<T, W: Write + Send> {
+    writer: parquet::arrow::ArrowWriter<W>,
+    _marker: PhantomData<T>,
+}
+
+#[cfg(feature = "parquet")]
+impl<T: Record, W: Write + Send> ParquetWriter<T, W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let schema = std::sync::Arc::new(schema::<T>());
+        Ok(ParquetWriter {
+            writer: parquet::arrow::ArrowWriter::try_new(writer, schema, None)?,
+            _marker: PhantomData,
+        })
+    }
+
+    /// write the footer and hand back the writer, the file is unreadable without it
+    pub fn finish(self) -> Result<W> {
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl<T: Record, W: Write + Send> Sink<T> for ParquetWriter<T, W> {
+    fn write(&mut self, records: &[T]) -> Result<()> {
+        if !records.is_empty() {
+            self.writer.write(&record_batch(records)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn klines() -> Vec<Kline> {
+        serde_json::from_str(
+            r#"[
+                {"id": 60, "open": "1.5", "close": "2", "high": "2.5", "low": "1", "vol": "10"},
+                {"id": 120, "open": "2", "close": "3", "high": "3", "low": "2", "vol": "4",
+                 "close_time": 180, "turnover": "10.25", "count": 3}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut writer = CsvWriter::new(vec![]).unwrap();
+        writer.write(&klines()).unwrap();
+        let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "id,open,close,high,low,vol,close_time,turnover,count\n\
+             60,1.5,2,2.5,1,10,,,\n\
+             120,2,3,3,2,4,180,10.25,3\n"
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::Float64Type;
+        use arrow_array::Array;
+
+        let mut writer = ParquetWriter::new(vec![]).unwrap();
+        writer.write(&klines()).unwrap();
+        writer.write(&klines()[..1]).unwrap();
+        let file = bytes::Bytes::from(writer.finish().unwrap());
+        let batches: Vec<_> =
+            parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        assert_eq!(rows, 3);
+        assert_eq!(batches[0].schema().as_ref(), &schema::<Kline>());
+        let turnover = batches[0].column(7).as_primitive::<Float64Type>();
+        assert!(turnover.is_null(0));
+        assert_eq!(turnover.value(1), 10.25);
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// the endpoints of a client in order of preference, the first one is the primary
+///
+/// after `threshold` consecutive failures of the active endpoint the next one takes
+/// over, the health check of the client fails back once the primary answers again
+#[derive(Debug)]
+pub struct EndpointPool {
+    endpoints: Vec<String>,
+    active: AtomicUsize,
+    failures: AtomicU32,
+    threshold: u32,
+}
+
+impl EndpointPool {
+    pub fn new(endpoints: Vec<String>, threshold: u32) -> Self {
+        EndpointPool {
+            endpoints,
+            active: AtomicUsize::new(0),
+            failures: AtomicU32::new(0),
+            threshold: threshold.max(1),
+        }
+    }
+
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    pub fn primary(&self) -> &str {
+        &self.endpoints[0]
+    }
+
+    pub fn current(&self) -> &str {
+        &self.endpoints[self.active.load(Ordering::SeqCst) % self.endpoints.len()]
+    }
+
+    pub fn is_failed_over(&self) -> bool {
+        self.active.load(Ordering::SeqCst) != 0
+    }
+
+    /// record the outcome of a request sent to `endpoint`, outcomes of other endpoints are ignored
+    pub fn report(&self, endpoint: &str, ok: bool) {
+        let active = self.active.load(Ordering::SeqCst);
+        if self.endpoints[active % self.endpoints.len()] != endpoint {
+            return;
+        }
+        if ok {
+            self.failures.store(0, Ordering::SeqCst);
+        } else if self.failures.fetch_add(1, Ordering::SeqCst) + 1 >= self.threshold {
+            let next = (active + 1) % self.endpoints.len();
+            if self
+                .active
+                .compare_exchange(active, next, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                self.failures.store(0, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub fn fail_back(&self) {
+        self.active.store(0, Ordering::SeqCst);
+        self.failures.store(0, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fail_over_and_back() {
+        let pool = EndpointPool::new(vec![String::from("a"), String::from("b")], 2);
+        pool.report("a", false);
+        assert_eq!(pool.current(), "a");
+        pool.report("a", true);
+        pool.report("a", false);
+        assert_eq!(pool.current(), "a");
+        pool.report("a", false);
+        assert_eq!(pool.current(), "b");
+        assert!(pool.is_failed_over());
+        pool.report("a", false);
+        assert_eq!(pool.current(), "b");
+        pool.fail_back();
+        assert_eq!(pool.current(), "a");
+    }
+}
@@ -0,0 +1,324 @@
+//! a transport wrapper injecting latency and failures, to exercise the retry, backoff and
+//! rate limit handling of a strategy against a test double or a staging endpoint
+//!
+//! faults are drawn from a seeded generator, the same seed and the same order of requests
+//! give the same faults on every run
+//!
+//! ```ignore
+//! let faults = Faults::new(7)
+//!     .latency(Latency::Uniform(Duration::from_millis(5), Duration::from_millis(50)))
+//!     .server_errors(0.05)
+//!     .rate_limited(0.02, Some(Duration::from_secs(1)));
+//! let client = FxdxBuilder::<PrivPub>::endpoint(url)
+//!     .transport(FaultyTransport::new(ReqwestTransport::from_options(&Default::default())?, faults))
+//!     .build()
+//!     .await?;
+//! ```
+
+use crate::encoding::SignedRequest;
+use crate::transport::{RawResponse, Transport, TransportFuture};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// the delay added before every request
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Latency {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// anywhere from the first to the second
+    Uniform(Duration, Duration),
+    /// the first, and the second for the given share of requests, e.g. a slow tail
+    Spikes(Duration, Duration, f64),
+}
+
+/// one kind of injected failure
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// a `503 Service Unavailable` without sending the request
+    ServerError,
+    /// sent, then no response within the timeout
+    Timeout,
+    /// refused before anything was sent, so a retry can not duplicate it
+    Refused,
+    /// sent, the body of the response cut in half
+    Malformed,
+    /// a `429 Too Many Requests` without sending the request
+    RateLimited,
+}
+
+const FAULTS: [Fault; 5] = [
+    Fault::ServerError,
+    Fault::Timeout,
+    Fault::Refused,
+    Fault::Malformed,
+    Fault::RateLimited,
+];
+
+fn index(fault: Fault) -> usize {
+    FAULTS.iter().position(|f| *f == fault).unwrap_or_default()
+}
+
+/// the error of an injected `Timeout` or `Refused`
+#[derive(Debug, thiserror::Error)]
+#[error("injected fault: {0:?}")]
+pub struct InjectedFault(pub Fault);
+
+/// how often each fault happens, every share is of all requests and they add up to at most 1
+#[derive(Debug, Clone)]
+pub struct Faults {
+    seed: u64,
+    latency: Latency,
+    shares: [f64; FAULTS.len()],
+    timeout: Duration,
+    retry_after: Option<Duration>,
+}
+
+impl Faults {
+    /// no latency and no faults until set
+    pub fn new(seed: u64) -> Self {
+        Faults {
+            seed,
+            latency: Latency::None,
+            shares: [0.0; FAULTS.len()],
+            timeout: Duration::from_secs(10),
+            retry_after: None,
+        }
+    }
+
+    pub fn latency(mut self, latency: Latency) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    fn share(mut self, fault: Fault, share: f64) -> Self {
+        self.shares[index(fault)] = share.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn server_errors(self, share: f64) -> Self {
+        self.share(Fault::ServerError, share)
+    }
+
+    /// time out `share` of the requests after `after`, the time the client would wait
+    pub fn timeouts(mut self, share: f64, after: Duration) -> Self {
+        self.timeout = after;
+        self.share(Fault::Timeout, share)
+    }
+
+    pub fn refused(self, share: f64) -> Self {
+        self.share(Fault::Refused, share)
+    }
+
+    pub fn malformed(self, share: f64) -> Self {
+        self.share(Fault::Malformed, share)
+    }
+
+    /// answer `share` of the requests with a 429, with a `Retry-After` header when set
+    pub fn rate_limited(mut self, share: f64, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self.share(Fault::RateLimited, share)
+    }
+}
+
+/// wraps `T`, see the module docs
+pub struct FaultyTransport<T> {
+    inner: T,
+    faults: Faults,
+    rng: Mutex<u64>,
+    injected: [AtomicU64; FAULTS.len()],
+}
+
+impl<T> FaultyTransport<T> {
+    pub fn new(inner: T, faults: Faults) -> Self {
+        FaultyTransport {
+            inner,
+            // xorshift never leaves zero
+            rng: Mutex::new(faults.seed | 1),
+            faults,
+            injected: Default::default(),
+        }
+    }
+
+    /// how often `fault` was injected so far
+    pub fn injected(&self, fault: Fault) -> u64 {
+        self.injected[index(fault)].load(Ordering::Relaxed)
+    }
+
+    /// the latency and the fault of the next request, drawn together so concurrent requests
+    /// do not change each other's draws
+    fn draw(&self) -> (Duration, Option<Fault>) {
+        let (latency, roll) = {
+            let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+            (unit(&mut rng), unit(&mut rng))
+        };
+        let latency = match self.faults.latency {
+            Latency::None => Duration::ZERO,
+            Latency::Fixed(latency) => latency,
+            Latency::Uniform(min, max) => min + max.saturating_sub(min).mul_f64(latency),
+            Latency::Spikes(base, spike, share) => {
+                if latency < share {
+                    spike
+                } else {
+                    base
+                }
+            }
+        };
+        let mut below = 0.0;
+        let fault = FAULTS
+            .iter()
+            .zip(self.faults.shares)
+            .find(|(_, share)| {
+                below += share;
+                roll < below
+            })
+            .map(|(fault, _)| *fault);
+        (latency, fault)
+    }
+}
+
+/// a number from 0 to 1, xorshift64
+fn unit(rng: &mut u64) -> f64 {
+    *rng ^= *rng << 13;
+    *rng ^= *rng >> 7;
+    *rng ^= *rng << 17;
+    (*rng >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn status(status: u16, headers: Vec<(String, String)>) -> RawResponse {
+    RawResponse {
+        status,
+        headers,
+        body: bytes::Bytes::new(),
+    }
+}
+
+impl<T: Transport> Transport for FaultyTransport<T> {
+    fn execute<'a>(&'a self, endpoint: &'a str, request: SignedRequest) -> TransportFuture<'a> {
+        let (latency, fault) = self.draw();
+        if let Some(fault) = fault {
+            self.injected[index(fault)].fetch_add(1, Ordering::Relaxed);
+        }
+        Box::pin(async move {
+            if !latency.is_zero() {
+                tokio::time::sleep(latency).await;
+            }
+            match fault {
+                None => self.inner.execute(endpoint, request).await,
+                Some(Fault::ServerError) => Ok(status(503, vec![])),
+                Some(Fault::RateLimited) => Ok(status(
+                    429,
+                    self.faults
+                        .retry_after
+                        .map(|after| {
+                            vec![(String::from("retry-after"), after.as_secs().to_string())]
+                        })
+                        .unwrap_or_default(),
+                )),
+                Some(Fault::Refused) => Err(InjectedFault(Fault::Refused).into()),
+                Some(Fault::Timeout) => {
+                    // the request is out and may still be executed, like a real timeout
+                    let deadline = tokio::time::Instant::now() + self.faults.timeout;
+                    let _ =
+                        tokio::time::timeout_at(deadline, self.inner.execute(endpoint, request))
+                            .await;
+                    tokio::time::sleep_until(deadline).await;
+                    Err(InjectedFault(Fault::Timeout).into())
+                }
+                Some(Fault::Malformed) => {
+                    let mut resp = self.inner.execute(endpoint, request).await?;
+                    resp.body = resp.body.slice(..resp.body.len() / 2);
+                    Ok(resp)
+                }
+            }
+        })
+    }
+
+    fn is_unreachable(&self, error: &anyhow::Error) -> bool {
+        match error.downcast_ref::<InjectedFault>() {
+            Some(InjectedFault(fault)) => *fault == Fault::Refused,
+            None => self.inner.is_unreachable(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::Method;
+
+    struct Ok200;
+
+    impl Transport for Ok200 {
+        fn execute<'a>(&'a self, _: &'a str, _: SignedRequest) -> TransportFuture<'a> {
+            Box::pin(async {
+                Ok(RawResponse {
+                    status: 200,
+                    headers: vec![],
+                    body: bytes::Bytes::from_static(br#"{"code":200,"data":[]}"#),
+                })
+            })
+        }
+    }
+
+    fn request() -> SignedRequest {
+        SignedRequest {
+            method: Method::Get,
+            uri: String::from("/symbols"),
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    async fn run(seed: u64) -> Vec<Result<u16, bool>> {
+        let transport = FaultyTransport::new(
+            Ok200,
+            Faults::new(seed)
+                .server_errors(0.2)
+                .refused(0.2)
+                .malformed(0.2)
+                .rate_limited(0.2, Some(Duration::from_secs(3)))
+                .timeouts(0.1, Duration::from_millis(1)),
+        );
+        let mut outcomes = vec![];
+        for _ in 0..200 {
+            let outcome = match transport.execute("http://fxdx", request()).await {
+                Ok(resp) if resp.status == 200 => {
+                    let valid = serde_json::from_slice::<serde_json::Value>(&resp.body).is_ok();
+                    assert_eq!(valid, resp.body.len() > 11);
+                    Ok(resp.body.len() as u16)
+                }
+                Ok(resp) => Ok(resp.status),
+                Err(e) => Err(transport.is_unreachable(&e)),
+            };
+            outcomes.push(outcome);
+        }
+        for fault in FAULTS {
+            assert!(transport.injected(fault) > 0, "{:?} never injected", fault);
+        }
+        outcomes
+    }
+
+    #[tokio::test]
+    async fn test_seeded_faults() {
+        let outcomes = run(7).await;
+        assert_eq!(outcomes, run(7).await);
+        assert_ne!(outcomes, run(8).await);
+        assert!(outcomes.contains(&Ok(429)) && outcomes.contains(&Ok(503)));
+        assert!(outcomes.contains(&Err(true)) && outcomes.contains(&Err(false)));
+
+        let latency = FaultyTransport::new(
+            Ok200,
+            Faults::new(1).latency(Latency::Uniform(
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+            )),
+        );
+        for _ in 0..20 {
+            let (delay, fault) = latency.draw();
+            assert!(fault.is_none());
+            assert!((Duration::from_millis(10)..=Duration::from_millis(20)).contains(&delay));
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::request::{NewOrder, OrderKind, Side};
+use crate::response::{Symbol, Trade};
+use bigdecimal::{BigDecimal, Zero};
+
+/// a fee split by the asset it is charged in, the exchange charges the asset received
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fee {
+    pub base: BigDecimal,
+    pub quote: BigDecimal,
+}
+
+impl Fee {
+    /// the whole fee in quote, the base part valued at `price`
+    pub fn in_quote(&self, price: &BigDecimal) -> BigDecimal {
+        &self.quote + &self.base * price
+    }
+}
+
+/// the fee of an order if it fills completely as taker or as maker
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeEstimate {
+    pub taker: Fee,
+    pub maker: Fee,
+}
+
+impl FeeEstimate {
+    /// the scenario an order of `kind` ends up in: post only orders only ever make, every other
+    /// kind is assumed to take, which is the worst case for limit orders
+    pub fn expected(&self, kind: OrderKind) -> &Fee {
+        match kind {
+            OrderKind::PostOnly => &self.maker,
+            _ => &self.taker,
+        }
+    }
+}
+
+/// the fees of `order` at the rates of `symbol`, market orders carry no price and are
+/// estimated with `estimate_at` instead
+pub fn estimate(order: &NewOrder, symbol: &Symbol) -> Result<FeeEstimate, crate::Error> {
+    let price = order.price.as_ref().ok_or_else(|| {
+        crate::Error::InvalidRequest(String::from(
+            "an order without price needs a reference price to estimate its fee",
+        ))
+    })?;
+    estimate_at(order, symbol, price)
+}
+
+/// the fees of `order` if it filled at `price`
+pub fn estimate_at(
+    order: &NewOrder,
+    symbol: &Symbol,
+    price: &BigDecimal,
+) -> Result<FeeEstimate, crate::Error> {
+    let charged = |rate: &BigDecimal| match order.side() {
+        // a bid receives base, an ask receives quote
+        Some(Side::Bid) => Ok(Fee {
+            base: &order.amount * rate,
+            quote: BigDecimal::zero(),
+        }),
+        Some(Side::Ask) => Ok(Fee {
+            base: BigDecimal::zero(),
+            quote: &order.amount * price * rate,
+        }),
+        None => Err(crate::Error::InvalidRequest(format!(
+            "unknown order type {}",
+            order.r#type
+        ))),
+    };
+    Ok(FeeEstimate {
+        taker: charged(&symbol.taker_fee)?,
+        maker: charged(&symbol.make_fee)?,
+    })
+}
+
+/// the fees the exchange charged for `trade`
+pub fn actual_fees(trade: &Trade) -> Fee {
+    Fee {
+        base: trade.base_fee.clone(),
+        quote: trade.quote_fee.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol() -> Symbol {
+        serde_json::from_str(
+            r#"{"base":1,"quote":0,"base_name":"BTC","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0.002","make_fee":"0.001","min_amount":"0.0001",
+            "min_vol":"1","enable_marker_order":true}"#,
+        )
+        .unwrap()
+    }
+
+    fn order(side: Side, kind: OrderKind, price: Option<i64>) -> NewOrder {
+        NewOrder::new(
+            side,
+            kind,
+            String::from("BTC-USDT"),
+            price.map(BigDecimal::from),
+            BigDecimal::from(2),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_estimate() {
+        let bid = estimate(&order(Side::Bid, OrderKind::PostOnly, Some(100)), &symbol()).unwrap();
+        assert_eq!(
+            bid.expected(OrderKind::PostOnly).base,
+            "0.002".parse().unwrap()
+        );
+        assert_eq!(bid.taker.base, "0.004".parse().unwrap());
+        assert!(bid.taker.quote.is_zero());
+        assert_eq!(
+            bid.taker.in_quote(&BigDecimal::from(100)),
+            "0.4".parse().unwrap()
+        );
+
+        let market = order(Side::Ask, OrderKind::Market, None);
+        assert!(estimate(&market, &symbol()).is_err());
+        let ask = estimate_at(&market, &symbol(), &BigDecimal::from(100)).unwrap();
+        assert_eq!(
+            ask.expected(OrderKind::Market).quote,
+            "0.4".parse().unwrap()
+        );
+        assert!(ask.maker.base.is_zero());
+    }
+}
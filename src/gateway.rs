@@ -0,0 +1,427 @@
+//! the client as a JSON-RPC 2.0 service, built with `--features gateway`, so components in
+//! other languages send their orders through the one process which holds the key, its rate
+//! limiter and its audit log
+//!
+//! requests and responses are JSON objects, one per line, over a TCP connection:
+//!
+//! ```text
+//! > {"jsonrpc":"2.0","id":1,"method":"place_order","params":{"symbol":"BTC-USDT","side":"buy","amount":"0.1","price":"30000"}}
+//! < {"jsonrpc":"2.0","id":1,"result":{"order_id":"42"}}
+//! ```
+//!
+//! methods: `ping`, `symbols`, `depth`, `kline`, `balances`, `open_orders`, `query_order`,
+//! `place_order` and `cancel_order`; with `Gateway::token` every request carries it as `auth`
+
+use crate::request::{OrderKind, Prefix, Request, Scale, Side};
+use crate::FxdxClient;
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// the longest request line read, a longer one closes the connection
+pub const MAX_REQUEST_LINE: usize = 64 * 1024;
+
+/// the error codes of JSON-RPC 2.0, and `SERVER_ERROR` for a failed call to the exchange
+pub mod codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const SERVER_ERROR: i64 = -32000;
+    /// a missing or wrong `auth`, or an order method on a read only gateway
+    pub const FORBIDDEN: i64 = -32001;
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    auth: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolParams {
+    symbol: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DepthParams {
+    symbol: String,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KlineParams {
+    symbol: String,
+    scale: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrderIdParams {
+    symbol: String,
+    order_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaceParams {
+    symbol: String,
+    side: String,
+    amount: BigDecimal,
+    price: Option<BigDecimal>,
+    /// `LIMIT`, `POST_ONLY`, `IOC` or `FOK`, defaults to `LIMIT` with a price and market without
+    kind: Option<String>,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(codes::INVALID_PARAMS, e))
+}
+
+fn exchange(e: anyhow::Error) -> RpcError {
+    RpcError::new(codes::SERVER_ERROR, e)
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(codes::SERVER_ERROR, e))
+}
+
+/// serves the JSON-RPC methods of the module docs with one client
+pub struct Gateway<P> {
+    client: FxdxClient<P>,
+    read_only: bool,
+    token: Option<String>,
+}
+
+impl<P> Gateway<P>
+where
+    P: Prefix + Send + Sync + 'static,
+{
+    pub fn new(client: FxdxClient<P>) -> Self {
+        Gateway {
+            client,
+            read_only: false,
+            token: None,
+        }
+    }
+
+    /// refuse `place_order` and `cancel_order`, e.g. for a dashboard
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// refuse every request whose `auth` is not `token`
+    pub fn token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// accept connections on `listener` until the client shuts down, every connection is
+    /// served by its own task
+    ///
+    /// the gateway signs for whoever reaches it, bind it to loopback or set a `token`
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        let gateway = Arc::new(self);
+        let client = gateway.client.clone();
+        client
+            .until_shutdown(async {
+                loop {
+                    let (stream, peer) = listener.accept().await?;
+                    let gateway = gateway.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = gateway.connection(stream).await {
+                            log::debug!("fxdx gateway connection of {} closed: {}", peer, e);
+                        }
+                    });
+                }
+            })
+            .await
+            .unwrap_or(Ok(()))
+    }
+
+    async fn connection(&self, stream: tokio::net::TcpStream) -> Result<()> {
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = (&mut lines)
+                .take(MAX_REQUEST_LINE as u64 + 1)
+                .read_line(&mut line)
+                .await?;
+            if read == 0 {
+                return Ok(());
+            }
+            anyhow::ensure!(
+                line.len() <= MAX_REQUEST_LINE,
+                "request longer than {} bytes",
+                MAX_REQUEST_LINE
+            );
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut reply = self.handle(&line).await;
+            reply.push('\n');
+            write.write_all(reply.as_bytes()).await?;
+        }
+    }
+
+    /// the response line to one request line
+    pub async fn handle(&self, line: &str) -> String {
+        let (id, result) = match serde_json::from_str::<RpcRequest>(line) {
+            Ok(req) => (req.id.clone(), self.call(req).await),
+            Err(e) if serde_json::from_str::<Value>(line).is_ok() => {
+                (Value::Null, Err(RpcError::new(codes::INVALID_REQUEST, e)))
+            }
+            Err(e) => (Value::Null, Err(RpcError::new(codes::PARSE_ERROR, e))),
+        };
+        let reply = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": e.code, "message": e.message },
+            }),
+        };
+        reply.to_string()
+    }
+
+    async fn call(&self, req: RpcRequest) -> Result<Value, RpcError> {
+        if let Some(token) = &self.token {
+            let given = req.auth.as_deref().unwrap_or_default();
+            if given.len() != token.len()
+                || !openssl::memcmp::eq(given.as_bytes(), token.as_bytes())
+            {
+                return Err(RpcError::new(codes::FORBIDDEN, "wrong auth"));
+            }
+        }
+        let ordering = matches!(req.method.as_str(), "place_order" | "cancel_order");
+        if ordering && self.read_only {
+            return Err(RpcError::new(codes::FORBIDDEN, "the gateway is read only"));
+        }
+        let client = &self.client;
+        let result = match req.method.as_str() {
+            "ping" => {
+                let rtt = client.ping().await.map_err(exchange)?;
+                json!({ "rtt_ms": rtt.as_secs_f64() * 1000.0 })
+            }
+            "symbols" => to_value(
+                client
+                    .query_symbols(Request::Symbols)
+                    .await
+                    .map_err(exchange)?,
+            )?,
+            "depth" => {
+                let DepthParams { symbol, limit } = params(req.params)?;
+                to_value(
+                    client
+                        .query_depth(Request::Depth { symbol, limit })
+                        .await
+                        .map_err(exchange)?,
+                )?
+            }
+            "kline" => {
+                let KlineParams { symbol, scale } = params(req.params)?;
+                let scale = scale
+                    .parse::<Scale>()
+                    .map_err(|e| RpcError::new(codes::INVALID_PARAMS, e))?;
+                to_value(
+                    client
+                        .query_kline(Request::Kline { symbol, scale })
+                        .await
+                        .map_err(exchange)?,
+                )?
+            }
+            "balances" => to_value(
+                client
+                    .query_account_balance(Request::Balances)
+                    .await
+                    .map_err(exchange)?,
+            )?,
+            "open_orders" => {
+                let SymbolParams { symbol } = params(req.params)?;
+                to_value(client.open_orders(&symbol).await.map_err(exchange)?)?
+            }
+            "query_order" => {
+                let OrderIdParams { symbol, order_id } = params(req.params)?;
+                to_value(
+                    client
+                        .query_order_by_id(Request::OrderById { symbol, order_id })
+                        .await
+                        .map_err(exchange)?,
+                )?
+            }
+            "place_order" => {
+                let order = place_order(params(req.params)?)?;
+                let order_id = client.pending_order(order).await.map_err(exchange)?;
+                json!({ "order_id": order_id })
+            }
+            "cancel_order" => {
+                let OrderIdParams { symbol, order_id } = params(req.params)?;
+                let order_id = client
+                    .cancel_order(Request::CancelOrder { symbol, order_id })
+                    .await
+                    .map_err(exchange)?;
+                json!({ "order_id": order_id })
+            }
+            method => {
+                return Err(RpcError::new(
+                    codes::METHOD_NOT_FOUND,
+                    format!("unknown method {}", method),
+                ))
+            }
+        };
+        if ordering {
+            log::info!("fxdx gateway {}: {}", req.method, result);
+        }
+        Ok(result)
+    }
+}
+
+fn place_order(params: PlaceParams) -> Result<Request, RpcError> {
+    let invalid = |e: crate::Error| RpcError::new(codes::INVALID_PARAMS, e);
+    let side = params.side.parse::<Side>().map_err(invalid)?;
+    let kind = match params.kind {
+        Some(kind) => kind.parse::<OrderKind>().map_err(invalid)?,
+        None if params.price.is_some() => OrderKind::Limit,
+        None => OrderKind::Market,
+    };
+    Request::order(side, kind, params.symbol, params.price, params.amount)
+        .map_err(|e| RpcError::new(codes::INVALID_PARAMS, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::PrivPub;
+    use crate::transport::{RawResponse, Transport, TransportFuture};
+    use crate::FxdxBuilder;
+
+    /// a book of 9 to 11 for every depth request
+    struct Book;
+
+    impl Transport for Book {
+        fn execute<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _request: crate::encoding::SignedRequest,
+        ) -> TransportFuture<'a> {
+            Box::pin(async {
+                Ok(RawResponse {
+                    status: 200,
+                    headers: vec![],
+                    body: bytes::Bytes::from_static(
+                        br#"{"code":200,"data":{"bids":[["9","1"]],"asks":[["11","1"]]}}"#,
+                    ),
+                })
+            })
+        }
+    }
+
+    async fn gateway() -> Gateway<PrivPub> {
+        let client = FxdxBuilder::<PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .paper_trading(true)
+            .transport(Book)
+            .build()
+            .await
+            .unwrap();
+        Gateway::new(client).token(String::from("letmein"))
+    }
+
+    fn reply(line: &str) -> Value {
+        serde_json::from_str(line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_rpc_calls() {
+        let gateway = gateway().await;
+        let placed = reply(&gateway.handle(
+            r#"{"jsonrpc":"2.0","id":1,"auth":"letmein","method":"place_order","params":{"symbol":"BTC-USDT","side":"buy","amount":"1","price":"8"}}"#,
+        ).await);
+        assert_eq!(placed["id"], 1);
+        let order_id = placed["result"]["order_id"].as_str().unwrap().to_string();
+
+        let open = reply(&gateway.handle(
+            r#"{"jsonrpc":"2.0","id":2,"auth":"letmein","method":"open_orders","params":{"symbol":"BTC-USDT"}}"#,
+        ).await);
+        assert_eq!(open["result"][0]["order_id"], order_id.as_str());
+
+        let error = |line: &str| reply(line)["error"]["code"].as_i64().unwrap();
+        assert_eq!(
+            error(&gateway.handle(r#"{"id":3,"method":"balances"}"#).await),
+            codes::FORBIDDEN
+        );
+        assert_eq!(
+            error(
+                &gateway
+                    .handle(r#"{"id":4,"auth":"letmein","method":"nope"}"#)
+                    .await
+            ),
+            codes::METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            error(&gateway.handle(
+                r#"{"id":5,"auth":"letmein","method":"place_order","params":{"symbol":"BTC-USDT","side":"up","amount":"1"}}"#,
+            ).await),
+            codes::INVALID_PARAMS
+        );
+        assert_eq!(error(&gateway.handle("{").await), codes::PARSE_ERROR);
+        assert_eq!(error(&gateway.handle("[]").await), codes::INVALID_REQUEST);
+
+        let gateway = gateway.read_only(true);
+        assert_eq!(
+            error(&gateway.handle(&format!(
+                r#"{{"id":6,"auth":"letmein","method":"cancel_order","params":{{"symbol":"BTC-USDT","order_id":"{}"}}}}"#,
+                order_id
+            )).await),
+            codes::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let gateway = gateway().await;
+        let client = gateway.client.clone();
+        let server = tokio::spawn(gateway.serve(listener));
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (read, mut write) = stream.into_split();
+        write
+            .write_all(
+                b"{\"id\":7,\"auth\":\"letmein\",\"method\":\"depth\",\"params\":{\"symbol\":\"BTC-USDT\"}}\n",
+            )
+            .await
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(read).read_line(&mut line).await.unwrap();
+        assert_eq!(reply(&line)["result"]["asks"][0][0], "11");
+
+        client.shutdown().await;
+        server.await.unwrap().unwrap();
+    }
+}
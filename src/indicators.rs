@@ -0,0 +1,267 @@
+//! moving averages, RSI and ATR on `Kline`s in `BigDecimal`, without a lossy detour over f64
+//!
+//! every indicator takes one candle at a time, so the same value works on a slice of history
+//! with `Indicator::series` and on a live stream, e.g. `candle_close_stream`, with `over_stream`
+
+use crate::response::Kline;
+use crate::rounding::{self, RoundingMode};
+use bigdecimal::{BigDecimal, One, Signed, Zero};
+use futures_util::{Stream, StreamExt};
+use std::collections::VecDeque;
+
+/// digits after the decimal point an indicator keeps, smoothing would grow them forever
+pub const SCALE: i64 = 16;
+
+fn round(value: BigDecimal) -> BigDecimal {
+    rounding::round_to_scale(&value, SCALE, RoundingMode::HalfUp)
+}
+
+pub trait Indicator {
+    type Output;
+
+    /// feed the next closed candle, `None` until enough candles were seen
+    fn next(&mut self, kline: &Kline) -> Option<Self::Output>;
+
+    /// one value for every candle of `klines`, oldest first
+    fn series(&mut self, klines: &[Kline]) -> Vec<Option<Self::Output>> {
+        klines.iter().map(|kline| self.next(kline)).collect()
+    }
+}
+
+/// the values of `indicator` over a stream of closed candles
+pub fn over_stream<S, I>(klines: S, mut indicator: I) -> impl Stream<Item = Option<I::Output>>
+where
+    S: Stream<Item = Kline>,
+    I: Indicator,
+{
+    klines.map(move |kline| indicator.next(&kline))
+}
+
+/// the mean close of the last `period` candles
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<BigDecimal>,
+    sum: BigDecimal,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Sma {
+            period: period.max(1),
+            window: VecDeque::new(),
+            sum: BigDecimal::zero(),
+        }
+    }
+
+    fn push(&mut self, value: BigDecimal) -> Option<BigDecimal> {
+        self.sum += &value;
+        self.window.push_back(value);
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap_or_default();
+        }
+        (self.window.len() == self.period)
+            .then(|| round(&self.sum / BigDecimal::from(self.period as u64)))
+    }
+}
+
+impl Indicator for Sma {
+    type Output = BigDecimal;
+
+    fn next(&mut self, kline: &Kline) -> Option<BigDecimal> {
+        self.push(kline.close.clone())
+    }
+}
+
+/// the close smoothed by `2 / (period + 1)`, seeded with the `Sma` of the first `period`
+#[derive(Debug, Clone)]
+pub struct Ema {
+    alpha: BigDecimal,
+    seed: Sma,
+    value: Option<BigDecimal>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Ema {
+            alpha: round(BigDecimal::from(2) / BigDecimal::from(period as u64 + 1)),
+            seed: Sma::new(period),
+            value: None,
+        }
+    }
+}
+
+impl Indicator for Ema {
+    type Output = BigDecimal;
+
+    fn next(&mut self, kline: &Kline) -> Option<BigDecimal> {
+        let value = match self.value.take() {
+            None => self.seed.next(kline),
+            Some(prev) => Some(round(&self.alpha * (&kline.close - &prev) + prev)),
+        };
+        self.value = value.clone();
+        value
+    }
+}
+
+/// Wilder's smoothing: the mean of the first `period` values, then
+/// `(prev * (period - 1) + value) / period`
+#[derive(Debug, Clone)]
+struct Wilder {
+    period: usize,
+    seen: usize,
+    value: BigDecimal,
+}
+
+impl Wilder {
+    fn new(period: usize) -> Self {
+        Wilder {
+            period: period.max(1),
+            seen: 0,
+            value: BigDecimal::zero(),
+        }
+    }
+
+    fn next(&mut self, value: BigDecimal) -> Option<BigDecimal> {
+        let period = BigDecimal::from(self.period as u64);
+        self.seen += 1;
+        if self.seen < self.period {
+            self.value += value;
+            return None;
+        }
+        self.value = if self.seen == self.period {
+            round((&self.value + value) / period)
+        } else {
+            round((&self.value * (&period - BigDecimal::one()) + value) / period)
+        };
+        Some(self.value.clone())
+    }
+}
+
+/// the relative strength index from 0 to 100 with Wilder's smoothing, the first value comes
+/// with the `period`th change, so after `period + 1` candles
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    prev_close: Option<BigDecimal>,
+    gains: Wilder,
+    losses: Wilder,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Rsi {
+            prev_close: None,
+            gains: Wilder::new(period),
+            losses: Wilder::new(period),
+        }
+    }
+}
+
+impl Indicator for Rsi {
+    type Output = BigDecimal;
+
+    fn next(&mut self, kline: &Kline) -> Option<BigDecimal> {
+        let prev = self.prev_close.replace(kline.close.clone())?;
+        let change = &kline.close - prev;
+        let (gain, loss) = if change.is_positive() {
+            (change, BigDecimal::zero())
+        } else {
+            (BigDecimal::zero(), -change)
+        };
+        // both sides see every change, before either can bail out
+        let (gain, loss) = (self.gains.next(gain), self.losses.next(loss));
+        let (gain, loss) = (gain?, loss?);
+        let hundred = BigDecimal::from(100);
+        if loss.is_zero() {
+            return Some(hundred);
+        }
+        Some(round(
+            &hundred - &hundred / (BigDecimal::one() + gain / loss),
+        ))
+    }
+}
+
+/// the average true range with Wilder's smoothing, the first candle's range is `high - low`
+#[derive(Debug, Clone)]
+pub struct Atr {
+    prev_close: Option<BigDecimal>,
+    ranges: Wilder,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Atr {
+            prev_close: None,
+            ranges: Wilder::new(period),
+        }
+    }
+}
+
+impl Indicator for Atr {
+    type Output = BigDecimal;
+
+    fn next(&mut self, kline: &Kline) -> Option<BigDecimal> {
+        let mut range = &kline.high - &kline.low;
+        if let Some(prev) = self.prev_close.replace(kline.close.clone()) {
+            range = range
+                .max((&kline.high - &prev).abs())
+                .max((&kline.low - &prev).abs());
+        }
+        self.ranges.next(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kline(high: &str, low: &str, close: &str) -> Kline {
+        serde_json::from_str(&format!(
+            r#"{{"id":0,"open":"{}","close":"{}","high":"{}","low":"{}","vol":"1"}}"#,
+            close, close, high, low
+        ))
+        .unwrap()
+    }
+
+    fn closes(closes: &[i64]) -> Vec<Kline> {
+        closes
+            .iter()
+            .map(|c| kline(&c.to_string(), &c.to_string(), &c.to_string()))
+            .collect()
+    }
+
+    fn values(series: Vec<Option<BigDecimal>>) -> Vec<Option<String>> {
+        series
+            .into_iter()
+            .map(|v| v.map(|v| v.normalized().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_indicators() {
+        let some = |v: &str| Some(v.to_string());
+        let klines = closes(&[1, 2, 3, 4, 5]);
+        assert_eq!(
+            values(Sma::new(3).series(&klines)),
+            vec![None, None, some("2"), some("3"), some("4")]
+        );
+        assert_eq!(
+            values(Ema::new(3).series(&klines)),
+            vec![None, None, some("2"), some("3"), some("4")]
+        );
+        assert_eq!(
+            values(Rsi::new(2).series(&closes(&[1, 2, 3, 2]))),
+            vec![None, None, some("100"), some("50")]
+        );
+        let klines = [
+            kline("2", "1", "1.5"),
+            kline("3", "2", "2.5"),
+            kline("3", "1", "2"),
+        ];
+        assert_eq!(
+            values(Atr::new(2).series(&klines)),
+            vec![None, some("1.25"), some("1.625")]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// one request and the response it got, enough to sign and send the request again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// unix seconds the request was signed at
+    pub timestamp: i64,
+    pub method: String,
+    pub uri: String,
+    pub formalized: Option<String>,
+    pub payload: Option<String>,
+    pub status: u16,
+    pub body: String,
+}
+
+impl JournalEntry {
+    /// only reads may ever be sent again
+    pub fn is_query(&self) -> bool {
+        self.method.eq_ignore_ascii_case("GET")
+    }
+}
+
+/// append-only JSON lines file of every request the client sent
+#[derive(Debug)]
+pub struct Journal {
+    file: Mutex<std::fs::File>,
+}
+
+impl Journal {
+    pub fn open<T: AsRef<Path>>(path: T) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Journal {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, entry: &JournalEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// every entry of a journal file in the order it was written, a torn last line is skipped
+    pub fn read<T: AsRef<Path>>(path: T) -> anyhow::Result<Vec<JournalEntry>> {
+        let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut entries = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) if e.is_eof() => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_read() {
+        let path = std::env::temp_dir().join(format!("fxdx-journal-{}.jsonl", std::process::id()));
+        let entry = JournalEntry {
+            timestamp: 1,
+            method: String::from("GET"),
+            uri: String::from("/maker/balances"),
+            formalized: None,
+            payload: None,
+            status: 200,
+            body: String::from(r#"{"code":200}"#),
+        };
+        let journal = Journal::open(&path).unwrap();
+        journal.record(&entry).unwrap();
+        journal.record(&entry).unwrap();
+        let entries = Journal::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(entries, vec![entry.clone(), entry]);
+        assert!(entries[0].is_query());
+    }
+}
@@ -0,0 +1,299 @@
+use crate::Error;
+use anyhow::Result;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::XSalsa20Poly1305;
+use schnorrkel::derive::{ChainCode, Derivation};
+use schnorrkel::{ExpansionMode, Keypair, MiniSecretKey, SecretKey};
+use serde::Deserialize;
+
+/// the phrase of an empty SURI such as `//Alice`, the well known substrate dev accounts
+pub const DEV_PHRASE: &str =
+    "bottom drive obey lake curtain smoke basket hold race lonely fit walk";
+
+const PKCS8_HEADER: [u8; 16] = [48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32];
+const PKCS8_DIVIDER: [u8; 5] = [161, 35, 3, 33, 0];
+const SCRYPT_LENGTH: usize = 32 + 3 * 4;
+const NONCE_LENGTH: usize = 24;
+
+fn invalid(reason: &str) -> anyhow::Error {
+    Error::InvalidKey(String::from(reason)).into()
+}
+
+/// a key in the subkey SURI format: a mnemonic or `0x` seed, then `//hard` and `/soft`
+/// junctions and a `///password`, e.g. `//Alice` or `<mnemonic>//fxdx//0`
+pub fn from_suri(suri: &str) -> Result<Keypair> {
+    let (rest, password) = match suri.split_once("///") {
+        Some((rest, password)) => (rest, Some(password)),
+        None => (suri, None),
+    };
+    let (phrase, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let phrase = match phrase.trim() {
+        "" => DEV_PHRASE,
+        phrase => phrase,
+    };
+    let mut keypair = match phrase.strip_prefix("0x") {
+        Some(_) => from_seed_hex(phrase)?,
+        None => {
+            let mnemonic =
+                bip39::Mnemonic::parse(phrase).map_err(|_| invalid("not a valid mnemonic"))?;
+            substrate_bip39::mini_secret_from_entropy(
+                &mnemonic.to_entropy(),
+                password.unwrap_or(""),
+            )
+            .map_err(|_| invalid("not a valid mnemonic"))?
+            .expand_to_keypair(ExpansionMode::Ed25519)
+        }
+    };
+    for junction in junctions(path)? {
+        keypair = match junction {
+            Junction::Soft(cc) => keypair.derived_key_simple(ChainCode(cc), []).0,
+            Junction::Hard(cc) => keypair
+                .hard_derive_mini_secret_key(Some(ChainCode(cc)), b"")
+                .0
+                .expand_to_keypair(ExpansionMode::Ed25519),
+        };
+    }
+    Ok(keypair)
+}
+
+/// a raw 32 byte seed in hex, with or without `0x`
+pub fn from_seed_hex(seed: &str) -> Result<Keypair> {
+    let seed = hex::decode(seed.trim().trim_start_matches("0x"))
+        .map_err(|_| invalid("seed is not hex"))?;
+    Ok(MiniSecretKey::from_bytes(&seed)
+        .map_err(|_| invalid("seed is not 32 bytes"))?
+        .expand_to_keypair(ExpansionMode::Ed25519))
+}
+
+/// an ed25519 key as its 32 byte seed or 64 byte keypair in hex, with or without `0x`
+pub fn ed25519_from_hex(key: &str) -> Result<ed25519_dalek::SigningKey> {
+    let bytes = hex::decode(key.trim().trim_start_matches("0x"))
+        .map_err(|_| invalid("ed25519 key is not hex"))?;
+    match bytes.len() {
+        32 => Ok(ed25519_dalek::SigningKey::from_bytes(
+            bytes.as_slice().try_into().unwrap(),
+        )),
+        64 => ed25519_dalek::SigningKey::from_keypair_bytes(bytes.as_slice().try_into().unwrap())
+            .map_err(|_| invalid("ed25519 public key does not match the seed")),
+        _ => Err(invalid("ed25519 key is neither 32 nor 64 bytes")),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Keystore {
+    encoded: String,
+    encoding: KeystoreEncoding,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeystoreEncoding {
+    content: Vec<String>,
+    #[serde(rename = "type")]
+    kind: OneOrMany,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn contains(&self, value: &str) -> bool {
+        match self {
+            OneOrMany::One(one) => one == value,
+            OneOrMany::Many(many) => many.iter().any(|v| v == value),
+        }
+    }
+}
+
+/// an account exported from Polkadot-JS as JSON, decrypted with its password
+pub fn from_keystore(json: &str, password: &str) -> Result<Keypair> {
+    let keystore: Keystore = serde_json::from_str(json)?;
+    if !keystore.encoding.content.iter().any(|c| c == "sr25519") {
+        return Err(invalid("keystore does not hold an sr25519 key"));
+    }
+    let encoded = openssl::base64::decode_block(&keystore.encoded)
+        .map_err(|_| invalid("keystore is not base64"))?;
+    let decrypted = if keystore.encoding.kind.contains("none") {
+        encoded
+    } else {
+        decrypt(
+            &encoded,
+            password,
+            keystore.encoding.kind.contains("scrypt"),
+        )?
+    };
+    let pkcs8 = decrypted
+        .strip_prefix(&PKCS8_HEADER)
+        .ok_or_else(|| invalid("keystore is not PKCS8"))?;
+    // the current format holds the 64 byte secret, the oldest one the 32 byte seed
+    if pkcs8.get(64..64 + PKCS8_DIVIDER.len()) == Some(&PKCS8_DIVIDER) {
+        let secret = SecretKey::from_ed25519_bytes(&pkcs8[..64])
+            .map_err(|_| invalid("keystore secret is malformed"))?;
+        Ok(secret.to_keypair())
+    } else if pkcs8.get(32..32 + PKCS8_DIVIDER.len()) == Some(&PKCS8_DIVIDER) {
+        Ok(MiniSecretKey::from_bytes(&pkcs8[..32])
+            .map_err(|_| invalid("keystore seed is malformed"))?
+            .expand_to_keypair(ExpansionMode::Ed25519))
+    } else {
+        Err(invalid("keystore is not PKCS8"))
+    }
+}
+
+fn decrypt(encoded: &[u8], password: &str, scrypt: bool) -> Result<Vec<u8>> {
+    let mut key = [0u8; 32];
+    let encoded = if scrypt {
+        if encoded.len() < SCRYPT_LENGTH {
+            return Err(invalid("keystore is truncated"));
+        }
+        let param =
+            |at: usize| u64::from(u32::from_le_bytes(encoded[at..at + 4].try_into().unwrap()));
+        let (salt, n, p, r) = (&encoded[..32], param(32), param(36), param(40));
+        let max_memory = 128 * r * (n + p + 2) + (1 << 20);
+        openssl::pkcs5::scrypt(password.as_bytes(), salt, n, r, p, max_memory, &mut key)?;
+        &encoded[SCRYPT_LENGTH..]
+    } else {
+        let password = password.as_bytes();
+        let len = password.len().min(key.len());
+        key[..len].copy_from_slice(&password[..len]);
+        encoded
+    };
+    if encoded.len() < NONCE_LENGTH {
+        return Err(invalid("keystore is truncated"));
+    }
+    let (nonce, sealed) = encoded.split_at(NONCE_LENGTH);
+    XSalsa20Poly1305::new(&key.into())
+        .decrypt(nonce.into(), sealed)
+        .map_err(|_| invalid("wrong keystore password"))
+}
+
+enum Junction {
+    Hard([u8; 32]),
+    Soft([u8; 32]),
+}
+
+/// the `//hard` and `/soft` junctions of a SURI path, each turned into its chain code: numbers
+/// as u64 and anything else as a SCALE string, hashed when longer than 32 bytes
+fn junctions(path: &str) -> Result<Vec<Junction>> {
+    let mut junctions = vec![];
+    let mut rest = path;
+    while !rest.is_empty() {
+        let (hard, tail) = match rest.strip_prefix("//") {
+            Some(tail) => (true, tail),
+            None => (false, &rest[1..]),
+        };
+        let end = tail.find('/').unwrap_or(tail.len());
+        let (name, next) = tail.split_at(end);
+        if name.is_empty() {
+            return Err(invalid("empty junction in derivation path"));
+        }
+        let encoded = match name.parse::<u64>() {
+            Ok(index) => index.to_le_bytes().to_vec(),
+            Err(_) => {
+                let mut encoded = compact_len(name.len());
+                encoded.extend_from_slice(name.as_bytes());
+                encoded
+            }
+        };
+        let mut cc = [0u8; 32];
+        if encoded.len() > cc.len() {
+            cc.copy_from_slice(&Blake2b::<U32>::digest(&encoded));
+        } else {
+            cc[..encoded.len()].copy_from_slice(&encoded);
+        }
+        junctions.push(if hard {
+            Junction::Hard(cc)
+        } else {
+            Junction::Soft(cc)
+        });
+        rest = next;
+    }
+    Ok(junctions)
+}
+
+/// the SCALE compact encoding of a length
+fn compact_len(len: usize) -> Vec<u8> {
+    match len {
+        0..=0x3f => vec![(len as u8) << 2],
+        0x40..=0x3fff => ((len as u16) << 2 | 1).to_le_bytes().to_vec(),
+        _ => ((len as u32) << 2 | 2).to_le_bytes().to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(keypair: &Keypair) -> String {
+        hex::encode(keypair.public.to_bytes())
+    }
+
+    #[test]
+    fn test_suri() {
+        // the public keys subkey prints for these
+        assert_eq!(
+            public(&from_suri("//Alice").unwrap()),
+            "d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"
+        );
+        assert_eq!(
+            public(&from_suri(DEV_PHRASE).unwrap()),
+            "46ebddef8cd9bb167dc30878d7113b7e168e6f0646beffd77d69d39bad76b47a"
+        );
+        let seed = "0xe5be9a5092b81bca64be81d212e7f2f9eba183bb7a90954f7b76361f6edb5c0a";
+        assert_eq!(
+            public(&from_seed_hex(seed).unwrap()),
+            public(&from_suri(seed).unwrap())
+        );
+        assert_ne!(
+            public(&from_suri("//Alice/0").unwrap()),
+            public(&from_suri("//Alice//0").unwrap())
+        );
+        assert!(from_suri("not a mnemonic").is_err());
+        assert!(from_suri("//Alice//").is_err());
+    }
+
+    #[test]
+    fn test_keystore() {
+        let keypair = from_suri("//Bob").unwrap();
+        let mut plain = PKCS8_HEADER.to_vec();
+        plain.extend_from_slice(&keypair.secret.to_ed25519_bytes());
+        plain.extend_from_slice(&PKCS8_DIVIDER);
+        plain.extend_from_slice(&keypair.public.to_bytes());
+
+        let salt = [7u8; 32];
+        let (n, p, r) = (1024u32, 1u32, 8u32);
+        let mut key = [0u8; 32];
+        openssl::pkcs5::scrypt(b"hunter2", &salt, n.into(), r.into(), p.into(), 0, &mut key)
+            .unwrap();
+        let nonce = [3u8; NONCE_LENGTH];
+        let sealed = XSalsa20Poly1305::new(&key.into())
+            .encrypt(&nonce.into(), plain.as_slice())
+            .unwrap();
+        let mut encoded = salt.to_vec();
+        for param in [n, p, r] {
+            encoded.extend_from_slice(&param.to_le_bytes());
+        }
+        encoded.extend_from_slice(&nonce);
+        encoded.extend_from_slice(&sealed);
+        let json = serde_json::json!({
+            "encoded": openssl::base64::encode_block(&encoded),
+            "encoding": {
+                "content": ["pkcs8", "sr25519"],
+                "type": ["scrypt", "xsalsa20-poly1305"],
+                "version": "3"
+            },
+            "address": "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty"
+        })
+        .to_string();
+
+        assert_eq!(
+            public(&from_keystore(&json, "hunter2").unwrap()),
+            public(&keypair)
+        );
+        assert!(from_keystore(&json, "hunter3").is_err());
+    }
+}
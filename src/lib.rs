@@ -1,259 +1,4081 @@
-mod request;
-mod response;
+extern crate alloc;
+
+pub mod assets;
+pub mod audit;
+pub mod backtest;
+pub mod balance;
+pub mod booklog;
+pub mod bulk;
+pub mod candles;
+pub mod config;
+pub mod connection;
+pub mod de;
+pub mod depthsync;
+pub mod dropcopy;
+pub mod encoding;
+pub mod environment;
+pub mod eventbuffer;
+pub mod exchange;
+pub mod execution;
+#[cfg(feature = "export")]
+pub mod export;
+pub mod failover;
+pub mod faults;
+pub mod fees;
+#[cfg(feature = "gateway")]
+pub mod gateway;
+#[cfg(feature = "indicators")]
+pub mod indicators;
+pub mod journal;
+pub mod keys;
+pub mod marketdata;
+pub mod nonce;
+pub mod numeric;
+pub mod orderbook;
+pub mod outbox;
+pub mod paper;
+pub mod poller;
+pub mod portfolio;
+pub mod priority;
+#[cfg(feature = "publish")]
+pub mod publish;
+pub mod ratelimit;
+pub mod reconcile;
+pub mod replay;
+pub mod request;
+pub mod response;
+pub mod responsecache;
+pub mod risk;
+pub mod rounding;
+pub mod sequence;
+pub mod shutdown;
+pub mod signing;
+pub mod spread;
+pub mod storage;
+pub mod symbols;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timing;
+pub mod tokenstore;
+pub mod transport;
+pub mod warmcache;
+pub mod websocket;
+pub mod wire;
 
 use anyhow::Result;
-use openssl::hash::MessageDigest;
-use openssl::pkey::PKey;
-use openssl::sign::Signer as OpensslSigner;
-use reqwest::header::HeaderValue;
+use secrecy::ExposeSecret;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Invalid request {0}")]
     InvalidRequest(String),
 
-    #[error("Invalid wait to gernerate signature {0:?}")]
-    InvalidSignature(request::Request),
+    /// carries the uri only, the request is not printed next to anything signing related
+    #[error("Invalid way to generate the signature of {0}")]
+    InvalidSignature(String),
+
+    /// the ids the venue returned can not be paired with the orders, some of which may be live
+    #[error("Batch submitted {submitted} orders but got {returned} results")]
+    BatchMismatch {
+        submitted: usize,
+        returned: usize,
+        ids: Vec<Option<String>>,
+    },
+
+    #[error("Corrupt order book of {0}")]
+    CorruptBook(String),
+
+    #[error("Request did not complete within {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("Permission {0:?} not granted to this key")]
+    PermissionDenied(request::Permission),
+
+    #[error("Request rejected with code {0}")]
+    Rejected(i32),
+
+    /// rejected by `risk::RiskGuard` without reaching the venue
+    #[error("Order rejected by risk limits: {0}")]
+    RiskLimit(risk::RiskViolation),
+
+    #[error("Invalid client configuration: {0}")]
+    Build(#[from] BuildError),
+
+    /// a number out of the range or precision of the type it was converted to
+    #[error("Can not convert {0}")]
+    Conversion(String),
+
+    #[error("Successful response without data")]
+    MissingData,
+
+    /// never carries any of the key material
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    /// the transport got no response, its own error is the source
+    #[error("No response from {0}")]
+    Transport(String),
+
+    /// 401 or 403, a 401 of a session with a handshake was already retried once after a fresh
+    /// handshake
+    #[error("Not authorized, status {0}")]
+    Unauthorized(u16),
+
+    /// 429 with the wait the `Retry-After` header asked for
+    #[error("Rate limited, retry after {0:?}")]
+    RateLimited(Option<std::time::Duration>),
+
+    /// any other unsuccessful status whose body is no API response, e.g. a proxy error page
+    #[error("HTTP status {status}, body {body}")]
+    Http { status: u16, body: String },
+
+    /// `body` is cut to `MAX_ERROR_BODY` bytes, or redacted with `FxdxBuilder::redact_bodies`
+    #[error("Undecodable {status} response from {endpoint}{uri}: {source}, body {body}")]
+    Decode {
+        status: u16,
+        endpoint: String,
+        uri: String,
+        body: String,
+        source: serde_json::Error,
+    },
 }
 
-struct Signer {
-    secret_key: String,
+/// why `FxdxBuilder::build` refused to build a client
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BuildError {
+    #[error("no endpoint configured")]
+    NoEndpoint,
+
+    #[error("invalid endpoint {endpoint}: {reason}")]
+    InvalidEndpoint { endpoint: String, reason: String },
+
+    #[error("no credentials, set a secret, an sr25519 key or an ed25519 key")]
+    MissingCredentials,
+
+    #[error("{0} conflicts with the credentials already set")]
+    ConflictingCredentials(&'static str),
+
+    #[error("{0} is not supported yet")]
+    Unsupported(&'static str),
+
+    #[error("invalid header {name}: {reason}")]
+    InvalidHeader { name: String, reason: String },
+}
+
+/// how much of an undecodable body `Error::Decode` keeps
+pub const MAX_ERROR_BODY: usize = 512;
+
+/// how many encoding buffers a client keeps, as many as calls usually run at once
+const POOLED_ENCODERS: usize = 16;
+
+/// the encoding buffers of one call, taken from the pool of the client and given back on drop
+/// so the next call writes its uri and parameters without allocating
+struct Encoder<'a> {
+    pool: &'a std::sync::Mutex<Vec<encoding::Encoded>>,
+    encoded: encoding::Encoded,
 }
 
-impl Signer {
-    pub fn new(secret: String) -> Self {
-        Signer { secret_key: secret }
+impl<'a> Encoder<'a> {
+    fn take(pool: &'a std::sync::Mutex<Vec<encoding::Encoded>>) -> Self {
+        let encoded = pool
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop()
+            .unwrap_or_default();
+        Encoder { pool, encoded }
     }
+}
 
-    pub fn sign(&self, formalized: String) -> Result<Vec<u8>> {
-        let secret = PKey::hmac(self.secret_key.as_bytes())?;
-        let mut signer = OpensslSigner::new(MessageDigest::sha1(), &secret)?;
-        signer.update(formalized.as_bytes())?;
-        Ok(signer.sign_to_vec()?)
+impl Drop for Encoder<'_> {
+    fn drop(&mut self) {
+        let mut pool = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        if pool.len() < POOLED_ENCODERS {
+            pool.push(std::mem::take(&mut self.encoded));
+        }
     }
 }
 
+/// what `FxdxClient::dispatch` got back
+struct Reply {
+    status: reqwest::StatusCode,
+    retry_after: Option<std::time::Duration>,
+    body: bytes::Bytes,
+}
+
+fn unix_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+fn unix_timestamp() -> Result<i64> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+    Ok(now.as_secs() as i64)
+}
+
+/// a handle to the client, clones are cheap and share the connection pool, the rate limiter,
+/// the session token and every other piece of state
+///
+/// it is `Send + Sync`, so one client can be cloned into as many tasks as need it
 pub struct FxdxClient<P> {
-    client: reqwest::Client,
-    endpoint: String,
-    address: String,
-    signer: Signer,
+    inner: std::sync::Arc<ClientState>,
     _marker: std::marker::PhantomData<P>,
 }
 
+impl<P> Clone for FxdxClient<P> {
+    fn clone(&self) -> Self {
+        FxdxClient {
+            inner: self.inner.clone(),
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// what the clones of a `FxdxClient` share, everything mutable is behind a lock or atomic
+struct ClientState {
+    transport: std::sync::Arc<dyn transport::Transport>,
+    endpoints: failover::EndpointPool,
+    mirror: Option<String>,
+    address: String,
+    signer: signing::Signer,
+    sequencer: sequence::Sequencer,
+    limiter: Option<ratelimit::RateLimiter>,
+    paper: Option<std::sync::Mutex<paper::PaperExchange>>,
+    journal: Option<journal::Journal>,
+    audit: Option<audit::AuditLog>,
+    drop_copy: Option<dropcopy::DropCopy>,
+    storage: Option<std::sync::Arc<dyn storage::Storage>>,
+    permissions: std::sync::RwLock<Option<Vec<request::Permission>>>,
+    timing: timing::SessionTiming,
+    outbox: Option<std::sync::Mutex<outbox::Outbox>>,
+    cache: Option<responsecache::ResponseCache>,
+    retry: Option<config::RetryPolicy>,
+    default_symbols: Vec<String>,
+    environment: environment::Environment,
+    wire: wire::WireProfile,
+    shutdown: shutdown::Shutdown,
+    redact_bodies: bool,
+    on_rate_change: Option<ratelimit::RateHook>,
+    /// sent with every request after the signed ones, see `FxdxBuilder::default_header`
+    headers: Vec<(&'static str, String)>,
+    /// the session token of the handshake, see `fresh`
+    token: std::sync::RwLock<Option<tokenstore::StoredToken>>,
+    token_store: Option<std::sync::Arc<dyn tokenstore::TokenStore>>,
+    nonces: Option<nonce::NonceManager>,
+    /// bumped by every `fresh`, tells `renew_token` whether the token it saw is still in use
+    token_generation: std::sync::atomic::AtomicU64,
+    renewing: tokio::sync::Mutex<()>,
+    /// the encoding buffers of finished calls, see `Encoder`
+    encoders: std::sync::Mutex<Vec<encoding::Encoded>>,
+}
+
+/// how often `cancel_verified` looks at the order
+const CANCEL_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// the code the simulator answers with, the one `response::Success` accepts
+const PAPER_OK: i32 = 200;
+
 impl<P> FxdxClient<P>
 where
     P: request::Prefix,
 {
-    async fn send(&self, req: request::Request) -> Result<reqwest::Response> {
-        let mut builder = self
-            .client
-            .request(req.method(), format!("{}{}", self.endpoint, req.uri::<P>()));
-        let timestamp = {
-            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
-            now.as_secs().to_string()
-        };
-        let mut signature = format!(
-            "{},{},{}",
-            self.signer.secret_key,
-            &timestamp,
-            req.uri::<P>()
+    async fn call<T: serde::de::DeserializeOwned>(&self, req: &request::Request) -> Result<T> {
+        self.call_to(self.inner.endpoints.current(), req).await
+    }
+
+    async fn call_to<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        req: &request::Request,
+    ) -> Result<T> {
+        self.check_permission(req)?;
+        let mut retries = 0;
+        let mut renewed = false;
+        // the same on every attempt, only the timestamp and signature change
+        let mut encoder = Encoder::take(&self.inner.encoders);
+        req.encode_with::<P>(&self.inner.wire, &mut encoder.encoded)?;
+        let encoded = &encoder.encoded;
+        let uri = encoded.uri.as_str();
+        let cached = self.cached(req, uri, encoded.formalized.as_deref());
+        if let Some((key, _)) = &cached {
+            let hit = self
+                .inner
+                .cache
+                .as_ref()
+                .and_then(|cache| cache.get(key, std::time::Instant::now()));
+            if let Some(body) = hit {
+                return self.decode(endpoint, uri, reqwest::StatusCode::OK, &body);
+            }
+        }
+        let reply = loop {
+            let generation = self
+                .inner
+                .token_generation
+                .load(std::sync::atomic::Ordering::SeqCst);
+            let outcome = self
+                .dispatch(endpoint, encoded, req.weight(), req.priority())
+                .await;
+            match (&self.inner.retry, outcome) {
+                (Some(policy), Err(e))
+                    if req.method() == reqwest::Method::GET
+                        && retries < policy.max_retries
+                        && matches!(e.downcast_ref::<Error>(), Some(Error::Transport(_))) =>
+                {
+                    tokio::time::sleep(policy.backoff(retries)).await;
+                    retries += 1;
+                }
+                // the session token expired, renew it once and send the request again
+                (_, Ok(reply))
+                    if reply.status == reqwest::StatusCode::UNAUTHORIZED
+                        && !renewed
+                        && self.renews_token(req) =>
+                {
+                    Box::pin(self.renew_token(generation)).await?;
+                    renewed = true;
+                }
+                // a rate limited request was not executed, so even a mutating one can go again
+                (Some(policy), Ok(reply))
+                    if reply.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        && retries < policy.max_retries =>
+                {
+                    tokio::time::sleep(policy.rate_limited(retries, reply.retry_after)).await;
+                    retries += 1;
+                }
+                (_, outcome) => break outcome?,
+            }
+        };
+        match reply.status.as_u16() {
+            _ if reply.status.is_success() => {
+                if let (Some(cache), Some((key, ttl))) = (&self.inner.cache, cached) {
+                    // a rejection is not worth remembering
+                    let accepted = serde_json::from_slice::<
+                        response::ApiResponse<serde::de::IgnoredAny>,
+                    >(&reply.body)
+                    .is_ok_and(|resp| resp.is_success());
+                    if accepted {
+                        cache.insert(key, std::time::Instant::now(), ttl, reply.body.clone());
+                    }
+                }
+                self.decode(endpoint, uri, reply.status, &reply.body)
+            }
+            status @ (401 | 403) => Err(Error::Unauthorized(status).into()),
+            429 => Err(Error::RateLimited(reply.retry_after).into()),
+            // the exchange answers some rejections with an error status and its usual envelope
+            status => serde_json::from_slice(&reply.body).map_err(|_| {
+                Error::Http {
+                    status,
+                    body: self.error_body(&reply.body),
+                }
+                .into()
+            }),
+        }
+    }
+
+    /// the cache key and ttl of `req` when the response cache covers it
+    fn cached(
+        &self,
+        req: &request::Request,
+        uri: &str,
+        formalized: Option<&str>,
+    ) -> Option<(String, std::time::Duration)> {
+        let ttl = self.inner.cache.as_ref()?.ttl(req)?;
+        Some((format!("{}?{}", uri, formalized.unwrap_or_default()), ttl))
+    }
+
+    /// drop the cached response to `req`, so the next call asks the exchange
+    pub fn uncache(&self, req: &request::Request) {
+        if let (Some(cache), Ok(formalized)) = (&self.inner.cache, req.formalize()) {
+            let key = format!("{}?{}", req.uri::<P>(), formalized.unwrap_or_default());
+            cache.remove(&key);
+        }
+    }
+
+    /// `body` for an error message, cut to `MAX_ERROR_BODY` or redacted
+    fn error_body(&self, body: &[u8]) -> String {
+        if self.inner.redact_bodies {
+            return format!("<{} bytes redacted>", body.len());
+        }
+        let mut text = String::from_utf8_lossy(body).into_owned();
+        if text.len() > MAX_ERROR_BODY {
+            let mut end = MAX_ERROR_BODY;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+            text.push_str("...");
+        }
+        text
+    }
+
+    /// read a response body, failing with the status, the endpoint and the body itself
+    fn decode<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        uri: &str,
+        status: reqwest::StatusCode,
+        body: &[u8],
+    ) -> Result<T> {
+        serde_json::from_slice(body).map_err(|source| {
+            Error::Decode {
+                status: status.as_u16(),
+                endpoint: endpoint.to_string(),
+                uri: uri.to_string(),
+                body: self.error_body(body),
+                source,
+            }
+            .into()
+        })
+    }
+
+    /// fail locally when the key is known to lack the permission `req` needs,
+    /// nothing is checked before `load_permissions`
+    fn check_permission(&self, req: &request::Request) -> Result<()> {
+        let permissions = self
+            .inner
+            .permissions
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        match (req.permission(), permissions.as_ref()) {
+            (Some(required), Some(granted)) if !granted.contains(&required) => {
+                Err(Error::PermissionDenied(required).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// the permissions of the key, `None` until `load_permissions` or `set_permissions`
+    pub fn permissions(&self) -> Option<Vec<request::Permission>> {
+        self.inner
+            .permissions
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn set_permissions(&self, permissions: Option<Vec<request::Permission>>) {
+        *self
+            .inner
+            .permissions
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = permissions;
+    }
+
+    /// fetch the permissions of the key, from then on requests it may not send fail locally
+    pub async fn load_permissions(&self) -> Result<Vec<request::Permission>> {
+        let info = self
+            .query_account_info(request::Request::AccountInfo)
+            .await?;
+        self.set_permissions(Some(info.permissions.clone()));
+        Ok(info.permissions)
+    }
+
+    /// sign and send one request, every request of the client goes through here
+    async fn dispatch(
+        &self,
+        endpoint: &str,
+        encoded: &encoding::Encoded,
+        weight: u32,
+        priority: priority::Priority,
+    ) -> Result<Reply> {
+        let (method, uri) = (encoded.method.as_str(), encoded.uri.as_str());
+        let (formalized, payload) = (encoded.formalized.as_deref(), encoded.payload.as_deref());
+        if let Some(limiter) = &self.inner.limiter {
+            let priority = priority::current().unwrap_or(priority);
+            limiter.acquire_prioritized(weight, priority).await;
+        }
+        let now = unix_timestamp()?;
+        let mut signed = self
+            .inner
+            .signer
+            .sign(encoded, &now.to_string(), &self.inner.address)?;
+        let token = self
+            .inner
+            .token
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|token| token.token.expose_secret().to_string());
+        if let Some(token) = token {
+            signed.headers.push((encoding::TOKEN_HEADER, token));
+            if let Some(nonces) = &self.inner.nonces {
+                signed
+                    .headers
+                    .push((encoding::NONCE_HEADER, nonces.next()?.to_string()));
+            }
+        }
+        signed.headers.extend(self.inner.headers.iter().cloned());
+        let signature_hash = self.inner.audit.as_ref().map(|_| {
+            signed
+                .headers
+                .iter()
+                .find(|(name, _)| *name == encoding::SIGNATURE_HEADER)
+                .map(|(_, signature)| audit::AuditRecord::hash_signature(signature))
+                .unwrap_or_default()
+        });
+        let sent = (std::time::SystemTime::now(), std::time::Instant::now());
+        let resp = self.inner.transport.execute(endpoint, signed).await;
+        let rtt = sent.1.elapsed();
+        if let (Some(audit), Some(signature_hash)) = (&self.inner.audit, signature_hash) {
+            let record = audit::AuditRecord {
+                timestamp: now,
+                endpoint: endpoint.to_string(),
+                method: method.to_string(),
+                uri: uri.to_string(),
+                summary: audit::AuditRecord::summarize(formalized, payload),
+                signature_hash,
+                status: resp.as_ref().ok().map(|resp| resp.status),
+            };
+            // the request is out, failing it now would only invite a duplicate
+            if let Err(e) = audit.record(&record) {
+                log::error!("audit record of {} {} lost: {}", method, uri, e);
+            }
+        }
+        if let Ok(resp) = &resp {
+            let server_date = resp
+                .header(reqwest::header::DATE.as_str())
+                .and_then(|v| httpdate::parse_http_date(v).ok());
+            self.inner.timing.record(sent.0, rtt, server_date);
+        }
+        let error = match &resp {
+            Ok(resp) if resp.status >= 500 => Some(format!("status {}", resp.status)),
+            Ok(_) => None,
+            Err(e) => Some(format!("{:#}", e)),
+        };
+        self.inner.endpoints.report(endpoint, error.is_none());
+        self.inner.timing.record_endpoint(
+            endpoint,
+            std::time::Instant::now(),
+            resp.is_ok().then_some(rtt),
+            error,
         );
-        if let Some(suffix) = req.formalize() {
-            signature = format!("{},{}", signature, suffix);
+        let resp = resp.map_err(|e| e.context(Error::Transport(endpoint.to_string())))?;
+        let status = reqwest::StatusCode::from_u16(resp.status)?;
+        let retry_after = resp
+            .header(reqwest::header::RETRY_AFTER.as_str())
+            .and_then(|v| ratelimit::parse_retry_after(v, std::time::SystemTime::now()));
+        let body = resp.body;
+        self.adapt_rate(status, retry_after);
+        // the venue has answered, a lost entry must not turn an accepted order into an error
+        if let Some(journal) = &self.inner.journal {
+            let recorded = journal.record(&journal::JournalEntry {
+                timestamp: now,
+                method: method.to_string(),
+                uri: uri.to_string(),
+                formalized: formalized.map(String::from),
+                payload: payload.map(String::from),
+                status: status.as_u16(),
+                body: String::from_utf8_lossy(&body).into_owned(),
+            });
+            if let Err(e) = recorded {
+                log::warn!("journal entry of {} {} lost: {}", method, uri, e);
+            }
         }
-        if let Some(payload) = req.payload()? {
-            builder = builder.body(payload);
+        Ok(Reply {
+            status,
+            retry_after,
+            body,
+        })
+    }
+
+    /// throttle the rate limiter on a 429 and recover it on success, reporting changes to the hook
+    fn adapt_rate(&self, status: reqwest::StatusCode, retry_after: Option<std::time::Duration>) {
+        let Some(limiter) = &self.inner.limiter else {
+            return;
+        };
+        let changed = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Some(limiter.throttle(retry_after))
+        } else if status.is_success() {
+            limiter.recover()
+        } else {
+            None
+        };
+        if let (Some(rate), Some(hook)) = (changed, &self.inner.on_rate_change) {
+            hook(rate);
         }
-        Ok(builder
-            .header("X-Timestamp", HeaderValue::from_str(&timestamp)?)
-            .header("X-Address", HeaderValue::from_str(&self.address)?)
-            .header("X-Signature", HeaderValue::from_str(&signature)?)
-            .send()
-            .await?)
     }
 
-    /// fresh the inner signer using sr25519
-    pub async fn fresh(&mut self) -> Result<()> {
-        unimplemented!()
+    /// requests per second the rate limiter currently lets through, `None` without one
+    pub fn effective_rate(&self) -> Option<f64> {
+        self.inner
+            .limiter
+            .as_ref()
+            .map(ratelimit::RateLimiter::effective_rate)
     }
 
-    /// send a pending order to fxdx
-    pub async fn pending_order(
+    /// sign and send a request the typed API does not cover yet, through the same rate limiter,
+    /// failover and journal as every other request; no permission is checked and nothing is retried
+    pub async fn send_raw(
         &self,
-        req: request::Request,
-    ) -> Result<response::PendingOrderResponse> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::PendingOrderResponse>()
-            .await?)
+        req: request::RawRequest,
+    ) -> Result<(reqwest::StatusCode, bytes::Bytes)> {
+        let encoded = encoding::Encoded {
+            method: transport::method(&req.method)?,
+            uri: req.uri,
+            formalized: req.formalized,
+            payload: req.body,
+        };
+        let reply = self
+            .dispatch(
+                self.inner.endpoints.current(),
+                &encoded,
+                req.weight,
+                priority::Priority::Normal,
+            )
+            .await?;
+        Ok((reply.status, reply.body))
     }
 
-    /// batch pending orders
-    pub async fn batch_pending_orders(
+    /// like `send_raw` with the body decoded as JSON whatever the status
+    pub async fn send_json<T: serde::de::DeserializeOwned>(
         &self,
-        req: request::Request,
-    ) -> Result<response::BatchPendingOrdersResponse> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::BatchPendingOrdersResponse>()
-            .await?)
+        req: request::RawRequest,
+    ) -> Result<T> {
+        let endpoint = self.inner.endpoints.current().to_string();
+        let uri = req.uri.clone();
+        let (status, body) = self.send_raw(req).await?;
+        self.decode(&endpoint, &uri, status, &body)
     }
 
-    pub async fn cancel_order(
+    /// sign and send a journaled query again and diff the answer against the recorded one,
+    /// mutating requests are refused so a replay can never touch orders or funds
+    pub async fn replay(&self, entry: &journal::JournalEntry) -> Result<replay::ReplayOutcome> {
+        if !entry.is_query() {
+            return Err(Error::InvalidRequest(format!(
+                "refuse to replay the mutating request {} {}",
+                entry.method, entry.uri
+            ))
+            .into());
+        }
+        let encoded = encoding::Encoded {
+            method: encoding::Method::Get,
+            uri: entry.uri.clone(),
+            formalized: entry.formalized.clone(),
+            payload: None,
+        };
+        let Reply { status, body, .. } = self
+            .dispatch(
+                self.inner.endpoints.current(),
+                &encoded,
+                1,
+                priority::Priority::Bulk,
+            )
+            .await?;
+        let body = String::from_utf8_lossy(&body).into_owned();
+        Ok(replay::ReplayOutcome {
+            differences: replay::diff_bodies(&entry.body, &body),
+            entry: entry.clone(),
+            status: status.as_u16(),
+            body,
+        })
+    }
+
+    /// replay every query of `entries` accepted by `select`, the rest is skipped
+    pub async fn replay_all<F>(
         &self,
-        req: request::Request,
-    ) -> Result<response::CancelOrderResponse> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::CancelOrderResponse>()
-            .await?)
+        entries: &[journal::JournalEntry],
+        select: F,
+    ) -> Result<Vec<replay::ReplayOutcome>>
+    where
+        F: Fn(&journal::JournalEntry) -> bool,
+    {
+        let mut outcomes = vec![];
+        for entry in entries.iter().filter(|e| e.is_query() && select(e)) {
+            outcomes.push(self.replay(entry).await?);
+        }
+        Ok(outcomes)
     }
 
-    pub async fn batch_cancel_orders(
+    /// run any call of the client under its own timeout, on top of the client wide one
+    ///
+    /// rate limiter waits count against the timeout and an expired call is dropped,
+    /// which aborts its in-flight request: `client.with_timeout(d, client.cancel_order(req))`
+    pub async fn with_timeout<F, T>(&self, timeout: std::time::Duration, call: F) -> Result<T>
+    where
+        F: std::future::Future<Output = Result<T>>,
+    {
+        tokio::time::timeout(timeout, call)
+            .await
+            .map_err(|_| Error::Timeout(timeout))?
+    }
+
+    /// like `with_timeout` with an absolute deadline shared by several calls
+    pub async fn with_deadline<F, T>(&self, deadline: tokio::time::Instant, call: F) -> Result<T>
+    where
+        F: std::future::Future<Output = Result<T>>,
+    {
+        let timeout = deadline.saturating_duration_since(tokio::time::Instant::now());
+        tokio::time::timeout_at(deadline, call)
+            .await
+            .map_err(|_| Error::Timeout(timeout))?
+    }
+
+    /// round trip histogram and server clock offset of every request sent so far
+    pub fn timing_report(&self) -> timing::TimingReport {
+        self.inner.timing.report()
+    }
+
+    /// latency percentiles and errors of every endpoint requests went to over the last few
+    /// minutes, see `timing::STATS_WINDOW`; a strategy can move to another venue when the
+    /// endpoints of fxdx degrade
+    pub fn stats(&self) -> std::collections::HashMap<String, timing::EndpointStats> {
+        self.inner.timing.endpoint_stats(std::time::Instant::now())
+    }
+
+    /// log the timing report at info level every `every` until the shutdown
+    pub async fn log_timing_periodically(&self, every: std::time::Duration) {
+        let mut ticker = tokio::time::interval(every);
+        ticker.tick().await;
+        self.until_shutdown(async {
+            loop {
+                ticker.tick().await;
+                log::info!("fxdx timing {}", self.inner.timing.report());
+            }
+        })
+        .await;
+    }
+
+    /// a signal for tasks driven outside the client, triggered by `shutdown`
+    pub fn shutdown_signal(&self) -> shutdown::ShutdownSignal {
+        self.inner.shutdown.signal()
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.inner.shutdown.signal().is_triggered()
+    }
+
+    /// drive `task` until it completes or the client shuts down, `None` in the latter case
+    pub async fn until_shutdown<F: std::future::Future>(&self, task: F) -> Option<F::Output> {
+        let mut signal = self.inner.shutdown.signal();
+        tokio::select! {
+            output = task => Some(output),
+            _ = signal.wait() => None,
+        }
+    }
+
+    /// `shutdown_with` the default options, open orders stay untouched
+    pub async fn shutdown(&self) -> shutdown::ShutdownReport {
+        self.shutdown_with(shutdown::ShutdownOptions::default())
+            .await
+    }
+
+    /// stop every periodic loop and market data session of this client, flush the outbox once
+    /// more and cancel the open orders if asked to
+    ///
+    /// requests can still be sent afterwards, only the background loops are gone
+    pub async fn shutdown_with(
         &self,
-        req: request::Request,
-    ) -> Result<response::BatchCancelOrdersResponse> {
-        Ok(self
-            .send(req)
+        options: shutdown::ShutdownOptions,
+    ) -> shutdown::ShutdownReport {
+        self.inner.shutdown.trigger();
+        let mut report = shutdown::ShutdownReport {
+            outbox: self.flush_outbox().await,
+            ..Default::default()
+        };
+        if options.cancel_open_orders {
+            let symbols = match self.inner.default_symbols.is_empty() {
+                true => vec![None],
+                false => self
+                    .inner
+                    .default_symbols
+                    .iter()
+                    .cloned()
+                    .map(Some)
+                    .collect(),
+            };
+            let mut cancelled = response::CancelAllSummary::default();
+            for symbol in symbols {
+                match self.cancel_all_orders(symbol).await {
+                    Ok(summary) => {
+                        cancelled.cancelled.extend(summary.cancelled);
+                        cancelled.failed.extend(summary.failed);
+                    }
+                    Err(e) => report.errors.push(e.to_string()),
+                }
+            }
+            report.cancelled = Some(cancelled);
+        }
+        report
+    }
+
+    pub fn environment(&self) -> environment::Environment {
+        self.inner.environment
+    }
+
+    /// an empty registry checking orders the way the environment of the client needs
+    pub fn symbol_registry(&self, max_age: std::time::Duration) -> symbols::SymbolRegistry {
+        symbols::SymbolRegistry::new(max_age).relaxed(self.inner.environment.relaxed_symbols())
+    }
+
+    /// the symbols configured with `FxdxBuilder::symbols`
+    pub fn default_symbols(&self) -> &[String] {
+        &self.inner.default_symbols
+    }
+
+    /// the endpoint requests currently go to
+    pub fn endpoint(&self) -> &str {
+        self.inner.endpoints.current()
+    }
+
+    /// open a connection to every endpoint and the mirror ahead of trading, so DNS, TCP and
+    /// TLS are not paid by the first order; fails only if the current endpoint is unreachable
+    ///
+    /// idle connections are closed after `ConnectionOptions::pool_idle_timeout`, `ping`
+    /// periodically to keep them open
+    pub async fn warm_up(&self) -> Result<()> {
+        let mut endpoints: Vec<&str> = self
+            .inner
+            .endpoints
+            .endpoints()
+            .iter()
+            .map(String::as_str)
+            .collect();
+        endpoints.extend(self.inner.mirror.as_deref());
+        let replies = futures_util::future::join_all(endpoints.iter().map(|endpoint| {
+            self.call_to::<response::SymbolsResponse>(endpoint, &request::Request::Symbols)
+        }))
+        .await;
+        let current = self.inner.endpoints.current();
+        for (endpoint, reply) in endpoints.into_iter().zip(replies) {
+            match reply {
+                Err(e) if endpoint == current => {
+                    return Err(e.context(format!("warming up {}", endpoint)))
+                }
+                Err(e) => log::warn!("warming up {} failed: {:#}", endpoint, e),
+                Ok(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// the round trip of the public symbols query to the current endpoint, signing and a wait
+    /// on the rate limiter included; every ping is kept in `timing_report().ping`
+    pub async fn ping(&self) -> Result<std::time::Duration> {
+        let start = std::time::Instant::now();
+        self.call::<response::SymbolsResponse>(&request::Request::Symbols)
+            .await?;
+        let rtt = start.elapsed();
+        self.inner.timing.record_ping(rtt);
+        Ok(rtt)
+    }
+
+    /// true if `endpoint` answers the public symbols query
+    pub async fn probe(&self, endpoint: &str) -> bool {
+        matches!(
+            self.call_to::<response::SymbolsResponse>(endpoint, &request::Request::Symbols)
+                .await,
+            Ok(resp) if resp.is_success()
+        )
+    }
+
+    /// fail back to the primary endpoint if it is healthy again, true when it is in use
+    pub async fn health_check(&self) -> bool {
+        if !self.inner.endpoints.is_failed_over() {
+            return true;
+        }
+        let primary = self.inner.endpoints.primary().to_string();
+        if self.probe(&primary).await {
+            self.inner.endpoints.fail_back();
+            return true;
+        }
+        false
+    }
+
+    /// run `health_check` every `every` until the shutdown
+    pub async fn run_health_checks(&self, every: std::time::Duration) {
+        let mut ticker = tokio::time::interval(every);
+        self.until_shutdown(async {
+            loop {
+                ticker.tick().await;
+                self.health_check().await;
+            }
+        })
+        .await;
+    }
+
+    /// run the nonce and token handshake for a new session token, e.g. after
+    /// `Error::Unauthorized`; done by `build` for ed25519 keys, an HMAC secret has no handshake
+    /// and fails with `Error::InvalidRequest`
+    pub async fn fresh(&self) -> Result<()> {
+        let nonce = self
+            .call::<response::NonceResponse>(&request::Request::Nonce)
+            .await?
+            .into_result()?;
+        let Some((pubkey, signature)) = self.inner.signer.sign_nonce(&nonce) else {
+            return Err(Error::InvalidRequest(String::from(
+                "no token handshake for an HMAC secret",
+            ))
+            .into());
+        };
+        let (token, expires_at) = self
+            .call::<response::TokenResponse>(&request::Request::Token {
+                nonce,
+                pubkey,
+                signature,
+            })
             .await?
-            .json::<response::BatchCancelOrdersResponse>()
-            .await?)
+            .into_result()?
+            .into_parts();
+        let token = tokenstore::StoredToken {
+            address: self.inner.address.clone(),
+            token: token.into(),
+            expires_at,
+        };
+        if let Some(store) = &self.inner.token_store {
+            if let Err(e) = store.save(&token) {
+                log::warn!("fxdx could not store the session token: {}", e);
+            }
+        }
+        *self.inner.token.write().unwrap_or_else(|e| e.into_inner()) = Some(token);
+        self.inner
+            .token_generation
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(())
     }
 
-    pub async fn query_order_by_id(
-        &self,
-        req: request::Request,
-    ) -> Result<response::QueryByIdResponse> {
+    /// true if a 401 to `req` may mean an expired session token
+    fn renews_token(&self, req: &request::Request) -> bool {
+        !matches!(
+            req,
+            request::Request::Nonce | request::Request::Token { .. }
+        ) && self
+            .inner
+            .token
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    /// `fresh` for a request rejected with the token of `generation`, unless a concurrent caller
+    /// renewed it meanwhile, so a rollover costs one handshake however many requests hit it
+    async fn renew_token(&self, generation: u64) -> Result<()> {
+        let _renewing = self.inner.renewing.lock().await;
+        if self
+            .inner
+            .token_generation
+            .load(std::sync::atomic::Ordering::SeqCst)
+            == generation
+        {
+            self.fresh().await?;
+        }
+        Ok(())
+    }
+
+    /// unix seconds the session token expires at, `None` without a token or when the exchange
+    /// did not say
+    pub fn token_expiry(&self) -> Option<i64> {
+        self.inner
+            .token
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .and_then(|token| token.expires_at)
+    }
+
+    /// the stored token if it is still good for this key, so a restart skips the handshake
+    fn stored_token(&self) -> Option<tokenstore::StoredToken> {
+        let token = match self.inner.token_store.as_ref()?.load() {
+            Ok(token) => token?,
+            Err(e) => {
+                log::warn!("fxdx could not load the session token: {}", e);
+                return None;
+            }
+        };
+        let now = unix_timestamp().ok()?;
+        token.is_usable(&self.inner.address, now).then_some(token)
+    }
+
+    pub fn is_paper_trading(&self) -> bool {
+        self.inner.paper.is_some()
+    }
+
+    fn simulator(&self) -> Option<std::sync::MutexGuard<'_, paper::PaperExchange>> {
+        self.inner
+            .paper
+            .as_ref()
+            .map(|paper| paper.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// live depth of `symbol`, resting paper orders are matched against it on the way
+    async fn paper_depth(&self, symbol: &str) -> Result<response::Depth> {
+        let depth = self
+            .query_depth(request::Request::Depth {
+                symbol: symbol.to_string(),
+                limit: None,
+            })
+            .await?;
+        if let Some(mut paper) = self.simulator() {
+            paper.on_depth(symbol, &depth, unix_timestamp()?);
+        }
+        Ok(depth)
+    }
+
+    async fn paper_place(&self, order: &request::NewOrder) -> Result<String> {
+        let depth = self.paper_depth(&order.symbol).await?;
+        match self.simulator() {
+            Some(mut paper) => paper.place(order, &depth, unix_timestamp()?),
+            None => Err(Error::InvalidRequest(String::from("not in paper trading mode")).into()),
+        }
+    }
+
+    async fn paper_cancel(&self, symbol: &str, order_id: &str) -> Result<bool> {
+        self.paper_depth(symbol).await?;
         Ok(self
-            .send(req)
-            .await?
-            .json::<response::QueryByIdResponse>()
-            .await?)
+            .simulator()
+            .is_some_and(|mut paper| paper.cancel(symbol, order_id)))
     }
 
-    pub async fn query_orders_by_page(
+    /// send a pending order to fxdx, returns the id of the order
+    pub async fn pending_order(&self, req: request::Request) -> Result<String> {
+        Ok(self.pending_order_response(req).await?.into_result()?)
+    }
+
+    async fn pending_order_response(
         &self,
         req: request::Request,
-    ) -> Result<response::QueryByPageResponse> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::QueryByPageResponse>()
-            .await?)
+    ) -> Result<response::PendingOrderResponse> {
+        let resp = match (self.is_paper_trading(), &req) {
+            (true, request::Request::PendingOrder(order)) => response::PendingOrderResponse {
+                code: PAPER_OK,
+                data: Some(self.paper_place(order).await?),
+            },
+            _ => self.call::<response::PendingOrderResponse>(&req).await?,
+        };
+        if let (Some(copy), request::Request::PendingOrder(order)) = (&self.inner.drop_copy, &req) {
+            match (resp.is_success(), &resp.data) {
+                (true, Some(order_id)) => copy.placed(order, order_id),
+                _ => copy.rejected(order, format!("rejected with code {}", resp.code)),
+            }
+        }
+        Ok(resp)
     }
 
-    pub async fn query_account_balance(
+    /// batch pending orders, every order is reported with its own outcome
+    pub async fn batch_pending_orders(
         &self,
         req: request::Request,
-    ) -> Result<response::BalancesResposne> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::BalancesResposne>()
-            .await?)
+    ) -> Result<response::BatchResult> {
+        let request::Request::BatchPendingOrders(orders) = &req else {
+            return Err(
+                Error::InvalidRequest(String::from("expect Request::BatchPendingOrders")).into(),
+            );
+        };
+        let result = if self.is_paper_trading() {
+            let mut items = Vec::with_capacity(orders.len());
+            for order in orders {
+                // the reason of a rejection is kept, a live batch only has the code
+                let outcome = match self.paper_place(order).await {
+                    Ok(order_id) => response::BatchOutcome::Placed(order_id),
+                    Err(e) => response::BatchOutcome::Failed(e.to_string()),
+                };
+                items.push(response::BatchItem {
+                    order: order.clone(),
+                    outcome,
+                });
+            }
+            response::BatchResult {
+                code: PAPER_OK,
+                items,
+            }
+        } else {
+            let resp = self
+                .call::<response::BatchPendingOrdersResponse>(&req)
+                .await?;
+            // a mismatch fails the call before the drop copy, whose orders may be live or not
+            response::BatchResult::correlate(orders.clone(), resp)?
+        };
+        if let Some(copy) = &self.inner.drop_copy {
+            for item in &result.items {
+                match &item.outcome {
+                    response::BatchOutcome::Placed(order_id) => copy.placed(&item.order, order_id),
+                    response::BatchOutcome::Failed(reason) => {
+                        copy.rejected(&item.order, reason.clone())
+                    }
+                }
+            }
+        }
+        Ok(result)
     }
 
-    pub async fn query_depth(&self, req: request::Request) -> Result<response::DepthResponse> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::DepthResponse>()
-            .await?)
+    /// cancel an order, returns the id of the cancelled order
+    pub async fn cancel_order(&self, req: request::Request) -> Result<String> {
+        Ok(self.cancel_order_response(req).await?.into_result()?)
     }
 
-    pub async fn query_kline(&self, req: request::Request) -> Result<response::KlineResponse> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::KlineResponse>()
-            .await?)
+    async fn cancel_order_response(
+        &self,
+        req: request::Request,
+    ) -> Result<response::CancelOrderResponse> {
+        let resp = match (self.is_paper_trading(), &req) {
+            (true, request::Request::CancelOrder { symbol, order_id }) => {
+                let cancelled = self.paper_cancel(symbol, order_id).await?;
+                response::CancelOrderResponse {
+                    code: if cancelled { PAPER_OK } else { 404 },
+                    data: cancelled.then(|| order_id.clone()),
+                }
+            }
+            _ => self.call::<response::CancelOrderResponse>(&req).await?,
+        };
+        if let (Some(copy), request::Request::CancelOrder { symbol, order_id }, true) =
+            (&self.inner.drop_copy, &req, resp.is_success())
+        {
+            copy.cancelled(symbol, order_id);
+        }
+        Ok(resp)
     }
 
-    pub async fn query_symbols(&self, req: request::Request) -> Result<response::SymbolsResponse> {
-        Ok(self
-            .send(req)
-            .await?
-            .json::<response::SymbolsResponse>()
-            .await?)
+    fn queue(&self) -> Option<std::sync::MutexGuard<'_, outbox::Outbox>> {
+        self.inner
+            .outbox
+            .as_ref()
+            .map(|outbox| outbox.lock().unwrap_or_else(|e| e.into_inner()))
     }
-}
 
-#[derive(Default)]
-pub struct FxdxBuilder<P> {
-    endpoint: String,
-    secret_key: String,
-    address: String,
-    is_sr25519: bool,
-    _marker: std::marker::PhantomData<P>,
-}
+    /// like `pending_order`, but with the outbox enabled an order which could not reach the
+    /// endpoint is queued instead of failing
+    ///
+    /// only connection failures are queued, a timed out request may have been received and is
+    /// still an error; while orders are queued new ones are queued behind them to keep their order
+    pub async fn submit_order(&self, order: request::NewOrder) -> Result<outbox::Submission> {
+        order.validate()?;
+        if self.inner.outbox.is_none() {
+            return Ok(outbox::Submission::Sent(
+                self.pending_order(request::Request::PendingOrder(order))
+                    .await?,
+            ));
+        }
+        if let Some(mut queue) = self.queue().filter(|queue| !queue.is_empty()) {
+            let expires_at = queue.push(order, std::time::Instant::now());
+            return Ok(outbox::Submission::Queued { expires_at });
+        }
+        match self
+            .pending_order(request::Request::PendingOrder(order.clone()))
+            .await
+        {
+            Ok(order_id) => Ok(outbox::Submission::Sent(order_id)),
+            Err(e) if self.inner.transport.is_unreachable(&e) => {
+                let expires_at = self
+                    .queue()
+                    .map(|mut queue| queue.push(order, std::time::Instant::now()))
+                    .unwrap_or_else(std::time::Instant::now);
+                Ok(outbox::Submission::Queued { expires_at })
+            }
+            Err(e) => Err(e),
+        }
+    }
 
-impl<P> FxdxBuilder<P>
-where
-    P: request::Prefix,
-{
-    pub fn endpoint(endpoint: String) -> Self {
-        FxdxBuilder {
-            endpoint,
-            secret_key: Default::default(),
-            address: Default::default(),
-            is_sr25519: false,
-            _marker: Default::default(),
+    /// send the queued orders oldest first and drop the expired ones, stops at the first order
+    /// which still can not reach the endpoint
+    pub async fn flush_outbox(&self) -> outbox::OutboxReport {
+        let mut report = outbox::OutboxReport::default();
+        loop {
+            let next = match self.queue() {
+                Some(mut queue) => {
+                    let (next, expired) = queue.pop(std::time::Instant::now());
+                    report.expired.extend(expired);
+                    next
+                }
+                None => None,
+            };
+            let Some(queued) = next else {
+                break;
+            };
+            match self
+                .pending_order(request::Request::PendingOrder(queued.order.clone()))
+                .await
+            {
+                Ok(order_id) => report.submitted.push((queued.order, order_id)),
+                Err(e) if self.inner.transport.is_unreachable(&e) => {
+                    if let Some(mut queue) = self.queue() {
+                        queue.requeue(queued);
+                    }
+                    break;
+                }
+                Err(e) => report.failed.push((queued.order, e.to_string())),
+            }
         }
+        report.pending = self.queue().map_or(0, |queue| queue.len());
+        report
     }
 
-    pub fn address(mut self, addr: String) -> Self {
-       self.address = address;
-       self
+    /// flush the outbox every `every` and hand every non-empty report to `on_report` until the
+    /// shutdown, which flushes it a last time
+    pub async fn flush_outbox_periodically<F>(&self, every: std::time::Duration, mut on_report: F)
+    where
+        F: FnMut(outbox::OutboxReport),
+    {
+        let mut ticker = tokio::time::interval(every);
+        self.until_shutdown(async {
+            loop {
+                ticker.tick().await;
+                let report = self.flush_outbox().await;
+                if !report.is_empty() {
+                    on_report(report);
+                }
+            }
+        })
+        .await;
     }
 
-    pub fn sr25519(mut self, private_key: String) -> Self {
-        self.secret_key = private_key; // FIXME: use the sr25519 handshake
-        self.is_sr25519 = true;
-        self
+    /// orders waiting in the outbox
+    pub fn queued_orders(&self) -> Vec<outbox::QueuedOrder> {
+        self.queue()
+            .map(|queue| queue.orders().cloned().collect())
+            .unwrap_or_default()
     }
 
-    pub fn secret(mut self, secret_key: String) -> Self {
-        if self.is_sr25519 {
-            panic!("could not set registered secret in sr25519 mode");
+    /// cancel several orders of one symbol, every order id is reported with its outcome
+    ///
+    /// ids a legacy reply leaves out are looked up, so a filled order is told apart from an
+    /// unknown one
+    pub async fn batch_cancel_orders(
+        &self,
+        req: request::Request,
+    ) -> Result<response::BatchCancelResult> {
+        let request::Request::BatchCancelOrders { symbol, order_ids } = &req else {
+            return Err(
+                Error::InvalidRequest(String::from("expect Request::BatchCancelOrders")).into(),
+            );
+        };
+        let (symbol, order_ids) = (symbol.clone(), order_ids.clone());
+        let resp = self.batch_cancel_orders_response(req).await?;
+        let code = resp.code;
+        let data = resp.into_result_or_default()?;
+        let mut items = Vec::with_capacity(order_ids.len());
+        for order_id in order_ids {
+            let outcome = match data.outcome(&order_id) {
+                Some(outcome) => outcome,
+                None => self.cancel_outcome(&symbol, &order_id).await?,
+            };
+            if let (Some(copy), response::CancelOutcome::Cancelled) =
+                (&self.inner.drop_copy, outcome)
+            {
+                copy.cancelled(&symbol, &order_id);
+            }
+            items.push(response::BatchCancelItem { order_id, outcome });
         }
-        self.secret_key = secret_key;
-        self
+        Ok(response::BatchCancelResult { code, items })
     }
 
-    pub async fn build(mut self) -> Result<FxdxClient<P>> {
-        if self.is_sr25519 {
-            let client = reqwest::Client::new();
-            // if sr25519 handshake else panic and set the default headers
-            let nonce = client
-                .post(format!("{}/maker/nonce", &self.endpoint))
-                .send()
-                .await?
-                .json::<response::NonceResponse>()
-                .await?;
-            // TODO: impl the Schnorrkel signature for this mode
-            unimplemented!()
-        } else {
-            let builder = reqwest::Client::builder();
-            Ok(FxdxClient {
-                client: builder.build()?,
-                endpoint: self.endpoint,
-                address: self.address,
-                signer: Signer::new(self.secret_key),
-                _marker: Default::default(),
-            })
+    /// what became of an order a cancel did not report on, from its current status
+    async fn cancel_outcome(
+        &self,
+        symbol: &str,
+        order_id: &str,
+    ) -> Result<response::CancelOutcome> {
+        match self.order_by_id(symbol, order_id).await {
+            Ok(order) => Ok(response::CancelOutcome::from_status(order.status)),
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<Error>(),
+                    Some(Error::Rejected(_) | Error::MissingData)
+                ) =>
+            {
+                Ok(response::CancelOutcome::NotFound)
+            }
+            Err(e) => Err(e),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
+    async fn batch_cancel_orders_response(
+        &self,
+        req: request::Request,
+    ) -> Result<response::BatchCancelOrdersResponse> {
+        if let (true, request::Request::BatchCancelOrders { symbol, order_ids }) =
+            (self.is_paper_trading(), &req)
+        {
+            let mut cancelled = vec![];
+            for order_id in order_ids {
+                if self.paper_cancel(symbol, order_id).await? {
+                    cancelled.push(order_id.clone());
+                }
+            }
+            return Ok(response::BatchCancelOrdersResponse {
+                code: PAPER_OK,
+                data: Some(response::BatchCancelData::Cancelled(cancelled.join("|"))),
+            });
+        }
+        self.call::<response::BatchCancelOrdersResponse>(&req).await
+    }
+
+    /// cancel `order_id` and place an order of `kind` on the same side at `new_price` for
+    /// `new_amount`
+    ///
+    /// the venue does not tell the kind of an order, so it is passed again: a post-only quote
+    /// replaced as a limit order could take liquidity. the replacement is only placed once the
+    /// cancel was accepted, with `skip_if_filled` nothing is placed either when the original
+    /// turns out to be fully filled; a market `kind`, which has no price, fails before the cancel
+    pub async fn replace_order(
+        &self,
+        symbol: String,
+        order_id: String,
+        kind: request::OrderKind,
+        new_price: bigdecimal::BigDecimal,
+        new_amount: bigdecimal::BigDecimal,
+        skip_if_filled: bool,
+    ) -> Result<response::ReplaceResult> {
+        let original = self.order_by_id(&symbol, &order_id).await?;
+        let replacement = request::Request::order(
+            original.direction,
+            kind,
+            symbol.clone(),
+            Some(new_price),
+            new_amount,
+        )?;
+        if skip_if_filled && original.status == request::OrderStatus::Dealed {
+            return Ok(response::ReplaceResult {
+                outcome: response::ReplaceOutcome::AlreadyFilled,
+                cancel: None,
+                new_order_id: None,
+            });
+        }
+        let cancel = self
+            .cancel_order_response(request::Request::CancelOrder {
+                symbol: symbol.clone(),
+                order_id: order_id.clone(),
+            })
+            .await?;
+        if !cancel.is_success() {
+            let filled =
+                self.order_by_id(&symbol, &order_id).await?.status == request::OrderStatus::Dealed;
+            return Ok(response::ReplaceResult {
+                outcome: if skip_if_filled && filled {
+                    response::ReplaceOutcome::AlreadyFilled
+                } else {
+                    response::ReplaceOutcome::CancelRejected
+                },
+                cancel: Some(cancel),
+                new_order_id: None,
+            });
+        }
+        let placed = self.pending_order_response(replacement).await?;
+        let new_order_id = placed.into_result().ok();
+        Ok(response::ReplaceResult {
+            outcome: if new_order_id.is_some() {
+                response::ReplaceOutcome::Replaced
+            } else {
+                response::ReplaceOutcome::PlaceRejected
+            },
+            cancel: Some(cancel),
+            new_order_id,
+        })
+    }
+
+    /// cancel `order_id` and poll it until it is terminal, telling apart whether the cancel
+    /// or a fill won the race; the cancel answer itself is not trusted either way
+    pub async fn cancel_verified(
+        &self,
+        symbol: String,
+        order_id: String,
+        timeout: std::time::Duration,
+    ) -> Result<response::CancelVerification> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        self.cancel_order_response(request::Request::CancelOrder {
+            symbol: symbol.clone(),
+            order_id: order_id.clone(),
+        })
+        .await?;
+        loop {
+            let status = self.order_by_id(&symbol, &order_id).await?.status;
+            match status {
+                request::OrderStatus::Cancel => return Ok(response::CancelVerification::Cancelled),
+                request::OrderStatus::Dealed => return Ok(response::CancelVerification::Filled),
+                _ => {}
+            }
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                return Ok(response::CancelVerification::TimedOut(status));
+            }
+            tokio::time::sleep(CANCEL_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    async fn order_by_id(&self, symbol: &str, order_id: &str) -> Result<response::QueryOrder> {
+        self.query_order_by_id(request::Request::OrderById {
+            symbol: symbol.to_string(),
+            order_id: order_id.to_string(),
+        })
+        .await
+    }
+
+    pub async fn query_order_by_id(&self, req: request::Request) -> Result<response::QueryOrder> {
+        Ok(self.query_order_by_id_response(req).await?.into_result()?)
+    }
+
+    async fn query_order_by_id_response(
+        &self,
+        req: request::Request,
+    ) -> Result<response::QueryByIdResponse> {
+        if let (true, request::Request::OrderById { symbol, order_id }) =
+            (self.is_paper_trading(), &req)
+        {
+            self.paper_depth(symbol).await?;
+            let order = self
+                .simulator()
+                .and_then(|paper| paper.order(symbol, order_id));
+            self.observe(order.as_slice()).await;
+            return Ok(response::QueryByIdResponse {
+                code: if order.is_some() { PAPER_OK } else { 404 },
+                data: order,
+            });
+        }
+        let resp = self.call::<response::QueryByIdResponse>(&req).await?;
+        self.observe(resp.data.as_slice()).await;
+        Ok(resp)
+    }
+
+    /// hand orders read back to the drop copy, which reports their new fills, and to the storage
+    async fn observe(&self, orders: &[response::QueryOrder]) {
+        if let Some(copy) = &self.inner.drop_copy {
+            orders.iter().for_each(|order| copy.observe(order));
+        }
+        let Some(store) = &self.inner.storage else {
+            return;
+        };
+        if orders.is_empty() {
+            return;
+        }
+        let seen_at = unix_millis();
+        let rows = orders
+            .iter()
+            .map(|order| storage::OrderRow::new(order, seen_at))
+            .collect::<Vec<_>>();
+        if let Err(e) = store.save_orders(&rows).await {
+            log::error!("storing {} orders failed: {}", rows.len(), e);
+        }
+        let fills = orders
+            .iter()
+            .flat_map(|order| {
+                storage::FillRow::read(&order.symbol, Some(&order.order_id), &order.trades)
+            })
+            .collect::<Vec<_>>();
+        self.store_fills(&fills).await;
+    }
+
+    async fn store_fills(&self, fills: &[storage::FillRow]) {
+        if let (Some(store), false) = (&self.inner.storage, fills.is_empty()) {
+            if let Err(e) = store.save_fills(fills).await {
+                log::error!("storing {} fills failed: {}", fills.len(), e);
+            }
+        }
+    }
+
+    /// the open orders the storage saw last, e.g. to pick up after a restart before the
+    /// exchange answered; empty without `FxdxBuilder::storage`
+    pub async fn stored_open_orders(&self, symbol: Option<&str>) -> Result<Vec<storage::OrderRow>> {
+        match &self.inner.storage {
+            Some(store) => store.open_orders(symbol).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    pub async fn query_orders_by_page(
+        &self,
+        req: request::Request,
+    ) -> Result<Vec<response::QueryOrder>> {
+        Ok(self
+            .query_orders_by_page_response(req)
+            .await?
+            .into_result_or_default()?)
+    }
+
+    async fn query_orders_by_page_response(
+        &self,
+        req: request::Request,
+    ) -> Result<response::QueryByPageResponse> {
+        if let (
+            true,
+            request::Request::OrderByPage {
+                symbol,
+                page,
+                pending,
+                filter,
+            },
+        ) = (self.is_paper_trading(), &req)
+        {
+            self.paper_depth(symbol).await?;
+            let orders = self
+                .simulator()
+                .map(|paper| paper.orders(symbol, *page, *pending, filter));
+            self.observe(orders.as_deref().unwrap_or_default()).await;
+            return Ok(response::QueryByPageResponse {
+                code: PAPER_OK,
+                data: orders,
+            });
+        }
+        let resp = self.call::<response::QueryByPageResponse>(&req).await?;
+        self.observe(resp.data.as_deref().unwrap_or_default()).await;
+        Ok(resp)
+    }
+
+    pub async fn query_account_balance(&self, req: request::Request) -> Result<response::Balance> {
+        Ok(self
+            .call::<response::BalancesResposne>(&req)
+            .await?
+            .into_result()?)
+    }
+
+    pub async fn query_account_info(&self, req: request::Request) -> Result<response::AccountInfo> {
+        Ok(self
+            .call::<response::AccountInfoResponse>(&req)
+            .await?
+            .into_result()?)
+    }
+
+    pub async fn query_depth(&self, req: request::Request) -> Result<response::Depth> {
+        Ok(self
+            .call::<response::DepthResponse>(&req)
+            .await?
+            .into_result()?)
+    }
+
+    /// every open order of `symbol`, walking all pages, e.g. to reconcile state on startup
+    pub async fn open_orders(&self, symbol: &str) -> Result<Vec<response::QueryOrder>> {
+        self.all_orders(symbol, true, request::OrderFilter::default())
+            .await
+    }
+
+    /// every order of `symbol` passing `filter`, open or not; the exchange filters, so only
+    /// the matching pages are walked
+    pub async fn order_history(
+        &self,
+        symbol: &str,
+        filter: request::OrderFilter,
+    ) -> Result<Vec<response::QueryOrder>> {
+        self.all_orders(symbol, false, filter).await
+    }
+
+    async fn all_orders(
+        &self,
+        symbol: &str,
+        pending: bool,
+        filter: request::OrderFilter,
+    ) -> Result<Vec<response::QueryOrder>> {
+        let mut all = vec![];
+        let mut page = request::Page::first(request::OPEN_ORDERS_PAGE_SIZE)?;
+        loop {
+            let orders = self
+                .query_orders_by_page(request::Request::OrderByPage {
+                    symbol: symbol.to_string(),
+                    page,
+                    pending,
+                    filter: filter.clone(),
+                })
+                .await?;
+            let last = page.is_last(orders.len());
+            all.extend(orders);
+            if last {
+                return Ok(all);
+            }
+            page = page.next();
+        }
+    }
+
+    /// cancel every open order of `symbol`, or of every listed symbol when `None`
+    ///
+    /// orders are cancelled in batches of `request::MAX_BATCH_CANCEL` through the rate limiter,
+    /// a failed batch is reported and the remaining batches are still sent
+    pub async fn cancel_all_orders(
+        &self,
+        symbol: Option<String>,
+    ) -> Result<response::CancelAllSummary> {
+        let symbols = match symbol {
+            Some(symbol) => vec![symbol],
+            None => self
+                .query_symbols(request::Request::Symbols)
+                .await?
+                .iter()
+                .map(response::Symbol::pair)
+                .collect(),
+        };
+        let mut summary = response::CancelAllSummary::default();
+        for symbol in symbols {
+            let ids = self
+                .open_orders(&symbol)
+                .await?
+                .into_iter()
+                .map(|o| o.order_id)
+                .collect::<Vec<_>>();
+            self.cancel_ids(&symbol, &ids, &mut summary).await;
+        }
+        Ok(summary)
+    }
+
+    /// cancel `ids` of `symbol` in batches, adding every outcome to `summary`
+    async fn cancel_ids(
+        &self,
+        symbol: &str,
+        ids: &[String],
+        summary: &mut response::CancelAllSummary,
+    ) {
+        for chunk in ids.chunks(request::MAX_BATCH_CANCEL) {
+            let outcome = self
+                .batch_cancel_orders(request::Request::BatchCancelOrders {
+                    symbol: symbol.to_string(),
+                    order_ids: chunk.to_vec(),
+                })
+                .await;
+            let outcomes: Vec<_> = match outcome {
+                Ok(result) => result
+                    .items
+                    .into_iter()
+                    .map(|item| (item.order_id, Ok(item.outcome)))
+                    .collect(),
+                Err(e) => chunk
+                    .iter()
+                    .map(|id| (id.clone(), Err(e.to_string())))
+                    .collect(),
+            };
+            for (order_id, outcome) in outcomes {
+                let symbol = symbol.to_string();
+                match outcome {
+                    Ok(response::CancelOutcome::Cancelled) => summary
+                        .cancelled
+                        .push(response::CancelledOrder { symbol, order_id }),
+                    Ok(outcome) => summary.failed.push(response::FailedCancel {
+                        symbol,
+                        order_id,
+                        reason: outcome.to_string(),
+                    }),
+                    Err(reason) => summary.failed.push(response::FailedCancel {
+                        symbol,
+                        order_id,
+                        reason,
+                    }),
+                }
+            }
+        }
+    }
+
+    /// fetch a depth snapshot into `book`, a corrupt snapshot is refetched once when the validator asks for it
+    /// and a snapshot overtaken by a concurrent sync is dropped
+    pub async fn sync_book(
+        &self,
+        book: &mut orderbook::OrderBook,
+        validator: &orderbook::BookValidator,
+    ) -> Result<()> {
+        let seq = self.inner.sequencer.next();
+        let req = request::Request::Depth {
+            symbol: book.symbol.clone(),
+            limit: None,
+        };
+        let depth = self.query_depth(req).await?;
+        if book.apply_snapshot(seq, depth, validator).is_ok() {
+            return Ok(());
+        }
+        if validator.should_refresh() {
+            let seq = self.inner.sequencer.next();
+            let req = request::Request::Depth {
+                symbol: book.symbol.clone(),
+                limit: None,
+            };
+            // the corrupt snapshot may be the cached one
+            self.uncache(&req);
+            let depth = self.query_depth(req).await?;
+            if let Ok(true) = book.apply_snapshot(seq, depth, validator) {
+                validator.emit(orderbook::BookEvent::Refreshed {
+                    symbol: book.symbol.clone(),
+                });
+                return Ok(());
+            }
+        }
+        Err(Error::CorruptBook(book.symbol.clone()).into())
+    }
+
+    /// refetch the depth snapshot of `sync` and replay its buffered deltas, call it while
+    /// `sync.needs_snapshot()`, e.g. after `depthsync::DeltaOutcome::Gap`
+    pub async fn resync_depth(
+        &self,
+        sync: &mut depthsync::DepthSync,
+        validator: &orderbook::BookValidator,
+    ) -> Result<usize> {
+        let symbol = sync.book().symbol.clone();
+        let req = request::Request::Depth {
+            symbol: symbol.clone(),
+            limit: None,
+        };
+        // a cached snapshot may be older than the deltas which made the book stale
+        self.uncache(&req);
+        let depth = self.query_depth(req).await?;
+        let seq = depth.seq.ok_or_else(|| {
+            Error::InvalidRequest(format!("depth snapshot of {} without seq", symbol))
+        })?;
+        sync.on_snapshot(seq, depth, validator)
+            .map_err(|_| Error::CorruptBook(symbol).into())
+    }
+
+    /// refresh `cache` from the balances endpoint, returns false if a newer read already landed
+    pub async fn sync_balances(&self, cache: &mut balance::BalanceCache) -> Result<bool> {
+        let seq = self.inner.sequencer.next();
+        let balance = self
+            .query_account_balance(request::Request::Balances)
+            .await?;
+        Ok(cache.update(seq, balance))
+    }
+
+    async fn balances(&self) -> Result<Vec<response::Balance>> {
+        Ok(vec![
+            self.query_account_balance(request::Request::Balances)
+                .await?,
+        ])
+    }
+
+    /// balances, every open order and the recent fills of the symbols of `FxdxBuilder::symbols`,
+    /// or of every listed symbol without them, all requested at once
+    ///
+    /// fails as a whole if any request fails, a snapshot with a symbol missing would look like
+    /// a symbol without orders
+    pub async fn account_snapshot(&self) -> Result<response::AccountSnapshot> {
+        let symbols = match self.default_symbols() {
+            [] => self
+                .query_symbols(request::Request::Symbols)
+                .await?
+                .iter()
+                .map(response::Symbol::pair)
+                .collect(),
+            symbols => symbols.to_vec(),
+        };
+        let taken_at = unix_millis();
+        let per_symbol = symbols.iter().map(|symbol| async move {
+            let (orders, fills) = futures_util::future::try_join(
+                self.open_orders(symbol),
+                self.query_my_trades(symbol.clone(), request::Page::first(request::RECENT_FILLS)?),
+            )
+            .await?;
+            Ok::<_, anyhow::Error>((symbol.clone(), orders, fills))
+        });
+        let (balances, per_symbol) = futures_util::future::try_join(
+            self.balances(),
+            futures_util::future::try_join_all(per_symbol),
+        )
+        .await?;
+        let mut snapshot = response::AccountSnapshot {
+            taken_at,
+            balances,
+            open_orders: Default::default(),
+            fills: Default::default(),
+        };
+        for (symbol, orders, fills) in per_symbol {
+            snapshot.open_orders.insert(symbol.clone(), orders);
+            snapshot.fills.insert(symbol, fills);
+        }
+        Ok(snapshot)
+    }
+
+    /// compare `tracker` against an `account_snapshot`, and with a `repair` other than
+    /// `reconcile::Repair::Report` bring it in line with the exchange
+    ///
+    /// the discrepancies are those found before repairing; the orphans are cancelled through
+    /// the rate limiter like `cancel_all_orders`, a failed cancel is reported and stays untracked
+    pub async fn reconcile(
+        &self,
+        tracker: &mut reconcile::OrderTracker,
+        repair: reconcile::Repair,
+    ) -> Result<reconcile::ReconcileReport> {
+        let snapshot = self.account_snapshot().await?;
+        let discrepancies = tracker.diff(&snapshot);
+        let (orphans, adopted) = reconcile::repair(tracker, &discrepancies, repair);
+        let mut cancelled = response::CancelAllSummary::default();
+        for (symbol, ids) in orphans {
+            self.cancel_ids(&symbol, &ids, &mut cancelled).await;
+        }
+        Ok(reconcile::ReconcileReport {
+            taken_at: snapshot.taken_at,
+            discrepancies,
+            cancelled,
+            adopted,
+        })
+    }
+
+    /// compare the exchange balances against the ones predicted by `reconciler`
+    pub async fn reconcile_balances(
+        &self,
+        reconciler: &balance::BalanceReconciler,
+    ) -> Result<Vec<balance::Drift>> {
+        Ok(reconciler.reconcile(&self.balances().await?))
+    }
+
+    /// reconcile every `every` and hand the drifts to `on_drift` until the shutdown, or an error
+    /// on a failed request
+    ///
+    /// fills keep being recorded into the shared reconciler while this runs
+    pub async fn reconcile_periodically<F>(
+        &self,
+        reconciler: std::sync::Arc<std::sync::Mutex<balance::BalanceReconciler>>,
+        every: std::time::Duration,
+        mut on_drift: F,
+    ) -> Result<()>
+    where
+        F: FnMut(Vec<balance::Drift>),
+    {
+        let mut ticker = tokio::time::interval(every);
+        self.until_shutdown(async {
+            loop {
+                ticker.tick().await;
+                let actual = self.balances().await?;
+                let drifts = reconciler
+                    .lock()
+                    .map_err(|_| Error::InvalidRequest(String::from("reconciler poisoned")))?
+                    .reconcile(&actual);
+                if !drifts.is_empty() {
+                    on_drift(drifts);
+                }
+            }
+        })
+        .await
+        .unwrap_or(Ok(()))
+    }
+
+    /// like `query_depth` but sent to the endpoint and the mirror at once, the first successful
+    /// response wins and the other request is dropped; without a mirror this is `query_depth`
+    pub async fn query_depth_raced(&self, req: request::Request) -> Result<response::Depth> {
+        match &self.inner.mirror {
+            Some(mirror) => Ok(self
+                .race(mirror, &req, |resp: &response::DepthResponse| {
+                    resp.is_success() && resp.data.is_some()
+                })
+                .await?
+                .into_result()?),
+            None => self.query_depth(req).await,
+        }
+    }
+
+    /// only reads are ever raced, a mutating request would be executed twice
+    async fn race<T, V>(&self, mirror: &str, req: &request::Request, valid: V) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        V: Fn(&T) -> bool,
+    {
+        if req.method() != reqwest::Method::GET {
+            return Err(Error::InvalidRequest(format!(
+                "refuse to race the mutating request {}",
+                req.uri::<P>()
+            ))
+            .into());
+        }
+        let primary = self.fetch_valid(self.inner.endpoints.current(), req, &valid);
+        let secondary = self.fetch_valid(mirror, req, &valid);
+        tokio::pin!(primary, secondary);
+        tokio::select! {
+            resp = &mut primary => match resp {
+                Ok(resp) => Ok(resp),
+                Err(_) => secondary.await,
+            },
+            resp = &mut secondary => match resp {
+                Ok(resp) => Ok(resp),
+                Err(_) => primary.await,
+            },
+        }
+    }
+
+    async fn fetch_valid<T, V>(
+        &self,
+        endpoint: &str,
+        req: &request::Request,
+        valid: &V,
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        V: Fn(&T) -> bool,
+    {
+        let resp = self.call_to::<T>(endpoint, req).await?;
+        if valid(&resp) {
+            Ok(resp)
+        } else {
+            Err(Error::InvalidRequest(format!("unsuccessful response from {}", endpoint)).into())
+        }
+    }
+
+    /// the listed symbols, or the ones of `cache` while the symbols endpoint is unavailable
+    pub async fn symbols_or_cached(
+        &self,
+        cache: Option<&warmcache::WarmCache>,
+    ) -> Result<Vec<response::Symbol>> {
+        match self
+            .call::<response::SymbolsResponse>(&request::Request::Symbols)
+            .await
+            .and_then(|resp| Ok(resp.into_result()?))
+        {
+            Ok(symbols) => Ok(symbols),
+            Err(e) => match cache {
+                Some(cache) => Ok(cache.symbols.clone()),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// load the listed symbols into `registry`, returns how many there are
+    pub async fn refresh_symbols(&self, registry: &symbols::SymbolRegistry) -> Result<usize> {
+        let listed = self.query_symbols(request::Request::Symbols).await?;
+        let count = listed.len();
+        registry.load(listed);
+        Ok(count)
+    }
+
+    /// the metadata of `pair` from `registry`, refreshed first when it is stale or does not
+    /// know the pair yet
+    pub async fn symbol_meta(
+        &self,
+        registry: &symbols::SymbolRegistry,
+        pair: &str,
+    ) -> Result<response::Symbol> {
+        if registry.is_stale() || registry.get(pair).is_none() {
+            self.refresh_symbols(registry).await?;
+        }
+        registry
+            .get(pair)
+            .ok_or_else(|| Error::InvalidRequest(format!("unknown symbol {}", pair)).into())
+    }
+
+    /// refresh `registry` every `every` until the shutdown, a failed refresh keeps the
+    /// previous listing and is retried with the next tick
+    pub async fn refresh_symbols_periodically(
+        &self,
+        registry: &symbols::SymbolRegistry,
+        every: std::time::Duration,
+    ) {
+        let mut ticker = tokio::time::interval(every);
+        self.until_shutdown(async {
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.refresh_symbols(registry).await {
+                    log::warn!("fxdx symbols refresh failed: {}", e);
+                }
+            }
+        })
+        .await;
+    }
+
+    /// run many read-only requests, e.g. the klines of every symbol, at most
+    /// `options.concurrency` at once, see `bulk::fan_out`; `bulk::collect` gathers the stream
+    /// into results and failures
+    pub fn bulk<'a, T, F, Fut>(
+        &'a self,
+        requests: Vec<request::Request>,
+        options: bulk::BulkOptions,
+        call: F,
+    ) -> impl futures_util::Stream<Item = bulk::BulkItem<T>> + 'a
+    where
+        F: Fn(&'a Self, request::Request) -> Fut + 'a,
+        Fut: std::future::Future<Output = Result<T>> + 'a,
+        T: 'a,
+    {
+        bulk::fan_out(requests, options, move |req| call(self, req))
+    }
+
+    pub async fn query_kline(&self, req: request::Request) -> Result<Vec<response::Kline>> {
+        Ok(self
+            .call::<response::KlineResponse>(&req)
+            .await?
+            .into_result_or_default()?)
+    }
+
+    /// the candle of `symbol` at `scale` which is still forming, `None` before the first trade
+    pub async fn latest_kline(
+        &self,
+        symbol: &str,
+        scale: request::Scale,
+    ) -> Result<Option<response::Kline>> {
+        let klines = self
+            .query_kline(request::Request::Kline {
+                symbol: symbol.to_string(),
+                scale,
+            })
+            .await?;
+        Ok(klines.into_iter().max_by_key(|kline| kline.id))
+    }
+
+    /// every candle of `symbol` at `scale` once it closed, fetched right after its close on the
+    /// clock of the exchange; with a websocket at hand `candles::CloseDetector` does the same
+    /// without polling
+    ///
+    /// a failed request is yielded as an error and the stream carries on with the next close
+    pub fn candle_close_stream<'a>(
+        &'a self,
+        symbol: &'a str,
+        scale: request::Scale,
+    ) -> impl futures_util::Stream<Item = Result<response::Kline>> + 'a {
+        candles::poll_closes(
+            scale,
+            move || {
+                let local = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |now| now.as_millis() as i64);
+                local + self.timing_report().clock_offset.mean_ms().unwrap_or(0)
+            },
+            move || {
+                self.query_kline(request::Request::Kline {
+                    symbol: symbol.to_string(),
+                    scale,
+                })
+            },
+        )
+    }
+
+    /// one page of the fills of the key on `symbol`, newest first
+    pub async fn query_my_trades(
+        &self,
+        symbol: String,
+        page: request::Page,
+    ) -> Result<Vec<response::Trade>> {
+        let req = request::Request::MyTrades { symbol, page };
+        let trades = self
+            .call::<response::MyTradesResponse>(&req)
+            .await?
+            .into_result_or_default()?;
+        if let (Some(_), request::Request::MyTrades { symbol, .. }) = (&self.inner.storage, &req) {
+            self.store_fills(&storage::FillRow::read(symbol, None, &trades))
+                .await;
+        }
+        Ok(trades)
+    }
+
+    /// walk the fills of `symbol` page by page from `first`, see `TradePages`
+    pub fn my_trades_pages(&self, symbol: String, first: request::Page) -> TradePages<'_, P> {
+        TradePages {
+            client: self,
+            symbol,
+            page: first,
+            done: false,
+        }
+    }
+
+    /// the latest public trades of `symbol`, newest first
+    pub async fn query_trades(
+        &self,
+        symbol: String,
+        limit: Option<u32>,
+    ) -> Result<Vec<response::PublicTrade>> {
+        Ok(self
+            .call::<response::TradesResponse>(&request::Request::Trades { symbol, limit })
+            .await?
+            .into_result_or_default()?)
+    }
+
+    pub async fn deposit_address(&self, asset: String) -> Result<response::DepositAddress> {
+        Ok(self
+            .call::<response::DepositAddressResponse>(&request::Request::DepositAddress { asset })
+            .await?
+            .into_result()?)
+    }
+
+    /// submit a withdrawal and return its id, refused in paper trading mode as it would move real funds
+    pub async fn withdraw(
+        &self,
+        asset: String,
+        amount: bigdecimal::BigDecimal,
+        address: String,
+        memo: Option<String>,
+    ) -> Result<String> {
+        if self.is_paper_trading() {
+            return Err(Error::InvalidRequest(String::from(
+                "refuse to withdraw in paper trading mode",
+            ))
+            .into());
+        }
+        Ok(self
+            .call::<response::WithdrawResponse>(&request::Request::Withdraw {
+                asset,
+                amount,
+                address,
+                memo,
+            })
+            .await?
+            .into_result()?)
+    }
+
+    /// one page of deposits of `asset`, newest first
+    pub async fn deposit_history(
+        &self,
+        asset: String,
+        page: request::Page,
+    ) -> Result<Vec<response::Transfer>> {
+        self.transfers(request::Request::DepositHistory { asset, page })
+            .await
+    }
+
+    /// one page of withdrawals of `asset`, newest first
+    pub async fn withdrawal_history(
+        &self,
+        asset: String,
+        page: request::Page,
+    ) -> Result<Vec<response::Transfer>> {
+        self.transfers(request::Request::WithdrawalHistory { asset, page })
+            .await
+    }
+
+    async fn transfers(&self, req: request::Request) -> Result<Vec<response::Transfer>> {
+        Ok(self
+            .call::<response::TransfersResponse>(&req)
+            .await?
+            .into_result_or_default()?)
+    }
+
+    pub async fn query_symbols(&self, req: request::Request) -> Result<Vec<response::Symbol>> {
+        Ok(self
+            .call::<response::SymbolsResponse>(&req)
+            .await?
+            .into_result_or_default()?)
+    }
+}
+
+/// the fills of one symbol fetched a page at a time, so a daily reconciliation does not
+/// have to hold the whole history: `while let Some(fills) = pages.next().await? { .. }`
+pub struct TradePages<'a, P> {
+    client: &'a FxdxClient<P>,
+    symbol: String,
+    page: request::Page,
+    done: bool,
+}
+
+impl<P> TradePages<'_, P>
+where
+    P: request::Prefix,
+{
+    /// the next page, `None` once a short page was returned
+    pub async fn next(&mut self) -> Result<Option<Vec<response::Trade>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let fills = self
+            .client
+            .query_my_trades(self.symbol.clone(), self.page)
+            .await?;
+        self.done = self.page.is_last(fills.len());
+        self.page = self.page.next();
+        Ok(if fills.is_empty() { None } else { Some(fills) })
+    }
+
+    /// write the remaining pages to `sink` as they arrive, returns the number of fills
+    #[cfg(feature = "export")]
+    pub async fn export<S: export::Sink<response::Trade>>(
+        &mut self,
+        sink: &mut S,
+    ) -> Result<usize> {
+        let mut written = 0;
+        while let Some(fills) = self.next().await? {
+            sink.write(&fills)?;
+            written += fills.len();
+        }
+        Ok(written)
+    }
+}
+
+#[derive(Default)]
+pub struct FxdxBuilder<P> {
+    endpoints: Vec<String>,
+    failover_threshold: u32,
+    secret_key: secrecy::SecretString,
+    address: String,
+    mirror: Option<String>,
+    is_sr25519: bool,
+    is_ed25519: bool,
+    /// a maker secret was set, so a later key conflicts with it
+    is_secret: bool,
+    sr25519_keypair: Option<schnorrkel::Keypair>,
+    rate_limit: Option<(u32, std::time::Duration)>,
+    paper_trading: bool,
+    connection: connection::ConnectionOptions,
+    journal: Option<std::path::PathBuf>,
+    audit: Option<audit::AuditLog>,
+    drop_copy: Option<dropcopy::DropCopy>,
+    storage: Option<std::sync::Arc<dyn storage::Storage>>,
+    signature_encoding: signing::SignatureEncoding,
+    outbox_ttl: Option<std::time::Duration>,
+    cache: Option<responsecache::CacheTtl>,
+    retry: Option<config::RetryPolicy>,
+    symbols: Vec<String>,
+    environment: environment::Environment,
+    wire: wire::WireProfile,
+    redact_bodies: bool,
+    on_rate_change: Option<ratelimit::RateHook>,
+    headers: Vec<(&'static str, String)>,
+    transport: Option<std::sync::Arc<dyn transport::Transport>>,
+    token_store: Option<std::sync::Arc<dyn tokenstore::TokenStore>>,
+    nonce_store: Option<std::sync::Arc<dyn nonce::NonceStore>>,
+    /// the first misuse of a builder method, reported by `validate`
+    invalid: Option<BuildError>,
+    _marker: std::marker::PhantomData<P>,
+}
+
+/// an endpoint without its trailing slashes, the uris of requests start with one; with a
+/// custom transport any scheme it understands is fine, e.g. `unix:`
+fn check_endpoint(endpoint: &str, any_scheme: bool) -> std::result::Result<&str, BuildError> {
+    let invalid = |reason: &str| BuildError::InvalidEndpoint {
+        endpoint: endpoint.to_string(),
+        reason: reason.to_string(),
+    };
+    let trimmed = endpoint.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Err(invalid("empty"));
+    }
+    if trimmed.chars().any(char::is_whitespace) {
+        return Err(invalid("contains whitespace"));
+    }
+    if any_scheme {
+        return Ok(trimmed);
+    }
+    let url = reqwest::Url::parse(trimmed).map_err(|e| invalid(&e.to_string()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid("the scheme is neither http nor https"));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(invalid("no host"));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(invalid(
+            "request paths are appended, it can not carry a query",
+        ));
+    }
+    Ok(trimmed)
+}
+
+impl<P> FxdxBuilder<P>
+where
+    P: request::Prefix,
+{
+    pub fn endpoint(endpoint: String) -> Self {
+        Self::endpoints(vec![endpoint])
+    }
+
+    /// several gateways of the same exchange, the first is the primary and the others
+    /// take over in order when it keeps failing
+    pub fn endpoints(endpoints: Vec<String>) -> Self {
+        FxdxBuilder {
+            endpoints,
+            failover_threshold: 3,
+            secret_key: Default::default(),
+            address: Default::default(),
+            mirror: None,
+            is_sr25519: false,
+            is_ed25519: false,
+            is_secret: false,
+            sr25519_keypair: None,
+            rate_limit: None,
+            paper_trading: false,
+            connection: Default::default(),
+            journal: None,
+            audit: None,
+            drop_copy: None,
+            storage: None,
+            signature_encoding: Default::default(),
+            outbox_ttl: None,
+            cache: None,
+            retry: None,
+            symbols: vec![],
+            environment: Default::default(),
+            wire: Default::default(),
+            redact_bodies: false,
+            on_rate_change: None,
+            headers: vec![],
+            transport: None,
+            token_store: None,
+            nonce_store: None,
+            invalid: None,
+            _marker: Default::default(),
+        }
+    }
+
+    /// the endpoints and quirks of a known deployment, see `environment::Environment`
+    pub fn environment(environment: environment::Environment) -> Self {
+        let mut builder = Self::endpoints(environment.endpoints());
+        builder.environment = environment;
+        builder
+    }
+
+    /// endpoint, address and secret from `FXDX_ENDPOINT`, `FXDX_ADDRESS` and `FXDX_SECRET`,
+    /// several endpoints are separated by commas with the primary first
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars<F>(var: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let require = |name: &str| {
+            var(name)
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| Error::InvalidRequest(format!("{} is not set", name)))
+        };
+        let endpoints = require("FXDX_ENDPOINT")?
+            .split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        Ok(Self::endpoints(endpoints)
+            .address(require("FXDX_ADDRESS")?)
+            .secret(require("FXDX_SECRET")?))
+    }
+
+    /// settings of a TOML or YAML file, see `config::ClientConfig`
+    pub fn from_config<T: AsRef<std::path::Path>>(path: T) -> Result<Self> {
+        Self::from_client_config(config::ClientConfig::load(path)?, |name| {
+            std::env::var(name).ok()
+        })
+    }
+
+    fn from_client_config<F>(config: config::ClientConfig, var: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut builder = match config.environment {
+            Some(environment) => {
+                let mut builder = Self::environment(environment);
+                if !config.endpoints.is_empty() {
+                    builder.endpoints = config.endpoints;
+                }
+                builder
+            }
+            None => Self::endpoints(config.endpoints),
+        };
+        if let Some(address) = config.address {
+            builder = builder.address(address);
+        }
+        if let Some(name) = config.secret_env {
+            let secret =
+                var(&name).ok_or_else(|| Error::InvalidRequest(format!("{} is not set", name)))?;
+            builder = builder.secret(secret);
+        }
+        if let Some(failures) = config.failover_threshold {
+            builder = builder.failover_threshold(failures);
+        }
+        if let Some(limit) = config.rate_limit {
+            builder = builder.rate_limit(
+                limit.requests,
+                std::time::Duration::from_millis(limit.per_ms),
+            );
+        }
+        if let Some(timeout) = config.timeout_ms {
+            builder = builder.timeout(std::time::Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = config.connect_timeout_ms {
+            builder = builder.connect_timeout(std::time::Duration::from_millis(timeout));
+        }
+        if let Some(policy) = config.retry {
+            builder = builder.retry(policy);
+        }
+        Ok(builder.symbols(config.symbols))
+    }
+
+    pub fn address(mut self, address: String) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// the key as a subkey SURI, e.g. a mnemonic with an optional `//hard/soft///password`
+    /// path, or a raw `0x` seed, see `keys::from_suri`
+    pub fn sr25519(mut self, private_key: String) -> Self {
+        if self.is_ed25519 || self.is_secret {
+            self.conflict("sr25519");
+            return self;
+        }
+        self.secret_key = private_key.into();
+        self.is_sr25519 = true;
+        self
+    }
+
+    /// the key of an account exported from Polkadot-JS, decrypted with `password`
+    pub fn sr25519_keystore(mut self, json: &str, password: &str) -> Result<Self> {
+        if self.is_ed25519 || self.is_secret {
+            self.conflict("sr25519");
+            return Ok(self);
+        }
+        self.sr25519_keypair = Some(keys::from_keystore(json, password)?);
+        self.is_sr25519 = true;
+        Ok(self)
+    }
+
+    /// an ed25519 API key registered for `address`, as its hex seed or keypair, see
+    /// `keys::ed25519_from_hex`; pair it with the `request::Ed25519` prefix
+    pub fn ed25519(mut self, address: String, key: String) -> Self {
+        if self.is_sr25519 || self.is_secret {
+            self.conflict("ed25519");
+            return self;
+        }
+        self.address = address;
+        self.secret_key = key.into();
+        self.is_ed25519 = true;
+        self
+    }
+
+    /// the maker secret, conflicts with `sr25519` and `ed25519` keys set before or after it,
+    /// which `build` reports
+    pub fn secret(mut self, secret_key: String) -> Self {
+        if self.is_sr25519 || self.is_ed25519 {
+            self.conflict("secret");
+            return self;
+        }
+        self.secret_key = secret_key.into();
+        self.is_secret = true;
+        self
+    }
+
+    /// keep the first conflict for `validate`, the builder methods can not fail
+    fn conflict(&mut self, credential: &'static str) {
+        self.invalid
+            .get_or_insert(BuildError::ConflictingCredentials(credential));
+    }
+
+    /// encoding of the HMAC digest sent as `X-Signature`, hex unless set
+    pub fn signature_encoding(mut self, encoding: signing::SignatureEncoding) -> Self {
+        self.signature_encoding = encoding;
+        self
+    }
+
+    /// send queries failing on the transport again, mutating requests are never retried
+    pub fn retry(mut self, policy: config::RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// the symbols the client is meant to trade, see `FxdxClient::default_symbols`
+    pub fn symbols(mut self, symbols: Vec<String>) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    /// field names of the JSON bodies for an API version which differs from the current one
+    pub fn wire_profile(mut self, profile: wire::WireProfile) -> Self {
+        self.wire = profile;
+        self
+    }
+
+    /// keep response bodies out of `Error::Decode`, for logs which must not carry account data
+    pub fn redact_bodies(mut self, redact: bool) -> Self {
+        self.redact_bodies = redact;
+        self
+    }
+
+    /// consecutive failures of an endpoint before the next one takes over
+    pub fn failover_threshold(mut self, failures: u32) -> Self {
+        self.failover_threshold = failures;
+        self
+    }
+
+    /// a mirror of the endpoint used by `query_depth_raced`, mutating requests never go there
+    pub fn mirror(mut self, mirror: String) -> Self {
+        self.mirror = Some(mirror);
+        self
+    }
+
+    /// append every request and its response to a JSON lines journal, see `FxdxClient::replay`
+    pub fn journal(mut self, path: std::path::PathBuf) -> Self {
+        self.journal = Some(path);
+        self
+    }
+
+    /// write every signed request to an append-only audit trail in `sink`, e.g.
+    /// `audit::FileSink` or `audit::SyslogSink`; it keeps no bodies and only a hash of the signature
+    pub fn audit<S: audit::AuditSink + 'static>(mut self, sink: S) -> Self {
+        self.audit = Some(audit::AuditLog::new(sink));
+        self
+    }
+
+    /// send a `dropcopy::ExecutionReport` of every placement, cancel and fill seen by the
+    /// client to `sink`, e.g. `dropcopy::channel` or a `dropcopy::LineSink`
+    pub fn drop_copy<S: dropcopy::DropCopySink + 'static>(mut self, sink: S) -> Self {
+        self.drop_copy = Some(dropcopy::DropCopy::new(sink));
+        self
+    }
+
+    /// save every order and fill the client reads to `store`, see `storage`
+    pub fn storage<S: storage::Storage + 'static>(mut self, store: S) -> Self {
+        self.storage = Some(std::sync::Arc::new(store));
+        self
+    }
+
+    /// simulate order placement, cancellation and order queries in-process against live depth,
+    /// every other endpoint is still served by the exchange
+    pub fn paper_trading(mut self, paper_trading: bool) -> Self {
+        self.paper_trading = paper_trading;
+        self
+    }
+
+    /// queue orders passed to `FxdxClient::submit_order` while the endpoint is unreachable,
+    /// a queued order is dropped when it could not be sent within `ttl`
+    pub fn outbox(mut self, ttl: std::time::Duration) -> Self {
+        self.outbox_ttl = Some(ttl);
+        self
+    }
+
+    /// reuse the responses of symbols, depth and kline requests for `ttl`, so tasks sharing the
+    /// client do not each ask the exchange for the same data; see `FxdxClient::uncache`
+    pub fn response_cache(mut self, ttl: responsecache::CacheTtl) -> Self {
+        self.cache = Some(ttl);
+        self
+    }
+
+    /// send at most `requests` request weights every `per`, bursts included, see `Request::weight`
+    pub fn rate_limit(mut self, requests: u32, per: std::time::Duration) -> Self {
+        self.rate_limit = Some((requests, per));
+        self
+    }
+
+    /// called with the new effective rate whenever a 429 throttles the rate limiter or
+    /// successful requests recover it, e.g. to export it as a gauge
+    pub fn on_rate_change<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(f64) + Send + Sync + 'static,
+    {
+        self.on_rate_change = Some(std::sync::Arc::new(hook));
+        self
+    }
+
+    /// keep the session token of the handshake in `store`, a restart reuses it until it
+    /// is about to expire instead of running the handshake again
+    pub fn token_store<T: tokenstore::TokenStore + 'static>(mut self, store: T) -> Self {
+        self.token_store = Some(std::sync::Arc::new(store));
+        self
+    }
+
+    /// send an increasing `X-Nonce` with every request of a token session, continuing above the
+    /// nonces issued before a restart as `store` remembers them
+    pub fn nonce_store<T: nonce::NonceStore + 'static>(mut self, store: T) -> Self {
+        self.nonce_store = Some(std::sync::Arc::new(store));
+        self
+    }
+
+    /// send requests through `transport` instead of reqwest, the connection options below
+    /// then have no effect
+    pub fn transport<T: transport::Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(std::sync::Arc::new(transport));
+        self
+    }
+
+    /// send `name: value` with every request, replacing an earlier value of `name`; the headers
+    /// the client signs with can not be set, `build` reports them and invalid headers
+    pub fn default_header(mut self, name: &'static str, value: &str) -> Self {
+        let invalid = |reason: &str| BuildError::InvalidHeader {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        };
+        let signed = [
+            encoding::TIMESTAMP_HEADER,
+            encoding::ADDRESS_HEADER,
+            encoding::SIGNATURE_HEADER,
+            encoding::TOKEN_HEADER,
+            encoding::NONCE_HEADER,
+        ];
+        if signed
+            .iter()
+            .any(|signed| signed.eq_ignore_ascii_case(name))
+        {
+            self.invalid
+                .get_or_insert(invalid("set by the client when signing"));
+        } else if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            self.invalid.get_or_insert(invalid("not a header name"));
+        } else if reqwest::header::HeaderValue::from_str(value).is_err() {
+            self.invalid.get_or_insert(invalid("not a header value"));
+        } else {
+            self.headers
+                .retain(|(set, _)| !set.eq_ignore_ascii_case(name));
+            self.headers.push((name, value.to_string()));
+        }
+        self
+    }
+
+    /// the `User-Agent` of every request, e.g. for a proxy routing or limiting by it
+    pub fn user_agent(self, agent: &str) -> Self {
+        self.default_header(reqwest::header::USER_AGENT.as_str(), agent)
+    }
+
+    /// the response encodings accepted and the requests sent gzipped, see
+    /// `connection::Compression`
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, compression: connection::Compression) -> Self {
+        self.connection.compression = compression;
+        self
+    }
+
+    /// keep at most `max` idle connections per host in the pool
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.connection.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    pub fn pool_idle_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.connection.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn tcp_keepalive(mut self, interval: std::time::Duration) -> Self {
+        self.connection.tcp_keepalive = Some(interval);
+        self
+    }
+
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.connection.tcp_nodelay = Some(nodelay);
+        self
+    }
+
+    pub fn http2_prior_knowledge(mut self, enable: bool) -> Self {
+        self.connection.http2_prior_knowledge = enable;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.connection.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// deadline of every request, from connecting until the response body is read
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.connection.timeout = Some(timeout);
+        self
+    }
+
+    /// pin `domain` to `addr` instead of resolving it through DNS
+    pub fn resolve(mut self, domain: String, addr: std::net::SocketAddr) -> Self {
+        self.connection.resolve.push((domain, addr));
+        self
+    }
+
+    /// route every request through an `http://`, `https://` or `socks5://` proxy
+    pub fn proxy(mut self, url: String) -> Self {
+        self.connection.proxy = Some(url);
+        self
+    }
+
+    pub fn proxy_basic_auth(mut self, username: String, password: String) -> Self {
+        self.connection.proxy_basic_auth = Some((username, password));
+        self
+    }
+
+    /// trust a PEM encoded CA certificate, e.g. the one of a self-signed staging deployment
+    pub fn root_certificate(mut self, pem: Vec<u8>) -> Self {
+        self.connection.root_certificates.push(pem);
+        self
+    }
+
+    /// present a client certificate to endpoints requiring mutual TLS
+    pub fn identity(mut self, identity: connection::ClientIdentity) -> Self {
+        self.connection.identity = Some(identity);
+        self
+    }
+
+    /// skip certificate verification, never use this against production
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.connection.danger_accept_invalid_certs = accept;
+        self
+    }
+
+    pub fn connection(mut self, options: connection::ConnectionOptions) -> Self {
+        self.connection = options;
+        self
+    }
+
+    /// what `build` refuses before it touches the network: no or a malformed endpoint,
+    /// missing or conflicting credentials
+    pub fn validate(&self) -> std::result::Result<(), BuildError> {
+        if let Some(invalid) = &self.invalid {
+            return Err(invalid.clone());
+        }
+        if self.endpoints.is_empty() {
+            return Err(BuildError::NoEndpoint);
+        }
+        let any_scheme = self.transport.is_some();
+        for endpoint in self.endpoints.iter().chain(&self.mirror) {
+            check_endpoint(endpoint, any_scheme)?;
+        }
+        if self.secret_key.expose_secret().is_empty() && self.sr25519_keypair.is_none() {
+            return Err(BuildError::MissingCredentials);
+        }
+        Ok(())
+    }
+
+    pub async fn build(self) -> Result<FxdxClient<P>> {
+        self.validate()?;
+        let any_scheme = self.transport.is_some();
+        let endpoints = self
+            .endpoints
+            .iter()
+            .map(|endpoint| check_endpoint(endpoint, any_scheme).map(String::from))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let mirror = self
+            .mirror
+            .as_deref()
+            .map(|mirror| check_endpoint(mirror, any_scheme).map(String::from))
+            .transpose()?;
+        let handshake = self.is_sr25519 || self.is_ed25519;
+        let signer = if self.is_sr25519 {
+            let key = match self.sr25519_keypair {
+                Some(key) => key,
+                None => keys::from_suri(self.secret_key.expose_secret())?,
+            };
+            signing::Signer::sr25519(key, self.signature_encoding)
+        } else if self.is_ed25519 {
+            let key = keys::ed25519_from_hex(self.secret_key.expose_secret())?;
+            signing::Signer::ed25519(key, self.signature_encoding)
+        } else {
+            signing::Signer::new(self.secret_key, self.signature_encoding)
+        };
+        let state = ClientState {
+            transport: match self.transport {
+                Some(transport) => transport,
+                None => std::sync::Arc::new(transport::ReqwestTransport::from_options(
+                    &self.connection,
+                )?),
+            },
+            endpoints: failover::EndpointPool::new(endpoints, self.failover_threshold),
+            mirror,
+            address: self.address,
+            signer,
+            sequencer: sequence::Sequencer::new(),
+            limiter: self
+                .rate_limit
+                .map(|(requests, per)| ratelimit::RateLimiter::new(requests, per)),
+            paper: self
+                .paper_trading
+                .then(|| std::sync::Mutex::new(paper::PaperExchange::new())),
+            journal: self.journal.map(journal::Journal::open).transpose()?,
+            audit: self.audit,
+            drop_copy: self.drop_copy,
+            storage: self.storage,
+            permissions: Default::default(),
+            timing: Default::default(),
+            retry: self.retry,
+            default_symbols: self.symbols,
+            environment: self.environment,
+            wire: self.wire,
+            shutdown: shutdown::Shutdown::new(),
+            redact_bodies: self.redact_bodies,
+            on_rate_change: self.on_rate_change,
+            headers: self.headers,
+            outbox: self
+                .outbox_ttl
+                .map(|ttl| std::sync::Mutex::new(outbox::Outbox::new(ttl))),
+            cache: self.cache.map(responsecache::ResponseCache::new),
+            token: Default::default(),
+            token_store: self.token_store,
+            nonces: self
+                .nonce_store
+                .map(nonce::NonceManager::load)
+                .transpose()?,
+            token_generation: Default::default(),
+            renewing: Default::default(),
+            encoders: Default::default(),
+        };
+        let client = FxdxClient {
+            inner: std::sync::Arc::new(state),
+            _marker: Default::default(),
+        };
+        if handshake {
+            match client.stored_token() {
+                Some(token) => {
+                    *client
+                        .inner
+                        .token
+                        .write()
+                        .unwrap_or_else(|e| e.into_inner()) = Some(token)
+                }
+                None => client.fresh().await?,
+            }
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let result = 2 + 2;
+        assert_eq!(result, 4);
+    }
+
+    #[test]
+    fn test_builder_from_vars() {
+        let vars = |name: &str| match name {
+            "FXDX_ENDPOINT" => Some(String::from("https://a.fxdx, https://b.fxdx")),
+            "FXDX_ADDRESS" => Some(String::from("0xabc")),
+            "FXDX_SECRET" => Some(String::from("secret")),
+            _ => None,
+        };
+        let builder = FxdxBuilder::<request::PrivPub>::from_vars(vars).unwrap();
+        assert_eq!(builder.endpoints, vec!["https://a.fxdx", "https://b.fxdx"]);
+        assert_eq!(builder.address, "0xabc");
+
+        let err = FxdxBuilder::<request::PrivPub>::from_vars(|name| {
+            vars(name).filter(|_| name != "FXDX_SECRET")
+        })
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("FXDX_SECRET"));
+    }
+
+    #[test]
+    fn test_builder_from_client_config() {
+        let config = config::ClientConfig::from_toml(
+            r#"
+endpoints = ["https://a.fxdx"]
+secret_env = "MAKER_SECRET"
+symbols = ["BTC-USDT"]
+"#,
+        )
+        .unwrap();
+        let builder = FxdxBuilder::<request::PrivPub>::from_client_config(config.clone(), |name| {
+            (name == "MAKER_SECRET").then(|| String::from("secret"))
+        })
+        .unwrap();
+        assert_eq!(builder.endpoints, vec!["https://a.fxdx"]);
+        assert_eq!(builder.symbols, vec!["BTC-USDT"]);
+        assert!(FxdxBuilder::<request::PrivPub>::from_client_config(config, |_| None).is_err());
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        let builder = |endpoint: &str| {
+            FxdxBuilder::<request::PrivPub>::endpoint(endpoint.to_string())
+                .secret(String::from("secret"))
+        };
+        assert!(builder("https://fxdx/").validate().is_ok());
+        assert_eq!(
+            check_endpoint("https://fxdx/api//", false),
+            Ok("https://fxdx/api")
+        );
+        for endpoint in [
+            "fxdx",
+            "ftp://fxdx",
+            "https://fxdx?x=1",
+            "https:// fxdx",
+            "/",
+        ] {
+            assert!(
+                matches!(
+                    builder(endpoint).validate(),
+                    Err(BuildError::InvalidEndpoint { .. })
+                ),
+                "{}",
+                endpoint
+            );
+        }
+        assert_eq!(
+            FxdxBuilder::<request::PrivPub>::endpoints(vec![])
+                .secret(String::from("secret"))
+                .validate(),
+            Err(BuildError::NoEndpoint)
+        );
+        assert_eq!(
+            FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx")).validate(),
+            Err(BuildError::MissingCredentials)
+        );
+        let conflicting = FxdxBuilder::<request::Sr25519>::endpoint(String::from("https://fxdx"))
+            .sr25519(String::from("//Alice"))
+            .secret(String::from("secret"));
+        assert_eq!(
+            conflicting.validate(),
+            Err(BuildError::ConflictingCredentials("secret"))
+        );
+    }
+
+    #[test]
+    fn test_conflicting_credentials_in_any_order() {
+        let builder = || {
+            FxdxBuilder::<request::Sr25519>::endpoint(String::from("https://fxdx"))
+                .address(String::from("0xabc"))
+        };
+        let ed25519 = || (String::from("0xabc"), String::from("00"));
+        let keystore = |builder: FxdxBuilder<request::Sr25519>| {
+            builder.sr25519_keystore("{}", "password").unwrap()
+        };
+        for (conflicting, credential) in [
+            (
+                builder()
+                    .secret(String::from("s"))
+                    .sr25519(String::from("//Alice")),
+                "sr25519",
+            ),
+            (keystore(builder().secret(String::from("s"))), "sr25519"),
+            (
+                keystore(builder().ed25519(ed25519().0, ed25519().1)),
+                "sr25519",
+            ),
+            (
+                builder()
+                    .secret(String::from("s"))
+                    .ed25519(ed25519().0, ed25519().1),
+                "ed25519",
+            ),
+            (
+                builder()
+                    .sr25519(String::from("//Alice"))
+                    .ed25519(ed25519().0, ed25519().1),
+                "ed25519",
+            ),
+            (
+                builder()
+                    .ed25519(ed25519().0, ed25519().1)
+                    .secret(String::from("s")),
+                "secret",
+            ),
+        ] {
+            assert_eq!(
+                conflicting.validate(),
+                Err(BuildError::ConflictingCredentials(credential)),
+                "{}",
+                credential
+            );
+        }
+        // the first conflict is kept, a key of the same kind again is no conflict
+        let twice = builder()
+            .sr25519(String::from("//Alice"))
+            .secret(String::from("s"))
+            .ed25519(ed25519().0, ed25519().1)
+            .sr25519(String::from("//Bob"));
+        assert_eq!(
+            twice.validate(),
+            Err(BuildError::ConflictingCredentials("secret"))
+        );
+        assert_eq!(
+            builder()
+                .sr25519(String::from("//Alice"))
+                .sr25519(String::from("//Bob"))
+                .validate(),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clones_share_state() {
+        fn shareable<T: Clone + Send + Sync + 'static>() {}
+        shareable::<FxdxClient<request::PrivPub>>();
+        shareable::<FxdxClient<request::Ed25519>>();
+
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .build()
+            .await
+            .unwrap();
+        let clone = client.clone();
+        let permissions = tokio::spawn(async move {
+            clone.set_permissions(Some(vec![]));
+            clone.shutdown().await;
+            clone.permissions()
+        })
+        .await
+        .unwrap();
+        assert_eq!(permissions, Some(vec![]));
+        assert_eq!(client.permissions(), Some(vec![]));
+        assert!(client.is_shut_down());
+    }
+
+    #[tokio::test]
+    async fn test_environment_preset() {
+        let config = config::ClientConfig::from_toml(r#"environment = "testnet""#).unwrap();
+        let client = FxdxBuilder::<request::PrivPub>::from_client_config(config, |_| None)
+            .unwrap()
+            .secret(String::from("secret"))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.endpoint(), "https://test-api.fxdx.finance");
+        assert!(client.environment().is_testnet());
+
+        let mut order = request::NewOrder::new(
+            request::Side::Bid,
+            request::OrderKind::Limit,
+            String::from("NEW-USDT"),
+            Some(1.into()),
+            1.into(),
+        )
+        .unwrap();
+        let registry = client.symbol_registry(std::time::Duration::from_secs(60));
+        assert!(registry.check_order(&order).is_ok());
+
+        let client =
+            FxdxBuilder::<request::PrivPub>::environment(environment::Environment::Mainnet)
+                .secret(String::from("secret"))
+                .build()
+                .await
+                .unwrap();
+        order.symbol = String::from("OLD-USDT");
+        let registry = client.symbol_registry(std::time::Duration::from_secs(60));
+        assert!(registry.check_order(&order).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .build()
+            .await
+            .unwrap();
+        let err = client
+            .with_timeout(std::time::Duration::from_millis(10), async {
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                Ok(())
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::Timeout(_))
+        ));
+        assert_eq!(
+            client
+                .with_timeout(std::time::Duration::from_secs(1), async { Ok(1) })
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_permission_gating() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .build()
+            .await
+            .unwrap();
+        client.set_permissions(Some(vec![request::Permission::Read]));
+        let err = client
+            .cancel_order(request::Request::CancelOrder {
+                symbol: String::from("BTC-USDT"),
+                order_id: String::from("1"),
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::PermissionDenied(request::Permission::Trade))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_outbox_queues_while_unreachable() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .outbox(std::time::Duration::from_secs(60))
+            .build()
+            .await
+            .unwrap();
+        let order = request::NewOrder::new(
+            request::Side::Bid,
+            request::OrderKind::Limit,
+            String::from("BTC-USDT"),
+            Some(bigdecimal::BigDecimal::from(100)),
+            bigdecimal::BigDecimal::from(1),
+        )
+        .unwrap();
+        assert!(matches!(
+            client.submit_order(order.clone()).await.unwrap(),
+            outbox::Submission::Queued { .. }
+        ));
+        assert!(matches!(
+            client.submit_order(order).await.unwrap(),
+            outbox::Submission::Queued { .. }
+        ));
+        let report = client.flush_outbox().await;
+        assert!(report.is_empty());
+        assert_eq!(report.pending, 2);
+        assert_eq!(client.queued_orders().len(), 2);
+    }
+
+    /// a legacy batch cancel which only cancels order 1, order 2 is filled and 3 unknown
+    struct LegacyCancel;
+
+    impl transport::Transport for LegacyCancel {
+        fn execute<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            request: encoding::SignedRequest,
+        ) -> transport::TransportFuture<'a> {
+            let body: &'static [u8] = match request.uri.as_str() {
+                "//maker/order/BTC-USDT/1|2|3" => br#"{"code":200,"data":"1"}"#,
+                "//maker/order/BTC-USDT/2" => {
+                    br#"{"code":200,"data":{"symbol":"BTC-USDT","order_id":2,"order_type":1,"direction":1,"amount":"1","status":3,"created_at":1700000000}}"#
+                }
+                _ => br#"{"code":404,"data":null}"#,
+            };
+            Box::pin(async move {
+                Ok(transport::RawResponse {
+                    status: 200,
+                    headers: vec![],
+                    body: bytes::Bytes::from_static(body),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_cancel_outcomes() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .transport(std::sync::Arc::new(LegacyCancel))
+            .build()
+            .await
+            .unwrap();
+        let result = client
+            .batch_cancel_orders(request::Request::BatchCancelOrders {
+                symbol: String::from("BTC-USDT"),
+                order_ids: vec![String::from("1"), String::from("2"), String::from("3")],
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            result
+                .items
+                .iter()
+                .map(|item| item.outcome)
+                .collect::<Vec<_>>(),
+            [
+                response::CancelOutcome::Cancelled,
+                response::CancelOutcome::AlreadyFilled,
+                response::CancelOutcome::NotFound
+            ]
+        );
+        assert_eq!(result.cancelled().collect::<Vec<_>>(), ["1"]);
+        assert!(!result.is_complete());
+    }
+
+    #[tokio::test]
+    async fn test_paper_trading_refuses_withdrawals() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .paper_trading(true)
+            .build()
+            .await
+            .unwrap();
+        let err = client
+            .withdraw(
+                String::from("USDT"),
+                bigdecimal::BigDecimal::from(1),
+                String::from("0xdead"),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("paper trading"));
+    }
+
+    #[tokio::test]
+    async fn test_replace_keeps_kind() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .paper_trading(true)
+            .transport(Exchange::default())
+            .build()
+            .await
+            .unwrap();
+        let symbol = || String::from("BTC-USDT");
+        let order_id = client
+            .pending_order(
+                request::Request::order(
+                    request::Side::Bid,
+                    request::OrderKind::PostOnly,
+                    symbol(),
+                    Some(bigdecimal::BigDecimal::from(10)),
+                    bigdecimal::BigDecimal::from(1),
+                )
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        let replace = |order_id: String, kind| {
+            client.replace_order(
+                symbol(),
+                order_id,
+                kind,
+                bigdecimal::BigDecimal::from(11),
+                bigdecimal::BigDecimal::from(1),
+                false,
+            )
+        };
+
+        // a market order has no price, refused while the original still rests
+        assert!(replace(order_id.clone(), request::OrderKind::Market)
+            .await
+            .is_err());
+        let original = client.order_by_id("BTC-USDT", &order_id).await.unwrap();
+        assert_eq!(original.status, request::OrderStatus::Undeal);
+
+        // with no liquidity in the book an IOC replacement is cancelled at once, a limit one would rest
+        let replaced = replace(order_id, request::OrderKind::IOC).await.unwrap();
+        assert_eq!(replaced.outcome, response::ReplaceOutcome::Replaced);
+        let replacement = client
+            .order_by_id("BTC-USDT", &replaced.new_order_id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(replacement.status, request::OrderStatus::Cancel);
+    }
+
+    #[tokio::test]
+    async fn test_paper_batch_reports_rejections() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .paper_trading(true)
+            .transport(Exchange::default())
+            .build()
+            .await
+            .unwrap();
+        let priced = request::NewOrder::builder()
+            .symbol("BTC-USDT")
+            .side(request::Side::Bid)
+            .price("100".parse().unwrap())
+            .amount("1".parse().unwrap())
+            .build();
+        let unpriced = request::NewOrder {
+            price: None,
+            ..priced.clone()
+        };
+        let result = client
+            .batch_pending_orders(request::Request::BatchPendingOrders(vec![priced, unpriced]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            &result.items[0].outcome,
+            response::BatchOutcome::Placed(order_id) if order_id.starts_with("paper-")
+        ));
+        assert_eq!(
+            result.items[1].outcome,
+            response::BatchOutcome::Failed(String::from(
+                "Invalid request LIMIT order requires a price"
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_loops() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .outbox(std::time::Duration::from_secs(60))
+            .build()
+            .await
+            .unwrap();
+        let every = std::time::Duration::from_millis(10);
+        let loops = async {
+            tokio::join!(
+                client.log_timing_periodically(every),
+                client.run_health_checks(every),
+                client.flush_outbox_periodically(every, |_| {}),
+            )
+        };
+        let shutdown = async {
+            tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+            client.shutdown().await
+        };
+        let (_, report) = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            tokio::join!(loops, shutdown)
+        })
+        .await
+        .unwrap();
+        assert!(client.is_shut_down());
+        assert!(report.cancelled.is_none());
+        assert_eq!(report.outbox.pending, 0);
+    }
+
+    #[tokio::test]
+    async fn test_race_refuses_mutating_requests() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .mirror(String::from("http://127.0.0.1:2"))
+            .build()
+            .await
+            .unwrap();
+        let req = request::Request::CancelOrder {
+            symbol: String::from("BTC-USDT"),
+            order_id: String::from("1"),
+        };
+        let err = client
+            .race::<response::CancelOrderResponse, _>("http://127.0.0.1:2", &req, |_| true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("refuse to race"));
+    }
+
+    /// answer each connection with the next of `responses`, a raw status line and headers
+    async fn serve(responses: Vec<(&'static str, &'static str)>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for (head, body) in responses {
+                let (mut tcp, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = tcp.read(&mut buf).await;
+                let reply = format!(
+                    "{}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    head,
+                    body.len(),
+                    body
+                );
+                tcp.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_status_mapping() {
+        let url = serve(vec![
+            ("HTTP/1.1 429 Too Many Requests\r\nretry-after: 3", ""),
+            ("HTTP/1.1 502 Bad Gateway", "<html>bad gateway</html>"),
+            ("HTTP/1.1 403 Forbidden", "{}"),
+            ("HTTP/1.1 400 Bad Request", r#"{"code":4001}"#),
+        ])
+        .await;
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(url)
+            .secret(String::from("secret"))
+            .build()
+            .await
+            .unwrap();
+        let status = |e: anyhow::Error| format!("{:?}", e.downcast_ref::<Error>().unwrap());
+        let req = request::Request::Depth {
+            symbol: String::from("BTC-USDT"),
+            limit: None,
+        };
+        let depth = || client.call::<response::DepthResponse>(&req);
+        assert_eq!(status(depth().await.unwrap_err()), "RateLimited(Some(3s))");
+        assert!(status(depth().await.unwrap_err()).contains("status: 502"));
+        assert_eq!(status(depth().await.unwrap_err()), "Unauthorized(403)");
+        assert_eq!(depth().await.unwrap().code, 4001);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_request_retried() {
+        let url = serve(vec![
+            ("HTTP/1.1 429 Too Many Requests\r\nretry-after: 0", ""),
+            ("HTTP/1.1 200 OK", r#"{"code":200,"data":[]}"#),
+        ])
+        .await;
+        let rates = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = rates.clone();
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(url)
+            .secret(String::from("secret"))
+            .rate_limit(100, std::time::Duration::from_secs(1))
+            .retry(config::RetryPolicy::default())
+            .on_rate_change(move |rate| seen.lock().unwrap().push(rate))
+            .build()
+            .await
+            .unwrap();
+        assert!(client
+            .query_symbols(request::Request::Symbols)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(*rates.lock().unwrap(), vec![50.0, 55.0]);
+        assert_eq!(client.effective_rate(), Some(55.0));
+    }
+
+    /// answers the symbols query, except on the endpoint `https://down`
+    #[derive(Default)]
+    struct Pinged {
+        endpoints: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl transport::Transport for Pinged {
+        fn execute<'a>(
+            &'a self,
+            endpoint: &'a str,
+            _request: encoding::SignedRequest,
+        ) -> transport::TransportFuture<'a> {
+            self.endpoints.lock().unwrap().push(endpoint.to_string());
+            Box::pin(async move {
+                anyhow::ensure!(endpoint != "https://down", "connection refused");
+                Ok(transport::RawResponse {
+                    status: 200,
+                    headers: vec![],
+                    body: bytes::Bytes::from_static(br#"{"code":200,"data":[]}"#),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_and_ping() {
+        let pinged = std::sync::Arc::new(Pinged::default());
+        let client = FxdxBuilder::<request::PrivPub>::endpoints(vec![
+            String::from("https://fxdx"),
+            String::from("https://down"),
+        ])
+        .mirror(String::from("https://mirror"))
+        .secret(String::from("secret"))
+        .transport(pinged.clone())
+        .build()
+        .await
+        .unwrap();
+        // a dead backup is only logged
+        client.warm_up().await.unwrap();
+        let mut warmed = pinged.endpoints.lock().unwrap().clone();
+        warmed.sort();
+        assert_eq!(warmed, ["https://down", "https://fxdx", "https://mirror"]);
+
+        for _ in 0..3 {
+            client.ping().await.unwrap();
+        }
+        let report = client.timing_report();
+        assert_eq!(report.ping.count(), 3);
+        assert!(report.ping.percentile(0.99).is_some());
+        // the warm-up is in the round trips of every request, not in the pings
+        assert_eq!(report.rtt.count(), 5);
+        let stats = client.stats();
+        assert_eq!(stats["https://fxdx"].requests, 4);
+        let down = &stats["https://down"];
+        assert_eq!((down.errors, down.rtt.count()), (1, 0));
+        assert!(down
+            .last_error
+            .as_deref()
+            .is_some_and(|e| e.contains("connection refused")));
+    }
+
+    #[tokio::test]
+    async fn test_response_cache() {
+        let pinged = std::sync::Arc::new(Pinged::default());
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .response_cache(
+                responsecache::CacheTtl::new()
+                    .kline(request::Scale::Hour, std::time::Duration::ZERO),
+            )
+            .transport(pinged.clone())
+            .build()
+            .await
+            .unwrap();
+        let sent = || pinged.endpoints.lock().unwrap().len();
+        for _ in 0..3 {
+            client
+                .query_symbols(request::Request::Symbols)
+                .await
+                .unwrap();
+        }
+        assert_eq!(sent(), 1);
+        client.uncache(&request::Request::Symbols);
+        client
+            .query_symbols(request::Request::Symbols)
+            .await
+            .unwrap();
+        assert_eq!(sent(), 2);
+
+        let kline = |scale| request::Request::Kline {
+            symbol: String::from("BTC-USDT"),
+            scale,
+        };
+        for _ in 0..2 {
+            client
+                .query_kline(kline(request::Scale::Minute))
+                .await
+                .unwrap();
+            client
+                .query_kline(kline(request::Scale::Hour))
+                .await
+                .unwrap();
+        }
+        // the minute candle once, the uncached hour one every time
+        assert_eq!(sent(), 5);
+    }
+
+    /// fails the first request, then answers with an empty depth
+    #[derive(Default)]
+    struct Double {
+        sent: std::sync::Mutex<Vec<encoding::SignedRequest>>,
+    }
+
+    impl transport::Transport for Double {
+        fn execute<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            request: encoding::SignedRequest,
+        ) -> transport::TransportFuture<'a> {
+            let mut sent = self.sent.lock().unwrap();
+            sent.push(request);
+            let first = sent.len() == 1;
+            Box::pin(async move {
+                anyhow::ensure!(!first, "socket closed");
+                Ok(transport::RawResponse {
+                    status: 200,
+                    headers: vec![(String::from("Content-Type"), String::from("json"))],
+                    body: bytes::Bytes::from_static(
+                        br#"{"code":200,"data":{"asks":[],"bids":[]}}"#,
+                    ),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_transport() {
+        let double = std::sync::Arc::new(Double::default());
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("unix:fxdx"))
+            .secret(String::from("secret"))
+            .address(String::from("0xabc"))
+            .retry(config::RetryPolicy::default())
+            .transport(double.clone())
+            .build()
+            .await
+            .unwrap();
+        let depth = client
+            .query_depth(request::Request::Depth {
+                symbol: String::from("BTC-USDT"),
+                limit: None,
+            })
+            .await
+            .unwrap();
+        assert!(depth.asks.is_empty());
+        let sent = double.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].uri, "//maker/depth/BTC-USDT");
+        assert!(sent[1]
+            .headers
+            .contains(&(encoding::ADDRESS_HEADER, String::from("0xabc"))));
+    }
+
+    /// answers the handshake and then an empty depth
+    #[derive(Default)]
+    struct Exchange {
+        sent: std::sync::Mutex<Vec<encoding::SignedRequest>>,
+    }
+
+    impl transport::Transport for Exchange {
+        fn execute<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            request: encoding::SignedRequest,
+        ) -> transport::TransportFuture<'a> {
+            let body: &'static [u8] = match request.uri.as_str() {
+                "/maker/nonce" => br#"{"code":200,"data":"n-1"}"#,
+                "//api/token" => br#"{"code":200,"data":{"token":"t-1","expiresAt":"4102444800"}}"#,
+                // one id whatever the size of the batch
+                "//maker/orders" => br#"{"code":200,"data":["o-1"]}"#,
+                _ => br#"{"code":200,"data":{"asks":[],"bids":[]}}"#,
+            };
+            self.sent.lock().unwrap().push(request);
+            Box::pin(async move {
+                Ok(transport::RawResponse {
+                    status: 200,
+                    headers: vec![],
+                    body: bytes::Bytes::from_static(body),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_headers() {
+        let exchange = std::sync::Arc::new(Exchange::default());
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .user_agent("desk-a/1.0")
+            .default_header("X-Desk", "a")
+            .default_header("x-desk", "b")
+            .transport(exchange.clone())
+            .build()
+            .await
+            .unwrap();
+        client
+            .query_depth(request::Request::Depth {
+                symbol: String::from("BTC-USDT"),
+                limit: None,
+            })
+            .await
+            .unwrap();
+        let headers = exchange.sent.lock().unwrap()[0].headers.clone();
+        assert_eq!(headers[2].0, encoding::SIGNATURE_HEADER);
+        assert_eq!(
+            headers[3..],
+            [
+                ("user-agent", String::from("desk-a/1.0")),
+                ("x-desk", String::from("b"))
+            ]
+        );
+
+        let builder = |name, value| {
+            FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+                .secret(String::from("secret"))
+                .default_header(name, value)
+                .validate()
+        };
+        assert!(matches!(
+            builder("x-signature", "forged"),
+            Err(BuildError::InvalidHeader { .. })
+        ));
+        assert!(builder("X Desk", "a").is_err());
+        assert!(builder("X-Desk", "a\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drop_copy_of_orders() {
+        let (sink, mut reports) = dropcopy::channel();
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .paper_trading(true)
+            .drop_copy(sink)
+            .transport(Exchange::default())
+            .build()
+            .await
+            .unwrap();
+        let order = request::Request::order(
+            request::Side::Bid,
+            request::OrderKind::Limit,
+            String::from("BTC-USDT"),
+            Some(bigdecimal::BigDecimal::from(10)),
+            bigdecimal::BigDecimal::from(1),
+        )
+        .unwrap();
+        let order_id = client.pending_order(order).await.unwrap();
+        client
+            .cancel_order(request::Request::CancelOrder {
+                symbol: String::from("BTC-USDT"),
+                order_id: order_id.clone(),
+            })
+            .await
+            .unwrap();
+
+        let placed = reports.try_recv().unwrap();
+        assert_eq!(
+            (placed.seq, placed.exec_type, placed.order_id.as_deref()),
+            (1, dropcopy::ExecType::New, Some(order_id.as_str()))
+        );
+        assert_eq!(placed.price, Some(bigdecimal::BigDecimal::from(10)));
+        let cancelled = reports.try_recv().unwrap();
+        assert_eq!(
+            (cancelled.seq, cancelled.exec_type),
+            (2, dropcopy::ExecType::Canceled)
+        );
+        assert!(reports.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batch_mismatch_is_no_rejection() {
+        let (sink, mut reports) = dropcopy::channel();
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .drop_copy(sink)
+            .transport(Exchange::default())
+            .build()
+            .await
+            .unwrap();
+        let order = |price: i32| {
+            request::NewOrder::new(
+                request::Side::Bid,
+                request::OrderKind::Limit,
+                String::from("BTC-USDT"),
+                Some(bigdecimal::BigDecimal::from(price)),
+                bigdecimal::BigDecimal::from(1),
+            )
+            .unwrap()
+        };
+        let err = client
+            .batch_pending_orders(request::Request::BatchPendingOrders(vec![
+                order(10),
+                order(11),
+            ]))
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::BatchMismatch {
+                submitted: 2,
+                returned: 1,
+                ids,
+            }) => assert_eq!(ids, &[Some(String::from("o-1"))]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(reports.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_storage_of_orders() {
+        let store = std::sync::Arc::new(storage::MemoryStore::new());
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .paper_trading(true)
+            .storage(store.clone())
+            .transport(Exchange::default())
+            .build()
+            .await
+            .unwrap();
+        let order = request::Request::order(
+            request::Side::Ask,
+            request::OrderKind::Limit,
+            String::from("BTC-USDT"),
+            Some(bigdecimal::BigDecimal::from(12)),
+            bigdecimal::BigDecimal::from(1),
+        )
+        .unwrap();
+        let order_id = client.pending_order(order).await.unwrap();
+        client.open_orders("BTC-USDT").await.unwrap();
+        let stored = client.stored_open_orders(Some("BTC-USDT")).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(
+            (stored[0].order_id.as_str(), stored[0].side),
+            (order_id.as_str(), request::Side::Ask)
+        );
+
+        let by_id = request::Request::OrderById {
+            symbol: String::from("BTC-USDT"),
+            order_id: order_id.clone(),
+        };
+        client
+            .cancel_order(request::Request::CancelOrder {
+                symbol: String::from("BTC-USDT"),
+                order_id: order_id.clone(),
+            })
+            .await
+            .unwrap();
+        client.query_order_by_id(by_id).await.unwrap();
+        assert!(client.stored_open_orders(None).await.unwrap().is_empty());
+        assert_eq!(
+            store.order(&order_id).unwrap().status,
+            request::OrderStatus::Cancel
+        );
+    }
+
+    /// answers balances, one open order of every symbol, no fills and cancels every order
+    struct Account;
+
+    impl transport::Transport for Account {
+        fn execute<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            request: encoding::SignedRequest,
+        ) -> transport::TransportFuture<'a> {
+            Box::pin(async move {
+                let data = if request.uri.contains("/balances") {
+                    String::from(r#"{"code":1,"name":"USDT","available":"10"}"#)
+                } else if let Some(path) = request.uri.split("/orders/").nth(1) {
+                    let symbol = path.split('/').next().unwrap();
+                    format!(
+                        r#"[{{"symbol":"{}","order_id":"{}-1","order_type":1,"direction":1,"amount":"1","price":"10","filled_base":"0","filled_quote":"0","status":1}}]"#,
+                        symbol, symbol
+                    )
+                } else if let Some(path) = request.uri.split("/order/").nth(1) {
+                    format!("{:?}", path.split('/').nth(1).unwrap())
+                } else {
+                    String::from("[]")
+                };
+                Ok(transport::RawResponse {
+                    status: 200,
+                    headers: vec![],
+                    body: format!(r#"{{"code":200,"data":{}}}"#, data).into(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_snapshot() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .symbols(vec![String::from("BTC-USDT"), String::from("ETH-USDT")])
+            .transport(Account)
+            .build()
+            .await
+            .unwrap();
+        let before = unix_millis();
+        let snapshot = client.account_snapshot().await.unwrap();
+        assert!(snapshot.taken_at >= before);
+        assert_eq!(snapshot.balances[0].name, "USDT");
+        assert_eq!(snapshot.open_order_count(), 2);
+        assert_eq!(snapshot.open_orders["ETH-USDT"][0].order_id, "ETH-USDT-1");
+        assert!(snapshot.fills["BTC-USDT"].is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_cancels_orphans() {
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .symbols(vec![String::from("BTC-USDT")])
+            .transport(Account)
+            .build()
+            .await
+            .unwrap();
+        let mut tracker = reconcile::OrderTracker::new();
+        let report = client
+            .reconcile(&mut tracker, reconcile::Repair::Report)
+            .await
+            .unwrap();
+        assert_eq!(report.discrepancies.len(), 1);
+        assert_eq!(report.discrepancies[0].order_id(), "BTC-USDT-1");
+
+        let report = client
+            .reconcile(&mut tracker, reconcile::Repair::CancelOrphans)
+            .await
+            .unwrap();
+        assert_eq!(report.cancelled.cancelled[0].order_id, "BTC-USDT-1");
+        assert!(tracker.is_empty());
+
+        let report = client
+            .reconcile(&mut tracker, reconcile::Repair::AdoptOrphans)
+            .await
+            .unwrap();
+        assert_eq!(report.adopted, 1);
+        assert!(client
+            .reconcile(&mut tracker, reconcile::Repair::Report)
+            .await
+            .unwrap()
+            .is_consistent());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_failed_journal_keeps_reply() {
+        let exchange = std::sync::Arc::new(Exchange::default());
+        // every write to /dev/full fails
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .journal(std::path::PathBuf::from("/dev/full"))
+            .transport(exchange.clone())
+            .build()
+            .await
+            .unwrap();
+        client
+            .query_depth(request::Request::Depth {
+                symbol: String::from("BTC-USDT"),
+                limit: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(exchange.sent.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_without_handshake() {
+        let exchange = std::sync::Arc::new(Exchange::default());
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("https://fxdx"))
+            .secret(String::from("secret"))
+            .transport(exchange.clone())
+            .build()
+            .await
+            .unwrap();
+        let e = client.fresh().await.unwrap_err();
+        assert!(matches!(e.downcast_ref(), Some(Error::InvalidRequest(_))));
+        // the nonce only, no token request
+        assert_eq!(exchange.sent.lock().unwrap().len(), 1);
+        assert_eq!(client.token_expiry(), None);
+    }
+
+    #[tokio::test]
+    async fn test_sr25519_handshake() {
+        let exchange = std::sync::Arc::new(Exchange::default());
+        let client = FxdxBuilder::<request::Sr25519>::endpoint(String::from("https://fxdx"))
+            .sr25519(String::from("//Alice"))
+            .transport(exchange.clone())
+            .build()
+            .await
+            .unwrap();
+        client.fresh().await.unwrap();
+
+        let key = keys::from_suri("//Alice").unwrap().public;
+        let sent = exchange.sent.lock().unwrap();
+        // the handshake of `build`, then the one of `fresh`
+        assert_eq!(sent.len(), 4);
+        let token: serde_json::Value =
+            serde_json::from_str(sent[3].body.as_ref().unwrap()).unwrap();
+        assert_eq!(token["pubkey"], hex::encode(key.to_bytes()));
+        let signature = hex::decode(token["signature"].as_str().unwrap()).unwrap();
+        let signature = schnorrkel::Signature::from_bytes(&signature).unwrap();
+        assert!(key
+            .verify_simple(signing::SR25519_CONTEXT, b"n-1", &signature)
+            .is_ok());
+        assert_eq!(client.token_expiry(), Some(4102444800));
+    }
+
+    #[tokio::test]
+    async fn test_ed25519_handshake() {
+        use ed25519_dalek::Verifier;
+        let seed = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        let exchange = std::sync::Arc::new(Exchange::default());
+        let client = FxdxBuilder::<request::Ed25519>::endpoint(String::from("https://fxdx"))
+            .ed25519(String::from("0xabc"), seed.to_string())
+            .transport(exchange.clone())
+            .build()
+            .await
+            .unwrap();
+        client
+            .query_depth(request::Request::Depth {
+                symbol: String::from("BTC-USDT"),
+                limit: None,
+            })
+            .await
+            .unwrap();
+
+        let key = keys::ed25519_from_hex(seed).unwrap().verifying_key();
+        let sent = exchange.sent.lock().unwrap();
+        let token: serde_json::Value =
+            serde_json::from_str(sent[1].body.as_ref().unwrap()).unwrap();
+        assert_eq!(token["pubkey"], hex::encode(key.as_bytes()));
+        let signature = hex::decode(token["signature"].as_str().unwrap()).unwrap();
+        let signature = ed25519_dalek::Signature::from_slice(&signature).unwrap();
+        assert!(key.verify(b"n-1", &signature).is_ok());
+
+        let header = |name| {
+            sent[2]
+                .headers
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.clone())
+                .unwrap()
+        };
+        assert_eq!(header(encoding::TOKEN_HEADER), "t-1");
+        assert_eq!(client.token_expiry(), Some(4102444800));
+        let signature = hex::decode(header(encoding::SIGNATURE_HEADER)).unwrap();
+        let payload = signing::signature_payload(
+            &header(encoding::TIMESTAMP_HEADER),
+            "//api/depth/BTC-USDT",
+            Some("BTC-USDT"),
+        );
+        let signature = ed25519_dalek::Signature::from_slice(&signature).unwrap();
+        assert!(key.verify(payload.as_bytes(), &signature).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_nonces_of_token_requests() {
+        let exchange = std::sync::Arc::new(Exchange::default());
+        let seed = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        let client = FxdxBuilder::<request::Ed25519>::endpoint(String::from("https://fxdx"))
+            .ed25519(String::from("0xabc"), seed.to_string())
+            .nonce_store(nonce::MemoryNonceStore::default())
+            .transport(exchange.clone())
+            .build()
+            .await
+            .unwrap();
+        let depth = || {
+            client.query_depth(request::Request::Depth {
+                symbol: String::from("BTC-USDT"),
+                limit: None,
+            })
+        };
+        let (a, b, c) = tokio::join!(depth(), depth(), depth());
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+
+        let sent = exchange.sent.lock().unwrap();
+        let nonces = sent
+            .iter()
+            .map(|request| {
+                request
+                    .headers
+                    .iter()
+                    .find(|(name, _)| *name == encoding::NONCE_HEADER)
+                    .map(|(_, nonce)| nonce.parse::<u64>().unwrap())
+            })
+            .collect::<Vec<_>>();
+        // the handshake runs before there is a token
+        assert_eq!(nonces[..2], [None, None]);
+        let nonces = nonces[2..].iter().flatten().collect::<Vec<_>>();
+        assert_eq!(nonces.len(), 3);
+        assert!(nonces.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_token_reused_after_restart() {
+        let store = std::sync::Arc::new(tokenstore::MemoryTokenStore::default());
+        let exchange = std::sync::Arc::new(Exchange::default());
+        let seed = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        for _ in 0..2 {
+            let client = FxdxBuilder::<request::Ed25519>::endpoint(String::from("https://fxdx"))
+                .ed25519(String::from("0xabc"), seed.to_string())
+                .transport(exchange.clone())
+                .token_store(store.clone())
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(client.token_expiry(), Some(4102444800));
+        }
+        // the second client found the token of the first
+        assert_eq!(exchange.sent.lock().unwrap().len(), 2);
+    }
+
+    /// rejects the first token, as if it expired right after the handshake
+    #[derive(Default)]
+    struct Rollover {
+        tokens: std::sync::atomic::AtomicUsize,
+    }
+
+    impl transport::Transport for Rollover {
+        fn execute<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            request: encoding::SignedRequest,
+        ) -> transport::TransportFuture<'a> {
+            use std::sync::atomic::Ordering;
+            let token = request
+                .headers
+                .iter()
+                .find(|(name, _)| *name == encoding::TOKEN_HEADER)
+                .map(|(_, token)| token.clone());
+            let (status, body) = match request.uri.as_str() {
+                "/maker/nonce" => (200, String::from(r#"{"code":200,"data":"n"}"#)),
+                "//api/token" => {
+                    let n = self.tokens.fetch_add(1, Ordering::SeqCst) + 1;
+                    (200, format!(r#"{{"code":200,"data":"t-{}"}}"#, n))
+                }
+                _ if token.as_deref() == Some("t-1") => (401, String::new()),
+                _ => (
+                    200,
+                    String::from(r#"{"code":200,"data":{"asks":[],"bids":[]}}"#),
+                ),
+            };
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                Ok(transport::RawResponse {
+                    status,
+                    headers: vec![],
+                    body: body.into(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_renewed_once() {
+        let rollover = std::sync::Arc::new(Rollover::default());
+        let client = FxdxBuilder::<request::Ed25519>::endpoint(String::from("https://fxdx"))
+            .ed25519(
+                String::from("0xabc"),
+                String::from("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+            )
+            .transport(rollover.clone())
+            .build()
+            .await
+            .unwrap();
+        let depth = || {
+            client.query_depth(request::Request::Depth {
+                symbol: String::from("BTC-USDT"),
+                limit: None,
+            })
+        };
+        let (a, b, c) = tokio::join!(depth(), depth(), depth());
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        assert_eq!(rollover.tokens.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_decode_error_context() {
+        let builder = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"));
+        let client = builder.build().await.unwrap();
+        let body = format!(r#"{{"code":"oops","data":"{}"}}"#, "x".repeat(1000));
+        let err = client
+            .decode::<response::DepthResponse>(
+                "http://127.0.0.1:1",
+                "/depth/BTC-USDT",
+                reqwest::StatusCode::BAD_GATEWAY,
+                body.as_bytes(),
+            )
+            .unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::Decode {
+                status, uri, body, ..
+            }) => {
+                assert_eq!(*status, 502);
+                assert_eq!(uri, "/depth/BTC-USDT");
+                assert_eq!(body.len(), MAX_ERROR_BODY + 3);
+                assert!(body.starts_with(r#"{"code":"oops""#));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let client = FxdxBuilder::<request::PrivPub>::endpoint(String::from("http://127.0.0.1:1"))
+            .secret(String::from("secret"))
+            .redact_bodies(true)
+            .build()
+            .await
+            .unwrap();
+        let err = client
+            .decode::<response::DepthResponse>("", "", reqwest::StatusCode::OK, b"secret")
+            .unwrap_err();
+        assert!(err.to_string().contains("<6 bytes redacted>"));
+        assert!(!err.to_string().contains("secret"));
     }
 }
@@ -0,0 +1,308 @@
+use crate::depthsync::{DeltaOutcome, DepthDelta, DepthSync};
+use crate::eventbuffer::{event_buffer, BufferPolicy, EventReceiver, EventSender};
+use crate::orderbook::{BookValidator, OrderBook};
+use crate::request::{Prefix, Scale};
+use crate::response::{Kline, PublicTrade};
+use crate::websocket::{HeartbeatConfig, WsClient};
+use crate::FxdxClient;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tokio::sync::mpsc;
+
+/// a message of the market data stream, tagged by its channel
+#[derive(Debug, Deserialize)]
+#[serde(tag = "channel", rename_all = "lowercase")]
+enum StreamMessage {
+    Depth(DepthDelta),
+    Trade {
+        symbol: String,
+        #[serde(flatten)]
+        trade: PublicTrade,
+    },
+    Kline {
+        symbol: String,
+        #[serde(flatten)]
+        kline: Kline,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    /// the book after a snapshot or a delta, never a stale one
+    Book(OrderBook),
+    Trade {
+        symbol: String,
+        trade: PublicTrade,
+    },
+    Kline {
+        symbol: String,
+        kline: Kline,
+    },
+    /// an update of `symbol` was missed or the book failed its checksum, a `Book` follows once
+    /// the snapshot was refetched
+    Gap {
+        symbol: String,
+    },
+}
+
+impl MarketEvent {
+    pub fn symbol(&self) -> &str {
+        match self {
+            MarketEvent::Book(book) => &book.symbol,
+            MarketEvent::Trade { symbol, .. }
+            | MarketEvent::Kline { symbol, .. }
+            | MarketEvent::Gap { symbol } => symbol,
+        }
+    }
+}
+
+fn subscription(channel: &str, symbol: &str, scale: Option<Scale>) -> String {
+    match scale {
+        Some(scale) => format!(
+            r#"{{"op":"subscribe","channel":"{}","symbol":"{}","scale":"{}"}}"#,
+            channel, symbol, scale
+        ),
+        None => format!(
+            r#"{{"op":"subscribe","channel":"{}","symbol":"{}"}}"#,
+            channel, symbol
+        ),
+    }
+}
+
+/// depth, trades and klines of several symbols over one websocket, with the books kept in
+/// sync from REST snapshots and depth deltas
+///
+/// every subscription gets its own receiver, buffered by the `BufferPolicy` it was made with
+///
+/// ```ignore
+/// let mut session = MarketDataSession::new(&client, url, Default::default());
+/// let mut books = session.subscribe_with(&[String::from("BTC-USDT")], BufferPolicy::CoalesceDepth(64));
+/// tokio::select! {
+///     _ = session.run() => {}
+///     _ = async { while let Some(event) = books.recv().await { /* .. */ } } => {}
+/// }
+/// ```
+pub struct MarketDataSession<'a, P> {
+    client: &'a FxdxClient<P>,
+    ws: WsClient,
+    books: BTreeMap<String, DepthSync>,
+    validator: BookValidator,
+    subscribers: Vec<Subscriber>,
+}
+
+struct Subscriber {
+    symbols: Vec<String>,
+    klines: bool,
+    events: EventSender,
+}
+
+impl Subscriber {
+    fn wants(&self, event: &MarketEvent) -> bool {
+        matches!(event, MarketEvent::Kline { .. }) == self.klines
+            && self.symbols.iter().any(|s| s == event.symbol())
+    }
+}
+
+impl<'a, P> MarketDataSession<'a, P>
+where
+    P: Prefix,
+{
+    pub fn new(client: &'a FxdxClient<P>, url: String, heartbeat: HeartbeatConfig) -> Self {
+        MarketDataSession {
+            client,
+            ws: WsClient::new(url, heartbeat),
+            books: BTreeMap::new(),
+            validator: BookValidator::new(),
+            subscribers: vec![],
+        }
+    }
+
+    pub fn validator(mut self, validator: BookValidator) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// depth and trades of `symbols`, subscribed on every connect
+    pub fn subscribe(&mut self, symbols: &[String]) -> EventReceiver {
+        self.subscribe_with(symbols, BufferPolicy::default())
+    }
+
+    pub fn subscribe_with(&mut self, symbols: &[String], policy: BufferPolicy) -> EventReceiver {
+        for symbol in symbols {
+            self.ws.subscribe(subscription("depth", symbol, None));
+            self.ws.subscribe(subscription("trade", symbol, None));
+            self.books
+                .entry(symbol.clone())
+                .or_insert_with(|| DepthSync::new(symbol.clone()));
+        }
+        self.add_subscriber(symbols, false, policy)
+    }
+
+    pub fn subscribe_klines(
+        &mut self,
+        symbols: &[String],
+        scale: Scale,
+        policy: BufferPolicy,
+    ) -> EventReceiver {
+        for symbol in symbols {
+            self.ws
+                .subscribe(subscription("kline", symbol, Some(scale)));
+        }
+        self.add_subscriber(symbols, true, policy)
+    }
+
+    fn add_subscriber(
+        &mut self,
+        symbols: &[String],
+        klines: bool,
+        policy: BufferPolicy,
+    ) -> EventReceiver {
+        let (events, receiver) = event_buffer(policy);
+        self.subscribers.push(Subscriber {
+            symbols: symbols.to_vec(),
+            klines,
+            events,
+        });
+        receiver
+    }
+
+    pub fn book(&self, symbol: &str) -> Option<&OrderBook> {
+        self.books.get(symbol).map(DepthSync::book)
+    }
+
+    pub fn connection(&self) -> &WsClient {
+        &self.ws
+    }
+
+    /// stream events to the subscribers until all their receivers are dropped or the client
+    /// shuts down, which ends every receiver once it is drained
+    pub async fn run(&mut self) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let Self {
+            client,
+            ws,
+            books,
+            validator,
+            subscribers,
+        } = self;
+        let process = async {
+            loop {
+                for sync in books.values_mut().filter(|sync| sync.needs_snapshot()) {
+                    match client.resync_depth(sync, validator).await {
+                        Ok(_) => publish(subscribers, MarketEvent::Book(sync.book().clone())).await,
+                        Err(e) => {
+                            log::warn!("fxdx snapshot of {} failed: {}", sync.book().symbol, e)
+                        }
+                    }
+                }
+                if subscribers.is_empty() {
+                    return;
+                }
+                let Some(text) = rx.recv().await else {
+                    return;
+                };
+                for event in handle(books, &text) {
+                    publish(subscribers, event).await;
+                }
+            }
+        };
+        let mut shutdown = client.shutdown_signal();
+        tokio::select! {
+            _ = process => {}
+            _ = shutdown.wait() => {}
+            _ = ws.run(|text| {
+                let _ = tx.send(text.to_string());
+            }) => {}
+        }
+        subscribers.clear();
+    }
+}
+
+/// hand `event` to every subscriber of it, forgetting those whose receiver was dropped
+async fn publish(subscribers: &mut Vec<Subscriber>, event: MarketEvent) {
+    for subscriber in subscribers.iter().filter(|s| s.wants(&event)) {
+        subscriber.events.send(event.clone()).await;
+    }
+    subscribers.retain(|s| !s.events.is_closed());
+}
+
+/// apply one stream message, deltas of symbols which are not subscribed are ignored
+fn handle(books: &mut BTreeMap<String, DepthSync>, text: &str) -> Vec<MarketEvent> {
+    let message = match serde_json::from_str::<StreamMessage>(text) {
+        Ok(message) => message,
+        Err(e) => {
+            log::debug!("fxdx stream message skipped: {}", e);
+            return vec![];
+        }
+    };
+    match message {
+        StreamMessage::Depth(delta) => match books.get_mut(&delta.symbol) {
+            Some(sync) => {
+                let symbol = delta.symbol.clone();
+                match sync.on_delta(delta) {
+                    DeltaOutcome::Applied => vec![MarketEvent::Book(sync.book().clone())],
+                    DeltaOutcome::Gap { .. } => vec![MarketEvent::Gap { symbol }],
+                    DeltaOutcome::ChecksumMismatch { expected, actual } => {
+                        log::warn!(
+                            "fxdx book of {} failed its checksum, {:08x} instead of {:08x}",
+                            symbol,
+                            actual,
+                            expected
+                        );
+                        vec![MarketEvent::Gap { symbol }]
+                    }
+                    DeltaOutcome::Buffered | DeltaOutcome::Outdated => vec![],
+                }
+            }
+            None => vec![],
+        },
+        StreamMessage::Trade { symbol, trade } => vec![MarketEvent::Trade { symbol, trade }],
+        StreamMessage::Kline { symbol, kline } => vec![MarketEvent::Kline { symbol, kline }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::{Depth, PriceLevel};
+    use bigdecimal::BigDecimal;
+
+    #[test]
+    fn test_handle_stream_messages() {
+        let mut books = BTreeMap::new();
+        let mut sync = DepthSync::new(String::from("BTC-USDT"));
+        sync.on_snapshot(
+            10,
+            Depth {
+                depth: 0,
+                seq: Some(10),
+                checksum: None,
+                bids: vec![PriceLevel::new(BigDecimal::from(10), BigDecimal::from(1))],
+                asks: vec![PriceLevel::new(BigDecimal::from(11), BigDecimal::from(1))],
+            },
+            &BookValidator::new(),
+        )
+        .unwrap();
+        books.insert(String::from("BTC-USDT"), sync);
+
+        let events = handle(
+            &mut books,
+            r#"{"channel":"depth","symbol":"BTC-USDT","prev_seq":10,"seq":11,"bids":[["9","2"]]}"#,
+        );
+        assert!(matches!(&events[..], [MarketEvent::Book(book)] if book.bids.len() == 2));
+
+        let events = handle(
+            &mut books,
+            r#"{"channel":"trade","symbol":"BTC-USDT","id":1,"ask_or_bid":1,"price":"11","amount":"1","timestamp":0}"#,
+        );
+        assert_eq!(events[0].symbol(), "BTC-USDT");
+
+        let events = handle(
+            &mut books,
+            r#"{"channel":"depth","symbol":"BTC-USDT","prev_seq":12,"seq":13}"#,
+        );
+        assert!(matches!(&events[..], [MarketEvent::Gap { .. }]));
+        assert!(books["BTC-USDT"].needs_snapshot());
+        assert!(handle(&mut books, "not json").is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+//! strictly increasing request nonces of a token session, sent as `X-Nonce` once
+//! `FxdxBuilder::nonce_store` is set, so parallel orders of one token are never rejected as
+//! replays of each other
+//!
+//! the store keeps a high-water mark reserved ahead of the nonces handed out, a restart
+//! continues above it without writing every nonce; nonces are at least the unix milliseconds,
+//! so a lost store only repeats nonces for a clock set back
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// nonces reserved by every write to the store
+pub const RESERVE: u64 = 1000;
+
+/// where the high-water mark outlives the process
+pub trait NonceStore: Send + Sync {
+    fn load(&self) -> anyhow::Result<Option<u64>>;
+
+    fn save(&self, reserved: u64) -> anyhow::Result<()>;
+}
+
+/// the mark as text in a file, replaced whole so a crash never leaves half of it
+#[derive(Debug, Clone)]
+pub struct FileNonceStore {
+    path: PathBuf,
+}
+
+impl FileNonceStore {
+    pub fn new<T: Into<PathBuf>>(path: T) -> Self {
+        FileNonceStore { path: path.into() }
+    }
+}
+
+impl NonceStore for FileNonceStore {
+    fn load(&self) -> anyhow::Result<Option<u64>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(mark) => Ok(Some(mark.trim().parse()?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, reserved: u64) -> anyhow::Result<()> {
+        let partial = self.path.with_extension("partial");
+        std::fs::write(&partial, reserved.to_string())?;
+        std::fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+}
+
+/// keeps the mark for the life of the process only, e.g. to share it between clients of a key
+#[derive(Debug, Default)]
+pub struct MemoryNonceStore {
+    reserved: Mutex<Option<u64>>,
+}
+
+impl NonceStore for MemoryNonceStore {
+    fn load(&self) -> anyhow::Result<Option<u64>> {
+        Ok(*self.reserved.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn save(&self, reserved: u64) -> anyhow::Result<()> {
+        *self.reserved.lock().unwrap_or_else(|e| e.into_inner()) = Some(reserved);
+        Ok(())
+    }
+}
+
+impl<T: NonceStore + ?Sized> NonceStore for std::sync::Arc<T> {
+    fn load(&self) -> anyhow::Result<Option<u64>> {
+        (**self).load()
+    }
+
+    fn save(&self, reserved: u64) -> anyhow::Result<()> {
+        (**self).save(reserved)
+    }
+}
+
+#[derive(Debug)]
+struct Issued {
+    last: u64,
+    reserved: u64,
+}
+
+/// hands out nonces to any number of tasks, each above every one before it
+///
+/// a nonce is taken right before its request is sent, requests racing each other over separate
+/// connections may still arrive out of order
+pub struct NonceManager {
+    store: Box<dyn NonceStore>,
+    issued: Mutex<Issued>,
+}
+
+impl std::fmt::Debug for NonceManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NonceManager")
+            .field("issued", &self.issued)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NonceManager {
+    /// continue above the mark of `store`
+    pub fn load<S: NonceStore + 'static>(store: S) -> anyhow::Result<Self> {
+        let reserved = store.load()?.unwrap_or_default();
+        Ok(NonceManager {
+            store: Box::new(store),
+            issued: Mutex::new(Issued {
+                last: reserved,
+                reserved,
+            }),
+        })
+    }
+
+    /// the next nonce, fails only if a new mark could not be stored
+    pub fn next(&self) -> anyhow::Result<u64> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let mut issued = self.issued.lock().unwrap_or_else(|e| e.into_inner());
+        let nonce = (issued.last + 1).max(now);
+        if nonce > issued.reserved {
+            let reserved = nonce + RESERVE;
+            self.store.save(reserved)?;
+            issued.reserved = reserved;
+        }
+        issued.last = nonce;
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_increasing_across_threads_and_restarts() {
+        let store = Arc::new(MemoryNonceStore::default());
+        let nonces = Arc::new(NonceManager::load(store.clone()).unwrap());
+        let threads = (0..4)
+            .map(|_| {
+                let nonces = nonces.clone();
+                std::thread::spawn(move || {
+                    (0..500).map(|_| nonces.next().unwrap()).collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut all = vec![];
+        for thread in threads {
+            let seen = thread.join().unwrap();
+            // every task sees its own nonces rise
+            assert!(seen.windows(2).all(|w| w[0] < w[1]));
+            all.extend(seen);
+        }
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 2000);
+
+        let last = *all.last().unwrap();
+        let reserved = store.load().unwrap().unwrap();
+        assert!(reserved >= last);
+        // a mark far ahead of the clock, as after the clock was set back
+        store.save(reserved + 10 * RESERVE * 1000).unwrap();
+        let restarted = NonceManager::load(store.clone()).unwrap();
+        assert!(restarted.next().unwrap() > reserved + 10 * RESERVE * 1000);
+
+        let path = std::env::temp_dir().join(format!("fxdx-nonce-{}", std::process::id()));
+        let file = FileNonceStore::new(&path);
+        assert!(file.load().unwrap().is_none());
+        file.save(42).unwrap();
+        assert_eq!(file.load().unwrap(), Some(42));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
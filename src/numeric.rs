@@ -0,0 +1,145 @@
+//! explicit conversions between the `BigDecimal`s of the crate and the numbers of pricing
+//! libraries, so each loss of precision is a visible, checked step
+//!
+//! `to_parts` and `from_parts` speak the mantissa and scale of `rust_decimal::Decimal`, i.e.
+//! `Decimal::from_i128_with_scale(mantissa, scale)` and `(d.mantissa(), d.scale())`; with
+//! `--features rust_decimal` the `Decimal` alias is that type and `to_decimal` converts to it
+
+use crate::rounding::{self, RoundingMode};
+use crate::Error;
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, Signed, ToPrimitive};
+
+/// the largest scale of a `rust_decimal::Decimal`
+pub const MAX_DECIMAL_SCALE: u32 = 28;
+
+/// the largest mantissa of a `rust_decimal::Decimal`, 96 bits
+const MAX_DECIMAL_MANTISSA: i128 = (1 << 96) - 1;
+
+/// the number type to compute with next to the crate: `rust_decimal::Decimal` with
+/// `--features rust_decimal`, else `BigDecimal`
+///
+/// requests and responses keep `BigDecimal`, which holds any number the venue sends, convert
+/// at the boundary with `to_decimal` and `from_decimal`
+#[cfg(feature = "rust_decimal")]
+pub type Decimal = rust_decimal::Decimal;
+
+#[cfg(not(feature = "rust_decimal"))]
+pub type Decimal = BigDecimal;
+
+/// `value` as a `Decimal`; a `rust_decimal::Decimal` is rounded and fails like `to_parts`
+pub fn to_decimal(value: &BigDecimal) -> Result<Decimal, Error> {
+    #[cfg(feature = "rust_decimal")]
+    {
+        let (mantissa, scale) = to_parts(value)?;
+        rust_decimal::Decimal::try_from_i128_with_scale(mantissa, scale)
+            .map_err(|e| Error::Conversion(format!("{}: {}", value, e)))
+    }
+    #[cfg(not(feature = "rust_decimal"))]
+    Ok(value.clone())
+}
+
+/// a `Decimal` as the `BigDecimal` of requests, never loses precision
+pub fn from_decimal(value: &Decimal) -> BigDecimal {
+    #[cfg(feature = "rust_decimal")]
+    return from_parts(value.mantissa(), value.scale());
+    #[cfg(not(feature = "rust_decimal"))]
+    value.clone()
+}
+
+/// the nearest f64, fails when `value` is out of its range rather than returning infinity
+///
+/// an f64 holds 15 to 17 significant digits, use it for statistics, never for order amounts
+pub fn to_f64(value: &BigDecimal) -> Result<f64, Error> {
+    value
+        .to_f64()
+        .filter(|f| f.is_finite())
+        .ok_or_else(|| Error::Conversion(format!("{} is out of the range of f64", value)))
+}
+
+/// the shortest decimal which reads back as `value`, so `0.1` becomes `0.1` and not the
+/// `0.1000000000000000055511151231257827` the binary value is; NaN and infinities fail
+pub fn from_f64(value: f64) -> Result<BigDecimal, Error> {
+    if !value.is_finite() {
+        return Err(Error::Conversion(format!("{} is not a number", value)));
+    }
+    // `Display` of f64 is the shortest round trip representation
+    format!("{}", value)
+        .parse()
+        .map_err(|e| Error::Conversion(format!("{}: {}", value, e)))
+}
+
+/// `from_f64` rounded to `scale` digits, e.g. the `price_scale` of a symbol
+pub fn from_f64_scaled(value: f64, scale: i64, mode: RoundingMode) -> Result<BigDecimal, Error> {
+    Ok(rounding::round_to_scale(&from_f64(value)?, scale, mode))
+}
+
+/// the mantissa and scale of `value` as a `rust_decimal::Decimal` takes them, rounded half up
+/// to at most `MAX_DECIMAL_SCALE` digits; fails when the mantissa needs more than 96 bits
+pub fn to_parts(value: &BigDecimal) -> Result<(i128, u32), Error> {
+    let value = value.normalized();
+    let (_, scale) = value.as_bigint_and_exponent();
+    let value = match scale {
+        scale if scale > MAX_DECIMAL_SCALE as i64 => {
+            rounding::round_to_scale(&value, MAX_DECIMAL_SCALE as i64, RoundingMode::HalfUp)
+        }
+        // a negative scale is a mantissa with trailing zeros
+        scale if scale < 0 => value.with_scale(0),
+        _ => value,
+    };
+    let (mantissa, scale) = value.as_bigint_and_exponent();
+    mantissa
+        .to_i128()
+        .filter(|m| m.abs() <= MAX_DECIMAL_MANTISSA)
+        .map(|m| (m, scale as u32))
+        .ok_or_else(|| Error::Conversion(format!("{} does not fit 96 bits", value)))
+}
+
+/// the value of a `rust_decimal::Decimal` from its mantissa and scale, never loses precision
+pub fn from_parts(mantissa: i128, scale: u32) -> BigDecimal {
+    BigDecimal::new(BigInt::from(mantissa), scale as i64)
+}
+
+/// whether `to_parts` keeps every digit of `value`
+pub fn fits_decimal(value: &BigDecimal) -> bool {
+    to_parts(value).is_ok_and(|(mantissa, scale)| &from_parts(mantissa, scale) == value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(from_f64(0.1).unwrap(), "0.1".parse().unwrap());
+        assert_eq!(from_f64(-1e-7).unwrap(), "-0.0000001".parse().unwrap());
+        assert!(from_f64(f64::NAN).is_err());
+        assert_eq!(
+            from_f64_scaled(2.675, 2, RoundingMode::HalfUp).unwrap(),
+            "2.68".parse().unwrap()
+        );
+        assert_eq!(to_f64(&"30000.5".parse().unwrap()).unwrap(), 30000.5);
+        assert!(to_f64(&"1e400".parse().unwrap()).is_err());
+
+        let price: BigDecimal = "30000.12500".parse().unwrap();
+        assert_eq!(to_parts(&price).unwrap(), (30000125, 3));
+        assert_eq!(from_parts(30000125, 3), price);
+        assert_eq!(to_parts(&"1e3".parse().unwrap()).unwrap(), (1000, 0));
+        let long: BigDecimal = "0.12345678901234567890123456789".parse().unwrap();
+        assert!(!fits_decimal(&long));
+        assert_eq!(to_parts(&long).unwrap().1, MAX_DECIMAL_SCALE);
+        assert!(to_parts(&"1e40".parse().unwrap()).is_err());
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn test_rust_decimal() {
+        let price: BigDecimal = "30000.12500".parse().unwrap();
+        let decimal: Decimal = to_decimal(&price).unwrap();
+        assert_eq!(decimal, rust_decimal::Decimal::new(3000012500, 5));
+        assert_eq!(from_decimal(&decimal), price);
+        let negative = rust_decimal::Decimal::new(-1, 28);
+        assert_eq!(to_decimal(&from_decimal(&negative)).unwrap(), negative);
+        assert!(to_decimal(&"1e40".parse().unwrap()).is_err());
+    }
+}
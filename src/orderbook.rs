@@ -0,0 +1,371 @@
+use crate::response::{Depth, PriceLevel};
+use crate::sequence::SequenceGuard;
+use bigdecimal::{BigDecimal, Signed, Zero};
+
+pub use crate::request::Side;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookViolation {
+    /// bids have to be strictly descending, asks strictly ascending
+    Unordered {
+        side: Side,
+        index: usize,
+    },
+    NegativeSize {
+        side: Side,
+        index: usize,
+    },
+    Crossed {
+        best_bid: BigDecimal,
+        best_ask: BigDecimal,
+    },
+    /// the levels do not hash to the checksum the gateway sent with them
+    Checksum {
+        expected: u32,
+        actual: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookEvent {
+    Violation {
+        symbol: String,
+        violations: Vec<BookViolation>,
+    },
+    Refreshed {
+        symbol: String,
+    },
+}
+
+/// levels per side which go into `checksum`
+pub const CHECKSUM_LEVELS: usize = 25;
+
+/// CRC-32 of the top `CHECKSUM_LEVELS` of each side, written as `price:amount` pairs
+/// alternating between bids and asks and joined by `:`, e.g. `99:3:101:1:98:1`
+pub fn checksum(bids: &[PriceLevel], asks: &[PriceLevel]) -> u32 {
+    let mut text = String::new();
+    for i in 0..CHECKSUM_LEVELS {
+        for level in [bids.get(i), asks.get(i)].into_iter().flatten() {
+            if !text.is_empty() {
+                text.push(':');
+            }
+            text.push_str(&format!("{}:{}", level.price, level.amount));
+        }
+    }
+    crc32(text.as_bytes())
+}
+
+/// the checksum violation if `actual` is not the `expected` one sent by the gateway, which
+/// may send it as a signed 32 bit number
+pub fn verify_checksum(expected: i64, actual: u32) -> Result<(), BookViolation> {
+    match expected as u32 {
+        expected if expected == actual => Ok(()),
+        expected => Err(BookViolation::Checksum { expected, actual }),
+    }
+}
+
+/// CRC-32 with the IEEE polynomial, as zlib computes it
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// check a decoded depth: ordered sides, non-negative sizes, no crossed book and the checksum
+/// if the gateway sent one
+pub fn validate(depth: &Depth) -> Vec<BookViolation> {
+    let mut violations = vec![];
+    validate_side(Side::Bid, &depth.bids, &mut violations);
+    validate_side(Side::Ask, &depth.asks, &mut violations);
+    if let (Some(bid), Some(ask)) = (depth.best_bid(), depth.best_ask()) {
+        if bid.price >= ask.price {
+            violations.push(BookViolation::Crossed {
+                best_bid: bid.price.clone(),
+                best_ask: ask.price.clone(),
+            });
+        }
+    }
+    if let Some(expected) = depth.checksum {
+        if let Err(violation) = verify_checksum(expected, checksum(&depth.bids, &depth.asks)) {
+            violations.push(violation);
+        }
+    }
+    violations
+}
+
+fn validate_side(side: Side, levels: &[PriceLevel], violations: &mut Vec<BookViolation>) {
+    let mut last: Option<&BigDecimal> = None;
+    for (index, level) in levels.iter().enumerate() {
+        if level.amount.is_negative() {
+            violations.push(BookViolation::NegativeSize { side, index });
+        }
+        if let Some(prev) = last {
+            let ordered = match side {
+                Side::Bid => &level.price < prev,
+                Side::Ask => &level.price > prev,
+            };
+            if !ordered {
+                violations.push(BookViolation::Unordered { side, index });
+            }
+        }
+        last = Some(&level.price);
+    }
+}
+
+pub type BookEventHandler = Box<dyn Fn(&BookEvent) + Send + Sync>;
+
+/// runs `validate` on every book update, reports violations to the handler
+/// and tells the owner whether a fresh snapshot should be fetched
+#[derive(Default)]
+pub struct BookValidator {
+    refresh_on_violation: bool,
+    handler: Option<BookEventHandler>,
+}
+
+impl BookValidator {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn refresh_on_violation(mut self, refresh: bool) -> Self {
+        self.refresh_on_violation = refresh;
+        self
+    }
+
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&BookEvent) + Send + Sync + 'static,
+    {
+        self.handler = Some(Box::new(handler));
+        self
+    }
+
+    pub fn should_refresh(&self) -> bool {
+        self.refresh_on_violation
+    }
+
+    pub fn emit(&self, event: BookEvent) {
+        if let Some(handler) = &self.handler {
+            handler(&event);
+        }
+    }
+
+    pub fn check(&self, symbol: &str, depth: &Depth) -> Result<(), Vec<BookViolation>> {
+        let violations = validate(depth);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        self.emit(BookEvent::Violation {
+            symbol: symbol.to_string(),
+            violations: violations.clone(),
+        });
+        Err(violations)
+    }
+}
+
+/// locally maintained book of one symbol, only ever holds data which passed validation
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pub symbol: String,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+    /// set when the last update was rejected, strategies should not act on a stale book
+    pub stale: bool,
+    guard: SequenceGuard,
+}
+
+impl OrderBook {
+    pub fn new(symbol: String) -> Self {
+        OrderBook {
+            symbol,
+            bids: vec![],
+            asks: vec![],
+            stale: true,
+            guard: SequenceGuard::default(),
+        }
+    }
+
+    /// sequence of the request which produced the current levels
+    pub fn seq(&self) -> Option<u64> {
+        self.guard.last()
+    }
+
+    /// replace the book with the snapshot read by request `seq`, a corrupt snapshot keeps
+    /// the previous levels and marks the book stale, an outdated one is ignored with `Ok(false)`
+    pub fn apply_snapshot(
+        &mut self,
+        seq: u64,
+        depth: Depth,
+        validator: &BookValidator,
+    ) -> Result<bool, Vec<BookViolation>> {
+        if self.seq().is_some_and(|last| seq <= last) {
+            return Ok(false);
+        }
+        if let Err(violations) = validator.check(&self.symbol, &depth) {
+            self.stale = true;
+            return Err(violations);
+        }
+        self.guard.accept(seq);
+        self.bids = depth.bids;
+        self.asks = depth.asks;
+        self.stale = false;
+        Ok(true)
+    }
+
+    /// replace the levels without any check, for `depthsync` which validates on its own
+    pub(crate) fn set_levels(&mut self, bids: Vec<PriceLevel>, asks: Vec<PriceLevel>) {
+        self.bids = bids;
+        self.asks = asks;
+    }
+
+    /// merge changed levels into the book, a zero amount removes the level
+    pub fn apply_changes(&mut self, bids: &[PriceLevel], asks: &[PriceLevel]) {
+        for level in bids {
+            merge_level(&mut self.bids, level, |a, b| b.cmp(a));
+        }
+        for level in asks {
+            merge_level(&mut self.asks, level, |a, b| a.cmp(b));
+        }
+    }
+
+    /// the `checksum` of the current levels, to compare with the one of the gateway
+    pub fn checksum(&self) -> u32 {
+        checksum(&self.bids, &self.asks)
+    }
+
+    pub fn best_bid(&self) -> Option<&BigDecimal> {
+        self.bids.first().map(|l| &l.price)
+    }
+
+    pub fn best_ask(&self) -> Option<&BigDecimal> {
+        self.asks.first().map(|l| &l.price)
+    }
+}
+
+/// `order` sorts prices the way the side is kept, descending bids and ascending asks
+fn merge_level<F>(levels: &mut Vec<PriceLevel>, level: &PriceLevel, order: F)
+where
+    F: Fn(&BigDecimal, &BigDecimal) -> std::cmp::Ordering,
+{
+    match levels.binary_search_by(|l| order(&l.price, &level.price)) {
+        Ok(index) if level.amount.is_zero() => {
+            levels.remove(index);
+        }
+        Ok(index) => levels[index].amount = level.amount.clone(),
+        Err(_) if level.amount.is_zero() => {}
+        Err(index) => levels.insert(index, level.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn depth(bids: &[(i64, i64)], asks: &[(i64, i64)]) -> Depth {
+        let levels = |l: &[(i64, i64)]| {
+            l.iter()
+                .map(|(p, a)| PriceLevel::new(BigDecimal::from(*p), BigDecimal::from(*a)))
+                .collect()
+        };
+        Depth {
+            depth: 0,
+            seq: None,
+            checksum: None,
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&depth(&[(10, 1), (9, 1)], &[(11, 1), (12, 1)])).is_empty());
+        assert_eq!(
+            validate(&depth(&[(10, 1), (10, 1)], &[(12, 1), (11, -1)])),
+            vec![
+                BookViolation::Unordered {
+                    side: Side::Bid,
+                    index: 1
+                },
+                BookViolation::NegativeSize {
+                    side: Side::Ask,
+                    index: 1
+                },
+                BookViolation::Unordered {
+                    side: Side::Ask,
+                    index: 1
+                },
+            ]
+        );
+        assert_eq!(
+            validate(&depth(&[(11, 1)], &[(11, 1)])),
+            vec![BookViolation::Crossed {
+                best_bid: BigDecimal::from(11),
+                best_ask: BigDecimal::from(11),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rejected_snapshot_keeps_book() {
+        let events = Arc::new(AtomicUsize::new(0));
+        let counter = events.clone();
+        let validator = BookValidator::new().on_event(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut book = OrderBook::new(String::from("BTC-USDT"));
+        book.apply_snapshot(1, depth(&[(10, 1)], &[(11, 1)]), &validator)
+            .unwrap();
+        assert!(!book.stale);
+        assert!(book
+            .apply_snapshot(2, depth(&[(12, 1)], &[(11, 1)]), &validator)
+            .is_err());
+        assert!(book.stale);
+        assert_eq!(book.best_bid(), Some(&BigDecimal::from(10)));
+        assert_eq!(events.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_outdated_snapshot_ignored() {
+        let validator = BookValidator::new();
+        let mut book = OrderBook::new(String::from("BTC-USDT"));
+        assert_eq!(
+            book.apply_snapshot(2, depth(&[(10, 1)], &[(11, 1)]), &validator),
+            Ok(true)
+        );
+        assert_eq!(
+            book.apply_snapshot(1, depth(&[(9, 1)], &[(11, 1)]), &validator),
+            Ok(false)
+        );
+        assert_eq!(book.best_bid(), Some(&BigDecimal::from(10)));
+        assert_eq!(book.seq(), Some(2));
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        let mut snapshot = depth(&[(10, 1), (9, 2)], &[(11, 1)]);
+        let sum = checksum(&snapshot.bids, &snapshot.asks);
+        assert_eq!(sum, crc32(b"10:1:11:1:9:2"));
+        // a gateway sending it as a signed 32 bit number
+        snapshot.checksum = Some(i64::from(sum as i32));
+        assert!(validate(&snapshot).is_empty());
+
+        snapshot.checksum = Some(i64::from(sum) + 1);
+        let mut book = OrderBook::new(String::from("BTC-USDT"));
+        assert_eq!(
+            book.apply_snapshot(1, snapshot, &BookValidator::new()),
+            Err(vec![BookViolation::Checksum {
+                expected: sum + 1,
+                actual: sum
+            }])
+        );
+        assert!(book.stale);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::request::NewOrder;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// an order which could not be sent because the endpoint was unreachable
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedOrder {
+    pub order: NewOrder,
+    pub queued_at: Instant,
+    /// the order is dropped instead of sent once this has passed
+    pub expires_at: Instant,
+}
+
+impl QueuedOrder {
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[derive(Debug)]
+pub enum Submission {
+    /// the id of the placed order
+    Sent(String),
+    /// the endpoint was unreachable, the order goes out with the next flush before `expires_at`
+    Queued { expires_at: Instant },
+}
+
+/// what became of the queued orders on a flush
+#[derive(Debug, Default)]
+pub struct OutboxReport {
+    /// order and order id
+    pub submitted: Vec<(NewOrder, String)>,
+    pub expired: Vec<QueuedOrder>,
+    /// dropped when rejected or failing other than on an unreachable endpoint
+    pub failed: Vec<(NewOrder, String)>,
+    /// still queued because the endpoint was unreachable again
+    pub pending: usize,
+}
+
+impl OutboxReport {
+    pub fn is_empty(&self) -> bool {
+        self.submitted.is_empty() && self.expired.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// orders waiting for connectivity, sent in the order they were created
+#[derive(Debug)]
+pub struct Outbox {
+    ttl: Duration,
+    queue: VecDeque<QueuedOrder>,
+}
+
+impl Outbox {
+    /// every queued order expires `ttl` after it was queued
+    pub fn new(ttl: Duration) -> Self {
+        Outbox {
+            ttl,
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, order: NewOrder, now: Instant) -> Instant {
+        let expires_at = now + self.ttl;
+        self.queue.push_back(QueuedOrder {
+            order,
+            queued_at: now,
+            expires_at,
+        });
+        expires_at
+    }
+
+    /// put an order taken with `pop` back in front of the queue
+    pub fn requeue(&mut self, queued: QueuedOrder) {
+        self.queue.push_front(queued);
+    }
+
+    /// the oldest order which has not expired, the expired ones in front of it are returned too
+    pub fn pop(&mut self, now: Instant) -> (Option<QueuedOrder>, Vec<QueuedOrder>) {
+        let mut expired = vec![];
+        while let Some(queued) = self.queue.pop_front() {
+            if queued.is_expired(now) {
+                expired.push(queued);
+            } else {
+                return (Some(queued), expired);
+            }
+        }
+        (None, expired)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &QueuedOrder> {
+        self.queue.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{OrderKind, Side};
+    use bigdecimal::BigDecimal;
+
+    fn order(amount: i64) -> NewOrder {
+        NewOrder::new(
+            Side::Bid,
+            OrderKind::Limit,
+            String::from("BTC-USDT"),
+            Some(BigDecimal::from(100)),
+            BigDecimal::from(amount),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_outbox_expiry() {
+        let start = Instant::now();
+        let mut outbox = Outbox::new(Duration::from_secs(10));
+        outbox.push(order(1), start);
+        outbox.push(order(2), start + Duration::from_secs(5));
+        outbox.push(order(3), start + Duration::from_secs(6));
+
+        let (next, expired) = outbox.pop(start + Duration::from_secs(12));
+        assert_eq!(next.unwrap().order, order(2));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].order, order(1));
+
+        let (next, expired) = outbox.pop(start + Duration::from_secs(12));
+        let next = next.unwrap();
+        assert!(expired.is_empty());
+        outbox.requeue(next);
+        assert_eq!(outbox.orders().next().unwrap().order, order(3));
+
+        let (next, expired) = outbox.pop(start + Duration::from_secs(20));
+        assert!(next.is_none());
+        assert_eq!(expired.len(), 1);
+        assert!(outbox.is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::request::{NewOrder, OrderFilter, OrderKind, OrderStatus, Page, Side};
+use crate::response::{Depth, QueryOrder, Trade};
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::BTreeMap;
+
+/// in-process matching of paper orders against live depth snapshots
+///
+/// a new order takes whatever the snapshot offers at its limit, the rest of a
+/// `Limit` or `PostOnly` order rests and fills once a later snapshot crosses it;
+/// the snapshot itself is never consumed, fees are not simulated
+#[derive(Debug, Default)]
+pub struct PaperExchange {
+    next_id: u64,
+    orders: BTreeMap<String, QueryOrder>,
+}
+
+impl PaperExchange {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// simulate `order` against `depth`, the returned id can be queried and cancelled
+    pub fn place(
+        &mut self,
+        order: &NewOrder,
+        depth: &Depth,
+        timestamp: i64,
+    ) -> anyhow::Result<String> {
+        order.validate()?;
+        let side = order.side().ok_or_else(|| {
+            crate::Error::InvalidRequest(format!("unknown order type {}", order.r#type))
+        })?;
+        self.next_id += 1;
+        let order_id = format!("paper-{}", self.next_id);
+        let mut sim = QueryOrder {
+            symbol: order.symbol.clone(),
+            order_id: order_id.clone(),
+            order_type: side,
+            direction: side,
+            amount: order.amount.clone(),
+            price: order.price.clone().unwrap_or_default(),
+            filled_base: BigDecimal::zero(),
+            filled_quote: BigDecimal::zero(),
+            avg_price: BigDecimal::zero(),
+            status: OrderStatus::Undeal,
+            trades: vec![],
+            created_at: Some(timestamp),
+            updated_at: Some(timestamp),
+        };
+        let fills = crossing(&sim, order.price.as_ref(), depth);
+        let fillable = fills
+            .iter()
+            .fold(BigDecimal::zero(), |acc, (_, amount)| acc + amount);
+        let rejected = match order.kind {
+            OrderKind::PostOnly => !fills.is_empty(),
+            OrderKind::FOK => fillable < order.amount,
+            _ => false,
+        };
+        if !rejected {
+            fills
+                .into_iter()
+                .for_each(|(price, amount)| fill(&mut sim, price, amount, timestamp));
+        }
+        if rejected
+            || (sim.status != OrderStatus::Dealed
+                && matches!(
+                    order.kind,
+                    OrderKind::Market | OrderKind::IOC | OrderKind::FOK
+                ))
+        {
+            sim.status = OrderStatus::Cancel;
+        }
+        self.orders.insert(order_id.clone(), sim);
+        Ok(order_id)
+    }
+
+    /// fill resting orders of `symbol` which the new snapshot crosses
+    pub fn on_depth(&mut self, symbol: &str, depth: &Depth, timestamp: i64) {
+        for sim in self.orders.values_mut().filter(|o| {
+            o.symbol == symbol
+                && matches!(o.status, OrderStatus::Undeal | OrderStatus::PartialDealed)
+        }) {
+            let limit = sim.price.clone();
+            for (price, amount) in crossing(sim, Some(&limit), depth) {
+                fill(sim, price, amount, timestamp);
+            }
+        }
+    }
+
+    /// false if the order is unknown or already terminal
+    pub fn cancel(&mut self, symbol: &str, order_id: &str) -> bool {
+        match self.orders.get_mut(order_id) {
+            Some(sim)
+                if sim.symbol == symbol
+                    && matches!(sim.status, OrderStatus::Undeal | OrderStatus::PartialDealed) =>
+            {
+                sim.status = OrderStatus::Cancel;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn order(&self, symbol: &str, order_id: &str) -> Option<QueryOrder> {
+        self.orders
+            .get(order_id)
+            .filter(|o| o.symbol == symbol)
+            .cloned()
+    }
+
+    /// orders of `symbol` in placement order, paged like the exchange does
+    pub fn orders(
+        &self,
+        symbol: &str,
+        page: Page,
+        pending: bool,
+        filter: &OrderFilter,
+    ) -> Vec<QueryOrder> {
+        let mut orders = self
+            .orders
+            .values()
+            .filter(|o| o.symbol == symbol)
+            .filter(|o| {
+                !pending || matches!(o.status, OrderStatus::Undeal | OrderStatus::PartialDealed)
+            })
+            .filter(|o| filter.matches(o))
+            .cloned()
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|o| {
+            o.order_id
+                .trim_start_matches("paper-")
+                .parse::<u64>()
+                .unwrap_or_default()
+        });
+        orders
+            .into_iter()
+            .skip(page.offset())
+            .take(page.size() as usize)
+            .collect()
+    }
+}
+
+/// the levels of `depth` the order can take at `limit`, `None` takes any price
+pub(crate) fn crossing(
+    sim: &QueryOrder,
+    limit: Option<&BigDecimal>,
+    depth: &Depth,
+) -> Vec<(BigDecimal, BigDecimal)> {
+    let mut remaining = &sim.amount - &sim.filled_base;
+    let levels = match sim.direction {
+        Side::Bid => &depth.asks,
+        Side::Ask => &depth.bids,
+    };
+    let mut fills = vec![];
+    for level in levels {
+        let crosses = match (limit, sim.direction) {
+            (None, _) => true,
+            (Some(limit), Side::Bid) => &level.price <= limit,
+            (Some(limit), Side::Ask) => &level.price >= limit,
+        };
+        if !crosses || remaining <= BigDecimal::zero() {
+            break;
+        }
+        let amount = remaining.clone().min(level.amount.clone());
+        remaining -= &amount;
+        fills.push((level.price.clone(), amount));
+    }
+    fills
+}
+
+fn fill(sim: &mut QueryOrder, price: BigDecimal, amount: BigDecimal, timestamp: i64) {
+    let quote = &price * &amount;
+    sim.filled_base += &amount;
+    sim.filled_quote += &quote;
+    sim.avg_price = &sim.filled_quote / &sim.filled_base;
+    sim.status = if sim.filled_base >= sim.amount {
+        OrderStatus::Dealed
+    } else {
+        OrderStatus::PartialDealed
+    };
+    sim.trades.push(Trade {
+        base: 0,
+        quote: 0,
+        ask_or_bid: sim.direction,
+        price,
+        amount,
+        quote_amount: quote,
+        quote_fee: BigDecimal::zero(),
+        base_fee: BigDecimal::zero(),
+        timestamp,
+    });
+    sim.updated_at = Some(timestamp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::PriceLevel;
+
+    fn depth() -> Depth {
+        Depth {
+            depth: 0,
+            seq: None,
+            checksum: None,
+            bids: vec![PriceLevel::new(BigDecimal::from(99), BigDecimal::from(1))],
+            asks: vec![
+                PriceLevel::new(BigDecimal::from(101), BigDecimal::from(1)),
+                PriceLevel::new(BigDecimal::from(102), BigDecimal::from(1)),
+            ],
+        }
+    }
+
+    fn order(kind: OrderKind, price: Option<i64>, amount: i64) -> NewOrder {
+        NewOrder::new(
+            Side::Bid,
+            kind,
+            String::from("BTC-USDT"),
+            price.map(BigDecimal::from),
+            BigDecimal::from(amount),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_paper_fills() {
+        let mut paper = PaperExchange::new();
+        let market = paper
+            .place(&order(OrderKind::Market, None, 2), &depth(), 0)
+            .unwrap();
+        let market = paper.order("BTC-USDT", &market).unwrap();
+        assert_eq!(market.status, OrderStatus::Dealed);
+        assert_eq!(market.filled_quote, BigDecimal::from(203));
+
+        let fok = paper
+            .place(&order(OrderKind::FOK, Some(101), 2), &depth(), 0)
+            .unwrap();
+        let fok = paper.order("BTC-USDT", &fok).unwrap();
+        assert_eq!(fok.status, OrderStatus::Cancel);
+        assert!(fok.trades.is_empty());
+
+        let post = paper
+            .place(&order(OrderKind::PostOnly, Some(101), 1), &depth(), 0)
+            .unwrap();
+        assert_eq!(
+            paper.order("BTC-USDT", &post).unwrap().status,
+            OrderStatus::Cancel
+        );
+
+        let limit = paper
+            .place(&order(OrderKind::Limit, Some(101), 2), &depth(), 0)
+            .unwrap();
+        assert_eq!(
+            paper.order("BTC-USDT", &limit).unwrap().status,
+            OrderStatus::PartialDealed
+        );
+        assert_eq!(
+            paper
+                .orders(
+                    "BTC-USDT",
+                    Page::first(10).unwrap(),
+                    true,
+                    &OrderFilter::default()
+                )
+                .len(),
+            1
+        );
+        let cancelled = OrderFilter {
+            status: Some(OrderStatus::Cancel),
+            ..Default::default()
+        };
+        assert_eq!(
+            paper
+                .orders("BTC-USDT", Page::first(10).unwrap(), false, &cancelled)
+                .len(),
+            2
+        );
+        paper.on_depth("BTC-USDT", &depth(), 1);
+        assert_eq!(
+            paper.order("BTC-USDT", &limit).unwrap().status,
+            OrderStatus::Dealed
+        );
+        assert!(!paper.cancel("BTC-USDT", &limit));
+    }
+}
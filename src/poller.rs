@@ -0,0 +1,395 @@
+//! a pseudo-stream over REST for deployments without websockets: depth, balances and open
+//! orders are polled on their own intervals and only what changed is handed on
+//!
+//! ```ignore
+//! let mut poller = Poller::new()
+//!     .depth("BTC-USDT", Duration::from_secs(1))
+//!     .open_orders("BTC-USDT", Duration::from_secs(5))
+//!     .balances(Duration::from_secs(30));
+//! poller.run(&client, |event| println!("{:?}", event)).await;
+//! ```
+
+use crate::request::{Prefix, Request};
+use crate::response::{Balance, Depth, DepthDiff, QueryOrder};
+use crate::FxdxClient;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/// the default share of an interval a poll is moved by at random, see `Poller::jitter`
+pub const DEFAULT_JITTER: f64 = 0.1;
+
+/// the default share of the effective rate of the client polls may use, see `Poller::budget`
+pub const DEFAULT_BUDGET: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PollTarget {
+    Depth(String),
+    Balances,
+    OpenOrders(String),
+}
+
+impl std::fmt::Display for PollTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PollTarget::Depth(symbol) => write!(f, "depth of {}", symbol),
+            PollTarget::Balances => write!(f, "balances"),
+            PollTarget::OpenOrders(symbol) => write!(f, "open orders of {}", symbol),
+        }
+    }
+}
+
+/// the open orders of a symbol which changed between two polls
+#[derive(Debug, Clone, Default)]
+pub struct OrderChanges {
+    /// orders not open at the previous poll
+    pub opened: Vec<QueryOrder>,
+    /// orders open at both polls with a new status, fill or update time
+    pub changed: Vec<QueryOrder>,
+    /// ids of orders no longer open, filled or cancelled
+    pub closed: Vec<String>,
+}
+
+impl OrderChanges {
+    fn between(before: &[QueryOrder], after: &[QueryOrder]) -> Self {
+        let old = before
+            .iter()
+            .map(|order| (order.order_id.as_str(), order))
+            .collect::<HashMap<_, _>>();
+        let mut changes = OrderChanges::default();
+        for order in after {
+            match old.get(order.order_id.as_str()) {
+                None => changes.opened.push(order.clone()),
+                Some(prev)
+                    if prev.status != order.status
+                        || prev.filled_base != order.filled_base
+                        || prev.updated_at != order.updated_at =>
+                {
+                    changes.changed.push(order.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        changes.closed = before
+            .iter()
+            .filter(|order| !after.iter().any(|o| o.order_id == order.order_id))
+            .map(|order| order.order_id.clone())
+            .collect();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.opened.is_empty() && self.changed.is_empty() && self.closed.is_empty()
+    }
+}
+
+/// what a poll found, a poll returning the same as the previous one produces no event
+#[derive(Debug, Clone)]
+pub enum PollEvent {
+    /// the first poll of a symbol diffs against an empty book, so every level is added
+    Depth {
+        symbol: String,
+        diff: DepthDiff,
+    },
+    Balance(Balance),
+    Orders {
+        symbol: String,
+        changes: OrderChanges,
+    },
+    /// the poll failed, it is tried again on its next interval
+    Failed {
+        target: PollTarget,
+        error: String,
+    },
+}
+
+#[derive(Debug)]
+struct Schedule {
+    target: PollTarget,
+    interval: Duration,
+    due: Instant,
+}
+
+/// polls depth, balances and open orders at their own intervals and delivers the changes
+///
+/// every poll lands `jitter` of its interval early or late at random, so polls of equal
+/// intervals spread out instead of bursting together, and consecutive polls keep the gap
+/// which holds them below `budget` of the effective rate of the client
+#[derive(Debug)]
+pub struct Poller {
+    schedules: Vec<Schedule>,
+    jitter: f64,
+    budget: f64,
+    rng: u64,
+    last_sent: Option<Instant>,
+    depths: HashMap<String, Depth>,
+    balance: Option<Balance>,
+    orders: HashMap<String, Vec<QueryOrder>>,
+}
+
+impl Default for Poller {
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Poller {
+            schedules: vec![],
+            jitter: DEFAULT_JITTER,
+            budget: DEFAULT_BUDGET,
+            // xorshift never leaves zero
+            rng: seed | 1,
+            last_sent: None,
+            depths: HashMap::new(),
+            balance: None,
+            orders: HashMap::new(),
+        }
+    }
+}
+
+impl Poller {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn depth(self, symbol: &str, every: Duration) -> Self {
+        self.poll(PollTarget::Depth(symbol.to_string()), every)
+    }
+
+    pub fn balances(self, every: Duration) -> Self {
+        self.poll(PollTarget::Balances, every)
+    }
+
+    pub fn open_orders(self, symbol: &str, every: Duration) -> Self {
+        self.poll(PollTarget::OpenOrders(symbol.to_string()), every)
+    }
+
+    /// poll `target` every `every`, replacing an earlier interval of it
+    pub fn poll(mut self, target: PollTarget, every: Duration) -> Self {
+        self.schedules.retain(|s| s.target != target);
+        self.schedules.push(Schedule {
+            target,
+            interval: every.max(Duration::from_millis(1)),
+            due: Instant::now(),
+        });
+        self
+    }
+
+    /// the share of an interval, from 0 to 0.5, a poll is moved by at random
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 0.5);
+        self
+    }
+
+    /// the share of the effective rate of the client, from 0 to 1, polls may use
+    pub fn budget(mut self, budget: f64) -> Self {
+        self.budget = budget.clamp(f64::EPSILON, 1.0);
+        self
+    }
+
+    /// the last polled depth of `symbol`
+    pub fn last_depth(&self, symbol: &str) -> Option<&Depth> {
+        self.depths.get(symbol)
+    }
+
+    pub fn last_balance(&self) -> Option<&Balance> {
+        self.balance.as_ref()
+    }
+
+    pub fn last_open_orders(&self, symbol: &str) -> Option<&[QueryOrder]> {
+        self.orders.get(symbol).map(Vec::as_slice)
+    }
+
+    /// poll until the client shuts down, every change goes to `on_event`
+    pub async fn run<P, F>(&mut self, client: &FxdxClient<P>, mut on_event: F)
+    where
+        P: Prefix,
+        F: FnMut(PollEvent),
+    {
+        if self.schedules.is_empty() {
+            return;
+        }
+        client
+            .until_shutdown(async {
+                loop {
+                    let rate = client.effective_rate();
+                    let (index, at) = self.next_poll(rate);
+                    tokio::time::sleep_until(at).await;
+                    let target = self.schedules[index].target.clone();
+                    self.last_sent = Some(Instant::now());
+                    self.reschedule(index);
+                    if let Some(event) = self.poll_once(client, target).await {
+                        on_event(event);
+                    }
+                }
+            })
+            .await;
+    }
+
+    async fn poll_once<P: Prefix>(
+        &mut self,
+        client: &FxdxClient<P>,
+        target: PollTarget,
+    ) -> Option<PollEvent> {
+        let result = match &target {
+            PollTarget::Depth(symbol) => client
+                .query_depth(Request::Depth {
+                    symbol: symbol.clone(),
+                    limit: None,
+                })
+                .await
+                .map(|depth| self.on_depth(symbol, depth)),
+            PollTarget::Balances => client
+                .query_account_balance(Request::Balances)
+                .await
+                .map(|balance| self.on_balance(balance)),
+            PollTarget::OpenOrders(symbol) => client
+                .open_orders(symbol)
+                .await
+                .map(|orders| self.on_orders(symbol, orders)),
+        };
+        result.unwrap_or_else(|e| {
+            log::debug!("fxdx poll of {} failed: {}", target, e);
+            Some(PollEvent::Failed {
+                target,
+                error: e.to_string(),
+            })
+        })
+    }
+
+    /// the schedule polled next and when, at least the gap of the budget after the last poll
+    fn next_poll(&self, rate: Option<f64>) -> (usize, Instant) {
+        let (index, due) = self
+            .schedules
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (i, s.due))
+            .min_by_key(|(_, due)| *due)
+            .unwrap_or((0, Instant::now()));
+        let earliest = match (self.last_sent, rate) {
+            (Some(last), Some(rate)) if rate > 0.0 => {
+                last + Duration::from_secs_f64(1.0 / (rate * self.budget))
+            }
+            _ => due,
+        };
+        (index, due.max(earliest))
+    }
+
+    fn reschedule(&mut self, index: usize) {
+        let offset = self.next_offset();
+        let schedule = &mut self.schedules[index];
+        let interval = schedule.interval.as_secs_f64();
+        let next = Duration::from_secs_f64(interval * (1.0 + self.jitter * offset));
+        schedule.due = Instant::now() + next;
+    }
+
+    /// a random number from -1 to 1, xorshift64 as jitter needs no better
+    fn next_offset(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    fn on_depth(&mut self, symbol: &str, depth: Depth) -> Option<PollEvent> {
+        let diff = match self.depths.get(symbol) {
+            Some(prev) => prev.diff(&depth),
+            None => Depth {
+                depth: 0,
+                seq: None,
+                checksum: None,
+                bids: vec![],
+                asks: vec![],
+            }
+            .diff(&depth),
+        };
+        self.depths.insert(symbol.to_string(), depth);
+        (!diff.is_empty()).then(|| PollEvent::Depth {
+            symbol: symbol.to_string(),
+            diff,
+        })
+    }
+
+    fn on_balance(&mut self, balance: Balance) -> Option<PollEvent> {
+        let unchanged = self.balance.as_ref().is_some_and(|prev| {
+            prev.name == balance.name
+                && prev.available == balance.available
+                && prev.frozen == balance.frozen
+        });
+        self.balance = Some(balance.clone());
+        (!unchanged).then_some(PollEvent::Balance(balance))
+    }
+
+    fn on_orders(&mut self, symbol: &str, orders: Vec<QueryOrder>) -> Option<PollEvent> {
+        let prev = self.orders.get(symbol).map_or(&[][..], Vec::as_slice);
+        let changes = OrderChanges::between(prev, &orders);
+        // the first poll reports every open order, even none, so the caller knows the state
+        let first = !self.orders.contains_key(symbol);
+        self.orders.insert(symbol.to_string(), orders);
+        (first || !changes.is_empty()).then(|| PollEvent::Orders {
+            symbol: symbol.to_string(),
+            changes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(id: &str, status: i32, filled: &str) -> QueryOrder {
+        serde_json::from_str(&format!(
+            r#"{{"symbol":"BTC-USDT","order_id":"{}","order_type":1,"direction":1,"amount":"1","filled_base":"{}","status":{}}}"#,
+            id, filled, status
+        ))
+        .unwrap()
+    }
+
+    fn depth(bids: &str) -> Depth {
+        serde_json::from_str(&format!(r#"{{"bids":{},"asks":[["11","1"]]}}"#, bids)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dedup_and_spacing() {
+        let mut poller = Poller::new()
+            .depth("BTC-USDT", Duration::from_secs(1))
+            .balances(Duration::from_secs(10))
+            .jitter(0.2);
+
+        assert!(matches!(
+            poller.on_depth("BTC-USDT", depth(r#"[["10","1"]]"#)),
+            Some(PollEvent::Depth { diff, .. }) if diff.bids.added.len() == 1
+        ));
+        assert!(poller
+            .on_depth("BTC-USDT", depth(r#"[["10","1"]]"#))
+            .is_none());
+        assert!(matches!(
+            poller.on_depth("BTC-USDT", depth(r#"[["10","2"]]"#)),
+            Some(PollEvent::Depth { diff, .. }) if diff.bids.changed.len() == 1
+        ));
+
+        assert!(poller.on_orders("BTC-USDT", vec![]).is_some());
+        let opened = vec![order("1", 1, "0"), order("2", 1, "0")];
+        assert!(poller.on_orders("BTC-USDT", opened.clone()).is_some());
+        assert!(poller.on_orders("BTC-USDT", opened).is_none());
+        match poller.on_orders("BTC-USDT", vec![order("2", 2, "0.5")]) {
+            Some(PollEvent::Orders { changes, .. }) => {
+                assert_eq!(changes.closed, vec![String::from("1")]);
+                assert_eq!(changes.changed[0].order_id, "2");
+                assert!(changes.opened.is_empty());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // at 10 requests per second and half of them for polls the gap is 200ms
+        let start = Instant::now();
+        poller.last_sent = Some(start);
+        let (_, at) = poller.next_poll(Some(10.0));
+        assert!(at >= start + Duration::from_millis(200));
+        poller.reschedule(0);
+        let due = poller.schedules[0].due - start;
+        assert!(due >= Duration::from_millis(800) && due <= Duration::from_millis(1200));
+        for _ in 0..1000 {
+            assert!((-1.0..=1.0).contains(&poller.next_offset()));
+        }
+    }
+}
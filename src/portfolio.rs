@@ -0,0 +1,415 @@
+use crate::response::{Side, Trade};
+use bigdecimal::{BigDecimal, Signed, Zero};
+use std::collections::{BTreeMap, HashMap};
+
+/// running position and PnL of one (strategy, symbol) bucket, all money values are in the quote asset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attribution {
+    pub fills: usize,
+    /// signed base position, long is positive
+    pub position: BigDecimal,
+    /// signed cost of the open position, `position * avg_price`
+    pub cost: BigDecimal,
+    pub realized_pnl: BigDecimal,
+    pub volume: BigDecimal,
+    pub base_fees: BigDecimal,
+    pub quote_fees: BigDecimal,
+    /// all fees converted into the quote asset at the price of the fill that paid them
+    pub fees_in_quote: BigDecimal,
+}
+
+impl Attribution {
+    pub fn avg_price(&self) -> Option<BigDecimal> {
+        if self.position.is_zero() {
+            None
+        } else {
+            Some(&self.cost / &self.position)
+        }
+    }
+
+    pub fn unrealized_pnl(&self, mark: &BigDecimal) -> BigDecimal {
+        &self.position * mark - &self.cost
+    }
+
+    /// realized PnL minus every fee paid
+    pub fn net_pnl(&self) -> BigDecimal {
+        &self.realized_pnl - &self.fees_in_quote
+    }
+
+    fn apply(&mut self, trade: &Trade) {
+        let signed = match trade.ask_or_bid {
+            Side::Bid => trade.amount.clone(),
+            Side::Ask => -trade.amount.clone(),
+        };
+        if self.position.is_zero() || self.position.signum() == signed.signum() {
+            self.cost += &signed * &trade.price;
+            self.position += signed;
+        } else {
+            let avg = &self.cost / &self.position;
+            let closing = signed.abs().min(self.position.abs());
+            let direction = self.position.signum();
+            self.realized_pnl += &closing * (&trade.price - &avg) * &direction;
+            self.position -= &direction * &closing;
+            self.cost = &self.position * &avg;
+            let remaining = signed.abs() - closing;
+            if !remaining.is_zero() {
+                let opening = remaining * signed.signum();
+                self.cost += &opening * &trade.price;
+                self.position += opening;
+            }
+        }
+        self.fills += 1;
+        self.volume += &trade.quote_amount;
+        self.base_fees += &trade.base_fee;
+        self.quote_fees += &trade.quote_fee;
+        self.fees_in_quote += &trade.quote_fee + &trade.base_fee * &trade.price;
+    }
+
+    fn merge(&mut self, other: &Attribution) {
+        self.fills += other.fills;
+        self.position += &other.position;
+        self.cost += &other.cost;
+        self.realized_pnl += &other.realized_pnl;
+        self.volume += &other.volume;
+        self.base_fees += &other.base_fees;
+        self.quote_fees += &other.quote_fees;
+        self.fees_in_quote += &other.fees_in_quote;
+    }
+}
+
+/// totals of a strategy in one quote asset, the positions and their cost only add up per
+/// symbol and base fees per base asset, see `PnlTracker::get`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuoteTotals {
+    pub fills: usize,
+    pub realized_pnl: BigDecimal,
+    pub volume: BigDecimal,
+    pub quote_fees: BigDecimal,
+    pub fees_in_quote: BigDecimal,
+}
+
+impl QuoteTotals {
+    /// realized PnL minus every fee paid
+    pub fn net_pnl(&self) -> BigDecimal {
+        &self.realized_pnl - &self.fees_in_quote
+    }
+
+    fn add(&mut self, attribution: &Attribution) {
+        self.fills += attribution.fills;
+        self.realized_pnl += &attribution.realized_pnl;
+        self.volume += &attribution.volume;
+        self.quote_fees += &attribution.quote_fees;
+        self.fees_in_quote += &attribution.fees_in_quote;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributionRow {
+    pub strategy: String,
+    pub symbol: String,
+    pub attribution: Attribution,
+}
+
+/// how far, in seconds, a fill may be older than the newest one of its symbol and still be
+/// recorded, e.g. for the fills of several orders queried one after the other
+pub const DEFAULT_OVERLAP: i64 = 60;
+
+/// attributes fills, fees and PnL per strategy tag and per symbol
+///
+/// the strategy tag is the part of the client order id before the separator,
+/// e.g. `grid-00017` is attributed to `grid` with the default `-` separator
+///
+/// every symbol has a watermark, the time of its newest fill: a fill older than the watermark
+/// by more than the overlap is stale and dropped, and only fills within the overlap are
+/// compared against the ones recorded before
+#[derive(Debug, Clone)]
+pub struct PnlTracker {
+    separator: char,
+    untagged: String,
+    overlap: i64,
+    buckets: BTreeMap<(String, String), Attribution>,
+    watermarks: HashMap<String, i64>,
+    /// the fills within the overlap of their watermark, with how often each was recorded
+    seen: HashMap<FillKey, usize>,
+}
+
+/// fills carry no id, the same order, time, side, price and amount is treated as the same
+/// fill, equal ones within one read are counted
+type FillKey = (Option<String>, String, i64, u8, BigDecimal, BigDecimal);
+
+impl Default for PnlTracker {
+    fn default() -> Self {
+        PnlTracker::new('-')
+    }
+}
+
+impl PnlTracker {
+    pub fn new(separator: char) -> Self {
+        PnlTracker {
+            separator,
+            untagged: String::from("untagged"),
+            overlap: DEFAULT_OVERLAP,
+            buckets: BTreeMap::new(),
+            watermarks: HashMap::new(),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// the seconds a fill may be older than the newest one of its symbol, `DEFAULT_OVERLAP`
+    /// unless set
+    pub fn overlap(mut self, seconds: i64) -> Self {
+        self.overlap = seconds.max(0);
+        self
+    }
+
+    /// the tag used for fills whose client order id is missing or empty
+    pub fn untagged(mut self, tag: String) -> Self {
+        self.untagged = tag;
+        self
+    }
+
+    pub fn strategy_tag(&self, client_order_id: Option<&str>) -> String {
+        match client_order_id
+            .and_then(|id| id.split(self.separator).next())
+            .filter(|tag| !tag.is_empty())
+        {
+            Some(tag) => tag.to_string(),
+            None => self.untagged.clone(),
+        }
+    }
+
+    /// attribute a fill, returns false for a fill which was already recorded or is stale,
+    /// e.g. when the same trades are returned by overlapping or retried queries
+    ///
+    /// equal fills, e.g. repeated partial fills of an untagged order within one second, are
+    /// only told apart when they come in one `record_fills`
+    pub fn record_fill(
+        &mut self,
+        client_order_id: Option<&str>,
+        symbol: &str,
+        trade: &Trade,
+    ) -> bool {
+        self.record_fills(client_order_id, symbol, std::slice::from_ref(trade)) == 1
+    }
+
+    /// attribute the fills of one read, returns how many were recorded; a fill is recorded
+    /// unless it is stale or earlier reads recorded as many equal fills as this read has
+    pub fn record_fills(
+        &mut self,
+        client_order_id: Option<&str>,
+        symbol: &str,
+        trades: &[Trade],
+    ) -> usize {
+        let oldest = self
+            .watermarks
+            .get(symbol)
+            .map(|watermark| watermark - self.overlap);
+        let mut read = HashMap::new();
+        let mut recorded = 0;
+        for trade in trades {
+            if oldest.is_some_and(|oldest| trade.timestamp < oldest) {
+                continue;
+            }
+            let key = (
+                client_order_id.map(String::from),
+                symbol.to_string(),
+                trade.timestamp,
+                trade.ask_or_bid.code(),
+                trade.price.clone(),
+                trade.amount.clone(),
+            );
+            let occurrence = read.entry(key.clone()).or_insert(0);
+            *occurrence += 1;
+            let seen = self.seen.entry(key).or_insert(0);
+            if *seen >= *occurrence {
+                continue;
+            }
+            *seen += 1;
+            let strategy = self.strategy_tag(client_order_id);
+            self.buckets
+                .entry((strategy, symbol.to_string()))
+                .or_default()
+                .apply(trade);
+            let watermark = self
+                .watermarks
+                .entry(symbol.to_string())
+                .or_insert(trade.timestamp);
+            *watermark = (*watermark).max(trade.timestamp);
+            recorded += 1;
+        }
+        if let Some(watermark) = self.watermarks.get(symbol) {
+            let oldest = watermark - self.overlap;
+            self.seen
+                .retain(|(_, s, timestamp, ..), _| s != symbol || *timestamp >= oldest);
+        }
+        recorded
+    }
+
+    pub fn get(&self, strategy: &str, symbol: &str) -> Option<&Attribution> {
+        self.buckets
+            .get(&(strategy.to_string(), symbol.to_string()))
+    }
+
+    /// every (strategy, symbol) bucket ordered by strategy then symbol
+    pub fn report(&self) -> Vec<AttributionRow> {
+        self.buckets
+            .iter()
+            .map(|((strategy, symbol), attribution)| AttributionRow {
+                strategy: strategy.clone(),
+                symbol: symbol.clone(),
+                attribution: attribution.clone(),
+            })
+            .collect()
+    }
+
+    /// totals of one strategy across its symbols by quote asset
+    pub fn by_strategy(&self, strategy: &str) -> BTreeMap<String, QuoteTotals> {
+        let mut totals = BTreeMap::<String, QuoteTotals>::new();
+        self.buckets
+            .iter()
+            .filter(|((s, _), _)| s == strategy)
+            .for_each(|((_, symbol), a)| {
+                totals.entry(quote(symbol).to_string()).or_default().add(a)
+            });
+        totals
+    }
+
+    /// totals of one symbol across all strategies
+    pub fn by_symbol(&self, symbol: &str) -> Attribution {
+        let mut total = Attribution::default();
+        self.buckets
+            .iter()
+            .filter(|((_, s), _)| s == symbol)
+            .for_each(|(_, a)| total.merge(a));
+        total
+    }
+
+    pub fn strategies(&self) -> Vec<String> {
+        let mut tags = self
+            .buckets
+            .keys()
+            .map(|(s, _)| s.clone())
+            .collect::<Vec<_>>();
+        tags.dedup();
+        tags
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+        self.watermarks.clear();
+        self.seen.clear();
+    }
+}
+
+/// the quote asset of a `BASE-QUOTE` symbol
+fn quote(symbol: &str) -> &str {
+    symbol.split_once('-').map_or(symbol, |(_, quote)| quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: Side, price: i64, amount: i64, quote_fee: i64) -> Trade {
+        Trade {
+            base: 1,
+            quote: 0,
+            ask_or_bid: side,
+            price: BigDecimal::from(price),
+            amount: BigDecimal::from(amount),
+            quote_amount: BigDecimal::from(price * amount),
+            quote_fee: BigDecimal::from(quote_fee),
+            base_fee: BigDecimal::zero(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_attribution_by_strategy() {
+        let mut tracker = PnlTracker::default();
+        tracker.record_fill(Some("grid-1"), "BTC-USDT", &fill(Side::Bid, 100, 2, 1));
+        tracker.record_fill(Some("grid-2"), "BTC-USDT", &fill(Side::Ask, 110, 1, 1));
+        tracker.record_fill(Some("mm-1"), "BTC-USDT", &fill(Side::Ask, 105, 1, 0));
+        tracker.record_fill(None, "ETH-USDT", &fill(Side::Bid, 10, 1, 0));
+
+        let grid = tracker.get("grid", "BTC-USDT").unwrap();
+        assert_eq!(grid.fills, 2);
+        assert_eq!(grid.position, BigDecimal::from(1));
+        assert_eq!(grid.realized_pnl, BigDecimal::from(10));
+        assert_eq!(grid.net_pnl(), BigDecimal::from(8));
+        assert_eq!(grid.avg_price(), Some(BigDecimal::from(100)));
+        assert_eq!(
+            grid.unrealized_pnl(&BigDecimal::from(120)),
+            BigDecimal::from(20)
+        );
+
+        let mm = tracker.get("mm", "BTC-USDT").unwrap();
+        assert_eq!(mm.position, BigDecimal::from(-1));
+        assert!(mm.realized_pnl.is_zero());
+
+        assert_eq!(tracker.by_symbol("BTC-USDT").fills, 3);
+        assert_eq!(tracker.by_strategy("untagged")["USDT"].fills, 1);
+        assert_eq!(tracker.strategies(), vec!["grid", "mm", "untagged"]);
+        assert_eq!(tracker.report().len(), 3);
+    }
+
+    #[test]
+    fn test_strategy_totals_by_quote() {
+        let mut tracker = PnlTracker::default();
+        tracker.record_fill(Some("arb-1"), "BTC-USDT", &fill(Side::Bid, 100, 1, 1));
+        tracker.record_fill(Some("arb-2"), "ETH-USDT", &fill(Side::Ask, 10, 2, 1));
+        tracker.record_fill(Some("arb-3"), "ETH-BTC", &fill(Side::Bid, 5, 3, 0));
+        tracker.record_fill(Some("arb-4"), "ETH-BTC", &fill(Side::Ask, 6, 1, 0));
+
+        let totals = tracker.by_strategy("arb");
+        assert_eq!(totals.keys().collect::<Vec<_>>(), vec!["BTC", "USDT"]);
+        let usdt = &totals["USDT"];
+        assert_eq!(usdt.fills, 2);
+        assert_eq!(usdt.volume, BigDecimal::from(120));
+        assert_eq!(usdt.net_pnl(), BigDecimal::from(-2));
+        let btc = &totals["BTC"];
+        assert_eq!(btc.realized_pnl, BigDecimal::from(1));
+        assert_eq!(btc.volume, BigDecimal::from(21));
+        assert!(tracker.by_strategy("grid").is_empty());
+    }
+
+    #[test]
+    fn test_position_flip() {
+        let mut tracker = PnlTracker::default();
+        tracker.record_fill(Some("t"), "BTC-USDT", &fill(Side::Bid, 100, 1, 0));
+        tracker.record_fill(Some("t"), "BTC-USDT", &fill(Side::Ask, 90, 3, 0));
+        assert!(!tracker.record_fill(Some("t"), "BTC-USDT", &fill(Side::Ask, 90, 3, 0)));
+        let t = tracker.get("t", "BTC-USDT").unwrap();
+        assert_eq!(t.fills, 2);
+        assert_eq!(t.realized_pnl, BigDecimal::from(-10));
+        assert_eq!(t.position, BigDecimal::from(-2));
+        assert_eq!(t.avg_price(), Some(BigDecimal::from(90)));
+    }
+
+    #[test]
+    fn test_watermark() {
+        let mut tracker = PnlTracker::default();
+        let at = |timestamp, amount| Trade {
+            timestamp,
+            ..fill(Side::Bid, 100, amount, 0)
+        };
+        // two equal partial fills of an untagged order in one second, then the read again
+        let read = [at(1000, 1), at(1000, 1)];
+        assert_eq!(tracker.record_fills(None, "BTC-USDT", &read), 2);
+        assert_eq!(tracker.record_fills(None, "BTC-USDT", &read), 0);
+        // a retried read which found one more of them
+        let read = [at(1000, 1), at(1000, 1), at(1000, 1)];
+        assert_eq!(tracker.record_fills(None, "BTC-USDT", &read), 1);
+        assert_eq!(tracker.by_symbol("BTC-USDT").fills, 3);
+
+        // within the overlap another order's earlier fill still counts, before it it is stale
+        assert!(tracker.record_fill(None, "BTC-USDT", &at(1000 - DEFAULT_OVERLAP, 2)));
+        assert!(!tracker.record_fill(None, "BTC-USDT", &at(999 - DEFAULT_OVERLAP, 2)));
+        // other symbols have watermarks of their own
+        assert!(tracker.record_fill(None, "ETH-USDT", &at(0, 1)));
+
+        assert!(tracker.record_fill(None, "BTC-USDT", &at(2000, 1)));
+        assert_eq!(tracker.seen.len(), 2);
+        assert_eq!(tracker.by_symbol("BTC-USDT").fills, 5);
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// the order in which requests waiting on a saturated rate limiter are let through,
+/// a request only gets tokens while nothing more urgent is waiting
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// klines, trade and order history
+    Bulk,
+    /// every other query
+    #[default]
+    Normal,
+    /// order placement
+    High,
+    /// cancels, which reduce the exposure
+    Critical,
+}
+
+impl Priority {
+    pub(crate) const COUNT: usize = 4;
+
+    pub(crate) fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Option<Priority>> = const { Cell::new(None) };
+}
+
+/// the priority set by an enclosing `with_priority`, if any
+pub fn current() -> Option<Priority> {
+    CURRENT.with(Cell::get)
+}
+
+/// run `call` with every request it sends at `priority` instead of the request's own,
+/// e.g. `with_priority(Priority::Critical, client.query_order_by_id(req))`
+pub fn with_priority<F: Future>(priority: Priority, call: F) -> WithPriority<F> {
+    WithPriority {
+        priority,
+        call: Box::pin(call),
+    }
+}
+
+/// see `with_priority`, the override is in place while the inner future is polled
+pub struct WithPriority<F> {
+    priority: Priority,
+    call: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for WithPriority<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let previous = CURRENT.with(|current| current.replace(Some(self.priority)));
+        let poll = self.call.as_mut().poll(cx);
+        CURRENT.with(|current| current.set(previous));
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_override_is_scoped() {
+        assert_eq!(current(), None);
+        let inner = with_priority(Priority::Critical, async {
+            tokio::task::yield_now().await;
+            current()
+        });
+        assert_eq!(
+            with_priority(Priority::Bulk, async { (current(), inner.await) }).await,
+            (Some(Priority::Bulk), Some(Priority::Critical))
+        );
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,320 @@
+//! pushes the events of a `marketdata::MarketDataSession` onto a message bus, built with
+//! `--features publish`, so downstream analytics share one exchange connection
+//!
+//! `NatsPublisher` publishes through async-nats, `KafkaPublisher` through rdkafka with
+//! `--features kafka`; any other bus plugs in by implementing `Publisher`
+//!
+//! ```ignore
+//! let events = session.subscribe(&[String::from("BTC-USDT")]);
+//! let nats = NatsPublisher::connect("127.0.0.1:4222").await?;
+//! tokio::join!(session.run(), forward(events, &nats, Format::MessagePack, &Topics::new("fxdx")));
+//! ```
+
+use crate::eventbuffer::EventReceiver;
+use crate::marketdata::MarketEvent;
+use crate::response::{PriceLevel, Side};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub type PublishFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// a message bus taking payloads by topic, a Kafka topic or a NATS subject
+pub trait Publisher: Send + Sync {
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> PublishFuture<'a>;
+}
+
+impl<T: Publisher + ?Sized> Publisher for Arc<T> {
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> PublishFuture<'a> {
+        (**self).publish(topic, payload)
+    }
+}
+
+/// how an event is serialized, both carry the fields of `message`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl Format {
+    pub fn encode(&self, event: &MarketEvent) -> Vec<u8> {
+        let message = message(event);
+        match self {
+            Format::Json => message.to_string().into_bytes(),
+            // a map of the JSON value serializes without fail
+            Format::MessagePack => rmp_serde::to_vec(&message).unwrap_or_default(),
+        }
+    }
+}
+
+/// the topic of every event: `{prefix}.{kind}.{symbol}`, e.g. `fxdx.depth.BTC-USDT`, where
+/// the kind is `depth`, `trade`, `kline` or `gap`
+#[derive(Debug, Clone)]
+pub struct Topics {
+    prefix: String,
+}
+
+impl Topics {
+    pub fn new(prefix: &str) -> Self {
+        Topics {
+            prefix: prefix.to_string(),
+        }
+    }
+
+    pub fn topic(&self, event: &MarketEvent) -> String {
+        format!("{}.{}.{}", self.prefix, kind(event), event.symbol())
+    }
+}
+
+fn kind(event: &MarketEvent) -> &'static str {
+    match event {
+        MarketEvent::Book(_) => "depth",
+        MarketEvent::Trade { .. } => "trade",
+        MarketEvent::Kline { .. } => "kline",
+        MarketEvent::Gap { .. } => "gap",
+    }
+}
+
+fn levels(levels: &[PriceLevel]) -> Value {
+    levels
+        .iter()
+        .map(|level| json!([level.price.to_string(), level.amount.to_string()]))
+        .collect()
+}
+
+/// the published form of `event`, decimals as strings so no consumer loses precision:
+/// `{"type":"depth","symbol":..,"bids":[[price, amount]..],"asks":[..]}`,
+/// `{"type":"trade","symbol":..,"id":..,"side":"bid"|"ask","price":..,"amount":..,"timestamp":..}`,
+/// `{"type":"kline","symbol":..,"open_time":..,"open":..,"high":..,"low":..,"close":..,"volume":..}`
+/// and `{"type":"gap","symbol":..}`
+pub fn message(event: &MarketEvent) -> Value {
+    let mut message = match event {
+        MarketEvent::Book(book) => json!({
+            "bids": levels(&book.bids),
+            "asks": levels(&book.asks),
+        }),
+        MarketEvent::Trade { trade, .. } => json!({
+            "id": trade.id,
+            "side": match trade.ask_or_bid {
+                Side::Bid => "bid",
+                Side::Ask => "ask",
+            },
+            "price": trade.price.to_string(),
+            "amount": trade.amount.to_string(),
+            "timestamp": trade.timestamp,
+        }),
+        MarketEvent::Kline { kline, .. } => json!({
+            "open_time": kline.id,
+            "open": kline.open.to_string(),
+            "high": kline.high.to_string(),
+            "low": kline.low.to_string(),
+            "close": kline.close.to_string(),
+            "volume": kline.vol.to_string(),
+        }),
+        MarketEvent::Gap { .. } => json!({}),
+    };
+    message["type"] = json!(kind(event));
+    message["symbol"] = json!(event.symbol());
+    message
+}
+
+/// publish every event of `events` until the session ends, returns how many were published;
+/// a failed publish ends the forwarding with its error
+pub async fn forward<B: Publisher + ?Sized>(
+    mut events: EventReceiver,
+    publisher: &B,
+    format: Format,
+    topics: &Topics,
+) -> Result<u64> {
+    let mut published = 0;
+    while let Some(event) = events.recv().await {
+        publisher
+            .publish(&topics.topic(&event), format.encode(&event))
+            .await?;
+        published += 1;
+    }
+    Ok(published)
+}
+
+/// a publisher on a NATS connection, the payload is sent as the message with the topic as
+/// its subject
+#[derive(Debug, Clone)]
+pub struct NatsPublisher {
+    client: async_nats::Client,
+}
+
+impl NatsPublisher {
+    /// connect with the default options, see `from_client` for others
+    pub async fn connect<A: async_nats::ToServerAddrs>(addrs: A) -> Result<Self> {
+        Ok(NatsPublisher {
+            client: async_nats::connect(addrs).await?,
+        })
+    }
+
+    pub fn from_client(client: async_nats::Client) -> Self {
+        NatsPublisher { client }
+    }
+
+    /// wait until every message published so far is written to the server
+    pub async fn flush(&self) -> Result<()> {
+        Ok(self.client.flush().await?)
+    }
+}
+
+impl Publisher for NatsPublisher {
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> PublishFuture<'a> {
+        Box::pin(async move {
+            self.client
+                .publish(topic.to_string(), payload.into())
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+/// a publisher on a Kafka producer, `--features kafka`; the topic of an event is the Kafka
+/// topic, so `Topics` with a prefix of letters, digits, `.`, `_` and `-` only
+#[cfg(feature = "kafka")]
+pub struct KafkaPublisher {
+    producer: rdkafka::producer::FutureProducer,
+    timeout: std::time::Duration,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaPublisher {
+    /// a producer of `bootstrap.servers`, e.g. `"kafka-1:9092,kafka-2:9092"`, see
+    /// `from_producer` for other settings
+    pub fn new(brokers: &str) -> Result<Self> {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()?;
+        Ok(Self::from_producer(producer))
+    }
+
+    pub fn from_producer(producer: rdkafka::producer::FutureProducer) -> Self {
+        KafkaPublisher {
+            producer,
+            timeout: std::time::Duration::from_secs(5),
+        }
+    }
+
+    /// how long a publish waits for room in the queue of the producer, 5 seconds by default
+    pub fn queue_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl Publisher for KafkaPublisher {
+    /// resolves once the broker acknowledged the message
+    fn publish<'a>(&'a self, topic: &'a str, payload: Vec<u8>) -> PublishFuture<'a> {
+        Box::pin(async move {
+            let record = rdkafka::producer::FutureRecord::<(), _>::to(topic).payload(&payload);
+            self.producer
+                .send(record, self.timeout)
+                .await
+                .map_err(|(e, _)| e)?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::OrderBook;
+    use bigdecimal::BigDecimal;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    fn book() -> MarketEvent {
+        let mut book = OrderBook::new(String::from("BTC-USDT"));
+        book.bids = vec![PriceLevel::new(BigDecimal::from(10), BigDecimal::from(1))];
+        MarketEvent::Book(book)
+    }
+
+    #[test]
+    fn test_encode_events() {
+        let event = book();
+        assert_eq!(Topics::new("fxdx").topic(&event), "fxdx.depth.BTC-USDT");
+        let json: Value = serde_json::from_slice(&Format::Json.encode(&event)).unwrap();
+        assert_eq!(
+            json,
+            json!({"type":"depth","symbol":"BTC-USDT","bids":[["10","1"]],"asks":[]})
+        );
+
+        let packed = Format::MessagePack.encode(&event);
+        // a map of four: asks, bids, symbol and type
+        assert_eq!(packed[0], 0x84);
+        assert_eq!(rmp_serde::from_slice::<Value>(&packed).unwrap(), json);
+        let small = rmp_serde::to_vec(&json!([1, 200, -2])).unwrap();
+        assert_eq!(small, [0x93, 0x01, 0xcc, 200, 0xfe]);
+    }
+
+    /// a NATS server enough for one client: answers its pings and returns what it published
+    async fn nats_server(listener: tokio::net::TcpListener) -> Vec<(String, String)> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        write
+            .write_all(b"INFO {\"server_id\":\"test\",\"max_payload\":1048576,\"proto\":1}\r\n")
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(read);
+        let mut published = vec![];
+        loop {
+            let mut line = String::new();
+            if lines.read_line(&mut line).await.unwrap() == 0 {
+                return published;
+            }
+            if line.starts_with("PING") {
+                write.write_all(b"PONG\r\n").await.unwrap();
+            } else if let Some(publish) = line.strip_prefix("PUB ") {
+                let subject = publish.split(' ').next().unwrap().to_string();
+                let mut payload = String::new();
+                lines.read_line(&mut payload).await.unwrap();
+                published.push((subject, payload.trim_end().to_string()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nats_publish() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(nats_server(listener));
+
+        let nats = NatsPublisher::connect(addr.to_string()).await.unwrap();
+        let (sender, events) = crate::eventbuffer::event_buffer(Default::default());
+        sender.send(book()).await;
+        drop(sender);
+        let topics = Topics::new("fxdx");
+        assert_eq!(
+            forward(events, &nats, Format::Json, &topics).await.unwrap(),
+            1
+        );
+        nats.flush().await.unwrap();
+        drop(nats);
+
+        let published = server.await.unwrap();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].0, "fxdx.depth.BTC-USDT");
+        assert!(published[0].1.starts_with(r#"{"asks":[]"#));
+    }
+
+    #[cfg(feature = "kafka")]
+    #[tokio::test]
+    async fn test_kafka_publish_fails_without_broker() {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", "127.0.0.1:1")
+            .set("message.timeout.ms", "200")
+            .create()
+            .unwrap();
+        let kafka = KafkaPublisher::from_producer(producer);
+        let payload = Format::Json.encode(&book());
+        assert!(kafka.publish("fxdx.depth.BTC-USDT", payload).await.is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::priority::Priority;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// receives the effective rate in requests per second after every change
+pub type RateHook = std::sync::Arc<dyn Fn(f64) + Send + Sync>;
+
+/// the lowest fraction of the configured rate repeated 429s can throttle down to
+pub const MIN_RATE_FRACTION: f64 = 0.1;
+
+/// the fraction of the configured rate every successful request wins back
+pub const RECOVERY_STEP: f64 = 0.05;
+
+/// token bucket shared by every request of a client, each request takes as many tokens as it weighs
+///
+/// the refill rate adapts AIMD style: every `throttle` halves it down to `MIN_RATE_FRACTION`
+/// of the configured rate, every `recover` adds `RECOVERY_STEP` of it back
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    per_second: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    /// the current refill rate, `RateLimiter::per_second` unless throttled
+    per_second: f64,
+    paused_until: Option<Instant>,
+    /// callers of `acquire_prioritized` still waiting, by priority
+    waiting: [usize; Priority::COUNT],
+}
+
+impl RateLimiter {
+    /// allow bursts of `requests` and refill them evenly over `per`
+    pub fn new(requests: u32, per: Duration) -> Self {
+        let capacity = f64::from(requests.max(1));
+        let per_second = capacity / per.as_secs_f64().max(f64::EPSILON);
+        RateLimiter {
+            capacity,
+            per_second,
+            state: Mutex::new(Bucket {
+                tokens: capacity,
+                refilled: Instant::now(),
+                per_second,
+                paused_until: None,
+                waiting: [0; Priority::COUNT],
+            }),
+        }
+    }
+
+    fn bucket(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// the refill rate in requests per second currently in effect
+    pub fn effective_rate(&self) -> f64 {
+        self.bucket().per_second
+    }
+
+    /// the exchange rate limited a request: halve the rate, drop the tokens and hold every
+    /// request for `retry_after` when given, returns the new rate
+    pub fn throttle(&self, retry_after: Option<Duration>) -> f64 {
+        let mut bucket = self.bucket();
+        let now = Instant::now();
+        bucket.per_second = (bucket.per_second / 2.0).max(self.per_second * MIN_RATE_FRACTION);
+        bucket.tokens = 0.0;
+        bucket.refilled = now;
+        // a pause beyond the range of `Instant` is no pause worth keeping
+        if let Some(until) = retry_after.and_then(|wait| now.checked_add(wait)) {
+            bucket.paused_until = bucket.paused_until.max(Some(until));
+        }
+        bucket.per_second
+    }
+
+    /// a request went through: raise a throttled rate by one step, returns the new rate if it changed
+    pub fn recover(&self) -> Option<f64> {
+        let mut bucket = self.bucket();
+        if bucket.per_second >= self.per_second {
+            return None;
+        }
+        bucket.per_second =
+            (bucket.per_second + self.per_second * RECOVERY_STEP).min(self.per_second);
+        Some(bucket.per_second)
+    }
+
+    /// take a token right away or tell how long to wait for the next one
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_weighted(1)
+    }
+
+    /// take `weight` tokens right away or tell how long to wait until there are enough,
+    /// a weight above the burst capacity is capped so it can still be served
+    pub fn try_acquire_weighted(&self, weight: u32) -> Result<(), Duration> {
+        self.try_acquire_as(weight, None)
+    }
+
+    /// like `try_acquire_weighted`, but without taking anything while a caller of a higher
+    /// `priority` waits
+    fn try_acquire_as(&self, weight: u32, priority: Option<Priority>) -> Result<(), Duration> {
+        let weight = f64::from(weight.max(1)).min(self.capacity);
+        let mut bucket = self.bucket();
+        let now = Instant::now();
+        if let Some(until) = bucket.paused_until {
+            if until > now {
+                return Err(until - now);
+            }
+            bucket.paused_until = None;
+            bucket.refilled = now;
+        }
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * bucket.per_second).min(self.capacity);
+        bucket.refilled = now;
+        let overtaken = priority.is_some_and(|priority| {
+            bucket.waiting[priority.index() + 1..]
+                .iter()
+                .any(|waiting| *waiting > 0)
+        });
+        if overtaken {
+            // look again once the next token is in, which the more urgent caller may take
+            return Err(Duration::from_secs_f64(1.0 / bucket.per_second));
+        }
+        if bucket.tokens >= weight {
+            bucket.tokens -= weight;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (weight - bucket.tokens) / bucket.per_second,
+            ))
+        }
+    }
+
+    pub async fn acquire(&self) {
+        self.acquire_weighted(1).await
+    }
+
+    pub async fn acquire_weighted(&self, weight: u32) {
+        self.acquire_prioritized(weight, Priority::Normal).await
+    }
+
+    /// wait for `weight` tokens behind every caller of a higher priority
+    pub async fn acquire_prioritized(&self, weight: u32, priority: Priority) {
+        let _waiting = Waiting::register(self, priority);
+        while let Err(wait) = self.try_acquire_as(weight, Some(priority)) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// counts a caller of `acquire_prioritized` as waiting until it is done or dropped
+struct Waiting<'a> {
+    limiter: &'a RateLimiter,
+    priority: Priority,
+}
+
+impl<'a> Waiting<'a> {
+    fn register(limiter: &'a RateLimiter, priority: Priority) -> Self {
+        limiter.bucket().waiting[priority.index()] += 1;
+        Waiting { limiter, priority }
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.limiter.bucket().waiting[self.priority.index()] -= 1;
+    }
+}
+
+/// the wait a `Retry-After` header asks for, given in seconds or as an HTTP date
+pub fn parse_retry_after(value: &str, now: std::time::SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        // a wait too long for a `Duration` is as useless as a negative one
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_wait() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_weighted() {
+        let limiter = RateLimiter::new(10, Duration::from_secs(10));
+        assert!(limiter.try_acquire_weighted(8).is_ok());
+        let wait = limiter.try_acquire_weighted(5).unwrap_err();
+        assert!(wait > Duration::from_secs(2) && wait <= Duration::from_secs(3));
+        assert!(limiter.try_acquire_weighted(2).is_ok());
+    }
+
+    #[test]
+    fn test_throttle_and_recover() {
+        let limiter = RateLimiter::new(10, Duration::from_secs(1));
+        assert_eq!(limiter.recover(), None);
+        assert_eq!(limiter.throttle(Some(Duration::from_secs(5))), 5.0);
+        let wait = limiter.try_acquire().unwrap_err();
+        assert!(wait > Duration::from_secs(4));
+        for _ in 0..10 {
+            limiter.throttle(None);
+        }
+        assert_eq!(limiter.effective_rate(), 1.0);
+        assert_eq!(limiter.recover(), Some(1.5));
+        for _ in 0..100 {
+            limiter.recover();
+        }
+        assert_eq!(limiter.effective_rate(), 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_priority_jumps_the_queue() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(20));
+        limiter.try_acquire().unwrap();
+        let order = Mutex::new(vec![]);
+        let waiter = |priority| {
+            let (limiter, order) = (&limiter, &order);
+            async move {
+                limiter.acquire_prioritized(1, priority).await;
+                order.lock().unwrap().push(priority);
+            }
+        };
+        // the bulk query is waiting first but the cancel still goes out before it
+        tokio::join!(
+            waiter(Priority::Bulk),
+            waiter(Priority::Bulk),
+            waiter(Priority::Critical),
+            waiter(Priority::High)
+        );
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![
+                Priority::Critical,
+                Priority::High,
+                Priority::Bulk,
+                Priority::Bulk
+            ]
+        );
+        assert_eq!(limiter.bucket().waiting, [0; Priority::COUNT]);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(parse_retry_after("2", now), Some(Duration::from_secs(2)));
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("-1", now), None);
+        assert_eq!(parse_retry_after("1e300", now), None);
+        assert_eq!(parse_retry_after("NaN", now), None);
+        assert_eq!(parse_retry_after("inf", now), None);
+
+        let limiter = RateLimiter::new(2, Duration::from_secs(1));
+        limiter.throttle(parse_retry_after("1e19", now));
+    }
+}
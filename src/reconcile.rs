@@ -0,0 +1,296 @@
+use crate::request::{NewOrder, Side};
+use crate::response::{AccountSnapshot, CancelAllSummary, QueryOrder};
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::HashMap;
+
+/// an order as the strategy believes it to be
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedOrder {
+    pub order_id: String,
+    pub symbol: String,
+    pub side: Option<Side>,
+    /// absent for market orders
+    pub price: Option<BigDecimal>,
+    pub amount: BigDecimal,
+    /// base filled as far as the strategy knows
+    pub filled: BigDecimal,
+}
+
+impl TrackedOrder {
+    fn from_exchange(order: &QueryOrder) -> Self {
+        TrackedOrder {
+            order_id: order.order_id.clone(),
+            symbol: order.symbol.clone(),
+            side: Some(order.direction),
+            price: (!order.price.is_zero()).then(|| order.price.clone()),
+            amount: order.amount.clone(),
+            filled: order.filled_base.clone(),
+        }
+    }
+}
+
+/// the open orders of a strategy, kept by the strategy itself: `track` after every placement,
+/// `fill` and `remove` as it learns of fills and cancels
+#[derive(Debug, Clone, Default)]
+pub struct OrderTracker {
+    orders: HashMap<String, TrackedOrder>,
+}
+
+impl OrderTracker {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn track(&mut self, order_id: String, order: &NewOrder) {
+        self.orders.insert(
+            order_id.clone(),
+            TrackedOrder {
+                order_id,
+                symbol: order.symbol.clone(),
+                side: order.side(),
+                price: order.price.clone(),
+                amount: order.amount.clone(),
+                filled: BigDecimal::default(),
+            },
+        );
+    }
+
+    /// take over an order the exchange knows, e.g. one placed before a restart
+    pub fn adopt(&mut self, order: &QueryOrder) {
+        self.orders
+            .insert(order.order_id.clone(), TrackedOrder::from_exchange(order));
+    }
+
+    /// set the base filled of `order_id`, false if it is not tracked
+    pub fn fill(&mut self, order_id: &str, filled: BigDecimal) -> bool {
+        match self.orders.get_mut(order_id) {
+            Some(order) => {
+                order.filled = filled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, order_id: &str) -> Option<TrackedOrder> {
+        self.orders.remove(order_id)
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&TrackedOrder> {
+        self.orders.get(order_id)
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.orders.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// how the tracked orders of the symbols in `snapshot` differ from its open orders,
+    /// ordered by symbol and order id; orders of other symbols are not compared
+    pub fn diff(&self, snapshot: &AccountSnapshot) -> Vec<Discrepancy> {
+        let mut discrepancies = vec![];
+        for (symbol, open) in &snapshot.open_orders {
+            for order in open {
+                match self.orders.get(&order.order_id) {
+                    None => discrepancies.push(Discrepancy::Orphaned(order.clone())),
+                    Some(tracked)
+                        if tracked.amount != order.amount
+                            || tracked.filled != order.filled_base =>
+                    {
+                        discrepancies.push(Discrepancy::QuantityMismatch {
+                            local: tracked.clone(),
+                            exchange: order.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+            discrepancies.extend(
+                self.orders
+                    .values()
+                    .filter(|tracked| {
+                        &tracked.symbol == symbol
+                            && !open.iter().any(|o| o.order_id == tracked.order_id)
+                    })
+                    .cloned()
+                    .map(Discrepancy::Missing),
+            );
+        }
+        discrepancies.sort_by(|a, b| (a.symbol(), a.order_id()).cmp(&(b.symbol(), b.order_id())));
+        discrepancies
+    }
+}
+
+/// one difference between the tracker and the exchange
+#[derive(Debug, Clone)]
+pub enum Discrepancy {
+    /// open on the exchange but not tracked, placed by another client or lost on a restart
+    Orphaned(QueryOrder),
+    /// tracked but not open on the exchange, filled or cancelled unnoticed
+    Missing(TrackedOrder),
+    /// open on both sides with a different amount or base filled
+    QuantityMismatch {
+        local: TrackedOrder,
+        exchange: QueryOrder,
+    },
+}
+
+impl Discrepancy {
+    pub fn symbol(&self) -> &str {
+        match self {
+            Discrepancy::Orphaned(order)
+            | Discrepancy::QuantityMismatch {
+                exchange: order, ..
+            } => &order.symbol,
+            Discrepancy::Missing(order) => &order.symbol,
+        }
+    }
+
+    pub fn order_id(&self) -> &str {
+        match self {
+            Discrepancy::Orphaned(order)
+            | Discrepancy::QuantityMismatch {
+                exchange: order, ..
+            } => &order.order_id,
+            Discrepancy::Missing(order) => &order.order_id,
+        }
+    }
+}
+
+/// what `FxdxClient::reconcile` does about the discrepancies it finds
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Repair {
+    /// only report them
+    #[default]
+    Report,
+    /// cancel orphaned orders, drop missing ones and take the exchange quantities
+    CancelOrphans,
+    /// track orphaned orders, drop missing ones and take the exchange quantities
+    AdoptOrphans,
+}
+
+/// the outcome of `FxdxClient::reconcile`
+#[derive(Debug)]
+pub struct ReconcileReport {
+    /// unix milliseconds of the snapshot compared against
+    pub taken_at: i64,
+    pub discrepancies: Vec<Discrepancy>,
+    /// the orphans cancelled with `Repair::CancelOrphans`
+    pub cancelled: CancelAllSummary,
+    /// the orphans tracked with `Repair::AdoptOrphans`
+    pub adopted: usize,
+}
+
+impl ReconcileReport {
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// bring `tracker` in line with the exchange for every discrepancy but the orphans to cancel,
+/// returns those by symbol
+pub(crate) fn repair(
+    tracker: &mut OrderTracker,
+    discrepancies: &[Discrepancy],
+    repair: Repair,
+) -> (HashMap<String, Vec<String>>, usize) {
+    let mut orphans: HashMap<String, Vec<String>> = HashMap::new();
+    let mut adopted = 0;
+    if repair == Repair::Report {
+        return (orphans, adopted);
+    }
+    for discrepancy in discrepancies {
+        match discrepancy {
+            Discrepancy::Orphaned(order) if repair == Repair::AdoptOrphans => {
+                tracker.adopt(order);
+                adopted += 1;
+            }
+            Discrepancy::Orphaned(order) => orphans
+                .entry(order.symbol.clone())
+                .or_default()
+                .push(order.order_id.clone()),
+            Discrepancy::Missing(order) => {
+                tracker.remove(&order.order_id);
+            }
+            Discrepancy::QuantityMismatch { exchange, .. } => tracker.adopt(exchange),
+        }
+    }
+    (orphans, adopted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::OrderKind;
+
+    fn open(order_id: &str, filled_base: &str) -> QueryOrder {
+        serde_json::from_str(&format!(
+            r#"{{"symbol":"BTC-USDT","order_id":"{}","order_type":1,"direction":1,"amount":"2","price":"10","filled_base":"{}","status":4}}"#,
+            order_id, filled_base
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_and_repair() {
+        let order = NewOrder::new(
+            Side::Bid,
+            OrderKind::Limit,
+            String::from("BTC-USDT"),
+            Some(BigDecimal::from(10)),
+            BigDecimal::from(2),
+        )
+        .unwrap();
+        let mut tracker = OrderTracker::new();
+        tracker.track(String::from("1"), &order);
+        tracker.track(String::from("2"), &order);
+        tracker.track(String::from("3"), &order);
+        let mut eth = order.clone();
+        eth.symbol = String::from("ETH-USDT");
+        tracker.track(String::from("4"), &eth);
+
+        let snapshot = AccountSnapshot {
+            taken_at: 0,
+            balances: vec![],
+            open_orders: HashMap::from([(
+                String::from("BTC-USDT"),
+                vec![open("1", "0"), open("2", "0.5"), open("9", "0")],
+            )]),
+            fills: HashMap::new(),
+        };
+        let discrepancies = tracker.diff(&snapshot);
+        // ETH-USDT was not in the snapshot, its order is not reported missing
+        assert_eq!(
+            discrepancies
+                .iter()
+                .map(|d| match d {
+                    Discrepancy::Orphaned(_) => ('o', d.order_id()),
+                    Discrepancy::Missing(_) => ('m', d.order_id()),
+                    Discrepancy::QuantityMismatch { .. } => ('q', d.order_id()),
+                })
+                .collect::<Vec<_>>(),
+            [('q', "2"), ('m', "3"), ('o', "9")]
+        );
+
+        let (orphans, adopted) = repair(&mut tracker, &discrepancies, Repair::CancelOrphans);
+        assert_eq!(
+            (orphans["BTC-USDT"].as_slice(), adopted),
+            (&["9".to_string()][..], 0)
+        );
+        assert!(tracker.get("3").is_none());
+        assert_eq!(tracker.get("2").unwrap().filled, "0.5".parse().unwrap());
+        assert_eq!(tracker.diff(&snapshot).len(), 1);
+
+        let discrepancies = tracker.diff(&snapshot);
+        repair(&mut tracker, &discrepancies, Repair::AdoptOrphans);
+        assert!(tracker.diff(&snapshot).is_empty());
+        assert_eq!(tracker.len(), 4);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::journal::JournalEntry;
+use serde_json::Value;
+
+/// a value which changed between the recorded and the current response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// JSON pointer of the value, e.g. `/data/0/available`
+    pub path: String,
+    pub recorded: Option<Value>,
+    pub current: Option<Value>,
+}
+
+#[derive(Debug)]
+pub struct ReplayOutcome {
+    pub entry: JournalEntry,
+    pub status: u16,
+    pub body: String,
+    pub differences: Vec<Difference>,
+}
+
+/// every difference between two JSON documents, bodies which are no JSON compare as strings
+pub fn diff_bodies(recorded: &str, current: &str) -> Vec<Difference> {
+    match (
+        serde_json::from_str::<Value>(recorded),
+        serde_json::from_str::<Value>(current),
+    ) {
+        (Ok(recorded), Ok(current)) => diff(&recorded, &current),
+        _ if recorded == current => vec![],
+        _ => vec![Difference {
+            path: String::new(),
+            recorded: Some(Value::String(recorded.to_string())),
+            current: Some(Value::String(current.to_string())),
+        }],
+    }
+}
+
+pub fn diff(recorded: &Value, current: &Value) -> Vec<Difference> {
+    let mut differences = vec![];
+    walk(
+        String::new(),
+        Some(recorded),
+        Some(current),
+        &mut differences,
+    );
+    differences
+}
+
+fn walk(
+    path: String,
+    recorded: Option<&Value>,
+    current: Option<&Value>,
+    out: &mut Vec<Difference>,
+) {
+    match (recorded, current) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let mut keys = a.keys().chain(b.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let child = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                walk(child, a.get(key), b.get(key), out);
+            }
+        }
+        (Some(Value::Array(a)), Some(Value::Array(b))) => {
+            for i in 0..a.len().max(b.len()) {
+                walk(format!("{}/{}", path, i), a.get(i), b.get(i), out);
+            }
+        }
+        (a, b) if a == b => {}
+        (a, b) => out.push(Difference {
+            path,
+            recorded: a.cloned(),
+            current: b.cloned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let differences = diff_bodies(
+            r#"{"code":200,"data":[{"name":"BTC","available":"1"}]}"#,
+            r#"{"code":200,"data":[{"name":"BTC","available":"2"},{"name":"ETH"}]}"#,
+        );
+        assert_eq!(
+            differences,
+            vec![
+                Difference {
+                    path: String::from("/data/0/available"),
+                    recorded: Some(Value::from("1")),
+                    current: Some(Value::from("2")),
+                },
+                Difference {
+                    path: String::from("/data/1"),
+                    recorded: None,
+                    current: Some(serde_json::json!({"name": "ETH"})),
+                },
+            ]
+        );
+        assert!(diff_bodies("down", "down").is_empty());
+    }
+}
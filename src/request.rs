@@ -1,10 +1,24 @@
+use crate::encoding;
+use crate::priority::Priority;
 use bigdecimal::BigDecimal;
 use serde::ser::Serializer;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_repr::Deserialize_repr;
 use serde_repr::Serialize_repr;
 use std::cmp::PartialEq;
-use std::marker::PhantomData;
+use std::fmt::{Display, Write};
+
+/// the most order ids the exchange accepts in one `Request::BatchCancelOrders`
+pub const MAX_BATCH_CANCEL: usize = 20;
+
+/// page size used when walking every open order
+pub const OPEN_ORDERS_PAGE_SIZE: i32 = 50;
+
+/// the largest page a paginated endpoint is asked for
+pub const MAX_PAGE_SIZE: i32 = 100;
+
+/// fills per symbol read by `FxdxClient::account_snapshot`
+pub const RECENT_FILLS: i32 = 50;
 
 pub trait Prefix {
     fn prefix() -> &'static str;
@@ -12,6 +26,7 @@ pub trait Prefix {
 
 pub struct PrivPub;
 pub struct Sr25519;
+pub struct Ed25519;
 
 impl Prefix for PrivPub {
     #[inline]
@@ -27,14 +42,133 @@ impl Prefix for Sr25519 {
     }
 }
 
-#[derive(Debug, Deserialize_repr, Serialize_repr, PartialEq)]
+impl Prefix for Ed25519 {
+    #[inline]
+    fn prefix() -> &'static str {
+        "/api"
+    }
+}
+
+/// the side of an order or a fill, `0` and `1` on the wire
+#[derive(Debug, Copy, Clone, Deserialize_repr, Serialize_repr, PartialEq, Eq, Hash)]
 #[repr(u8)]
-pub enum OrderType {
+pub enum Side {
     Ask = 0,
     Bid = 1,
 }
 
-#[derive(Debug, Deserialize_repr, Serialize_repr, PartialEq)]
+#[deprecated(note = "use `Side`")]
+pub type OrderType = Side;
+
+impl Side {
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Ask => Side::Bid,
+            Side::Bid => Side::Ask,
+        }
+    }
+
+    /// the numeric form the API uses, e.g. in the `type` of an order
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl TryFrom<u8> for Side {
+    type Error = crate::Error;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        match code {
+            0 => Ok(Side::Ask),
+            1 => Ok(Side::Bid),
+            _ => Err(crate::Error::InvalidRequest(format!(
+                "unknown side {}",
+                code
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Ask => f.write_str("ASK"),
+            Side::Bid => f.write_str("BID"),
+        }
+    }
+}
+
+/// accepts the numeric wire form as well as `ask`/`sell` and `bid`/`buy` in any case
+impl std::str::FromStr for Side {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "0" | "ASK" | "SELL" => Ok(Side::Ask),
+            "1" | "BID" | "BUY" => Ok(Side::Bid),
+            _ => Err(crate::Error::InvalidRequest(format!("unknown side {}", s))),
+        }
+    }
+}
+
+/// execution style of a pending order, `Limit` is what the exchange assumes when omitted
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OrderKind {
+    #[default]
+    Limit,
+    Market,
+    PostOnly,
+    IOC,
+    FOK,
+}
+
+impl OrderKind {
+    /// market orders take whatever the book offers, every other kind has to carry a price
+    pub fn requires_price(&self) -> bool {
+        !matches!(self, OrderKind::Market)
+    }
+}
+
+impl Serialize for OrderKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl std::fmt::Display for OrderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderKind::Limit => f.write_str("LIMIT"),
+            OrderKind::Market => f.write_str("MARKET"),
+            OrderKind::PostOnly => f.write_str("POST_ONLY"),
+            OrderKind::IOC => f.write_str("IOC"),
+            OrderKind::FOK => f.write_str("FOK"),
+        }
+    }
+}
+
+impl std::str::FromStr for OrderKind {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "LIMIT" => Ok(OrderKind::Limit),
+            "MARKET" => Ok(OrderKind::Market),
+            "POST_ONLY" => Ok(OrderKind::PostOnly),
+            "IOC" => Ok(OrderKind::IOC),
+            "FOK" => Ok(OrderKind::FOK),
+            _ => Err(crate::Error::InvalidRequest(format!(
+                "unknown order kind {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Deserialize_repr, Serialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum OrderStatus {
     Undeal = 1,
@@ -43,7 +177,160 @@ pub enum OrderStatus {
     PartialDealed = 4,
 }
 
-#[derive(Debug, Copy, Clone)]
+/// the numeric code, as some responses send it quoted
+impl std::str::FromStr for OrderStatus {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "1" => Ok(OrderStatus::Undeal),
+            "2" => Ok(OrderStatus::Cancel),
+            "3" => Ok(OrderStatus::Dealed),
+            "4" => Ok(OrderStatus::PartialDealed),
+            _ => Err(crate::Error::InvalidRequest(format!(
+                "unknown order status {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// narrows a `Request::OrderByPage` down, a field left `None` matches every order
+///
+/// the set filters go into the query string of the uri and into the signature
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OrderFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<OrderStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<Side>,
+    /// unix seconds, orders created at or after it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<i64>,
+    /// unix seconds, orders created before it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<i64>,
+}
+
+impl OrderFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == OrderFilter::default()
+    }
+
+    /// true if `order` passes every set filter, an order without `created_at` fails a time range
+    pub fn matches(&self, order: &crate::response::QueryOrder) -> bool {
+        let created = order.created_at;
+        self.status.is_none_or(|status| status == order.status)
+            && self.side.is_none_or(|side| side == order.direction)
+            && self
+                .start_time
+                .is_none_or(|start| created.is_some_and(|created| created >= start))
+            && self
+                .end_time
+                .is_none_or(|end| created.is_some_and(|created| created < end))
+    }
+
+    /// the set filters with their values in the order of their names, as they are signed
+    fn fields(&self) -> [(&'static str, Option<i64>); 4] {
+        [
+            ("end_time", self.end_time),
+            ("side", self.side.map(|side| i64::from(side.code()))),
+            ("start_time", self.start_time),
+            ("status", self.status.map(|status| status as i64)),
+        ]
+    }
+
+    fn write_query(&self, out: &mut String) {
+        let mut separator = '?';
+        for (name, value) in self.fields() {
+            if let Some(value) = value {
+                let _ = write!(out, "{}{}={}", separator, name, value);
+                separator = '&';
+            }
+        }
+    }
+}
+
+/// one page of a paginated endpoint, numbered from 1 and holding 1 to `MAX_PAGE_SIZE` items
+///
+/// written into the uri as `{page}/{size}`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Page {
+    #[serde(rename = "page")]
+    number: i32,
+    size: i32,
+}
+
+impl Page {
+    pub fn new(number: i32, size: i32) -> anyhow::Result<Self> {
+        if number < 1 {
+            return Err(crate::Error::InvalidRequest(format!(
+                "page {} before the first page",
+                number
+            ))
+            .into());
+        }
+        if !(1..=MAX_PAGE_SIZE).contains(&size) {
+            return Err(crate::Error::InvalidRequest(format!(
+                "page size {} outside 1 to {}",
+                size, MAX_PAGE_SIZE
+            ))
+            .into());
+        }
+        Ok(Page { number, size })
+    }
+
+    pub fn first(size: i32) -> anyhow::Result<Self> {
+        Page::new(1, size)
+    }
+
+    pub fn number(&self) -> i32 {
+        self.number
+    }
+
+    pub fn size(&self) -> i32 {
+        self.size
+    }
+
+    /// the page after this one, of the same size
+    pub fn next(&self) -> Self {
+        Page {
+            number: self.number.saturating_add(1),
+            size: self.size,
+        }
+    }
+
+    /// true if a page answered with `len` items is the last one, the exchange sends no more
+    /// than the page holds
+    pub fn is_last(&self, len: usize) -> bool {
+        len < self.size as usize
+    }
+
+    /// items on the pages before this one
+    pub fn offset(&self) -> usize {
+        (self.number as usize - 1) * self.size as usize
+    }
+}
+
+impl Display for Page {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.number, self.size)
+    }
+}
+
+/// what an API key is allowed to do, as listed by `Request::AccountInfo`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Permission {
+    Read,
+    Trade,
+    Withdraw,
+    #[serde(other)]
+    Unknown,
+}
+
+/// candle sizes, ordered from the shortest to the longest
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Scale {
     Minute,
     Minute5,
@@ -55,6 +342,57 @@ pub enum Scale {
     Week,
 }
 
+/// the monday after the unix epoch, where weekly candles start
+const FIRST_WEEK_START: i64 = 4 * 86400;
+
+impl Scale {
+    /// every scale, shortest first
+    pub fn all() -> [Scale; 8] {
+        [
+            Scale::Minute,
+            Scale::Minute5,
+            Scale::Minute15,
+            Scale::Minute30,
+            Scale::Hour,
+            Scale::Hour4,
+            Scale::Day,
+            Scale::Week,
+        ]
+    }
+
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.seconds() as u64)
+    }
+
+    fn seconds(&self) -> i64 {
+        match self {
+            Scale::Minute => 60,
+            Scale::Minute5 => 5 * 60,
+            Scale::Minute15 => 15 * 60,
+            Scale::Minute30 => 30 * 60,
+            Scale::Hour => 3600,
+            Scale::Hour4 => 4 * 3600,
+            Scale::Day => 86400,
+            Scale::Week => 7 * 86400,
+        }
+    }
+
+    /// how many whole candles fit in `range`
+    pub fn candles_in(&self, range: std::time::Duration) -> u64 {
+        range.as_secs() / self.seconds() as u64
+    }
+
+    /// the open time of the candle containing the unix timestamp `ts` in seconds, days start
+    /// at midnight UTC and weeks on monday
+    pub fn candle_start(&self, ts: i64) -> i64 {
+        let offset = match self {
+            Scale::Week => FIRST_WEEK_START,
+            _ => 0,
+        };
+        (ts - offset).div_euclid(self.seconds()) * self.seconds() + offset
+    }
+}
+
 impl Serialize for Scale {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -73,22 +411,348 @@ impl Serialize for Scale {
     }
 }
 
-impl std::string::ToString for Scale {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Scale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Scale::Minute => String::from("MINUTE"),
-            Scale::Minute5 => String::from("MINUTE_5"),
-            Scale::Minute15 => String::from("MINUTE_15"),
-            Scale::Minute30 => String::from("MINUTE_30"),
-            Scale::Hour => String::from("HOUR"),
-            Scale::Hour4 => String::from("HOUR4"),
-            Scale::Day => String::from("DAY"),
-            Scale::Week => String::from("WEEK"),
+            Scale::Minute => f.write_str("MINUTE"),
+            Scale::Minute5 => f.write_str("MINUTE_5"),
+            Scale::Minute15 => f.write_str("MINUTE_15"),
+            Scale::Minute30 => f.write_str("MINUTE_30"),
+            Scale::Hour => f.write_str("HOUR"),
+            Scale::Hour4 => f.write_str("HOUR4"),
+            Scale::Day => f.write_str("DAY"),
+            Scale::Week => f.write_str("WEEK"),
+        }
+    }
+}
+
+/// accepts the names the API uses, case insensitive, and the usual shorthands like `5m` or `4h`
+impl std::str::FromStr for Scale {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "MINUTE" | "1M" => Ok(Scale::Minute),
+            "MINUTE_5" | "5M" => Ok(Scale::Minute5),
+            "MINUTE_15" | "15M" => Ok(Scale::Minute15),
+            "MINUTE_30" | "30M" => Ok(Scale::Minute30),
+            "HOUR" | "1H" => Ok(Scale::Hour),
+            "HOUR4" | "4H" => Ok(Scale::Hour4),
+            "DAY" | "1D" => Ok(Scale::Day),
+            "WEEK" | "1W" => Ok(Scale::Week),
+            _ => Err(crate::Error::InvalidRequest(format!(
+                "unknown kline scale {}",
+                s
+            ))),
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+impl<'de> Deserialize<'de> for Scale {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// a single order as submitted by `Request::PendingOrder` and `Request::BatchPendingOrders`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NewOrder {
+    pub r#type: String,
+    pub kind: OrderKind,
+    pub symbol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<BigDecimal>,
+    pub amount: BigDecimal,
+}
+
+impl NewOrder {
+    /// build an order from typed parts, the price must be absent for market orders
+    pub fn new(
+        side: Side,
+        kind: OrderKind,
+        symbol: String,
+        price: Option<BigDecimal>,
+        amount: BigDecimal,
+    ) -> anyhow::Result<Self> {
+        let order = NewOrder {
+            r#type: side.code().to_string(),
+            kind,
+            symbol,
+            price,
+            amount,
+        };
+        order.validate()?;
+        Ok(order)
+    }
+
+    pub fn side(&self) -> Option<Side> {
+        match self.r#type.as_str() {
+            "0" => Some(Side::Ask),
+            "1" => Some(Side::Bid),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.kind.requires_price() != self.price.is_some() {
+            return Err(crate::Error::InvalidRequest(format!(
+                "{} order {} a price",
+                self.kind,
+                if self.kind.requires_price() {
+                    "requires"
+                } else {
+                    "must not carry"
+                }
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn formalize(&self) -> anyhow::Result<String> {
+        let mut formalized = String::new();
+        self.write_formalized(&mut formalized)?;
+        Ok(formalized)
+    }
+
+    /// `formalize` appended to `out`, see `Request::write_formalized`
+    pub fn write_formalized(&self, out: &mut String) -> anyhow::Result<()> {
+        self.validate()?;
+        let amount = Decimal(&self.amount);
+        match &self.price {
+            Some(price) => encoding::write_fields(
+                out,
+                &[
+                    &amount,
+                    &self.kind,
+                    &Decimal(price),
+                    &self.symbol,
+                    &self.r#type,
+                ],
+            ),
+            None => encoding::write_fields(out, &[&amount, &self.kind, &self.symbol, &self.r#type]),
+        }
+        Ok(())
+    }
+}
+
+impl NewOrder {
+    /// a builder which only compiles `build` once symbol, side, price or market and amount are set
+    ///
+    /// ```
+    /// # use fxdx_rs::request::{NewOrder, Side};
+    /// let order = NewOrder::builder()
+    ///     .symbol("BTC-USDT")
+    ///     .side(Side::Bid)
+    ///     .price("100.5".parse().unwrap())
+    ///     .post_only()
+    ///     .amount(2.into())
+    ///     .build();
+    /// ```
+    ///
+    /// ```compile_fail
+    /// # use fxdx_rs::request::{NewOrder, Side};
+    /// // no amount
+    /// let order = NewOrder::builder()
+    ///     .symbol("BTC-USDT")
+    ///     .side(Side::Bid)
+    ///     .market()
+    ///     .build();
+    /// ```
+    pub fn builder() -> OrderBuilder<Unset, Unset, Unset, Unset> {
+        OrderBuilder {
+            symbol: Unset,
+            side: Unset,
+            pricing: Unset,
+            amount: Unset,
+        }
+    }
+}
+
+impl From<NewOrder> for Request {
+    fn from(order: NewOrder) -> Self {
+        Request::PendingOrder(order)
+    }
+}
+
+/// a slot of `OrderBuilder` which was not set yet
+#[derive(Debug, Clone, Copy)]
+pub struct Unset;
+
+/// the pricing slot of an order resting at or crossing to a price
+#[derive(Debug, Clone)]
+pub struct Priced {
+    kind: OrderKind,
+    price: BigDecimal,
+}
+
+/// the pricing slot of a market order
+#[derive(Debug, Clone, Copy)]
+pub struct AtMarket;
+
+/// see `NewOrder::builder`, every slot is set exactly once
+#[derive(Debug, Clone)]
+pub struct OrderBuilder<S, D, P, A> {
+    symbol: S,
+    side: D,
+    pricing: P,
+    amount: A,
+}
+
+impl<D, P, A> OrderBuilder<Unset, D, P, A> {
+    pub fn symbol<T: Into<String>>(self, symbol: T) -> OrderBuilder<String, D, P, A> {
+        OrderBuilder {
+            symbol: symbol.into(),
+            side: self.side,
+            pricing: self.pricing,
+            amount: self.amount,
+        }
+    }
+}
+
+impl<S, P, A> OrderBuilder<S, Unset, P, A> {
+    pub fn side(self, side: Side) -> OrderBuilder<S, Side, P, A> {
+        OrderBuilder {
+            symbol: self.symbol,
+            side,
+            pricing: self.pricing,
+            amount: self.amount,
+        }
+    }
+}
+
+impl<S, D, A> OrderBuilder<S, D, Unset, A> {
+    /// a limit order at `price`, see `post_only`, `ioc` and `fok` for the other kinds
+    pub fn price(self, price: BigDecimal) -> OrderBuilder<S, D, Priced, A> {
+        OrderBuilder {
+            symbol: self.symbol,
+            side: self.side,
+            pricing: Priced {
+                kind: OrderKind::Limit,
+                price,
+            },
+            amount: self.amount,
+        }
+    }
+
+    pub fn market(self) -> OrderBuilder<S, D, AtMarket, A> {
+        OrderBuilder {
+            symbol: self.symbol,
+            side: self.side,
+            pricing: AtMarket,
+            amount: self.amount,
+        }
+    }
+}
+
+impl<S, D, A> OrderBuilder<S, D, Priced, A> {
+    pub fn post_only(mut self) -> Self {
+        self.pricing.kind = OrderKind::PostOnly;
+        self
+    }
+
+    pub fn ioc(mut self) -> Self {
+        self.pricing.kind = OrderKind::IOC;
+        self
+    }
+
+    pub fn fok(mut self) -> Self {
+        self.pricing.kind = OrderKind::FOK;
+        self
+    }
+}
+
+impl<S, D, P> OrderBuilder<S, D, P, Unset> {
+    pub fn amount(self, amount: BigDecimal) -> OrderBuilder<S, D, P, BigDecimal> {
+        OrderBuilder {
+            symbol: self.symbol,
+            side: self.side,
+            pricing: self.pricing,
+            amount,
+        }
+    }
+}
+
+impl OrderBuilder<String, Side, Priced, BigDecimal> {
+    pub fn build(self) -> NewOrder {
+        NewOrder {
+            r#type: self.side.code().to_string(),
+            kind: self.pricing.kind,
+            symbol: self.symbol,
+            price: Some(self.pricing.price),
+            amount: self.amount,
+        }
+    }
+}
+
+impl OrderBuilder<String, Side, AtMarket, BigDecimal> {
+    pub fn build(self) -> NewOrder {
+        NewOrder {
+            r#type: self.side.code().to_string(),
+            kind: OrderKind::Market,
+            symbol: self.symbol,
+            price: None,
+            amount: self.amount,
+        }
+    }
+}
+
+/// a request to an endpoint without a `Request` variant, see `FxdxClient::send_raw`
+///
+/// `uri` is the full path below the endpoint, e.g. `/maker/positions`, and is signed
+/// together with `formalized` exactly like a typed request
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawRequest {
+    pub method: reqwest::Method,
+    pub uri: String,
+    pub formalized: Option<String>,
+    pub body: Option<String>,
+    pub weight: u32,
+}
+
+impl RawRequest {
+    pub fn new(method: reqwest::Method, uri: String) -> Self {
+        RawRequest {
+            method,
+            uri,
+            formalized: None,
+            body: None,
+            weight: 1,
+        }
+    }
+
+    pub fn get(uri: String) -> Self {
+        Self::new(reqwest::Method::GET, uri)
+    }
+
+    pub fn post(uri: String) -> Self {
+        Self::new(reqwest::Method::POST, uri)
+    }
+
+    /// the parameters signed next to the uri, comma separated in the order of their names
+    pub fn formalized(mut self, formalized: String) -> Self {
+        self.formalized = Some(formalized);
+        self
+    }
+
+    pub fn body(mut self, body: String) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// rate limit weight, 1 unless set
+    pub fn weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(untagged)]
 pub enum Request {
@@ -98,13 +762,8 @@ pub enum Request {
         pubkey: String,
         signature: String,
     },
-    PendingOrder {
-        r#type: String,
-        symbol: String,
-        price: BigDecimal,
-        amount: BigDecimal,
-    },
-    BatchPendingOrders(Vec<Self>),
+    PendingOrder(NewOrder),
+    BatchPendingOrders(Vec<NewOrder>),
     CancelOrder {
         symbol: String,
         order_id: String,
@@ -117,61 +776,225 @@ pub enum Request {
         symbol: String,
         order_id: String,
     },
+    /// a page of the orders of `symbol`, only the open ones with `pending`
     OrderByPage {
         symbol: String,
-        page: i32,
-        size: i32,
+        #[serde(flatten)]
+        page: Page,
         pending: bool,
+        #[serde(skip_serializing_if = "OrderFilter::is_empty")]
+        filter: OrderFilter,
     },
     Balances,
+    AccountInfo,
+    /// `limit` asks for the top levels of each side only, `None` for the full book
     Depth {
         symbol: String,
+        limit: Option<u32>,
     },
     Kline {
         symbol: String,
         scale: Scale,
     },
     Symbols,
+    /// the latest public trades of `symbol`, newest first, `None` takes the exchange default
+    Trades {
+        symbol: String,
+        limit: Option<u32>,
+    },
+    /// fills of the key on `symbol`, newest first
+    MyTrades {
+        symbol: String,
+        #[serde(flatten)]
+        page: Page,
+    },
+    DepositAddress {
+        asset: String,
+    },
+    /// send `amount` of `asset` to an external address, needs `Permission::Withdraw`
+    Withdraw {
+        asset: String,
+        amount: BigDecimal,
+        address: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        memo: Option<String>,
+    },
+    DepositHistory {
+        asset: String,
+        #[serde(flatten)]
+        page: Page,
+    },
+    WithdrawalHistory {
+        asset: String,
+        #[serde(flatten)]
+        page: Page,
+    },
 }
 
 // FIXME: for more effective profermance we have to change the implemation into generic type
 impl Request {
+    /// build a `Request::PendingOrder` from typed parts, the price must be absent for market orders
+    pub fn order(
+        side: Side,
+        kind: OrderKind,
+        symbol: String,
+        price: Option<BigDecimal>,
+        amount: BigDecimal,
+    ) -> anyhow::Result<Self> {
+        Ok(Request::PendingOrder(NewOrder::new(
+            side, kind, symbol, price, amount,
+        )?))
+    }
+
     pub fn uri<P: Prefix>(&self) -> String {
+        let mut uri = String::new();
+        self.write_uri::<P>(&mut uri);
+        uri
+    }
+
+    /// `uri` appended to `out`; a hot loop clears and reuses one buffer, which stops
+    /// allocating once it has grown to the longest uri
+    pub fn write_uri<P: Prefix>(&self, out: &mut String) {
+        let path = |out: &mut String, segments: &[&dyn Display]| {
+            encoding::write_path(out, P::prefix(), segments)
+        };
         match self {
-            Request::Nonce => format!("/maker/nonce"),
-            Request::Token { .. } => format!("/{}/token", P::prefix()),
-            Request::PendingOrder { .. } => format!("/{}/order", P::prefix()),
-            Request::BatchPendingOrders { .. } => format!("/{}/orders", P::prefix()),
-            Request::CancelOrder { symbol, order_id } => {
-                format!("/{}/order/{}/{}", P::prefix(), symbol, order_id)
+            Request::Nonce => out.push_str("/maker/nonce"),
+            Request::Token { .. } => path(out, &[&"token"]),
+            Request::PendingOrder { .. } => path(out, &[&"order"]),
+            Request::BatchPendingOrders { .. } => path(out, &[&"orders"]),
+            Request::CancelOrder { symbol, order_id } | Request::OrderById { symbol, order_id } => {
+                path(out, &[&"order", symbol, order_id])
             }
             Request::BatchCancelOrders { symbol, order_ids } => {
-                format!("/{}/order/{}/{}", P::prefix(), symbol, order_ids.join("|"))
-            }
-            Request::OrderById { symbol, order_id } => {
-                format!("/{}/order/{}/{}", P::prefix(), symbol, order_id)
+                path(out, &[&"order", symbol, &Joined(order_ids, '|')])
             }
             Request::OrderByPage {
                 symbol,
                 page,
-                size,
                 pending,
-            } => format!(
-                "/{}/orders/{}/{}/{}/{}",
-                P::prefix(),
-                symbol,
-                page,
-                size,
-                pending
-            ),
-            Request::Balances => format!("/{}/balances", P::prefix()),
-            Request::Depth { symbol } => format!("/{}/depth/{}", P::prefix(), symbol),
-            Request::Kline { symbol, scale } => {
-                format!("/{}/kline/{}/{}", P::prefix(), symbol, scale.to_string())
+                filter,
+            } => {
+                path(out, &[&"orders", symbol, page, pending]);
+                filter.write_query(out);
             }
-            Request::Symbols => format!("/{}/symbols", P::prefix()),
+            Request::Balances => path(out, &[&"balances"]),
+            Request::AccountInfo => path(out, &[&"account"]),
+            Request::Depth {
+                symbol,
+                limit: None,
+            } => path(out, &[&"depth", symbol]),
+            Request::Depth {
+                symbol,
+                limit: Some(limit),
+            } => path(out, &[&"depth", symbol, limit]),
+            Request::Kline { symbol, scale } => path(out, &[&"kline", symbol, scale]),
+            Request::Symbols => path(out, &[&"symbols"]),
+            Request::Trades {
+                symbol,
+                limit: None,
+            } => path(out, &[&"trades", symbol]),
+            Request::Trades {
+                symbol,
+                limit: Some(limit),
+            } => path(out, &[&"trades", symbol, limit]),
+            Request::MyTrades { symbol, page } => path(out, &[&"fills", symbol, page]),
+            Request::DepositAddress { asset } => path(out, &[&"deposit", &"address", asset]),
+            Request::Withdraw { .. } => path(out, &[&"withdraw"]),
+            Request::DepositHistory { asset, page } => path(out, &[&"deposits", asset, page]),
+            Request::WithdrawalHistory { asset, page } => path(out, &[&"withdrawals", asset, page]),
+        }
+    }
+
+    /// the uri, formalized parameters and body of the request for signing and sending
+    /// elsewhere, see `encoding::sign`
+    pub fn encode<P: Prefix>(&self) -> anyhow::Result<encoding::Encoded> {
+        Ok(encoding::Encoded {
+            method: crate::transport::method(&self.method())?,
+            uri: self.uri::<P>(),
+            formalized: self.formalize()?,
+            payload: self.payload()?,
+        })
+    }
+
+    /// `encode` into the buffers of `out` with the body written by `wire`, what the client does
+    /// for every request; a reused `out` stops allocating for the uri and the formalized
+    /// parameters once it has grown to the longest request
+    pub fn encode_with<P: Prefix>(
+        &self,
+        wire: &crate::wire::WireProfile,
+        out: &mut encoding::Encoded,
+    ) -> anyhow::Result<()> {
+        out.method = crate::transport::method(&self.method())?;
+        out.uri.clear();
+        self.write_uri::<P>(&mut out.uri);
+        let mut formalized = out.formalized.take().unwrap_or_default();
+        formalized.clear();
+        out.formalized = self
+            .write_formalized(&mut formalized)?
+            .then_some(formalized);
+        out.payload = wire.payload(self)?;
+        Ok(())
+    }
+
+    /// how many rate limit tokens the request takes, full depth costs more than a balance
+    /// lookup and a batch weighs as much as its orders
+    pub fn weight(&self) -> u32 {
+        match self {
+            Request::BatchPendingOrders(orders) => orders.len().max(1) as u32,
+            Request::BatchCancelOrders { order_ids, .. } => order_ids.len().max(1) as u32,
+            Request::Depth {
+                limit: Some(limit), ..
+            } if *limit <= 20 => 1,
+            Request::Depth {
+                limit: Some(limit), ..
+            } if *limit <= 100 => 2,
+            Request::Depth { .. } => 5,
+            Request::Kline { .. }
+            | Request::OrderByPage { .. }
+            | Request::Symbols
+            | Request::Trades { .. }
+            | Request::MyTrades { .. } => 2,
+            _ => 1,
         }
     }
+
+    /// where the request queues on a saturated rate limiter, see `priority::with_priority`
+    /// to override it for a call
+    pub fn priority(&self) -> Priority {
+        match self {
+            Request::CancelOrder { .. } | Request::BatchCancelOrders { .. } => Priority::Critical,
+            Request::PendingOrder(_) | Request::BatchPendingOrders(_) => Priority::High,
+            Request::Kline { .. }
+            | Request::OrderByPage { .. }
+            | Request::MyTrades { .. }
+            | Request::Trades { .. }
+            | Request::DepositHistory { .. }
+            | Request::WithdrawalHistory { .. } => Priority::Bulk,
+            _ => Priority::Normal,
+        }
+    }
+
+    /// the key permission the request needs, `None` for public and handshake endpoints
+    pub fn permission(&self) -> Option<Permission> {
+        match self {
+            Request::PendingOrder(_)
+            | Request::BatchPendingOrders(_)
+            | Request::CancelOrder { .. }
+            | Request::BatchCancelOrders { .. } => Some(Permission::Trade),
+            Request::OrderById { .. }
+            | Request::OrderByPage { .. }
+            | Request::Balances
+            | Request::AccountInfo
+            | Request::MyTrades { .. }
+            | Request::DepositAddress { .. }
+            | Request::DepositHistory { .. }
+            | Request::WithdrawalHistory { .. } => Some(Permission::Read),
+            Request::Withdraw { .. } => Some(Permission::Withdraw),
+            _ => None,
+        }
+    }
+
     pub fn method(&self) -> reqwest::Method {
         match self {
             Request::Nonce => reqwest::Method::POST,
@@ -183,60 +1006,238 @@ impl Request {
             Request::OrderById { .. } => reqwest::Method::GET,
             Request::OrderByPage { .. } => reqwest::Method::GET,
             Request::Balances => reqwest::Method::GET,
+            Request::AccountInfo => reqwest::Method::GET,
             Request::Depth { .. } => reqwest::Method::GET,
             Request::Kline { .. } => reqwest::Method::GET,
             Request::Symbols => reqwest::Method::GET,
+            Request::Trades { .. } => reqwest::Method::GET,
+            Request::MyTrades { .. } => reqwest::Method::GET,
+            Request::DepositAddress { .. } => reqwest::Method::GET,
+            Request::Withdraw { .. } => reqwest::Method::POST,
+            Request::DepositHistory { .. } => reqwest::Method::GET,
+            Request::WithdrawalHistory { .. } => reqwest::Method::GET,
         }
     }
 
-    pub fn formalize(&self) -> Option<String> {
+    pub fn formalize(&self) -> anyhow::Result<Option<String>> {
+        let mut formalized = String::new();
+        Ok(self
+            .write_formalized(&mut formalized)?
+            .then_some(formalized))
+    }
+
+    /// `formalize` appended to `out`, false when the request has no parameters to sign
+    pub fn write_formalized(&self, out: &mut String) -> anyhow::Result<bool> {
+        let fields = |out: &mut String, values: &[&dyn Display]| {
+            encoding::write_fields(out, values);
+        };
         match self {
-            Request::PendingOrder {
-                r#type,
-                symbol,
-                price,
-                amount,
-            } => Some(format!("{},{},{},{}", amount, price, symbol, r#type)),
-            Request::BatchPendingOrders(orders) => Some(format!(
-                "{}",
-                orders
-                    .iter()
-                    .map(|o| o.formalize().unwrap()) // have to use the Request::PendingOrder varints else panic
-                    .collect::<Vec<String>>()
-                    .join(",")
-            )),
-            Request::CancelOrder { symbol, order_id } => Some(format!("{},{}", order_id, symbol)),
+            Request::PendingOrder(order) => order.write_formalized(out)?,
+            Request::BatchPendingOrders(orders) => {
+                if orders.is_empty() {
+                    return Err(crate::Error::InvalidRequest(String::from(
+                        "batch without any order",
+                    ))
+                    .into());
+                }
+                for (i, order) in orders.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    order.write_formalized(out)?;
+                }
+            }
+            Request::CancelOrder { symbol, order_id } | Request::OrderById { symbol, order_id } => {
+                fields(out, &[order_id, symbol])
+            }
             Request::BatchCancelOrders { symbol, order_ids } => {
-                Some(format!("{},{}", order_ids.join("|"), symbol))
+                fields(out, &[&Joined(order_ids, '|'), symbol])
             }
-            Request::OrderById { symbol, order_id } => Some(format!("{},{}", order_id, symbol)),
             Request::OrderByPage {
                 symbol,
                 page,
-                size,
                 pending,
-            } => Some(format!("{},{},{},{}", page, pending, size, symbol)),
-            Request::Depth { symbol } => Some(format!("{}", symbol)),
-            Request::Kline { symbol, scale } => Some(format!("{},{}", scale.to_string(), symbol)),
-            _ => None,
+                filter,
+            } => {
+                let (page, size) = (page.number(), page.size());
+                // the filters fall between the other fields by their names
+                let [end_time, side, start_time, status] = filter.fields().map(|(_, value)| value);
+                let values: [Option<&dyn Display>; 8] = [
+                    end_time.as_ref().map(|v| v as _),
+                    Some(&page),
+                    Some(pending),
+                    side.as_ref().map(|v| v as _),
+                    Some(&size),
+                    start_time.as_ref().map(|v| v as _),
+                    status.as_ref().map(|v| v as _),
+                    Some(symbol),
+                ];
+                for (i, value) in values.into_iter().flatten().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    let _ = write!(out, "{}", value);
+                }
+            }
+            Request::Depth {
+                symbol,
+                limit: None,
+            }
+            | Request::Trades {
+                symbol,
+                limit: None,
+            } => out.push_str(symbol),
+            Request::Depth {
+                symbol,
+                limit: Some(limit),
+            }
+            | Request::Trades {
+                symbol,
+                limit: Some(limit),
+            } => fields(out, &[limit, symbol]),
+            Request::Kline { symbol, scale } => fields(out, &[scale, symbol]),
+            Request::MyTrades { symbol, page } => {
+                fields(out, &[&page.number(), &page.size(), symbol])
+            }
+            Request::DepositAddress { asset } => out.push_str(asset),
+            // every field of a withdrawal is signed, the destination above all
+            Request::Withdraw {
+                asset,
+                amount,
+                address,
+                memo: None,
+            } => fields(out, &[address, amount, asset]),
+            Request::Withdraw {
+                asset,
+                amount,
+                address,
+                memo: Some(memo),
+            } => fields(out, &[address, amount, asset, memo]),
+            Request::DepositHistory { asset, page }
+            | Request::WithdrawalHistory { asset, page } => {
+                fields(out, &[asset, &page.number(), &page.size()])
+            }
+            _ => return Ok(false),
         }
+        Ok(true)
     }
 
+    /// the JSON body with the field names of the current API version, see `wire::WireProfile`
     pub fn payload(&self) -> anyhow::Result<Option<String>> {
         Ok(match self {
-            Request::Token { .. } | Request::PendingOrder { .. } => {
-                Some(serde_json::to_string(self)?)
-            }
+            Request::Token { .. } => Some(serde_json::to_string(self)?),
+            Request::PendingOrder(ref order) => Some(serde_json::to_string(order)?),
             Request::BatchPendingOrders(ref orders) => Some(serde_json::to_string(orders)?),
+            Request::Withdraw { .. } => Some(serde_json::to_string(self)?),
             _ => None,
         })
     }
 }
 
+/// `items` separated by `separator`, written without joining them into a `String` first
+struct Joined<'a>(&'a [String], char);
+
+impl Display for Joined<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_char(self.1)?;
+            }
+            f.write_str(item)?;
+        }
+        Ok(())
+    }
+}
+
+/// a price or amount written like the `Display` of `BigDecimal` from its digits on the stack,
+/// that one allocates several strings per value; larger values and formatting flags fall
+/// back to it
+pub(crate) struct Decimal<'a>(pub(crate) &'a BigDecimal);
+
+impl Display for Decimal<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use bigdecimal::num_bigint::Sign;
+        use bigdecimal::ToPrimitive;
+
+        let (int, scale) = self.0.as_bigint_and_exponent();
+        let magnitude = int.magnitude().to_u64();
+        let plain = f.width().is_none() && f.precision().is_none() && !f.sign_plus();
+        let Some(mut magnitude) = magnitude.filter(|_| plain) else {
+            return Display::fmt(self.0, f);
+        };
+        let mut buf = [0u8; 20];
+        let mut start = buf.len();
+        loop {
+            start -= 1;
+            buf[start] = b'0' + (magnitude % 10) as u8;
+            magnitude /= 10;
+            if magnitude == 0 {
+                break;
+            }
+        }
+        let digits = std::str::from_utf8(&buf[start..]).map_err(|_| std::fmt::Error)?;
+        let zeros =
+            |f: &mut std::fmt::Formatter<'_>, n: i64| (0..n).try_for_each(|_| f.write_char('0'));
+        if int.sign() == Sign::Minus {
+            f.write_char('-')?;
+        }
+        let len = digits.len() as i64;
+        if scale >= len {
+            f.write_str("0.")?;
+            zeros(f, scale - len)?;
+            f.write_str(digits)
+        } else if scale <= 0 {
+            f.write_str(digits)?;
+            zeros(f, -scale)
+        } else {
+            let (before, after) = digits.split_at((len - scale) as usize);
+            f.write_str(before)?;
+            f.write_char('.')?;
+            f.write_str(after)
+        }
+    }
+}
+
+/// the string the `serde` feature of `bigdecimal` writes
+impl Serialize for Decimal<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_decimal_like_display() {
+        for value in [
+            "0",
+            "7",
+            "100",
+            "30000.125",
+            "-0.5",
+            "0.001",
+            "-123.45",
+            "1e3",
+            "-25e2",
+            "18446744073709551615.5",
+            "-184467440737095516150",
+            "1.10",
+        ] {
+            let value: BigDecimal = value.parse().unwrap();
+            assert_eq!(Decimal(&value).to_string(), value.to_string());
+            assert_eq!(
+                format!("{:>12.1}", Decimal(&value)),
+                format!("{:>12.1}", value)
+            );
+            assert_eq!(
+                serde_json::to_string(&Decimal(&value)).unwrap(),
+                serde_json::to_string(&value).unwrap()
+            );
+        }
+    }
+
     struct MockClient<T> {
         _marker: std::marker::PhantomData<T>,
     }
@@ -261,4 +1262,474 @@ mod tests {
         let client = MockClient::<PrivPub>::new();
         assert_eq!(client.prefix(), "/maker")
     }
+
+    #[test]
+    fn test_order_kind() {
+        let limit = Request::order(
+            Side::Bid,
+            OrderKind::PostOnly,
+            String::from("BTC-USDT"),
+            Some(BigDecimal::from(100)),
+            BigDecimal::from(2),
+        )
+        .unwrap();
+        assert_eq!(
+            limit.formalize().unwrap().unwrap(),
+            "2,POST_ONLY,100,BTC-USDT,1"
+        );
+        assert_eq!(
+            limit.payload().unwrap().unwrap(),
+            r#"{"type":"1","kind":"POST_ONLY","symbol":"BTC-USDT","price":"100","amount":"2"}"#
+        );
+
+        let market = Request::order(
+            Side::Ask,
+            OrderKind::Market,
+            String::from("BTC-USDT"),
+            None,
+            BigDecimal::from(2),
+        )
+        .unwrap();
+        assert_eq!(market.formalize().unwrap().unwrap(), "2,MARKET,BTC-USDT,0");
+        assert_eq!(
+            market.payload().unwrap().unwrap(),
+            r#"{"type":"0","kind":"MARKET","symbol":"BTC-USDT","amount":"2"}"#
+        );
+
+        assert!(Request::order(
+            Side::Ask,
+            OrderKind::Market,
+            String::from("BTC-USDT"),
+            Some(BigDecimal::from(100)),
+            BigDecimal::from(2),
+        )
+        .is_err());
+        assert!(Request::order(
+            Side::Ask,
+            OrderKind::IOC,
+            String::from("BTC-USDT"),
+            None,
+            BigDecimal::from(2),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_batch_formalize() {
+        let order = |price: Option<i64>| NewOrder {
+            r#type: String::from("1"),
+            kind: OrderKind::Limit,
+            symbol: String::from("BTC-USDT"),
+            price: price.map(BigDecimal::from),
+            amount: BigDecimal::from(1),
+        };
+        let batch = Request::BatchPendingOrders(vec![order(Some(10)), order(Some(11))]);
+        assert_eq!(
+            batch.formalize().unwrap().unwrap(),
+            "1,LIMIT,10,BTC-USDT,1,1,LIMIT,11,BTC-USDT,1"
+        );
+        assert!(
+            Request::BatchPendingOrders(vec![order(Some(10)), order(None)])
+                .formalize()
+                .is_err()
+        );
+        assert!(Request::BatchPendingOrders(vec![]).formalize().is_err());
+    }
+
+    #[test]
+    fn test_withdraw_signs_every_field() {
+        let withdraw = Request::Withdraw {
+            asset: String::from("USDT"),
+            amount: BigDecimal::from(25),
+            address: String::from("0xdead"),
+            memo: Some(String::from("42")),
+        };
+        assert_eq!(withdraw.formalize().unwrap().unwrap(), "0xdead,25,USDT,42");
+        assert_eq!(withdraw.permission(), Some(Permission::Withdraw));
+        assert_eq!(
+            withdraw.payload().unwrap().unwrap(),
+            r#"{"asset":"USDT","amount":"25","address":"0xdead","memo":"42"}"#
+        );
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let full = Request::Depth {
+            symbol: String::from("BTC-USDT"),
+            limit: None,
+        };
+        let top = Request::Depth {
+            symbol: String::from("BTC-USDT"),
+            limit: Some(5),
+        };
+        assert_eq!(full.uri::<PrivPub>(), "//maker/depth/BTC-USDT");
+        assert_eq!(top.uri::<PrivPub>(), "//maker/depth/BTC-USDT/5");
+        assert_eq!(top.formalize().unwrap().unwrap(), "5,BTC-USDT");
+        assert!(top.weight() < full.weight());
+
+        let encoded = top.encode::<PrivPub>().unwrap();
+        assert_eq!(encoded.method, encoding::Method::Get);
+        assert_eq!(
+            (encoded.uri.as_str(), encoded.formalized.as_deref()),
+            ("//maker/depth/BTC-USDT/5", Some("5,BTC-USDT"))
+        );
+    }
+
+    #[test]
+    fn test_write_into_reused_buffer() {
+        let cancel = Request::BatchCancelOrders {
+            symbol: String::from("BTC-USDT"),
+            order_ids: vec![String::from("1"), String::from("2")],
+        };
+        let mut buf = String::new();
+        cancel.write_uri::<PrivPub>(&mut buf);
+        assert_eq!(buf, "//maker/order/BTC-USDT/1|2");
+        assert_eq!(buf, cancel.uri::<PrivPub>());
+        let capacity = buf.capacity();
+        buf.clear();
+        assert!(cancel.write_formalized(&mut buf).unwrap());
+        assert_eq!(buf, "1|2,BTC-USDT");
+        // the buffer was large enough, nothing was allocated
+        assert_eq!(buf.capacity(), capacity);
+        buf.clear();
+        assert!(!Request::Balances.write_formalized(&mut buf).unwrap());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_encode_with_reused_buffers() {
+        let wire = crate::wire::WireProfile::default();
+        let cancel = Request::CancelOrder {
+            symbol: String::from("BTC-USDT"),
+            order_id: String::from("17"),
+        };
+        let order = Request::order(
+            Side::Bid,
+            OrderKind::Limit,
+            String::from("BTC-USDT"),
+            Some(BigDecimal::from(100)),
+            BigDecimal::from(1),
+        )
+        .unwrap();
+        let mut encoded = encoding::Encoded::default();
+        for req in [&order, &cancel, &Request::Balances, &cancel] {
+            req.encode_with::<PrivPub>(&wire, &mut encoded).unwrap();
+            let fresh = req.encode::<PrivPub>().unwrap();
+            assert_eq!(
+                (encoded.method, &encoded.uri, &encoded.formalized),
+                (fresh.method, &fresh.uri, &fresh.formalized)
+            );
+            assert_eq!(encoded.payload, wire.payload(req).unwrap());
+        }
+        let uri = encoded.uri.capacity();
+        cancel.encode_with::<PrivPub>(&wire, &mut encoded).unwrap();
+        assert_eq!(encoded.uri.capacity(), uri);
+    }
+
+    #[test]
+    fn test_order_filter_is_signed() {
+        let page = |filter| Request::OrderByPage {
+            symbol: String::from("BTC-USDT"),
+            page: Page::first(50).unwrap(),
+            pending: false,
+            filter,
+        };
+        let all = page(OrderFilter::default());
+        assert_eq!(all.uri::<PrivPub>(), "//maker/orders/BTC-USDT/1/50/false");
+        assert_eq!(all.formalize().unwrap().unwrap(), "1,false,50,BTC-USDT");
+
+        let filled = page(OrderFilter {
+            status: Some(OrderStatus::Dealed),
+            side: Some(Side::Bid),
+            start_time: Some(1700000000),
+            end_time: None,
+        });
+        assert_eq!(
+            filled.uri::<PrivPub>(),
+            "//maker/orders/BTC-USDT/1/50/false?side=1&start_time=1700000000&status=3"
+        );
+        assert_eq!(
+            filled.formalize().unwrap().unwrap(),
+            "1,false,1,50,1700000000,3,BTC-USDT"
+        );
+    }
+
+    #[test]
+    fn test_page_bounds() {
+        assert!(Page::new(0, 10).is_err());
+        assert!(Page::first(0).is_err());
+        assert!(Page::first(MAX_PAGE_SIZE + 1).is_err());
+        let page = Page::first(20).unwrap().next();
+        assert_eq!((page.number(), page.offset()), (2, 20));
+        assert!(page.is_last(19) && !page.is_last(20));
+
+        let fills = Request::MyTrades {
+            symbol: String::from("BTC-USDT"),
+            page,
+        };
+        assert_eq!(fills.uri::<PrivPub>(), "//maker/fills/BTC-USDT/2/20");
+        assert_eq!(fills.formalize().unwrap().unwrap(), "2,20,BTC-USDT");
+        assert_eq!(
+            serde_json::to_value(&fills).unwrap(),
+            serde_json::json!({"symbol": "BTC-USDT", "page": 2, "size": 20})
+        );
+    }
+
+    #[test]
+    fn test_scale_from_str() {
+        for scale in [Scale::Minute, Scale::Minute15, Scale::Hour4, Scale::Week] {
+            assert_eq!(scale.to_string().parse::<Scale>().unwrap(), scale);
+        }
+        assert_eq!("5m".parse::<Scale>().unwrap(), Scale::Minute5);
+        assert_eq!("day".parse::<Scale>().unwrap(), Scale::Day);
+        assert!("MINUTE_3".parse::<Scale>().is_err());
+        assert_eq!(
+            serde_json::from_str::<Scale>(r#""1h""#).unwrap(),
+            Scale::Hour
+        );
+    }
+
+    #[test]
+    fn test_scale_durations() {
+        let all = Scale::all();
+        assert!(all
+            .windows(2)
+            .all(|w| w[0] < w[1] && w[0].duration() < w[1].duration()));
+        assert_eq!(
+            Scale::Hour4.duration(),
+            std::time::Duration::from_secs(14400)
+        );
+        assert_eq!(
+            Scale::Minute15.candles_in(std::time::Duration::from_secs(3599)),
+            3
+        );
+        // 2024-01-03 12:00 UTC, a wednesday
+        let ts = 1704283200;
+        assert_eq!(Scale::Hour4.candle_start(ts + 3600), ts);
+        assert_eq!(Scale::Day.candle_start(ts), 1704240000);
+        assert_eq!(Scale::Week.candle_start(ts), 1704067200);
+    }
+
+    #[test]
+    fn test_order_builder() {
+        let order = NewOrder::builder()
+            .side(Side::Ask)
+            .amount(BigDecimal::from(1))
+            .symbol("BTC-USDT")
+            .price(BigDecimal::from(100))
+            .fok()
+            .build();
+        assert_eq!(
+            order,
+            NewOrder::new(
+                Side::Ask,
+                OrderKind::FOK,
+                String::from("BTC-USDT"),
+                Some(BigDecimal::from(100)),
+                BigDecimal::from(1),
+            )
+            .unwrap()
+        );
+        let market = NewOrder::builder()
+            .symbol("BTC-USDT")
+            .side(Side::Bid)
+            .market()
+            .amount(BigDecimal::from(1))
+            .build();
+        assert!(market.validate().is_ok());
+        assert!(matches!(Request::from(market), Request::PendingOrder(o) if o.price.is_none()));
+    }
+
+    #[test]
+    fn test_side() {
+        assert_eq!("buy".parse::<Side>().unwrap(), Side::Bid);
+        assert_eq!("0".parse::<Side>().unwrap(), Side::Ask);
+        assert_eq!(Side::try_from(1).unwrap(), Side::Bid);
+        assert!(Side::try_from(2).is_err());
+        assert_eq!(Side::Ask.to_string(), "ASK");
+        assert_eq!(Side::Bid.opposite(), Side::Ask);
+        assert_eq!(serde_json::from_str::<Side>("1").unwrap(), Side::Bid);
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+        use serde_json::Value;
+
+        fn decimal() -> impl Strategy<Value = BigDecimal> {
+            (0u64..10_000_000_000, 0i64..9)
+                .prop_map(|(digits, scale)| BigDecimal::new(digits.into(), scale))
+        }
+
+        fn symbol() -> impl Strategy<Value = String> {
+            "[A-Z0-9]{1,6}-[A-Z0-9]{1,6}"
+        }
+
+        fn page() -> impl Strategy<Value = Page> {
+            (1..1000, 1..=MAX_PAGE_SIZE).prop_map(|(number, size)| Page::new(number, size).unwrap())
+        }
+
+        fn filter() -> impl Strategy<Value = OrderFilter> {
+            (
+                proptest::option::of(prop_oneof![
+                    Just(OrderStatus::Undeal),
+                    Just(OrderStatus::Cancel),
+                    Just(OrderStatus::Dealed),
+                    Just(OrderStatus::PartialDealed),
+                ]),
+                proptest::option::of(prop_oneof![Just(Side::Ask), Just(Side::Bid)]),
+                proptest::option::of(0..i64::from(u32::MAX)),
+                proptest::option::of(0..i64::from(u32::MAX)),
+            )
+                .prop_map(|(status, side, start_time, end_time)| OrderFilter {
+                    status,
+                    side,
+                    start_time,
+                    end_time,
+                })
+        }
+
+        fn order() -> impl Strategy<Value = NewOrder> {
+            (
+                prop_oneof![Just(Side::Ask), Just(Side::Bid)],
+                prop_oneof![
+                    Just(OrderKind::Limit),
+                    Just(OrderKind::Market),
+                    Just(OrderKind::PostOnly),
+                    Just(OrderKind::IOC),
+                    Just(OrderKind::FOK),
+                ],
+                symbol(),
+                decimal(),
+                decimal(),
+            )
+                .prop_map(|(side, kind, symbol, price, amount)| {
+                    let price = kind.requires_price().then_some(price);
+                    NewOrder::new(side, kind, symbol, price, amount).unwrap()
+                })
+        }
+
+        /// every request with signed fields
+        fn request() -> impl Strategy<Value = Request> {
+            let id = "[0-9]{1,12}";
+            prop_oneof![
+                order().prop_map(Request::PendingOrder),
+                (symbol(), id)
+                    .prop_map(|(symbol, order_id)| Request::CancelOrder { symbol, order_id }),
+                (symbol(), proptest::collection::vec(id, 1..MAX_BATCH_CANCEL)).prop_map(
+                    |(symbol, order_ids)| Request::BatchCancelOrders { symbol, order_ids }
+                ),
+                (symbol(), id)
+                    .prop_map(|(symbol, order_id)| Request::OrderById { symbol, order_id }),
+                (symbol(), page(), any::<bool>(), filter()).prop_map(
+                    |(symbol, page, pending, filter)| Request::OrderByPage {
+                        symbol,
+                        page,
+                        pending,
+                        filter,
+                    }
+                ),
+                (symbol(), proptest::option::of(1..1000u32))
+                    .prop_map(|(symbol, limit)| Request::Depth { symbol, limit }),
+                (symbol(), proptest::sample::select(Scale::all().to_vec()))
+                    .prop_map(|(symbol, scale)| Request::Kline { symbol, scale }),
+                (symbol(), proptest::option::of(1..1000u32))
+                    .prop_map(|(symbol, limit)| Request::Trades { symbol, limit }),
+                (symbol(), page()).prop_map(|(symbol, page)| Request::MyTrades { symbol, page }),
+                (
+                    "[A-Z]{2,5}",
+                    decimal(),
+                    "0x[0-9a-f]{8}",
+                    proptest::option::of("[a-z0-9]{1,8}")
+                )
+                    .prop_map(|(asset, amount, address, memo)| Request::Withdraw {
+                        asset,
+                        amount,
+                        address,
+                        memo,
+                    }),
+                ("[A-Z]{2,5}", page())
+                    .prop_map(|(asset, page)| Request::DepositHistory { asset, page }),
+                ("[A-Z]{2,5}", page())
+                    .prop_map(|(asset, page)| Request::WithdrawalHistory { asset, page }),
+            ]
+        }
+
+        fn leaf(value: &Value) -> String {
+            match value {
+                Value::String(s) => s.clone(),
+                Value::Array(items) => items.iter().map(leaf).collect::<Vec<_>>().join("|"),
+                value => value.to_string(),
+            }
+        }
+
+        /// the fields of the payload by name, nested objects flattened and nulls left out
+        fn fields(payload: &Value, out: &mut Vec<(String, String)>) {
+            for (name, value) in payload.as_object().into_iter().flatten() {
+                match value {
+                    Value::Null => {}
+                    Value::Object(_) => fields(value, out),
+                    value => out.push((name.clone(), leaf(value))),
+                }
+            }
+        }
+
+        proptest! {
+            /// the signing contract: the formalized string is the payload fields in the order
+            /// of their names joined by commas
+            #[test]
+            fn formalized_is_payload_by_name(req in request()) {
+                let mut expected = vec![];
+                fields(&serde_json::to_value(&req).unwrap(), &mut expected);
+                expected.sort();
+                let expected = expected.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+                prop_assert_eq!(req.formalize().unwrap().unwrap(), expected.join(","));
+            }
+
+            /// the uri of a query carries every field of the payload, the path segments in
+            /// a fixed order and the filters as a query string sorted by name
+            #[test]
+            fn uri_carries_the_payload(req in request()) {
+                let uri = req.uri::<PrivPub>();
+                let mut reused = String::from("/stale/uri");
+                reused.clear();
+                req.write_uri::<PrivPub>(&mut reused);
+                prop_assert_eq!(&reused, &uri);
+                prop_assert!(!uri.contains(char::is_whitespace));
+
+                let (path, query) = uri.split_once('?').unwrap_or((&uri, ""));
+                let segments = path.strip_prefix("//maker/").unwrap().split('/').collect::<Vec<_>>();
+                prop_assert!(segments.iter().all(|segment| !segment.is_empty()));
+                let names = query.split('&').filter(|q| !q.is_empty()).map(|q| q.split_once('=').unwrap());
+                let names = names.collect::<Vec<_>>();
+                prop_assert!(names.windows(2).all(|w| w[0].0 < w[1].0));
+
+                if !matches!(req, Request::PendingOrder(_) | Request::Withdraw { .. }) {
+                    let mut expected = vec![];
+                    fields(&serde_json::to_value(&req).unwrap(), &mut expected);
+                    let mut expected = expected.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+                    let mut carried = segments[1..].iter().map(|s| s.to_string()).collect::<Vec<_>>();
+                    carried.extend(names.iter().map(|(_, v)| v.to_string()));
+                    expected.sort();
+                    carried.sort();
+                    prop_assert_eq!(carried, expected);
+                }
+            }
+
+            /// decimals reach the payload exactly, whatever their scale
+            #[test]
+            fn payload_keeps_decimals(order in order()) {
+                let payload = serde_json::to_value(Request::PendingOrder(order.clone())).unwrap();
+                let amount = payload["amount"].as_str().unwrap().parse::<BigDecimal>().unwrap();
+                prop_assert_eq!(amount, order.amount);
+                match &order.price {
+                    Some(price) => prop_assert_eq!(
+                        &payload["price"].as_str().unwrap().parse::<BigDecimal>().unwrap(),
+                        price
+                    ),
+                    None => prop_assert!(payload.get("price").is_none()),
+                }
+            }
+        }
+    }
 }
@@ -1,7 +1,8 @@
-use anyhow::Result;
-use bigdecimal::BigDecimal;
-use serde::Deserialize;
-use serde_repr::Deserialize_repr;
+use crate::de;
+pub use crate::request::Side;
+use crate::rounding::{self, RoundingMode};
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 
 pub trait Success {
@@ -14,149 +15,1152 @@ impl Success for i32 {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize_repr)]
-#[repr(u8)]
-pub enum Direction {
-    Ask = 0,
-    Bid = 1,
+/// the `{ code, data }` envelope every endpoint answers with
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiResponse<T> {
+    #[serde(deserialize_with = "de::number")]
+    pub code: i32,
+    pub data: Option<T>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct NonceResponse {
-    pub code: i32,
-    pub data: Option<String>,
+impl<T> ApiResponse<T> {
+    pub fn is_success(&self) -> bool {
+        self.code.is_success()
+    }
+
+    /// the data of a successful response, a rejection or a missing `data` is an error
+    pub fn into_result(self) -> Result<T, crate::Error> {
+        match (self.is_success(), self.data) {
+            (false, _) => Err(crate::Error::Rejected(self.code)),
+            (true, None) => Err(crate::Error::MissingData),
+            (true, Some(data)) => Ok(data),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct TokenResponse {
-    pub code: i32,
-    pub data: Option<String>,
+impl<T: Default> ApiResponse<T> {
+    /// like `into_result` for lists, where a successful response without `data` is empty
+    pub fn into_result_or_default(self) -> Result<T, crate::Error> {
+        if !self.is_success() {
+            return Err(crate::Error::Rejected(self.code));
+        }
+        Ok(self.data.unwrap_or_default())
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct PendingOrderResponse {
-    pub code: i32,
-    pub data: Option<String>,
+#[deprecated(note = "use `Side`")]
+pub type Direction = Side;
+
+pub type NonceResponse = ApiResponse<String>;
+
+pub type TokenResponse = ApiResponse<SessionToken>;
+
+/// the token of the handshake, bare or with its expiry in unix seconds
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SessionToken {
+    Bare(String),
+    Expiring {
+        token: String,
+        #[serde(rename = "expiresAt", default, deserialize_with = "de::option_number")]
+        expires_at: Option<i64>,
+    },
 }
 
-#[derive(Debug, Deserialize)]
-pub struct BatchPendingOrdersResponse {
-    pub code: i32,
-    pub data: Option<Vec<String>>,
+impl SessionToken {
+    pub fn into_parts(self) -> (String, Option<i64>) {
+        match self {
+            SessionToken::Bare(token) => (token, None),
+            SessionToken::Expiring { token, expires_at } => (token, expires_at),
+        }
+    }
+}
+
+pub type PendingOrderResponse = ApiResponse<String>;
+
+/// an order id for every accepted order, `null` or an empty id for the rejected ones
+pub type BatchPendingOrdersResponse = ApiResponse<Vec<Option<String>>>;
+
+#[derive(Debug, PartialEq)]
+pub enum BatchOutcome {
+    Placed(String),
+    Failed(String),
 }
 
-#[derive(Debug, Deserialize)]
-pub struct CancelOrderResponse {
+#[derive(Debug)]
+pub struct BatchItem {
+    pub order: crate::request::NewOrder,
+    pub outcome: BatchOutcome,
+}
+
+/// every submitted order paired with its outcome, in submission order
+#[derive(Debug)]
+pub struct BatchResult {
     pub code: i32,
-    pub data: Option<String>,
+    pub items: Vec<BatchItem>,
+}
+
+impl BatchResult {
+    /// pair the submitted orders with the ids the exchange returned, which come back in the same order
+    ///
+    /// when the number of ids differs from the number of orders no id can be trusted to belong to
+    /// its order, nor any order be taken as rejected: `Error::BatchMismatch` carries the ids
+    pub fn correlate(
+        orders: Vec<crate::request::NewOrder>,
+        resp: BatchPendingOrdersResponse,
+    ) -> anyhow::Result<Self> {
+        let outcomes = match resp.data {
+            Some(ids) if ids.len() != orders.len() => {
+                return Err(crate::Error::BatchMismatch {
+                    submitted: orders.len(),
+                    returned: ids.len(),
+                    ids,
+                }
+                .into());
+            }
+            Some(ids) => ids
+                .into_iter()
+                .map(|id| match id {
+                    Some(id) if !id.is_empty() => BatchOutcome::Placed(id),
+                    _ => BatchOutcome::Failed(format!("rejected with code {}", resp.code)),
+                })
+                .collect::<Vec<_>>(),
+            None => (0..orders.len())
+                .map(|_| BatchOutcome::Failed(format!("batch rejected with code {}", resp.code)))
+                .collect(),
+        };
+        Ok(BatchResult {
+            code: resp.code,
+            items: orders
+                .into_iter()
+                .zip(outcomes)
+                .map(|(order, outcome)| BatchItem { order, outcome })
+                .collect(),
+        })
+    }
+
+    pub fn placed(&self) -> impl Iterator<Item = (&crate::request::NewOrder, &str)> {
+        self.items.iter().filter_map(|i| match &i.outcome {
+            BatchOutcome::Placed(id) => Some((&i.order, id.as_str())),
+            BatchOutcome::Failed(_) => None,
+        })
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = (&crate::request::NewOrder, &str)> {
+        self.items.iter().filter_map(|i| match &i.outcome {
+            BatchOutcome::Failed(reason) => Some((&i.order, reason.as_str())),
+            BatchOutcome::Placed(_) => None,
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.failed().next().is_none()
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct BatchCancelOrdersResponse {
+pub type CancelOrderResponse = ApiResponse<String>;
+
+pub type BatchCancelOrdersResponse = ApiResponse<BatchCancelData>;
+
+/// the data of a batch cancel: an outcome per order, or the `|` joined ids of the cancelled
+/// orders older gateways answer with
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum BatchCancelData {
+    Outcomes(Vec<BatchCancelItem>),
+    Cancelled(String),
+}
+
+impl Default for BatchCancelData {
+    fn default() -> Self {
+        BatchCancelData::Cancelled(String::new())
+    }
+}
+
+impl BatchCancelData {
+    /// the outcome reported for `order_id`, `None` if the response does not mention it: a
+    /// legacy reply only lists the cancelled ids, the others may be filled or unknown
+    pub fn outcome(&self, order_id: &str) -> Option<CancelOutcome> {
+        match self {
+            BatchCancelData::Outcomes(items) => items
+                .iter()
+                .find(|item| item.order_id == order_id)
+                .map(|item| item.outcome),
+            BatchCancelData::Cancelled(ids) => ids
+                .split('|')
+                .any(|id| id == order_id)
+                .then_some(CancelOutcome::Cancelled),
+        }
+    }
+}
+
+/// what a cancel did to an order
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelOutcome {
+    Cancelled,
+    /// filled before the cancel reached it
+    AlreadyFilled,
+    /// no open order has the id, it was cancelled before or never placed
+    NotFound,
+    /// still open, the cancel did not reach it and can be sent again
+    Open,
+}
+
+impl std::fmt::Display for CancelOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CancelOutcome::Cancelled => "cancelled",
+            CancelOutcome::AlreadyFilled => "already filled",
+            CancelOutcome::NotFound => "not found",
+            CancelOutcome::Open => "still open",
+        })
+    }
+}
+
+impl CancelOutcome {
+    /// the outcome of an order the cancel did not report on, from the status it has now
+    pub fn from_status(status: crate::request::OrderStatus) -> Self {
+        match status {
+            crate::request::OrderStatus::Cancel => CancelOutcome::Cancelled,
+            crate::request::OrderStatus::Dealed => CancelOutcome::AlreadyFilled,
+            _ => CancelOutcome::Open,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchCancelItem {
+    #[serde(deserialize_with = "de::string")]
+    pub order_id: String,
+    pub outcome: CancelOutcome,
+}
+
+/// every order id of a batch cancel with its outcome, in submission order
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchCancelResult {
     pub code: i32,
-    pub data: Option<String>,
+    pub items: Vec<BatchCancelItem>,
 }
 
-#[derive(Debug, Deserialize)]
+impl BatchCancelResult {
+    pub fn cancelled(&self) -> impl Iterator<Item = &str> {
+        self.items
+            .iter()
+            .filter(|item| item.outcome == CancelOutcome::Cancelled)
+            .map(|item| item.order_id.as_str())
+    }
+
+    pub fn outcome(&self, order_id: &str) -> Option<CancelOutcome> {
+        self.items
+            .iter()
+            .find(|item| item.order_id == order_id)
+            .map(|item| item.outcome)
+    }
+
+    /// true if every order is cancelled
+    pub fn is_complete(&self) -> bool {
+        self.items
+            .iter()
+            .all(|item| item.outcome == CancelOutcome::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancelledOrder {
+    pub symbol: String,
+    pub order_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedCancel {
+    pub symbol: String,
+    pub order_id: String,
+    pub reason: String,
+}
+
+/// what `cancel_all_orders` managed to cancel
+#[derive(Debug, Default)]
+pub struct CancelAllSummary {
+    pub cancelled: Vec<CancelledOrder>,
+    pub failed: Vec<FailedCancel>,
+}
+
+impl CancelAllSummary {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// balances, open orders and recent fills read together by `account_snapshot`, e.g. to
+/// reconcile on startup
+#[derive(Debug, Clone)]
+pub struct AccountSnapshot {
+    /// unix milliseconds the requests were sent at, nothing older than this is missing
+    pub taken_at: i64,
+    pub balances: Vec<Balance>,
+    /// every open order by symbol
+    pub open_orders: std::collections::HashMap<String, Vec<QueryOrder>>,
+    /// the latest `request::RECENT_FILLS` fills by symbol, newest first
+    pub fills: std::collections::HashMap<String, Vec<Trade>>,
+}
+
+impl AccountSnapshot {
+    pub fn open_order_count(&self) -> usize {
+        self.open_orders.values().map(Vec::len).sum()
+    }
+}
+
+/// how the order behind a `cancel_verified` ended up
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CancelVerification {
+    /// confirmed cancelled, possibly after a partial fill
+    Cancelled,
+    /// filled before the cancel reached it
+    Filled,
+    /// still not terminal when the timeout expired, with the last status seen
+    TimedOut(crate::request::OrderStatus),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReplaceOutcome {
+    /// cancelled and the replacement was accepted
+    Replaced,
+    /// the original was filled before it could be cancelled, nothing was placed
+    AlreadyFilled,
+    /// the cancel was refused, nothing was placed to avoid doubling the exposure
+    CancelRejected,
+    /// cancelled but the replacement was refused
+    PlaceRejected,
+}
+
+#[derive(Debug)]
+pub struct ReplaceResult {
+    pub outcome: ReplaceOutcome,
+    /// `None` when the cancel was skipped because the original had already filled
+    pub cancel: Option<CancelOrderResponse>,
+    pub new_order_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
+    #[serde(deserialize_with = "de::number")]
     pub base: i32,
+    #[serde(deserialize_with = "de::number")]
     pub quote: i32,
-    pub ask_or_bid: Direction,
+    #[serde(deserialize_with = "de::number")]
+    pub ask_or_bid: Side,
     pub price: BigDecimal,
     pub amount: BigDecimal,
     pub quote_amount: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub quote_fee: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub base_fee: BigDecimal,
+    #[serde(deserialize_with = "de::number")]
+    pub timestamp: i64,
+}
+
+pub type MyTradesResponse = ApiResponse<Vec<Trade>>;
+
+/// an executed print of the public trade feed, `ask_or_bid` is the taker side
+#[derive(Debug, Clone, Deserialize)]
+pub struct PublicTrade {
+    #[serde(deserialize_with = "de::number")]
+    pub id: i64,
+    #[serde(deserialize_with = "de::number")]
+    pub ask_or_bid: Side,
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+    #[serde(deserialize_with = "de::number")]
     pub timestamp: i64,
 }
 
-#[derive(Debug, Deserialize)]
+pub type TradesResponse = ApiResponse<Vec<PublicTrade>>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepositAddress {
+    pub asset: String,
+    pub address: String,
+    /// tag or memo some chains need next to the address
+    pub memo: Option<String>,
+}
+
+pub type DepositAddressResponse = ApiResponse<DepositAddress>;
+
+/// the id of the submitted withdrawal
+pub type WithdrawResponse = ApiResponse<String>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransferStatus {
+    Pending,
+    Confirmed,
+    Failed,
+    Cancelled,
+    #[serde(other)]
+    Unknown,
+}
+
+/// a deposit or a withdrawal
+#[derive(Debug, Clone, Deserialize)]
+pub struct Transfer {
+    #[serde(deserialize_with = "de::string")]
+    pub id: String,
+    pub asset: String,
+    pub amount: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
+    pub fee: BigDecimal,
+    pub address: String,
+    pub memo: Option<String>,
+    pub tx_hash: Option<String>,
+    pub status: TransferStatus,
+    #[serde(deserialize_with = "de::number")]
+    pub timestamp: i64,
+}
+
+pub type TransfersResponse = ApiResponse<Vec<Transfer>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryOrder {
     pub symbol: String,
+    #[serde(deserialize_with = "de::string")]
     pub order_id: String,
-    pub order_type: crate::request::OrderType,
-    pub direction: Direction,
+    #[serde(deserialize_with = "de::number")]
+    pub order_type: Side,
+    #[serde(deserialize_with = "de::number")]
+    pub direction: Side,
     pub amount: BigDecimal,
+    /// zero for market orders
+    #[serde(default, deserialize_with = "de::null_default")]
     pub price: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub filled_base: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub filled_quote: BigDecimal,
+    /// zero until the first fill
+    #[serde(default, deserialize_with = "de::null_default")]
     pub avg_price: BigDecimal,
+    #[serde(deserialize_with = "de::number")]
     pub status: crate::request::OrderStatus,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub trades: Vec<Trade>,
+    /// unix seconds, older gateways leave it out
+    #[serde(default, deserialize_with = "de::option_number")]
+    pub created_at: Option<i64>,
+    /// unix seconds of the last fill or status change
+    #[serde(default, deserialize_with = "de::option_number")]
+    pub updated_at: Option<i64>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct QueryByIdResponse {
-    pub code: i32,
-    pub data: Option<QueryOrder>,
+fn unix_time(seconds: i64) -> std::time::SystemTime {
+    match u64::try_from(seconds) {
+        Ok(seconds) => std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds),
+        Err(_) => std::time::UNIX_EPOCH - std::time::Duration::from_secs(seconds.unsigned_abs()),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct QueryByPageResponse {
-    pub code: i32,
-    pub data: Option<Vec<QueryOrder>>,
+impl QueryOrder {
+    pub fn created(&self) -> Option<std::time::SystemTime> {
+        self.created_at.map(unix_time)
+    }
+
+    pub fn updated(&self) -> Option<std::time::SystemTime> {
+        self.updated_at.map(unix_time)
+    }
+
+    /// how long ago the order was placed, zero for a server clock ahead of `now`
+    pub fn age(&self, now: std::time::SystemTime) -> Option<std::time::Duration> {
+        Some(now.duration_since(self.created()?).unwrap_or_default())
+    }
+
+    /// how long the order has not changed, its age until the first update
+    pub fn idle(&self, now: std::time::SystemTime) -> Option<std::time::Duration> {
+        let since = self.updated().or(self.created())?;
+        Some(now.duration_since(since).unwrap_or_default())
+    }
+
+    /// true for an order placed longer than `max_age` ago, false when the time is unknown so a
+    /// cleanup never cancels an order it cannot date
+    pub fn is_older_than(&self, max_age: std::time::Duration, now: std::time::SystemTime) -> bool {
+        self.age(now).is_some_and(|age| age > max_age)
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn created_at_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp(self.created_at?, 0)
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn updated_at_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp(self.updated_at?, 0)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+pub type QueryByIdResponse = ApiResponse<QueryOrder>;
+
+pub type QueryByPageResponse = ApiResponse<Vec<QueryOrder>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Balance {
+    #[serde(deserialize_with = "de::number")]
     pub code: i32,
     pub name: String,
     pub available: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub frozen: BigDecimal,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct BalancesResposne {
-    pub code: i32,
-    pub data: Option<Balance>,
+pub type BalancesResposne = ApiResponse<Balance>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountInfo {
+    pub address: Option<String>,
+    #[serde(default, deserialize_with = "de::null_default")]
+    pub permissions: Vec<crate::request::Permission>,
+}
+
+pub type AccountInfoResponse = ApiResponse<AccountInfo>;
+
+/// one level of a depth snapshot, read from the `[price, amount]` pairs the exchange sends
+/// as well as from `{"price", "amount"}` objects and written back as a pair
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "RawLevel")]
+pub struct PriceLevel {
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+impl PriceLevel {
+    pub fn new(price: BigDecimal, amount: BigDecimal) -> Self {
+        PriceLevel { price, amount }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawLevel {
+    Pair(BigDecimal, BigDecimal),
+    Object {
+        price: BigDecimal,
+        amount: BigDecimal,
+    },
+}
+
+impl From<RawLevel> for PriceLevel {
+    fn from(raw: RawLevel) -> Self {
+        match raw {
+            RawLevel::Pair(price, amount) | RawLevel::Object { price, amount } => {
+                PriceLevel { price, amount }
+            }
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+impl Serialize for PriceLevel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (&self.price, &self.amount).serialize(serializer)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Depth {
+    #[serde(default, deserialize_with = "de::number")]
     pub depth: i32,
-    pub bids: Vec<Vec<BigDecimal>>,
-    pub asks: Vec<Vec<BigDecimal>>,
+    /// the update the snapshot reflects, depth deltas continue from it, see `depthsync`
+    #[serde(default, deserialize_with = "de::option_number")]
+    pub seq: Option<u64>,
+    #[serde(default, deserialize_with = "de::null_default")]
+    pub bids: Vec<PriceLevel>,
+    #[serde(default, deserialize_with = "de::null_default")]
+    pub asks: Vec<PriceLevel>,
+    /// CRC-32 of the top levels if the gateway sends one, see `orderbook::checksum`
+    #[serde(default, deserialize_with = "de::option_number")]
+    pub checksum: Option<i64>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct DepthResponse {
-    pub code: i32,
-    pub data: Option<Depth>,
+impl Depth {
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.first()
+    }
+
+    pub fn mid_price(&self) -> Option<BigDecimal> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some((&bid.price + &ask.price) / BigDecimal::from(2)),
+            _ => None,
+        }
+    }
+
+    /// average price of taking `amount` from one side of the book, `None` when the side
+    /// does not hold that much
+    pub fn vwap(&self, side: Side, amount: &BigDecimal) -> Option<BigDecimal> {
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        if *amount <= BigDecimal::zero() {
+            return None;
+        }
+        let mut remaining = amount.clone();
+        let mut quote = BigDecimal::zero();
+        for level in levels {
+            let taken = remaining.clone().min(level.amount.clone());
+            quote += &taken * &level.price;
+            remaining -= taken;
+            if remaining <= BigDecimal::zero() {
+                return Some(quote / amount);
+            }
+        }
+        None
+    }
+
+    /// `(bids - asks) / (bids + asks)` of the summed amounts, from -1 (only asks) to 1 (only bids)
+    pub fn imbalance(&self) -> Option<BigDecimal> {
+        let sum = |levels: &[PriceLevel]| {
+            levels
+                .iter()
+                .fold(BigDecimal::zero(), |acc, level| acc + &level.amount)
+        };
+        let (bids, asks) = (sum(&self.bids), sum(&self.asks));
+        let total = &bids + &asks;
+        if total.is_zero() {
+            None
+        } else {
+            Some((bids - asks) / total)
+        }
+    }
+
+    /// the levels which differ from `self` in the later snapshot `other`
+    pub fn diff(&self, other: &Depth) -> DepthDiff {
+        DepthDiff {
+            bids: LevelChanges::between(&self.bids, &other.bids),
+            asks: LevelChanges::between(&self.asks, &other.asks),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// the levels of one side which differ between two snapshots
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelChanges {
+    /// prices only in the later snapshot
+    pub added: Vec<PriceLevel>,
+    /// prices only in the earlier snapshot, with the amount they had
+    pub removed: Vec<PriceLevel>,
+    /// prices in both, with the new amount
+    pub changed: Vec<PriceLevel>,
+}
+
+impl LevelChanges {
+    fn between(before: &[PriceLevel], after: &[PriceLevel]) -> Self {
+        let amounts = |levels: &[PriceLevel]| {
+            levels
+                .iter()
+                .map(|level| (level.price.clone(), level.amount.clone()))
+                .collect::<std::collections::BTreeMap<_, _>>()
+        };
+        let (old, new) = (amounts(before), amounts(after));
+        let mut changes = LevelChanges::default();
+        for level in before {
+            match new.get(&level.price) {
+                None => changes.removed.push(level.clone()),
+                Some(amount) if *amount != level.amount => changes
+                    .changed
+                    .push(PriceLevel::new(level.price.clone(), amount.clone())),
+                Some(_) => {}
+            }
+        }
+        changes.added = after
+            .iter()
+            .filter(|level| !old.contains_key(&level.price))
+            .cloned()
+            .collect();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// the changes the way a delta stream sends them: the new amount of every added or
+    /// changed level and a zero amount for every removed one
+    pub fn updates(&self) -> Vec<PriceLevel> {
+        self.added
+            .iter()
+            .chain(&self.changed)
+            .cloned()
+            .chain(
+                self.removed
+                    .iter()
+                    .map(|level| PriceLevel::new(level.price.clone(), BigDecimal::zero())),
+            )
+            .collect()
+    }
+
+    /// `levels` with the changes applied, sorted best first for `side`
+    fn apply(&self, levels: &[PriceLevel], side: Side) -> Vec<PriceLevel> {
+        let mut book = levels
+            .iter()
+            .map(|level| (level.price.clone(), level.amount.clone()))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        for level in &self.removed {
+            book.remove(&level.price);
+        }
+        for level in self.added.iter().chain(&self.changed) {
+            book.insert(level.price.clone(), level.amount.clone());
+        }
+        let levels = book
+            .into_iter()
+            .map(|(price, amount)| PriceLevel::new(price, amount));
+        match side {
+            Side::Bid => levels.rev().collect(),
+            Side::Ask => levels.collect(),
+        }
+    }
+}
+
+/// what changed between two depth snapshots, see `Depth::diff`; a recording can keep the
+/// first snapshot and the diffs after it instead of every full snapshot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DepthDiff {
+    pub bids: LevelChanges,
+    pub asks: LevelChanges,
+}
+
+impl DepthDiff {
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /// the later snapshot rebuilt from the earlier one, levels sorted best first
+    pub fn apply(&self, depth: &Depth) -> Depth {
+        Depth {
+            depth: depth.depth,
+            seq: depth.seq,
+            checksum: None,
+            bids: self.bids.apply(&depth.bids, Side::Bid),
+            asks: self.asks.apply(&depth.asks, Side::Ask),
+        }
+    }
+
+    /// the diff as the delta stream of `depthsync` would have sent it, for consumers which
+    /// only speak deltas but are fed from REST snapshots
+    pub fn to_delta(
+        &self,
+        symbol: String,
+        prev_seq: u64,
+        seq: u64,
+    ) -> crate::depthsync::DepthDelta {
+        crate::depthsync::DepthDelta {
+            symbol,
+            prev_seq,
+            seq,
+            checksum: None,
+            bids: self.bids.updates(),
+            asks: self.asks.updates(),
+        }
+    }
+}
+
+pub type DepthResponse = ApiResponse<Depth>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Kline {
+    #[serde(deserialize_with = "de::number")]
     pub id: i64,
     pub open: BigDecimal,
     pub close: BigDecimal,
     pub high: BigDecimal,
     pub low: BigDecimal,
     pub vol: BigDecimal,
+    /// unix seconds the candle closes at, this and the fields below are missing from older responses
+    #[serde(default, deserialize_with = "de::option_number")]
+    pub close_time: Option<i64>,
+    /// traded quote volume
+    #[serde(default)]
+    pub turnover: Option<BigDecimal>,
+    #[serde(default, deserialize_with = "de::option_number")]
+    pub count: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct KlineResponse {
-    pub code: i32,
-    pub data: Option<Vec<Kline>>,
-}
+pub type KlineResponse = ApiResponse<Vec<Kline>>;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Symbol {
+    #[serde(deserialize_with = "de::number")]
     pub base: i32,
+    #[serde(deserialize_with = "de::number")]
     pub quote: i32,
     pub base_name: String,
     pub quote_name: String,
+    #[serde(deserialize_with = "de::number")]
     pub base_scale: i32,
+    #[serde(deserialize_with = "de::number")]
     pub quote_scale: i32,
     pub taker_fee: BigDecimal,
     pub make_fee: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub min_amount: BigDecimal,
+    #[serde(default, deserialize_with = "de::null_default")]
     pub min_vol: BigDecimal,
+    #[serde(default, deserialize_with = "de::boolean")]
     pub enable_marker_order: bool,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct SymbolsResponse {
-    pub code: i32,
-    pub data: Option<Vec<Symbol>>,
+impl Symbol {
+    /// the `BASE-QUOTE` form used by the symbol parameter of requests
+    pub fn pair(&self) -> String {
+        format!("{}-{}", self.base_name, self.quote_name)
+    }
+
+    /// the smallest price step, one unit at `quote_scale`
+    pub fn price_tick(&self) -> BigDecimal {
+        rounding::unit(self.quote_scale.into())
+    }
+
+    /// the smallest amount step, one unit at `base_scale`
+    pub fn amount_step(&self) -> BigDecimal {
+        rounding::unit(self.base_scale.into())
+    }
+
+    /// `price` with the precision the exchange accepts for this symbol
+    pub fn round_price(&self, price: &BigDecimal, mode: RoundingMode) -> BigDecimal {
+        rounding::round_to_scale(price, self.quote_scale.into(), mode)
+    }
+
+    /// `amount` with the precision the exchange accepts for this symbol
+    pub fn round_amount(&self, amount: &BigDecimal, mode: RoundingMode) -> BigDecimal {
+        rounding::round_to_scale(amount, self.base_scale.into(), mode)
+    }
+}
+
+pub type SymbolsResponse = ApiResponse<Vec<Symbol>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{NewOrder, OrderKind, Side};
+
+    fn orders(n: i64) -> Vec<NewOrder> {
+        (1..=n)
+            .map(|i| {
+                NewOrder::new(
+                    Side::Bid,
+                    OrderKind::Limit,
+                    String::from("BTC-USDT"),
+                    Some(BigDecimal::from(i)),
+                    BigDecimal::from(1),
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_result_correlation() {
+        let resp = serde_json::from_str::<BatchPendingOrdersResponse>(
+            r#"{"code":200,"data":["a",null,""]}"#,
+        )
+        .unwrap();
+        let result = BatchResult::correlate(orders(3), resp).unwrap();
+        assert_eq!(
+            result.items[0].outcome,
+            BatchOutcome::Placed(String::from("a"))
+        );
+        assert_eq!(result.placed().count(), 1);
+        assert_eq!(result.failed().count(), 2);
+        assert!(!result.is_complete());
+
+        let resp =
+            serde_json::from_str::<BatchPendingOrdersResponse>(r#"{"code":200,"data":["a"]}"#)
+                .unwrap();
+        let err = BatchResult::correlate(orders(2), resp).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<crate::Error>(),
+            Some(crate::Error::BatchMismatch { submitted: 2, returned: 1, ids })
+                if ids == &[Some(String::from("a"))]
+        ));
+    }
+
+    #[test]
+    fn test_batch_cancel_outcomes() {
+        let resp = serde_json::from_str::<BatchCancelOrdersResponse>(
+            r#"{"code":200,"data":[{"order_id":1,"outcome":"cancelled"},{"order_id":"2","outcome":"already_filled"}]}"#,
+        )
+        .unwrap();
+        let data = resp.into_result_or_default().unwrap();
+        assert_eq!(data.outcome("1"), Some(CancelOutcome::Cancelled));
+        assert_eq!(data.outcome("2"), Some(CancelOutcome::AlreadyFilled));
+        assert_eq!(data.outcome("3"), None);
+
+        let legacy =
+            serde_json::from_str::<BatchCancelOrdersResponse>(r#"{"code":200,"data":"1|3"}"#)
+                .unwrap()
+                .into_result_or_default()
+                .unwrap();
+        assert_eq!(legacy.outcome("3"), Some(CancelOutcome::Cancelled));
+        assert_eq!(legacy.outcome("2"), None);
+    }
+
+    #[test]
+    fn test_api_response_into_result() {
+        let ok = serde_json::from_str::<DepositAddressResponse>(
+            r#"{"code":200,"data":{"asset":"USDT","address":"0xabc","memo":null}}"#,
+        )
+        .unwrap();
+        assert_eq!(ok.into_result().unwrap().address, "0xabc");
+        let rejected =
+            serde_json::from_str::<PendingOrderResponse>(r#"{"code":403,"data":null}"#).unwrap();
+        assert!(matches!(
+            rejected.into_result(),
+            Err(crate::Error::Rejected(403))
+        ));
+        let empty = serde_json::from_str::<QueryByPageResponse>(r#"{"code":200}"#).unwrap();
+        assert!(matches!(
+            empty.clone().into_result(),
+            Err(crate::Error::MissingData)
+        ));
+        assert!(empty.into_result_or_default().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_public_trades() {
+        let resp = serde_json::from_str::<TradesResponse>(
+            r#"{"code":200,"data":[
+            {"id":2,"ask_or_bid":1,"price":"101.5","amount":"0.2","timestamp":1650000001},
+            {"id":1,"ask_or_bid":0,"price":"101","amount":"1","timestamp":1650000000}]}"#,
+        )
+        .unwrap();
+        let trades = resp.data.unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].ask_or_bid, Side::Bid);
+        assert_eq!(trades[1].price, BigDecimal::from(101));
+    }
+
+    #[test]
+    fn test_depth_levels() {
+        let depth = serde_json::from_str::<Depth>(
+            r#"{"depth":0,
+            "bids":[["99","3"],{"price":"98","amount":"1"}],
+            "asks":[["101","1"],["103","1"]]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            depth.bids[1],
+            PriceLevel::new(BigDecimal::from(98), BigDecimal::from(1))
+        );
+        assert_eq!(depth.mid_price(), Some(BigDecimal::from(100)));
+        assert_eq!(
+            depth.vwap(Side::Ask, &BigDecimal::from(2)),
+            Some(BigDecimal::from(102))
+        );
+        assert_eq!(depth.vwap(Side::Ask, &BigDecimal::from(3)), None);
+        assert_eq!(
+            depth.imbalance(),
+            Some(BigDecimal::from(2) / BigDecimal::from(6))
+        );
+        assert_eq!(
+            serde_json::to_string(&depth.asks[0]).unwrap(),
+            r#"["101","1"]"#
+        );
+    }
+
+    #[test]
+    fn test_depth_diff() {
+        let before = serde_json::from_str::<Depth>(
+            r#"{"bids":[["99","3"],["98","1"]],"asks":[["101","1"],["103","1"]]}"#,
+        )
+        .unwrap();
+        let after = serde_json::from_str::<Depth>(
+            r#"{"bids":[["99.5","2"],["99","1"],["98","1"]],"asks":[["101","1"]]}"#,
+        )
+        .unwrap();
+        let level =
+            |price: &str, amount: i64| PriceLevel::new(price.parse().unwrap(), amount.into());
+        let diff = before.diff(&after);
+        assert_eq!(diff.bids.added, [level("99.5", 2)]);
+        assert_eq!(diff.bids.changed, [level("99", 1)]);
+        assert_eq!(diff.asks.removed, [level("103", 1)]);
+        assert!(diff.asks.added.is_empty() && diff.asks.changed.is_empty());
+        assert_eq!(diff.apply(&before).bids, after.bids);
+        assert_eq!(diff.apply(&before).asks, after.asks);
+        assert!(after.diff(&after).is_empty());
+
+        let delta = diff.to_delta(String::from("BTC-USDT"), 7, 8);
+        assert_eq!(delta.asks, [level("103", 0)]);
+        assert_eq!(delta.bids.len(), 2);
+    }
+
+    #[test]
+    fn test_kline_optional_fields() {
+        let klines = serde_json::from_str::<Vec<Kline>>(
+            r#"[{"id":1,"open":"1","close":"2","high":"3","low":"1","vol":"10"},
+            {"id":2,"open":"2","close":"2","high":"2","low":"2","vol":"1",
+            "close_time":120,"turnover":"2","count":3}]"#,
+        )
+        .unwrap();
+        assert_eq!(klines[0].close_time, None);
+        assert_eq!(klines[1].close_time, Some(120));
+        assert_eq!(klines[1].turnover, Some(BigDecimal::from(2)));
+        assert_eq!(klines[1].count, Some(3));
+    }
+
+    #[test]
+    fn test_symbol_rounding() {
+        let symbol = serde_json::from_str::<Symbol>(
+            r#"{"base":1,"quote":0,"base_name":"BTC","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0.001","make_fee":"0.001","min_amount":"0.0001",
+            "min_vol":"1","enable_marker_order":true}"#,
+        )
+        .unwrap();
+        let price = "27123.456".parse().unwrap();
+        assert_eq!(
+            symbol.round_price(&price, RoundingMode::Floor),
+            "27123.45".parse().unwrap()
+        );
+        assert_eq!(
+            symbol.round_price(&price, RoundingMode::Ceil),
+            "27123.46".parse().unwrap()
+        );
+        assert_eq!(
+            symbol.round_amount(&"0.00015".parse().unwrap(), RoundingMode::HalfUp),
+            "0.0002".parse().unwrap()
+        );
+        assert_eq!(symbol.price_tick(), "0.01".parse().unwrap());
+    }
+
+    #[test]
+    fn test_lenient_payloads() {
+        let symbols = serde_json::from_str::<SymbolsResponse>(include_str!(
+            "../fixtures/responses/symbols.json"
+        ))
+        .unwrap()
+        .into_result()
+        .unwrap();
+        assert_eq!(symbols[0].base_scale, 4);
+        assert!(symbols[0].enable_marker_order);
+        assert!(symbols[0].min_vol.is_zero());
+
+        let order = serde_json::from_str::<QueryByIdResponse>(include_str!(
+            "../fixtures/responses/query_order.json"
+        ))
+        .unwrap()
+        .into_result()
+        .unwrap();
+        assert_eq!(order.order_id, "1234567");
+        assert_eq!(order.created_at, Some(1700000000));
+        assert!(order.updated_at.is_none());
+        let now = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1700000090);
+        assert_eq!(order.age(now), Some(std::time::Duration::from_secs(90)));
+        assert_eq!(order.idle(now), order.age(now));
+        assert!(order.is_older_than(std::time::Duration::from_secs(60), now));
+        #[cfg(feature = "chrono")]
+        assert_eq!(order.created_at_utc().unwrap().timestamp(), 1700000000);
+        assert_eq!(order.order_type, Side::Bid);
+        assert_eq!(order.status, crate::request::OrderStatus::Undeal);
+        assert!(order.trades.is_empty() && order.price.is_zero());
+
+        let trades = serde_json::from_str::<MyTradesResponse>(include_str!(
+            "../fixtures/responses/my_trades.json"
+        ))
+        .unwrap()
+        .into_result()
+        .unwrap();
+        assert_eq!(trades[0].ask_or_bid, Side::Ask);
+        assert_eq!(trades[0].timestamp, 1700000000);
+        assert!(trades[0].base_fee.is_zero());
+
+        let depth =
+            serde_json::from_str::<DepthResponse>(include_str!("../fixtures/responses/depth.json"))
+                .unwrap()
+                .into_result()
+                .unwrap();
+        assert_eq!((depth.depth, depth.seq), (20, Some(1042)));
+        assert_eq!(depth.bids.len(), 2);
+        assert!(depth.asks.is_empty());
+
+        let transfers = serde_json::from_str::<TransfersResponse>(include_str!(
+            "../fixtures/responses/transfers.json"
+        ))
+        .unwrap()
+        .into_result()
+        .unwrap();
+        assert_eq!(transfers[0].id, "77");
+        assert_eq!(transfers[0].memo, None);
+
+        let klines =
+            serde_json::from_str::<KlineResponse>(include_str!("../fixtures/responses/kline.json"))
+                .unwrap()
+                .into_result()
+                .unwrap();
+        assert_eq!(klines[0].id, 1700000000);
+        assert_eq!((klines[0].close_time, klines[0].count), (None, Some(12)));
+    }
+
+    /// decode `body` as every response, the result is thrown away, only a panic fails
+    fn decode_all(body: &[u8]) {
+        fn decode<T: serde::de::DeserializeOwned>(body: &[u8]) {
+            if let Ok(resp) = serde_json::from_slice::<ApiResponse<T>>(body) {
+                let _ = resp.into_result();
+            }
+        }
+        decode::<SessionToken>(body);
+        decode::<Vec<Option<String>>>(body);
+        decode::<BatchCancelData>(body);
+        decode::<Vec<Trade>>(body);
+        decode::<Vec<PublicTrade>>(body);
+        decode::<DepositAddress>(body);
+        decode::<Vec<Transfer>>(body);
+        decode::<QueryOrder>(body);
+        decode::<Vec<QueryOrder>>(body);
+        decode::<Balance>(body);
+        decode::<AccountInfo>(body);
+        decode::<Depth>(body);
+        decode::<Vec<Kline>>(body);
+        decode::<Vec<Symbol>>(body);
+    }
+
+    const FIXTURES: [&str; 6] = [
+        include_str!("../fixtures/responses/depth.json"),
+        include_str!("../fixtures/responses/kline.json"),
+        include_str!("../fixtures/responses/my_trades.json"),
+        include_str!("../fixtures/responses/query_order.json"),
+        include_str!("../fixtures/responses/symbols.json"),
+        include_str!("../fixtures/responses/transfers.json"),
+    ];
+
+    proptest::proptest! {
+        #[test]
+        fn decoding_never_panics(body in proptest::collection::vec(proptest::num::u8::ANY, 0..256)) {
+            decode_all(&body);
+        }
+
+        #[test]
+        fn decoding_json_never_panics(body in r#"\{"code":-?[0-9]{1,4},"data":[\[\]\{\}",:0-9a-z_.\- ]{0,64}\}"#) {
+            decode_all(body.as_bytes());
+        }
+
+        /// fixtures cut short or with one byte changed, as from a broken connection
+        #[test]
+        fn decoding_damaged_fixtures_never_panics(
+            fixture in proptest::sample::select(FIXTURES.to_vec()),
+            cut in proptest::num::usize::ANY,
+            at in proptest::num::usize::ANY,
+            byte in proptest::num::u8::ANY,
+        ) {
+            let mut body = fixture.as_bytes().to_vec();
+            body.truncate(cut % (body.len() + 1));
+            if !body.is_empty() {
+                let at = at % body.len();
+                body[at] = byte;
+            }
+            decode_all(&body);
+        }
+    }
 }
@@ -0,0 +1,142 @@
+use crate::request::{Request, Scale};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// entries kept before the expired ones are swept out
+const SWEEP_ABOVE: usize = 256;
+
+/// how long the responses of the public endpoints are reused, see `FxdxBuilder::response_cache`
+///
+/// symbols change with listings, depth with every trade and a candle at most as often as its
+/// scale allows; a zero ttl turns caching of that endpoint off
+#[derive(Debug, Clone)]
+pub struct CacheTtl {
+    symbols: Duration,
+    depth: Duration,
+    kline: HashMap<Scale, Duration>,
+}
+
+impl Default for CacheTtl {
+    fn default() -> Self {
+        CacheTtl {
+            symbols: Duration::from_secs(300),
+            depth: Duration::from_millis(250),
+            // a second of a minute candle, a minute of a day candle
+            kline: Scale::all()
+                .into_iter()
+                .map(|scale| (scale, scale.duration() / 60))
+                .collect(),
+        }
+    }
+}
+
+impl CacheTtl {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn symbols(mut self, ttl: Duration) -> Self {
+        self.symbols = ttl;
+        self
+    }
+
+    pub fn depth(mut self, ttl: Duration) -> Self {
+        self.depth = ttl;
+        self
+    }
+
+    pub fn kline(mut self, scale: Scale, ttl: Duration) -> Self {
+        self.kline.insert(scale, ttl);
+        self
+    }
+
+    /// how long the response to `req` is reused, `None` if it is not cached at all
+    pub fn ttl(&self, req: &Request) -> Option<Duration> {
+        let ttl = match req {
+            Request::Symbols => self.symbols,
+            Request::Depth { .. } => self.depth,
+            Request::Kline { scale, .. } => self.kline.get(scale).copied()?,
+            _ => return None,
+        };
+        (!ttl.is_zero()).then_some(ttl)
+    }
+}
+
+/// successful response bodies of public requests by uri, shared by every clone of a client
+#[derive(Debug)]
+pub(crate) struct ResponseCache {
+    ttl: CacheTtl,
+    entries: Mutex<HashMap<String, (Instant, Bytes)>>,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(ttl: CacheTtl) -> Self {
+        ResponseCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn ttl(&self, req: &Request) -> Option<Duration> {
+        self.ttl.ttl(req)
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, (Instant, Bytes)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// the body stored under `key` if it has not expired at `now`
+    pub(crate) fn get(&self, key: &str, now: Instant) -> Option<Bytes> {
+        match self.entries().get(key) {
+            Some((expires, body)) if *expires > now => Some(body.clone()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn insert(&self, key: String, now: Instant, ttl: Duration, body: Bytes) {
+        let mut entries = self.entries();
+        if entries.len() >= SWEEP_ABOVE {
+            entries.retain(|_, (expires, _)| *expires > now);
+        }
+        entries.insert(key, (now + ttl, body));
+    }
+
+    pub(crate) fn remove(&self, key: &str) {
+        self.entries().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_per_endpoint() {
+        let ttl = CacheTtl::new().kline(Scale::Hour, Duration::ZERO);
+        assert_eq!(ttl.ttl(&Request::Symbols), Some(Duration::from_secs(300)));
+        let kline = |scale| Request::Kline {
+            symbol: String::from("BTC-USDT"),
+            scale,
+        };
+        assert_eq!(ttl.ttl(&kline(Scale::Minute)), Some(Duration::from_secs(1)));
+        assert_eq!(ttl.ttl(&kline(Scale::Hour)), None);
+        assert_eq!(ttl.ttl(&Request::Balances), None);
+
+        let cache = ResponseCache::new(ttl);
+        let now = Instant::now();
+        cache.insert(
+            String::from("/symbols"),
+            now,
+            Duration::from_secs(1),
+            Bytes::from_static(b"{}"),
+        );
+        assert!(cache.get("/symbols", now).is_some());
+        assert!(cache
+            .get("/symbols", now + Duration::from_secs(1))
+            .is_none());
+        cache.remove("/symbols");
+        assert!(cache.get("/symbols", now).is_none());
+    }
+}
@@ -0,0 +1,636 @@
+//! pre-trade limits, checked locally before an order goes out to the venue
+
+use crate::exchange::{
+    AssetBalance, Book, Candle, ExchangeClient, ExchangeFuture, Level, Market, Order, OrderRequest,
+};
+use crate::orderbook::OrderBook;
+use crate::request::{Scale, Side};
+use crate::Error;
+use anyhow::Result;
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// `None` leaves a limit unchecked
+#[derive(Debug, Clone, Default)]
+pub struct RiskLimits {
+    /// over all symbols, counting the orders the guard knows to be open
+    pub max_open_orders: Option<usize>,
+    /// `price * amount` in quote, market orders are priced at the top of the book
+    pub max_order_notional: Option<BigDecimal>,
+    /// the most of one base asset held, plus what open bids would add to it
+    pub max_position: Option<BigDecimal>,
+    /// the prices of limit orders against the book, a fat-finger guard
+    pub pre_trade_check: Option<PreTradeCheck>,
+}
+
+/// limits on the price of a limit order relative to the book, as fractions of a price, e.g.
+/// `0.01` for one percent; the book is the one given to `RiskGuard::update_book`, else the top
+/// of the venue's book
+#[derive(Debug, Clone, Default)]
+pub struct PreTradeCheck {
+    /// how far past the best price of the other side an order may be priced, `0` rejects
+    /// every order that would take liquidity
+    pub max_cross: Option<BigDecimal>,
+    /// how far from the mid price an order may be priced
+    pub max_from_mid: Option<BigDecimal>,
+    pub action: PreTradeAction,
+}
+
+/// what the guard does with an order failing the `PreTradeCheck`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PreTradeAction {
+    #[default]
+    Reject,
+    /// send it anyway, with a warning and a `RiskEvent::Flagged`
+    Flag,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskViolation {
+    KillSwitch,
+    OpenOrders {
+        open: usize,
+        limit: usize,
+    },
+    Notional {
+        notional: BigDecimal,
+        limit: BigDecimal,
+    },
+    Position {
+        asset: String,
+        position: BigDecimal,
+        limit: BigDecimal,
+    },
+    /// priced past `best`, the best price of the other side, by more than `max_cross`
+    CrossesSpread {
+        price: BigDecimal,
+        best: BigDecimal,
+    },
+    /// priced further from the mid price than `max_from_mid`
+    AwayFromMid {
+        price: BigDecimal,
+        mid: BigDecimal,
+    },
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskViolation::KillSwitch => write!(f, "kill switch engaged"),
+            RiskViolation::OpenOrders { open, limit } => {
+                write!(f, "{} open orders, limit {}", open, limit)
+            }
+            RiskViolation::Notional { notional, limit } => {
+                write!(f, "notional {}, limit {}", notional, limit)
+            }
+            RiskViolation::Position {
+                asset,
+                position,
+                limit,
+            } => write!(f, "{} position {}, limit {}", asset, position, limit),
+            RiskViolation::CrossesSpread { price, best } => {
+                write!(f, "price {} crosses the best {} too far", price, best)
+            }
+            RiskViolation::AwayFromMid { price, mid } => {
+                write!(f, "price {} too far from mid {}", price, mid)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskEvent {
+    Rejected {
+        order: Box<OrderRequest>,
+        violation: RiskViolation,
+    },
+    KillSwitch {
+        engaged: bool,
+    },
+    /// sent despite failing the `PreTradeCheck`, with `PreTradeAction::Flag`
+    Flagged {
+        order: Box<OrderRequest>,
+        violation: RiskViolation,
+    },
+}
+
+pub type RiskEventHandler = Box<dyn Fn(&RiskEvent) + Send + Sync>;
+
+/// an order placed through the guard and not yet known to be closed
+#[derive(Debug, Clone)]
+struct Tracked {
+    symbol: String,
+    side: Side,
+    amount: BigDecimal,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    open: HashMap<String, Tracked>,
+    /// the total of every asset as of the last `sync`, moved by the fills of closed orders
+    positions: HashMap<String, BigDecimal>,
+    /// the best bid and ask of the local books, by symbol
+    tops: HashMap<String, (Option<BigDecimal>, Option<BigDecimal>)>,
+}
+
+/// wraps a client, e.g. `FxdxClient`, and rejects orders breaking `RiskLimits` before they
+/// reach the venue; every rejection is an `Error::RiskLimit` and a `RiskEvent` for the handler
+///
+/// open orders are the ones placed through the guard, or seen by `open_orders` through it,
+/// until it sees them closed; positions come from `sync`, call it once before trading
+pub struct RiskGuard<C> {
+    inner: C,
+    limits: RiskLimits,
+    killed: AtomicBool,
+    state: Mutex<State>,
+    handler: Option<RiskEventHandler>,
+}
+
+impl<C: ExchangeClient> RiskGuard<C> {
+    pub fn new(inner: C, limits: RiskLimits) -> Self {
+        RiskGuard {
+            inner,
+            limits,
+            killed: AtomicBool::new(false),
+            state: Default::default(),
+            handler: None,
+        }
+    }
+
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&RiskEvent) + Send + Sync + 'static,
+    {
+        self.handler = Some(Box::new(handler));
+        self
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    pub fn limits(&self) -> &RiskLimits {
+        &self.limits
+    }
+
+    /// reject every new order until `release`, cancels are still let through
+    pub fn kill(&self) {
+        if !self.killed.swap(true, Ordering::SeqCst) {
+            self.emit(RiskEvent::KillSwitch { engaged: true });
+        }
+    }
+
+    pub fn release(&self) {
+        if self.killed.swap(false, Ordering::SeqCst) {
+            self.emit(RiskEvent::KillSwitch { engaged: false });
+        }
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+    /// the number of orders the guard counts as open
+    pub fn open_order_count(&self) -> usize {
+        self.state().open.len()
+    }
+
+    /// take the best prices of a local book, e.g. of a `MarketDataSession`, for the
+    /// `PreTradeCheck` of its symbol; a stale book is dropped and the venue's book used instead
+    pub fn update_book(&self, book: &OrderBook) {
+        let mut state = self.state();
+        if book.stale {
+            state.tops.remove(&book.symbol);
+        } else {
+            state.tops.insert(
+                book.symbol.clone(),
+                (book.best_bid().cloned(), book.best_ask().cloned()),
+            );
+        }
+    }
+
+    /// reload the positions from the balances of the venue
+    pub async fn sync(&self) -> Result<()> {
+        let balances = self.inner.balances().await?;
+        self.sync_positions(&balances);
+        Ok(())
+    }
+
+    fn sync_positions(&self, balances: &[AssetBalance]) {
+        self.state().positions = balances
+            .iter()
+            .map(|balance| (balance.asset.clone(), balance.total()))
+            .collect();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn emit(&self, event: RiskEvent) {
+        if let Some(handler) = &self.handler {
+            handler(&event);
+        }
+    }
+
+    /// the first limit `order` breaks
+    async fn check(&self, order: &OrderRequest) -> Result<Option<RiskViolation>> {
+        if self.is_killed() {
+            return Ok(Some(RiskViolation::KillSwitch));
+        }
+        if let Some(limit) = self.limits.max_open_orders {
+            let open = self.open_order_count();
+            if open >= limit {
+                return Ok(Some(RiskViolation::OpenOrders { open, limit }));
+            }
+        }
+        if let Some(limit) = &self.limits.max_order_notional {
+            let price = match &order.price {
+                Some(price) => price.clone(),
+                None => self.top_of_book(order).await?,
+            };
+            let notional = price * &order.amount;
+            if &notional > limit {
+                return Ok(Some(RiskViolation::Notional {
+                    notional,
+                    limit: limit.clone(),
+                }));
+            }
+        }
+        if let (Some(limit), Side::Bid) = (&self.limits.max_position, order.side) {
+            let asset = base(&order.symbol).to_string();
+            let state = self.state();
+            let bids = state
+                .open
+                .values()
+                .filter(|tracked| tracked.side == Side::Bid && base(&tracked.symbol) == asset)
+                .fold(BigDecimal::zero(), |acc, tracked| acc + &tracked.amount);
+            let held = state.positions.get(&asset).cloned().unwrap_or_default();
+            let position = held + bids + &order.amount;
+            if &position > limit {
+                return Ok(Some(RiskViolation::Position {
+                    asset,
+                    position,
+                    limit: limit.clone(),
+                }));
+            }
+        }
+        if let Some(check) = &self.limits.pre_trade_check {
+            if let Some(violation) = self.check_price(order, check).await? {
+                if check.action == PreTradeAction::Reject {
+                    return Ok(Some(violation));
+                }
+                log::warn!("pre-trade check flagged {:?}: {}", order, violation);
+                self.emit(RiskEvent::Flagged {
+                    order: Box::new(order.clone()),
+                    violation,
+                });
+            }
+        }
+        Ok(None)
+    }
+
+    /// the first limit of `check` the price of a limit order breaks, market orders pass
+    async fn check_price(
+        &self,
+        order: &OrderRequest,
+        check: &PreTradeCheck,
+    ) -> Result<Option<RiskViolation>> {
+        let Some(price) = &order.price else {
+            return Ok(None);
+        };
+        let local = self.state().tops.get(&order.symbol).cloned();
+        let (bid, ask) = match local {
+            Some(top) => top,
+            None => {
+                let book = self.inner.depth(&order.symbol, Some(1)).await?;
+                let best = |levels: &[Level]| levels.first().map(|l| l.price.clone());
+                (best(&book.bids), best(&book.asks))
+            }
+        };
+        let one = BigDecimal::from(1);
+        if let Some(limit) = &check.max_cross {
+            let crossed = match (order.side, &bid, &ask) {
+                (Side::Bid, _, Some(ask)) => (price > &(ask * (&one + limit))).then_some(ask),
+                (Side::Ask, Some(bid), _) => (price < &(bid * (&one - limit))).then_some(bid),
+                _ => None,
+            };
+            if let Some(best) = crossed {
+                return Ok(Some(RiskViolation::CrossesSpread {
+                    price: price.clone(),
+                    best: best.clone(),
+                }));
+            }
+        }
+        if let (Some(limit), Some(bid), Some(ask)) = (&check.max_from_mid, &bid, &ask) {
+            let mid = (bid + ask) / BigDecimal::from(2);
+            if !mid.is_zero() {
+                let deviation = ((price - &mid) / &mid).abs();
+                if &deviation > limit {
+                    return Ok(Some(RiskViolation::AwayFromMid {
+                        price: price.clone(),
+                        mid,
+                    }));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// the price a market order would take first
+    async fn top_of_book(&self, order: &OrderRequest) -> Result<BigDecimal> {
+        let book = self.inner.depth(&order.symbol, Some(1)).await?;
+        let levels = match order.side {
+            Side::Bid => &book.asks,
+            Side::Ask => &book.bids,
+        };
+        let level = levels.first().ok_or_else(|| {
+            Error::InvalidRequest(format!(
+                "no book to price a market order on {}",
+                order.symbol
+            ))
+        })?;
+        Ok(level.price.clone())
+    }
+
+    /// drop a tracked order seen closed, its fills move the position
+    fn close(&self, order: &Order) {
+        let mut state = self.state();
+        if order.state.is_open() || state.open.remove(&order.id).is_none() {
+            return;
+        }
+        let position = state
+            .positions
+            .entry(base(&order.symbol).to_string())
+            .or_default();
+        match order.side {
+            Side::Bid => *position += &order.filled,
+            Side::Ask => *position -= &order.filled,
+        }
+    }
+}
+
+/// the base asset of a `BASE-QUOTE` symbol
+fn base(symbol: &str) -> &str {
+    symbol.split_once('-').map_or(symbol, |(base, _)| base)
+}
+
+impl<C: ExchangeClient> ExchangeClient for RiskGuard<C> {
+    fn venue(&self) -> &str {
+        self.inner.venue()
+    }
+
+    fn place_order(&self, order: OrderRequest) -> ExchangeFuture<'_, String> {
+        Box::pin(async move {
+            if let Some(violation) = self.check(&order).await? {
+                log::warn!("risk limit rejected {:?}: {}", order, violation);
+                self.emit(RiskEvent::Rejected {
+                    order: Box::new(order),
+                    violation: violation.clone(),
+                });
+                return Err(Error::RiskLimit(violation).into());
+            }
+            let tracked = Tracked {
+                symbol: order.symbol.clone(),
+                side: order.side,
+                amount: order.amount.clone(),
+            };
+            let id = self.inner.place_order(order).await?;
+            self.state().open.insert(id.clone(), tracked);
+            Ok(id)
+        })
+    }
+
+    fn cancel_order<'a>(
+        &'a self,
+        symbol: &'a str,
+        order_id: &'a str,
+    ) -> ExchangeFuture<'a, String> {
+        Box::pin(async move {
+            let id = self.inner.cancel_order(symbol, order_id).await?;
+            // what filled before the cancel shows up with the next `sync`
+            self.state().open.remove(order_id);
+            Ok(id)
+        })
+    }
+
+    fn order<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> ExchangeFuture<'a, Order> {
+        Box::pin(async move {
+            let order = self.inner.order(symbol, order_id).await?;
+            self.close(&order);
+            Ok(order)
+        })
+    }
+
+    fn open_orders<'a>(&'a self, symbol: &'a str) -> ExchangeFuture<'a, Vec<Order>> {
+        Box::pin(async move {
+            let orders = self.inner.open_orders(symbol).await?;
+            let mut state = self.state();
+            state.open.retain(|_, tracked| tracked.symbol != symbol);
+            for order in orders.iter().filter(|order| order.state.is_open()) {
+                let tracked = Tracked {
+                    symbol: order.symbol.clone(),
+                    side: order.side,
+                    amount: &order.amount - &order.filled,
+                };
+                state.open.insert(order.id.clone(), tracked);
+            }
+            Ok(orders)
+        })
+    }
+
+    fn balances(&self) -> ExchangeFuture<'_, Vec<AssetBalance>> {
+        Box::pin(async move {
+            let balances = self.inner.balances().await?;
+            self.sync_positions(&balances);
+            Ok(balances)
+        })
+    }
+
+    fn depth<'a>(&'a self, symbol: &'a str, limit: Option<u32>) -> ExchangeFuture<'a, Book> {
+        self.inner.depth(symbol, limit)
+    }
+
+    fn klines<'a>(&'a self, symbol: &'a str, scale: Scale) -> ExchangeFuture<'a, Vec<Candle>> {
+        self.inner.klines(symbol, scale)
+    }
+
+    fn markets(&self) -> ExchangeFuture<'_, Vec<Market>> {
+        self.inner.markets()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::{BacktestOptions, SimulatedExchange};
+    use crate::request::OrderKind;
+    use crate::response::{Depth, PriceLevel};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn bid(price: Option<i64>, amount: i64) -> OrderRequest {
+        OrderRequest {
+            symbol: String::from("BTC-USDT"),
+            side: Side::Bid,
+            kind: match price {
+                Some(_) => OrderKind::Limit,
+                None => OrderKind::Market,
+            },
+            price: price.map(BigDecimal::from),
+            amount: amount.into(),
+        }
+    }
+
+    fn violation(e: anyhow::Error) -> RiskViolation {
+        match e.downcast::<Error>().unwrap() {
+            Error::RiskLimit(violation) => violation,
+            e => panic!("unexpected {}", e),
+        }
+    }
+
+    fn venue() -> SimulatedExchange {
+        let symbol = serde_json::from_str(
+            r#"{"base":1,"quote":0,"base_name":"BTC","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0","make_fee":"0","min_amount":"0.0001",
+            "min_vol":"0","enable_marker_order":true}"#,
+        )
+        .unwrap();
+        let venue = SimulatedExchange::new(
+            vec![symbol],
+            [
+                (String::from("USDT"), BigDecimal::from(10_000)),
+                (String::from("BTC"), BigDecimal::from(1)),
+            ],
+            BacktestOptions {
+                latency: Duration::ZERO,
+                ..Default::default()
+            },
+        );
+        let depth = Depth {
+            depth: 0,
+            seq: Some(1),
+            checksum: None,
+            bids: vec![PriceLevel::new(99.into(), 10.into())],
+            asks: vec![PriceLevel::new(101.into(), 10.into())],
+        };
+        venue.on_depth(0, "BTC-USDT", depth);
+        venue
+    }
+
+    #[tokio::test]
+    async fn test_limits_reject_locally() {
+        let venue = venue();
+        let events = Arc::new(Mutex::new(vec![]));
+        let guard = RiskGuard::new(
+            venue,
+            RiskLimits {
+                max_open_orders: Some(2),
+                max_order_notional: Some(500.into()),
+                max_position: Some(4.into()),
+                pre_trade_check: None,
+            },
+        )
+        .on_event({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        });
+        guard.sync().await.unwrap();
+
+        // priced at the best ask
+        let e = guard.place_order(bid(None, 5)).await.unwrap_err();
+        assert!(matches!(violation(e), RiskViolation::Notional { .. }));
+
+        let first = guard.place_order(bid(Some(90), 2)).await.unwrap();
+        // 1 held, 2 bid, 2 more would make 5
+        let e = guard.place_order(bid(Some(90), 2)).await.unwrap_err();
+        assert!(matches!(violation(e), RiskViolation::Position { .. }));
+        guard.place_order(bid(Some(90), 1)).await.unwrap();
+        let e = guard.place_order(bid(Some(90), 1)).await.unwrap_err();
+        assert_eq!(
+            violation(e),
+            RiskViolation::OpenOrders { open: 2, limit: 2 }
+        );
+
+        guard.cancel_order("BTC-USDT", &first).await.unwrap();
+        guard.kill();
+        let e = guard.place_order(bid(Some(90), 1)).await.unwrap_err();
+        assert_eq!(violation(e), RiskViolation::KillSwitch);
+        guard.release();
+        guard.place_order(bid(Some(90), 1)).await.unwrap();
+        // nothing rejected reached the venue
+        assert_eq!(guard.inner().open_orders("BTC-USDT").len(), 2);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[3], RiskEvent::KillSwitch { engaged: true });
+    }
+
+    #[tokio::test]
+    async fn test_pre_trade_check() {
+        let check = PreTradeCheck {
+            max_cross: Some("0.01".parse().unwrap()),
+            max_from_mid: Some("0.05".parse().unwrap()),
+            action: PreTradeAction::Reject,
+        };
+        let guard = RiskGuard::new(
+            venue(),
+            RiskLimits {
+                pre_trade_check: Some(check.clone()),
+                ..Default::default()
+            },
+        );
+        // the venue's book is 99 / 101, mid 100
+        let e = guard.place_order(bid(Some(103), 1)).await.unwrap_err();
+        assert!(matches!(
+            violation(e),
+            RiskViolation::CrossesSpread { best, .. } if best == BigDecimal::from(101)
+        ));
+        let e = guard.place_order(bid(Some(94), 1)).await.unwrap_err();
+        assert!(matches!(violation(e), RiskViolation::AwayFromMid { .. }));
+        guard.place_order(bid(Some(96), 1)).await.unwrap();
+        guard.place_order(bid(None, 1)).await.unwrap();
+
+        // a local book moved down wins over the venue's
+        let mut book = OrderBook::new(String::from("BTC-USDT"));
+        book.bids = vec![PriceLevel::new(93.into(), 1.into())];
+        book.asks = vec![PriceLevel::new(95.into(), 1.into())];
+        book.stale = false;
+        guard.update_book(&book);
+        guard.place_order(bid(Some(94), 1)).await.unwrap();
+        book.stale = true;
+        guard.update_book(&book);
+        assert!(guard.place_order(bid(Some(94), 1)).await.is_err());
+
+        let flagged = Arc::new(Mutex::new(vec![]));
+        let guard = RiskGuard::new(
+            venue(),
+            RiskLimits {
+                pre_trade_check: Some(PreTradeCheck {
+                    action: PreTradeAction::Flag,
+                    ..check
+                }),
+                ..Default::default()
+            },
+        )
+        .on_event({
+            let flagged = flagged.clone();
+            move |event| flagged.lock().unwrap().push(event.clone())
+        });
+        guard.place_order(bid(Some(103), 1)).await.unwrap();
+        assert!(matches!(
+            &flagged.lock().unwrap()[..],
+            [RiskEvent::Flagged {
+                violation: RiskViolation::CrossesSpread { .. },
+                ..
+            }]
+        ));
+    }
+}
@@ -0,0 +1,100 @@
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, Signed};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// towards negative infinity
+    Floor,
+    /// towards positive infinity
+    Ceil,
+    /// to the nearest, ties away from zero
+    HalfUp,
+    /// drop the extra digits
+    TowardZero,
+}
+
+/// one unit of the last digit at `scale`, e.g. `0.01` at scale 2
+pub fn unit(scale: i64) -> BigDecimal {
+    BigDecimal::new(BigInt::from(1), scale)
+}
+
+/// `value` with `scale` digits after the decimal point
+pub fn round_to_scale(value: &BigDecimal, scale: i64, mode: RoundingMode) -> BigDecimal {
+    let truncated = value.with_scale(scale);
+    if &truncated == value {
+        return truncated;
+    }
+    let step = unit(scale);
+    match mode {
+        RoundingMode::TowardZero => truncated,
+        RoundingMode::Floor if value.is_negative() => truncated - step,
+        RoundingMode::Floor => truncated,
+        RoundingMode::Ceil if value.is_positive() => truncated + step,
+        RoundingMode::Ceil => truncated,
+        RoundingMode::HalfUp => {
+            if (value - &truncated).abs() * BigDecimal::from(2) < step {
+                truncated
+            } else if value.is_negative() {
+                truncated - step
+            } else {
+                truncated + step
+            }
+        }
+    }
+}
+
+/// `value` rounded to a multiple of `tick`, which must be positive
+pub fn round_to_tick(value: &BigDecimal, tick: &BigDecimal, mode: RoundingMode) -> BigDecimal {
+    round_to_scale(&(value / tick), 0, mode) * tick
+}
+
+/// the largest multiple of `tick` not above `value`
+pub fn floor_to_tick(value: &BigDecimal, tick: &BigDecimal) -> BigDecimal {
+    round_to_tick(value, tick, RoundingMode::Floor)
+}
+
+/// the smallest multiple of `tick` not below `value`
+pub fn ceil_to_tick(value: &BigDecimal, tick: &BigDecimal) -> BigDecimal {
+    round_to_tick(value, tick, RoundingMode::Ceil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> BigDecimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_round_to_scale() {
+        let cases = [
+            ("1.235", RoundingMode::Floor, "1.23"),
+            ("1.235", RoundingMode::Ceil, "1.24"),
+            ("1.235", RoundingMode::HalfUp, "1.24"),
+            ("1.2349", RoundingMode::HalfUp, "1.23"),
+            ("-1.235", RoundingMode::Floor, "-1.24"),
+            ("-1.235", RoundingMode::Ceil, "-1.23"),
+            ("-1.235", RoundingMode::HalfUp, "-1.24"),
+            ("-1.235", RoundingMode::TowardZero, "-1.23"),
+            ("1.2", RoundingMode::Ceil, "1.20"),
+        ];
+        for (value, mode, expected) in cases {
+            assert_eq!(
+                round_to_scale(&dec(value), 2, mode),
+                dec(expected),
+                "{} {:?}",
+                value,
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn test_ticks() {
+        assert_eq!(floor_to_tick(&dec("100.37"), &dec("0.05")), dec("100.35"));
+        assert_eq!(ceil_to_tick(&dec("100.37"), &dec("0.05")), dec("100.40"));
+        assert_eq!(ceil_to_tick(&dec("100.35"), &dec("0.05")), dec("100.35"));
+        assert_eq!(floor_to_tick(&dec("7"), &dec("2.5")), dec("5"));
+    }
+}
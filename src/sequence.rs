@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// hands out strictly increasing sequence numbers, taken when a request is issued
+/// so that a response can be ordered against responses of requests issued later
+#[derive(Debug, Default)]
+pub struct Sequencer {
+    next: AtomicU64,
+}
+
+impl Sequencer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn next(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+/// remembers the newest sequence applied to a piece of local state
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SequenceGuard {
+    last: Option<u64>,
+}
+
+impl SequenceGuard {
+    pub fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// true if `seq` is newer than everything applied so far, duplicates and late arrivals are refused
+    pub fn accept(&mut self, seq: u64) -> bool {
+        match self.last {
+            Some(last) if seq <= last => false,
+            _ => {
+                self.last = Some(seq);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_refuses_stale() {
+        let sequencer = Sequencer::new();
+        let (first, second) = (sequencer.next(), sequencer.next());
+        let mut guard = SequenceGuard::default();
+        assert!(guard.accept(second));
+        assert!(!guard.accept(first));
+        assert!(!guard.accept(second));
+        assert_eq!(guard.last(), Some(second));
+    }
+}
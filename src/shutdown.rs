@@ -0,0 +1,84 @@
+use crate::outbox::OutboxReport;
+use crate::response::CancelAllSummary;
+use tokio::sync::watch;
+
+/// what `FxdxClient::shutdown_with` does besides stopping the background loops
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownOptions {
+    /// cancel the open orders of the symbols set with `FxdxBuilder::symbols`, or of every
+    /// listed symbol when none are set
+    pub cancel_open_orders: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    /// the last flush of the outbox, orders still pending are lost with the process
+    pub outbox: OutboxReport,
+    /// `None` unless cancelling was asked for
+    pub cancelled: Option<CancelAllSummary>,
+    /// requests which failed as a whole, e.g. listing the open orders
+    pub errors: Vec<String>,
+}
+
+/// the trigger shared by the client and every loop it drives, triggered once and for good
+#[derive(Debug)]
+pub(crate) struct Shutdown {
+    trigger: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        Shutdown {
+            trigger: watch::Sender::new(false),
+        }
+    }
+
+    /// true if this call triggered it, false if it already was
+    pub(crate) fn trigger(&self) -> bool {
+        self.trigger.send_if_modified(|triggered| {
+            let first = !*triggered;
+            *triggered = true;
+            first
+        })
+    }
+
+    pub(crate) fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            receiver: self.trigger.subscribe(),
+        }
+    }
+}
+
+/// lets a task outside the client stop along with it
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal {
+    receiver: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    pub fn is_triggered(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// resolves once the shutdown was triggered
+    pub async fn wait(&mut self) {
+        // a dropped client counts as shut down
+        let _ = self.receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_signal() {
+        let shutdown = Shutdown::new();
+        let mut signal = shutdown.signal();
+        assert!(!signal.is_triggered());
+        assert!(shutdown.trigger());
+        assert!(!shutdown.trigger());
+        signal.wait().await;
+        assert!(shutdown.signal().is_triggered());
+    }
+}
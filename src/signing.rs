@@ -0,0 +1,370 @@
+use ed25519_dalek::Signer as _;
+use openssl::sha::{sha1, Sha1};
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox, SecretString};
+
+use crate::encoding::{self, Encoded, SignedRequest};
+
+pub use crate::encoding::{signature_payload, SignatureEncoding};
+
+/// HMAC-SHA1 of the signature payload keyed with the secret
+pub fn digest(secret: &str, payload: &str) -> Vec<u8> {
+    HmacKey::new(secret.as_bytes())
+        .sign(payload.as_bytes())
+        .to_vec()
+}
+
+const SHA1_BLOCK: usize = 64;
+
+/// the secret padded to a block and xored with ipad and opad (RFC 2104), derived once per
+/// signer so a request only costs the two hashes of the payload
+#[derive(Debug)]
+struct HmacKey {
+    inner: SecretBox<[u8; SHA1_BLOCK]>,
+    outer: SecretBox<[u8; SHA1_BLOCK]>,
+}
+
+impl HmacKey {
+    fn new(secret: &[u8]) -> Self {
+        // a key longer than a block is hashed first, a shorter one is padded with zeros
+        let mut key = SecretBox::new(Box::new([0u8; SHA1_BLOCK]));
+        if secret.len() > SHA1_BLOCK {
+            key.expose_secret_mut()[..20].copy_from_slice(&sha1(secret));
+        } else {
+            key.expose_secret_mut()[..secret.len()].copy_from_slice(secret);
+        }
+        let pad = |byte: u8| {
+            let mut pad = SecretBox::new(Box::new([0u8; SHA1_BLOCK]));
+            for (pad, key) in pad.expose_secret_mut().iter_mut().zip(key.expose_secret()) {
+                *pad = key ^ byte;
+            }
+            pad
+        };
+        HmacKey {
+            inner: pad(0x36),
+            outer: pad(0x5c),
+        }
+    }
+
+    fn sign(&self, payload: &[u8]) -> [u8; 20] {
+        let mut inner = Sha1::new();
+        inner.update(self.inner.expose_secret());
+        inner.update(payload);
+        let mut outer = Sha1::new();
+        outer.update(self.outer.expose_secret());
+        outer.update(&inner.finish());
+        outer.finish()
+    }
+}
+
+/// the hex encoded value of the `X-Signature` header of a request
+pub fn sign_request(
+    secret: &str,
+    timestamp: &str,
+    uri: &str,
+    formalized: Option<&str>,
+) -> anyhow::Result<String> {
+    sign_request_with(SignatureEncoding::Hex, secret, timestamp, uri, formalized)
+}
+
+/// `sign_request` with the signature in `encoding`
+pub fn sign_request_with(
+    encoding: SignatureEncoding,
+    secret: &str,
+    timestamp: &str,
+    uri: &str,
+    formalized: Option<&str>,
+) -> anyhow::Result<String> {
+    let payload = signature_payload(timestamp, uri, formalized);
+    Ok(encoding.encode(&digest(secret, &payload)))
+}
+
+/// true if `signature` is the hex signature of these parts, compared in constant time
+pub fn verify_request(
+    secret: &str,
+    timestamp: &str,
+    uri: &str,
+    formalized: Option<&str>,
+    signature: &str,
+) -> bool {
+    verify_request_with(
+        SignatureEncoding::Hex,
+        secret,
+        timestamp,
+        uri,
+        formalized,
+        signature,
+    )
+}
+
+/// `verify_request` of a signature in `encoding`
+pub fn verify_request_with(
+    encoding: SignatureEncoding,
+    secret: &str,
+    timestamp: &str,
+    uri: &str,
+    formalized: Option<&str>,
+    signature: &str,
+) -> bool {
+    match sign_request_with(encoding, secret, timestamp, uri, formalized) {
+        Ok(expected) => {
+            expected.len() == signature.len()
+                && openssl::memcmp::eq(expected.as_bytes(), signature.as_bytes())
+        }
+        Err(_) => false,
+    }
+}
+
+/// the context of sr25519 signatures, the one of substrate accounts
+pub const SR25519_CONTEXT: &[u8] = b"substrate";
+
+enum Key {
+    Hmac(HmacKey),
+    Ed25519(Box<ed25519_dalek::SigningKey>),
+    Sr25519(Box<schnorrkel::Keypair>),
+}
+
+/// the `Debug` of schnorrkel prints the secret, only the public key is shown here
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Hmac(key) => f.debug_tuple("Hmac").field(key).finish(),
+            Key::Ed25519(key) => f.debug_tuple("Ed25519").field(key).finish(),
+            Key::Sr25519(key) => f
+                .debug_tuple("Sr25519")
+                .field(&hex::encode(key.public.to_bytes()))
+                .finish(),
+        }
+    }
+}
+
+/// signs requests with a key derived once, not per request, and is shared between tasks
+/// behind an `Arc`; the key is cleared on drop and its `Debug` output is redacted
+#[derive(Debug)]
+pub struct Signer {
+    key: Key,
+    encoding: SignatureEncoding,
+}
+
+impl Signer {
+    /// the HMAC-SHA1 signer of `secret`
+    pub fn new(secret: SecretString, encoding: SignatureEncoding) -> Self {
+        Signer {
+            key: Key::Hmac(HmacKey::new(secret.expose_secret().as_bytes())),
+            encoding,
+        }
+    }
+
+    /// signs every request with the ed25519 key instead of an HMAC, see `keys::ed25519_from_hex`
+    pub fn ed25519(key: ed25519_dalek::SigningKey, encoding: SignatureEncoding) -> Self {
+        Signer {
+            key: Key::Ed25519(Box::new(key)),
+            encoding,
+        }
+    }
+
+    /// signs every request and the handshake with the sr25519 key in `SR25519_CONTEXT`, see
+    /// `keys::from_suri`
+    pub fn sr25519(key: schnorrkel::Keypair, encoding: SignatureEncoding) -> Self {
+        Signer {
+            key: Key::Sr25519(Box::new(key)),
+            encoding,
+        }
+    }
+
+    /// the hex public key and the hex signature of `nonce` for the token handshake, `None`
+    /// for an HMAC secret which needs no handshake
+    pub fn sign_nonce(&self, nonce: &str) -> Option<(String, String)> {
+        match &self.key {
+            Key::Hmac(_) => None,
+            Key::Ed25519(key) => Some((
+                hex::encode(key.verifying_key().as_bytes()),
+                hex::encode(key.sign(nonce.as_bytes()).to_bytes()),
+            )),
+            Key::Sr25519(key) => Some((
+                hex::encode(key.public.to_bytes()),
+                hex::encode(
+                    key.sign_simple(SR25519_CONTEXT, nonce.as_bytes())
+                        .to_bytes(),
+                ),
+            )),
+        }
+    }
+
+    /// the request with its timestamp, address and signature headers
+    pub fn sign(
+        &self,
+        request: &Encoded,
+        timestamp: &str,
+        address: &str,
+    ) -> anyhow::Result<SignedRequest> {
+        encoding::sign(
+            request,
+            timestamp,
+            address,
+            self.encoding,
+            |payload| match &self.key {
+                Key::Hmac(key) => Ok(key.sign(payload).to_vec()),
+                Key::Ed25519(key) => Ok(key.sign(payload).to_bytes().to_vec()),
+                Key::Sr25519(key) => Ok(key
+                    .sign_simple(SR25519_CONTEXT, payload)
+                    .to_bytes()
+                    .to_vec()),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_vectors() {
+        assert_eq!(
+            sign_request("key", "1650000000", "/maker/balances", None).unwrap(),
+            "7c3ab95f1c63cc3f1baa9e18d1839e935cefecc9"
+        );
+        assert_eq!(
+            sign_request(
+                "key",
+                "1650000000",
+                "/maker/depth/BTC-USDT",
+                Some("BTC-USDT")
+            )
+            .unwrap(),
+            "4fb893d25ada46d3592f21edaeb860db490139fc"
+        );
+        assert_eq!(
+            sign_request_with(
+                SignatureEncoding::Base64,
+                "key",
+                "1650000000",
+                "/maker/depth/BTC-USDT",
+                Some("BTC-USDT")
+            )
+            .unwrap(),
+            "T7iT0lraRtNZLyHtrrhg20kBOfw="
+        );
+        assert!(verify_request(
+            "key",
+            "1650000000",
+            "/maker/balances",
+            None,
+            "7c3ab95f1c63cc3f1baa9e18d1839e935cefecc9"
+        ));
+        assert!(!verify_request(
+            "key",
+            "1650000001",
+            "/maker/balances",
+            None,
+            "7c3ab95f1c63cc3f1baa9e18d1839e935cefecc9"
+        ));
+    }
+
+    #[test]
+    fn test_empty_secret() {
+        assert_eq!(
+            hex::encode(digest("", "payload")),
+            "38ba9081126a040d59d09e18865a930f16313df6"
+        );
+        assert!(sign_request("", "1650000000", "/maker/balances", None).is_ok());
+    }
+
+    #[test]
+    fn test_signer_debug_redacted() {
+        let signer = Signer::new(
+            SecretString::from("a-very-secret-maker-key"),
+            SignatureEncoding::Hex,
+        );
+        assert!(!format!("{:?}", signer).contains("a-very-secret-maker-key"));
+    }
+
+    #[test]
+    fn test_secret_never_in_signature() {
+        let secret = "a-very-secret-maker-key";
+        let signature = sign_request(secret, "1650000000", "/maker/balances", None).unwrap();
+        assert!(!signature.contains(secret));
+        assert!(!signature_payload("1650000000", "/maker/balances", None).contains(secret));
+    }
+
+    /// a placement may take a microsecond to encode into the buffers of the client and sign,
+    /// only held in release builds: `cargo test --release budget`
+    #[test]
+    #[cfg_attr(debug_assertions, ignore)]
+    fn test_encode_and_sign_budget() {
+        use crate::request::{NewOrder, PrivPub, Request, Side};
+        use std::hint::black_box;
+        use std::time::{Duration, Instant};
+
+        const BUDGET: Duration = Duration::from_micros(1);
+        let order = Request::PendingOrder(
+            NewOrder::builder()
+                .symbol("BTC-USDT")
+                .side(Side::Bid)
+                .price("30000.125".parse().unwrap())
+                .amount("0.5".parse().unwrap())
+                .build(),
+        );
+        let signer = Signer::new(
+            SecretString::from("a-maker-secret-of-the-usual-length-0123456789"),
+            SignatureEncoding::Hex,
+        );
+        let (wire, mut encoded) = (crate::wire::WireProfile::default(), Encoded::default());
+        // short rounds for up to a few seconds, one within the budget is enough so the other
+        // tests running alongside do not count against it
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut fastest = Duration::MAX;
+        while fastest > BUDGET && Instant::now() < deadline {
+            let start = Instant::now();
+            for _ in 0..200 {
+                order.encode_with::<PrivPub>(&wire, &mut encoded).unwrap();
+                black_box(signer.sign(&encoded, "1650000000", "0xabc").unwrap());
+            }
+            fastest = fastest.min(start.elapsed() / 200);
+            std::thread::yield_now();
+        }
+        assert!(
+            fastest <= BUDGET,
+            "encoding and signing an order takes {:?}, over the {:?} budget",
+            fastest,
+            BUDGET
+        );
+    }
+
+    #[test]
+    fn test_cached_key_signs_like_the_secret() {
+        fn shared<T: Send + Sync>(_: &T) {}
+        // RFC 2202, a key longer than a block
+        assert_eq!(
+            hex::encode(
+                HmacKey::new(&[0xaa; 80])
+                    .sign(b"Test Using Larger Than Block-Size Key - Hash Key First")
+            ),
+            "aa4ae5e15272d00e95705637ce8a3b55ed402112"
+        );
+        let signer = std::sync::Arc::new(Signer::new(
+            SecretString::from("key"),
+            SignatureEncoding::Hex,
+        ));
+        shared(&signer);
+        let request = || Encoded {
+            method: encoding::Method::Get,
+            uri: String::from("/maker/balances"),
+            formalized: None,
+            payload: None,
+        };
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let signer = signer.clone();
+                std::thread::spawn(move || signer.sign(&request(), "1650000000", "0xabc").unwrap())
+            })
+            .collect();
+        for thread in threads {
+            let signed = thread.join().unwrap();
+            assert!(signed.headers.contains(&(
+                encoding::SIGNATURE_HEADER,
+                String::from("7c3ab95f1c63cc3f1baa9e18d1839e935cefecc9")
+            )));
+        }
+    }
+}
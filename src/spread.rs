@@ -0,0 +1,206 @@
+//! watches the books of one pair on two venues and reports when buying on one and selling
+//! on the other pays more than the taker fees of both
+
+use crate::exchange::{Book, ExchangeClient, Level, Market};
+use anyhow::Result;
+use bigdecimal::{BigDecimal, One, Zero};
+use std::time::Duration;
+
+/// one side of the watched pair, `symbol` as the venue lists it
+pub struct Leg<'a> {
+    pub client: &'a dyn ExchangeClient,
+    pub symbol: String,
+    /// both legs cross the book, so only the taker fee matters
+    pub taker_fee: BigDecimal,
+}
+
+impl<'a> Leg<'a> {
+    pub fn new(client: &'a dyn ExchangeClient, market: &Market) -> Self {
+        Leg {
+            client,
+            symbol: market.symbol.clone(),
+            taker_fee: market.taker_fee.clone(),
+        }
+    }
+
+    /// look up the fees of `symbol` on the venue
+    pub async fn load(client: &'a dyn ExchangeClient, symbol: &str) -> Result<Leg<'a>> {
+        let markets = client.markets().await?;
+        let market = markets.iter().find(|m| m.symbol == symbol).ok_or_else(|| {
+            crate::Error::InvalidRequest(format!("{} does not list {}", client.venue(), symbol))
+        })?;
+        Ok(Leg::new(client, market))
+    }
+}
+
+/// buy `amount` on `buy_venue` and sell it on `sell_venue`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opportunity {
+    pub buy_venue: String,
+    pub sell_venue: String,
+    /// what both books hold of the configured size
+    pub amount: BigDecimal,
+    /// average prices of taking `amount` from each book
+    pub buy_price: BigDecimal,
+    pub sell_price: BigDecimal,
+    /// `(sell - buy) / buy` before fees
+    pub gross_spread: BigDecimal,
+    /// the profit over the cost of the buy, both taker fees paid
+    pub net_spread: BigDecimal,
+    /// in quote, both taker fees paid
+    pub net_profit: BigDecimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpreadOptions {
+    /// the smallest `net_spread` reported, e.g. `0.001` for 10 bps
+    pub threshold: BigDecimal,
+    /// the amount in base an opportunity is priced for
+    pub size: BigDecimal,
+}
+
+/// the better of the two directions if its net spread reaches `options.threshold`
+pub fn evaluate(
+    a: (&str, &Book, &BigDecimal),
+    b: (&str, &Book, &BigDecimal),
+    options: &SpreadOptions,
+) -> Option<Opportunity> {
+    let ab = direction(a, b, &options.size);
+    let ba = direction(b, a, &options.size);
+    let best = match (ab, ba) {
+        (Some(ab), Some(ba)) if ba.net_spread > ab.net_spread => Some(ba),
+        (Some(ab), _) => Some(ab),
+        (None, ba) => ba,
+    };
+    best.filter(|opportunity| opportunity.net_spread >= options.threshold)
+}
+
+/// buy on `buy` and sell on `sell`, `None` when either side is empty
+fn direction(
+    (buy_venue, buy, buy_fee): (&str, &Book, &BigDecimal),
+    (sell_venue, sell, sell_fee): (&str, &Book, &BigDecimal),
+    size: &BigDecimal,
+) -> Option<Opportunity> {
+    let amount = size.clone().min(total(&buy.asks)).min(total(&sell.bids));
+    if amount <= BigDecimal::zero() {
+        return None;
+    }
+    let buy_price = average(&buy.asks, &amount)?;
+    let sell_price = average(&sell.bids, &amount)?;
+    let cost = &buy_price * &amount * (BigDecimal::one() + buy_fee);
+    let proceeds = &sell_price * &amount * (BigDecimal::one() - sell_fee);
+    let net_profit = proceeds - &cost;
+    Some(Opportunity {
+        buy_venue: buy_venue.to_string(),
+        sell_venue: sell_venue.to_string(),
+        gross_spread: (&sell_price - &buy_price) / &buy_price,
+        net_spread: &net_profit / &cost,
+        net_profit,
+        amount,
+        buy_price,
+        sell_price,
+    })
+}
+
+fn total(levels: &[Level]) -> BigDecimal {
+    levels
+        .iter()
+        .fold(BigDecimal::zero(), |acc, level| acc + &level.amount)
+}
+
+/// the average price of taking `amount` from the top of `levels`
+fn average(levels: &[Level], amount: &BigDecimal) -> Option<BigDecimal> {
+    let mut remaining = amount.clone();
+    let mut quote = BigDecimal::zero();
+    for level in levels {
+        let taken = remaining.clone().min(level.amount.clone());
+        quote += &taken * &level.price;
+        remaining -= taken;
+        if remaining <= BigDecimal::zero() {
+            return Some(quote / amount);
+        }
+    }
+    None
+}
+
+/// polls both legs and evaluates their books, see `evaluate`
+pub struct SpreadMonitor<'a> {
+    pub left: Leg<'a>,
+    pub right: Leg<'a>,
+    pub options: SpreadOptions,
+}
+
+impl SpreadMonitor<'_> {
+    /// fetch both books at once and evaluate them
+    pub async fn poll(&self) -> Result<Option<Opportunity>> {
+        let (left, right) = futures_util::future::try_join(
+            self.left.client.depth(&self.left.symbol, None),
+            self.right.client.depth(&self.right.symbol, None),
+        )
+        .await?;
+        Ok(evaluate(
+            (self.left.client.venue(), &left, &self.left.taker_fee),
+            (self.right.client.venue(), &right, &self.right.taker_fee),
+            &self.options,
+        ))
+    }
+
+    /// poll every `every` and hand each opportunity to `on_opportunity`, until a request fails
+    pub async fn run<F>(&self, every: Duration, mut on_opportunity: F) -> Result<()>
+    where
+        F: FnMut(Opportunity),
+    {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            if let Some(opportunity) = self.poll().await? {
+                on_opportunity(opportunity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(bids: &[(i64, i64)], asks: &[(i64, i64)]) -> Book {
+        let levels = |l: &[(i64, i64)]| {
+            l.iter()
+                .map(|(price, amount)| Level {
+                    price: (*price).into(),
+                    amount: (*amount).into(),
+                })
+                .collect()
+        };
+        Book {
+            bids: levels(bids),
+            asks: levels(asks),
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn test_fee_adjusted_spread() {
+        let fee: BigDecimal = "0.001".parse().unwrap();
+        let cheap = book(&[(99, 5)], &[(100, 1), (101, 1)]);
+        let rich = book(&[(103, 1), (102, 5)], &[(104, 5)]);
+        let options = SpreadOptions {
+            threshold: "0.005".parse().unwrap(),
+            size: 2.into(),
+        };
+        let opportunity = evaluate(("a", &cheap, &fee), ("b", &rich, &fee), &options).unwrap();
+        assert_eq!(opportunity.buy_venue, "a");
+        assert_eq!(opportunity.buy_price, "100.5".parse().unwrap());
+        assert_eq!(opportunity.sell_price, "102.5".parse().unwrap());
+        // 205 * 0.999 - 201 * 1.001
+        assert_eq!(opportunity.net_profit, "3.594".parse().unwrap());
+
+        let options = SpreadOptions {
+            threshold: "0.02".parse().unwrap(),
+            ..options
+        };
+        assert!(evaluate(("a", &cheap, &fee), ("b", &rich, &fee), &options).is_none());
+        assert!(evaluate(("a", &cheap, &fee), ("b", &cheap, &fee), &options).is_none());
+    }
+}
@@ -0,0 +1,784 @@
+//! persists every order and fill the client reads into a normalized SQL schema, so a restart
+//! reloads the open orders and the history is there for analysis in SQL
+//!
+//! the crate ships the schema and statements of `Dialect`, a `MemoryStore`, and sqlx stores
+//! with `--features postgres` or `--features sqlite`:
+//!
+//! ```ignore
+//! let pool = sqlx::postgres::PgPoolOptions::new().connect(url).await?;
+//! let client = FxdxBuilder::<PrivPub>::endpoint(endpoint)
+//!     .storage(PgStore::open(pool).await?)
+//!     // ..
+//! ```
+//!
+//! any other driver implements `Storage` with the statements of `Dialect`
+
+use crate::request::{OrderStatus, Side};
+use crate::response::{QueryOrder, Trade};
+use anyhow::Result;
+use bigdecimal::BigDecimal;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// where `FxdxBuilder::storage` writes orders and fills; a failed write is logged and does not
+/// fail the request which read them
+pub trait Storage: Send + Sync {
+    /// insert or update orders by id, a row never replaces one seen later
+    fn save_orders<'a>(&'a self, orders: &'a [OrderRow]) -> StorageFuture<'a, ()>;
+
+    /// insert fills, a fill already stored is skipped, see `FillRow`
+    fn save_fills<'a>(&'a self, fills: &'a [FillRow]) -> StorageFuture<'a, ()>;
+
+    /// the orders last seen open, of `symbol` or of every symbol
+    fn open_orders<'a>(&'a self, symbol: Option<&'a str>) -> StorageFuture<'a, Vec<OrderRow>>;
+}
+
+impl<T: Storage + ?Sized> Storage for std::sync::Arc<T> {
+    fn save_orders<'a>(&'a self, orders: &'a [OrderRow]) -> StorageFuture<'a, ()> {
+        (**self).save_orders(orders)
+    }
+
+    fn save_fills<'a>(&'a self, fills: &'a [FillRow]) -> StorageFuture<'a, ()> {
+        (**self).save_fills(fills)
+    }
+
+    fn open_orders<'a>(&'a self, symbol: Option<&'a str>) -> StorageFuture<'a, Vec<OrderRow>> {
+        (**self).open_orders(symbol)
+    }
+}
+
+/// one row of `fxdx_orders`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRow {
+    pub order_id: String,
+    pub symbol: String,
+    pub side: Side,
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+    pub filled_base: BigDecimal,
+    pub filled_quote: BigDecimal,
+    pub avg_price: BigDecimal,
+    pub status: OrderStatus,
+    pub created_at: Option<i64>,
+    pub updated_at: Option<i64>,
+    /// unix milliseconds the client read the order at, orders the newest read
+    pub seen_at: i64,
+}
+
+impl OrderRow {
+    pub fn new(order: &QueryOrder, seen_at: i64) -> Self {
+        OrderRow {
+            order_id: order.order_id.clone(),
+            symbol: order.symbol.clone(),
+            side: order.direction,
+            price: order.price.clone(),
+            amount: order.amount.clone(),
+            filled_base: order.filled_base.clone(),
+            filled_quote: order.filled_quote.clone(),
+            avg_price: order.avg_price.clone(),
+            status: order.status,
+            created_at: order.created_at,
+            updated_at: order.updated_at,
+            seen_at,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.status,
+            OrderStatus::Undeal | OrderStatus::PartialDealed
+        )
+    }
+
+    /// the text columns in the order of `Dialect::upsert_order`, decimals as exact strings
+    pub fn text_columns(&self) -> [String; 8] {
+        [
+            self.order_id.clone(),
+            self.symbol.clone(),
+            self.side.to_string(),
+            self.price.to_string(),
+            self.amount.to_string(),
+            self.filled_base.to_string(),
+            self.filled_quote.to_string(),
+            self.avg_price.to_string(),
+        ]
+    }
+}
+
+/// symbol, timestamp, price, amount and side, what the venue tells of a fill
+pub type FillContent = (String, i64, String, String, String);
+
+/// the content, the order id or empty and the occurrence, the unique index of `fxdx_fills`
+pub type FillKey = (FillContent, String, u32);
+
+/// one row of `fxdx_fills`, the fills of `query_my_trades` have no order id
+///
+/// fills carry no id of their own, so equal fills of one read are numbered by `occurrence`
+/// and a fill is stored once per order id and occurrence. a fill read without its order is
+/// skipped when the fills stored with the same content are already as many as its
+/// occurrence, and a fill read with its order takes over one stored without an id.
+///
+/// what stays ambiguous: equal fills of different orders read without their orders are only
+/// counted, which order takes over which of them is arbitrary; and equal fills split over two
+/// pages of `query_my_trades` are numbered per page, so the second one is taken for the first
+/// until its order is read
+#[derive(Debug, Clone, PartialEq)]
+pub struct FillRow {
+    pub order_id: Option<String>,
+    pub symbol: String,
+    pub side: Side,
+    pub price: BigDecimal,
+    pub amount: BigDecimal,
+    pub quote_amount: BigDecimal,
+    pub base_fee: BigDecimal,
+    pub quote_fee: BigDecimal,
+    pub timestamp: i64,
+    /// the number of equal fills before this one in the same read
+    pub occurrence: u32,
+}
+
+impl FillRow {
+    pub fn new(symbol: &str, order_id: Option<&str>, trade: &Trade) -> Self {
+        FillRow {
+            order_id: order_id.map(str::to_string),
+            symbol: symbol.to_string(),
+            side: trade.ask_or_bid,
+            price: trade.price.clone(),
+            amount: trade.amount.clone(),
+            quote_amount: trade.quote_amount.clone(),
+            base_fee: trade.base_fee.clone(),
+            quote_fee: trade.quote_fee.clone(),
+            timestamp: trade.timestamp,
+            occurrence: 0,
+        }
+    }
+
+    /// the fills of one read, equal ones numbered in the order they were read
+    pub fn read<'a>(
+        symbol: &str,
+        order_id: Option<&str>,
+        trades: impl IntoIterator<Item = &'a Trade>,
+    ) -> Vec<Self> {
+        let mut seen = HashMap::new();
+        trades
+            .into_iter()
+            .map(|trade| {
+                let mut row = FillRow::new(symbol, order_id, trade);
+                let occurrence = seen.entry(row.content()).or_insert(0);
+                row.occurrence = *occurrence;
+                *occurrence += 1;
+                row
+            })
+            .collect()
+    }
+
+    /// what the venue tells of the fill, equal for equal fills of different orders
+    pub fn content(&self) -> FillContent {
+        (
+            self.symbol.clone(),
+            self.timestamp,
+            self.price.to_string(),
+            self.amount.to_string(),
+            self.side.to_string(),
+        )
+    }
+
+    pub fn key(&self) -> FillKey {
+        (
+            self.content(),
+            self.order_id.clone().unwrap_or_default(),
+            self.occurrence,
+        )
+    }
+
+    /// the text columns in the order of `Dialect::insert_fill`, an unknown order id as empty
+    pub fn text_columns(&self) -> [String; 8] {
+        [
+            self.order_id.clone().unwrap_or_default(),
+            self.symbol.clone(),
+            self.side.to_string(),
+            self.price.to_string(),
+            self.amount.to_string(),
+            self.quote_amount.to_string(),
+            self.base_fee.to_string(),
+            self.quote_fee.to_string(),
+        ]
+    }
+}
+
+/// the SQL of the schema and the statements in the placeholders of each database
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Dialect {
+    Postgres,
+    Sqlite,
+}
+
+impl Dialect {
+    /// idempotent DDL, run it on every start
+    ///
+    /// decimals are text in SQLite, which has no exact decimal type, and `NUMERIC` in Postgres
+    pub fn schema(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => {
+                "CREATE TABLE IF NOT EXISTS fxdx_orders (
+    order_id TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price NUMERIC NOT NULL,
+    amount NUMERIC NOT NULL,
+    filled_base NUMERIC NOT NULL,
+    filled_quote NUMERIC NOT NULL,
+    avg_price NUMERIC NOT NULL,
+    status SMALLINT NOT NULL,
+    created_at BIGINT,
+    updated_at BIGINT,
+    seen_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS fxdx_orders_open ON fxdx_orders (symbol) WHERE status IN (1, 4);
+CREATE TABLE IF NOT EXISTS fxdx_fills (
+    order_id TEXT NOT NULL DEFAULT '',
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price NUMERIC NOT NULL,
+    amount NUMERIC NOT NULL,
+    quote_amount NUMERIC NOT NULL,
+    base_fee NUMERIC NOT NULL,
+    quote_fee NUMERIC NOT NULL,
+    timestamp BIGINT NOT NULL,
+    occurrence INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (symbol, timestamp, price, amount, side, order_id, occurrence)
+);"
+            }
+            Dialect::Sqlite => {
+                "CREATE TABLE IF NOT EXISTS fxdx_orders (
+    order_id TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    filled_base TEXT NOT NULL,
+    filled_quote TEXT NOT NULL,
+    avg_price TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    created_at INTEGER,
+    updated_at INTEGER,
+    seen_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS fxdx_orders_open ON fxdx_orders (symbol) WHERE status IN (1, 4);
+CREATE TABLE IF NOT EXISTS fxdx_fills (
+    order_id TEXT NOT NULL DEFAULT '',
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    quote_amount TEXT NOT NULL,
+    base_fee TEXT NOT NULL,
+    quote_fee TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    occurrence INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (symbol, timestamp, price, amount, side, order_id, occurrence)
+);"
+            }
+        }
+    }
+
+    /// the 8 `OrderRow::text_columns`, then status, created_at, updated_at and seen_at
+    pub fn upsert_order(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => {
+                "INSERT INTO fxdx_orders (order_id, symbol, side, price, amount, filled_base, \
+                 filled_quote, avg_price, status, created_at, updated_at, seen_at) \
+                 VALUES ($1, $2, $3, $4::TEXT::NUMERIC, $5::TEXT::NUMERIC, \
+                 $6::TEXT::NUMERIC, $7::TEXT::NUMERIC, $8::TEXT::NUMERIC, $9, $10, $11, $12) \
+                 ON CONFLICT (order_id) DO UPDATE SET filled_base = excluded.filled_base, \
+                 filled_quote = excluded.filled_quote, avg_price = excluded.avg_price, \
+                 status = excluded.status, updated_at = excluded.updated_at, \
+                 seen_at = excluded.seen_at WHERE fxdx_orders.seen_at <= excluded.seen_at"
+            }
+            Dialect::Sqlite => {
+                "INSERT INTO fxdx_orders (order_id, symbol, side, price, amount, filled_base, \
+                 filled_quote, avg_price, status, created_at, updated_at, seen_at) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+                 ON CONFLICT (order_id) DO UPDATE SET filled_base = excluded.filled_base, \
+                 filled_quote = excluded.filled_quote, avg_price = excluded.avg_price, \
+                 status = excluded.status, updated_at = excluded.updated_at, \
+                 seen_at = excluded.seen_at WHERE fxdx_orders.seen_at <= excluded.seen_at"
+            }
+        }
+    }
+
+    /// the first 5 `FillRow::text_columns`, then timestamp and occurrence; a fill with its
+    /// order id takes over a row of the same content stored without one, run before
+    /// `insert_fill`
+    pub fn claim_fill(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => {
+                "UPDATE fxdx_fills SET order_id = $1, occurrence = $7 \
+                 WHERE $1 <> '' AND order_id = '' AND symbol = $2 AND side = $3 \
+                 AND price = $4::TEXT::NUMERIC AND amount = $5::TEXT::NUMERIC AND timestamp = $6 \
+                 AND occurrence = (SELECT MAX(occurrence) FROM fxdx_fills WHERE order_id = '' \
+                 AND symbol = $2 AND side = $3 AND price = $4::TEXT::NUMERIC \
+                 AND amount = $5::TEXT::NUMERIC AND timestamp = $6) \
+                 AND NOT EXISTS (SELECT 1 FROM fxdx_fills WHERE order_id = $1 AND symbol = $2 \
+                 AND side = $3 AND price = $4::TEXT::NUMERIC AND amount = $5::TEXT::NUMERIC \
+                 AND timestamp = $6 AND occurrence = $7)"
+            }
+            Dialect::Sqlite => {
+                "UPDATE fxdx_fills SET order_id = ?1, occurrence = ?7 \
+                 WHERE ?1 <> '' AND order_id = '' AND symbol = ?2 AND side = ?3 AND price = ?4 \
+                 AND amount = ?5 AND timestamp = ?6 \
+                 AND occurrence = (SELECT MAX(occurrence) FROM fxdx_fills WHERE order_id = '' \
+                 AND symbol = ?2 AND side = ?3 AND price = ?4 AND amount = ?5 AND timestamp = ?6) \
+                 AND NOT EXISTS (SELECT 1 FROM fxdx_fills WHERE order_id = ?1 AND symbol = ?2 \
+                 AND side = ?3 AND price = ?4 AND amount = ?5 AND timestamp = ?6 \
+                 AND occurrence = ?7)"
+            }
+        }
+    }
+
+    /// the 8 `FillRow::text_columns`, then timestamp and occurrence; a fill without its order
+    /// id is only inserted while fewer fills of the same content than its occurrence are stored
+    pub fn insert_fill(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => {
+                "INSERT INTO fxdx_fills (order_id, symbol, side, price, amount, quote_amount, \
+                 base_fee, quote_fee, timestamp, occurrence) SELECT $1::TEXT, $2::TEXT, $3::TEXT, \
+                 $4::TEXT::NUMERIC, $5::TEXT::NUMERIC, $6::TEXT::NUMERIC, $7::TEXT::NUMERIC, \
+                 $8::TEXT::NUMERIC, $9::BIGINT, $10::INTEGER \
+                 WHERE $1::TEXT <> '' OR (SELECT COUNT(*) FROM fxdx_fills WHERE symbol = $2::TEXT \
+                 AND side = $3::TEXT AND price = $4::TEXT::NUMERIC AND amount = $5::TEXT::NUMERIC \
+                 AND timestamp = $9::BIGINT) <= $10::INTEGER \
+                 ON CONFLICT DO NOTHING"
+            }
+            Dialect::Sqlite => {
+                "INSERT INTO fxdx_fills (order_id, symbol, side, price, amount, quote_amount, \
+                 base_fee, quote_fee, timestamp, occurrence) \
+                 SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10 \
+                 WHERE ?1 <> '' OR (SELECT COUNT(*) FROM fxdx_fills WHERE symbol = ?2 \
+                 AND side = ?3 AND price = ?4 AND amount = ?5 AND timestamp = ?9) <= ?10 \
+                 ON CONFLICT DO NOTHING"
+            }
+        }
+    }
+
+    /// the open orders, bind the symbol twice, or null twice for every symbol
+    pub fn select_open_orders(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => {
+                "SELECT order_id, symbol, side, price::TEXT, amount::TEXT, filled_base::TEXT, \
+                 filled_quote::TEXT, avg_price::TEXT, status, created_at, updated_at, seen_at \
+                 FROM fxdx_orders WHERE status IN (1, 4) AND ($1::TEXT IS NULL OR symbol = $2) \
+                 ORDER BY order_id"
+            }
+            Dialect::Sqlite => {
+                "SELECT order_id, symbol, side, price, amount, filled_base, filled_quote, \
+                 avg_price, status, created_at, updated_at, seen_at \
+                 FROM fxdx_orders WHERE status IN (1, 4) AND (? IS NULL OR symbol = ?) \
+                 ORDER BY order_id"
+            }
+        }
+    }
+}
+
+/// a store in memory, for tests and paper trading
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    orders: Mutex<BTreeMap<String, OrderRow>>,
+    fills: Mutex<BTreeMap<FillKey, FillRow>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn order(&self, order_id: &str) -> Option<OrderRow> {
+        self.orders
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(order_id)
+            .cloned()
+    }
+
+    /// every stored fill, by symbol and time
+    pub fn fills(&self) -> Vec<FillRow> {
+        self.fills
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+impl Storage for MemoryStore {
+    fn save_orders<'a>(&'a self, orders: &'a [OrderRow]) -> StorageFuture<'a, ()> {
+        let mut stored = self.orders.lock().unwrap_or_else(|e| e.into_inner());
+        for row in orders {
+            match stored.get(&row.order_id) {
+                Some(prev) if prev.seen_at > row.seen_at => {}
+                _ => {
+                    stored.insert(row.order_id.clone(), row.clone());
+                }
+            }
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn save_fills<'a>(&'a self, fills: &'a [FillRow]) -> StorageFuture<'a, ()> {
+        let mut stored = self.fills.lock().unwrap_or_else(|e| e.into_inner());
+        for row in fills {
+            let key = row.key();
+            if stored.contains_key(&key) {
+                continue;
+            }
+            let content = row.content();
+            let mut same = stored.keys().filter(|(c, _, _)| c == &content);
+            match &row.order_id {
+                Some(_) => {
+                    // the last one of the same content stored without an id, as `claim_fill`
+                    let anonymous = same.rfind(|(_, id, _)| id.is_empty()).cloned();
+                    if let Some(anonymous) = anonymous {
+                        stored.remove(&anonymous);
+                    }
+                }
+                None if same.count() > row.occurrence as usize => continue,
+                None => {}
+            }
+            stored.insert(key, row.clone());
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn open_orders<'a>(&'a self, symbol: Option<&'a str>) -> StorageFuture<'a, Vec<OrderRow>> {
+        let open = self
+            .orders
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .filter(|row| row.is_open() && symbol.is_none_or(|s| row.symbol == s))
+            .cloned()
+            .collect();
+        Box::pin(async { Ok(open) })
+    }
+}
+
+/// an `OrderRow` from the columns of `Dialect::select_open_orders`
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+fn order_row(
+    text: [String; 8],
+    status: impl Into<i64>,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+    seen_at: i64,
+) -> Result<OrderRow> {
+    let [order_id, symbol, side, price, amount, filled_base, filled_quote, avg_price] = text;
+    Ok(OrderRow {
+        order_id,
+        symbol,
+        side: side.parse()?,
+        price: price.parse()?,
+        amount: amount.parse()?,
+        filled_base: filled_base.parse()?,
+        filled_quote: filled_quote.parse()?,
+        avg_price: avg_price.parse()?,
+        status: status.into().to_string().parse()?,
+        created_at,
+        updated_at,
+        seen_at,
+    })
+}
+
+/// a `Storage` on an sqlx pool, running the statements of `$dialect`; `$status` is the
+/// integer type of the status column
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+macro_rules! sqlx_store {
+    ($(#[$doc:meta])* $name:ident, $feature:literal, $pool:ty, $dialect:expr, $status:ty) => {
+        $(#[$doc])*
+        #[cfg(feature = $feature)]
+        #[derive(Debug, Clone)]
+        pub struct $name {
+            pool: $pool,
+        }
+
+        #[cfg(feature = $feature)]
+        impl $name {
+            /// run the schema on `pool`, then store into it
+            pub async fn open(pool: $pool) -> Result<Self> {
+                sqlx::raw_sql($dialect.schema()).execute(&pool).await?;
+                Ok($name { pool })
+            }
+
+            pub fn pool(&self) -> &$pool {
+                &self.pool
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl Storage for $name {
+            fn save_orders<'a>(&'a self, orders: &'a [OrderRow]) -> StorageFuture<'a, ()> {
+                Box::pin(async move {
+                    let mut tx = self.pool.begin().await?;
+                    for row in orders {
+                        let mut query = sqlx::query($dialect.upsert_order());
+                        for column in row.text_columns() {
+                            query = query.bind(column);
+                        }
+                        query
+                            .bind(row.status as $status)
+                            .bind(row.created_at)
+                            .bind(row.updated_at)
+                            .bind(row.seen_at)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    tx.commit().await?;
+                    Ok(())
+                })
+            }
+
+            fn save_fills<'a>(&'a self, fills: &'a [FillRow]) -> StorageFuture<'a, ()> {
+                Box::pin(async move {
+                    let mut tx = self.pool.begin().await?;
+                    for row in fills {
+                        let occurrence = i32::try_from(row.occurrence)?;
+                        let text = row.text_columns();
+                        let mut claim = sqlx::query($dialect.claim_fill());
+                        for column in &text[..5] {
+                            claim = claim.bind(column);
+                        }
+                        claim
+                            .bind(row.timestamp)
+                            .bind(occurrence)
+                            .execute(&mut *tx)
+                            .await?;
+                        let mut insert = sqlx::query($dialect.insert_fill());
+                        for column in text {
+                            insert = insert.bind(column);
+                        }
+                        insert
+                            .bind(row.timestamp)
+                            .bind(occurrence)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    tx.commit().await?;
+                    Ok(())
+                })
+            }
+
+            fn open_orders<'a>(
+                &'a self,
+                symbol: Option<&'a str>,
+            ) -> StorageFuture<'a, Vec<OrderRow>> {
+                Box::pin(async move {
+                    use sqlx::Row;
+                    let rows = sqlx::query($dialect.select_open_orders())
+                        .bind(symbol)
+                        .bind(symbol)
+                        .fetch_all(&self.pool)
+                        .await?;
+                    rows.iter()
+                        .map(|row| {
+                            let mut text: [String; 8] = Default::default();
+                            for (i, column) in text.iter_mut().enumerate() {
+                                *column = row.try_get(i)?;
+                            }
+                            order_row(
+                                text,
+                                row.try_get::<$status, _>(8)?,
+                                row.try_get(9)?,
+                                row.try_get(10)?,
+                                row.try_get(11)?,
+                            )
+                        })
+                        .collect()
+                })
+            }
+        }
+    };
+}
+
+#[cfg(feature = "postgres")]
+sqlx_store!(
+    /// the Postgres store, `--features postgres`
+    PgStore,
+    "postgres",
+    sqlx::PgPool,
+    Dialect::Postgres,
+    i16
+);
+
+#[cfg(feature = "sqlite")]
+sqlx_store!(
+    /// the SQLite store, `--features sqlite`; an in-memory database needs a pool of one
+    /// connection, every connection opens a database of its own
+    SqliteStore,
+    "sqlite",
+    sqlx::SqlitePool,
+    Dialect::Sqlite,
+    i64
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(status: i32, seen_at: i64) -> OrderRow {
+        let order: QueryOrder = serde_json::from_str(&format!(
+            r#"{{"symbol":"BTC-USDT","order_id":"7","order_type":1,"direction":1,"amount":"2","price":"10","status":{}}}"#,
+            status
+        ))
+        .unwrap();
+        OrderRow::new(&order, seen_at)
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new();
+        store.save_orders(&[order(1, 2)]).await.unwrap();
+        // an older read of the cancelled order does not win
+        store.save_orders(&[order(2, 1)]).await.unwrap();
+        let open = store.open_orders(Some("BTC-USDT")).await.unwrap();
+        assert_eq!(open.len(), 1);
+        assert!(store
+            .open_orders(Some("ETH-USDT"))
+            .await
+            .unwrap()
+            .is_empty());
+        store.save_orders(&[order(2, 3)]).await.unwrap();
+        assert!(store.open_orders(None).await.unwrap().is_empty());
+
+        let trade: Trade = serde_json::from_str(
+            r#"{"base":1,"quote":2,"ask_or_bid":1,"price":"10","amount":"1","quote_amount":"10","timestamp":5}"#,
+        )
+        .unwrap();
+        // read through `query_my_trades` without its order, then with it
+        store
+            .save_fills(&[FillRow::new("BTC-USDT", None, &trade)])
+            .await
+            .unwrap();
+        let fill = FillRow::new("BTC-USDT", Some("7"), &trade);
+        store
+            .save_fills(&[fill.clone(), fill.clone()])
+            .await
+            .unwrap();
+        store
+            .save_fills(&[FillRow::new("BTC-USDT", None, &trade)])
+            .await
+            .unwrap();
+        assert_eq!(store.fills(), [fill]);
+        assert_eq!(order(1, 0).text_columns()[2], "BID");
+        assert!(Dialect::Sqlite.schema().contains("fxdx_fills"));
+    }
+
+    /// two fills of orders 7 and 8, equal but for their order, read by `query_my_trades` and
+    /// then through their orders in `order_ids`
+    fn equal_fills(order_ids: [&str; 2]) -> Vec<Vec<FillRow>> {
+        let trade: Trade = serde_json::from_str(
+            r#"{"base":1,"quote":2,"ask_or_bid":1,"price":"10.5","amount":"1","quote_amount":"10.5","timestamp":5}"#,
+        )
+        .unwrap();
+        let mut reads = vec![FillRow::read("BTC-USDT", None, [&trade, &trade])];
+        for order_id in order_ids {
+            reads.push(FillRow::read("BTC-USDT", Some(order_id), [&trade]));
+        }
+        reads.push(FillRow::read("BTC-USDT", None, [&trade, &trade]));
+        reads
+    }
+
+    #[tokio::test]
+    async fn test_equal_fills_of_orders() {
+        let reads = equal_fills(["7", "8"]);
+        assert_eq!(
+            reads[0].iter().map(|f| f.occurrence).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        let store = MemoryStore::new();
+        store.save_fills(&reads[0]).await.unwrap();
+        assert_eq!(store.fills().len(), 2);
+        for read in &reads[1..] {
+            store.save_fills(read).await.unwrap();
+        }
+        let order_ids = store
+            .fills()
+            .into_iter()
+            .map(|f| f.order_id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order_ids,
+            [Some(String::from("7")), Some(String::from("8"))]
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        use sqlx::Row;
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let store = SqliteStore::open(pool.clone()).await.unwrap();
+        // the schema runs again on every start
+        let store = SqliteStore::open(store.pool().clone()).await.unwrap();
+        store.save_orders(&[order(1, 2)]).await.unwrap();
+        store.save_orders(&[order(2, 1)]).await.unwrap();
+        assert_eq!(
+            store.open_orders(Some("BTC-USDT")).await.unwrap(),
+            [order(1, 2)]
+        );
+        assert!(store
+            .open_orders(Some("ETH-USDT"))
+            .await
+            .unwrap()
+            .is_empty());
+        store.save_orders(&[order(2, 3)]).await.unwrap();
+        assert!(store.open_orders(None).await.unwrap().is_empty());
+
+        let trade: Trade = serde_json::from_str(
+            r#"{"base":1,"quote":2,"ask_or_bid":1,"price":"10.5","amount":"1","quote_amount":"10.5","timestamp":5}"#,
+        )
+        .unwrap();
+        store
+            .save_fills(&[FillRow::new("BTC-USDT", None, &trade)])
+            .await
+            .unwrap();
+        store
+            .save_fills(&[FillRow::new("BTC-USDT", Some("7"), &trade)])
+            .await
+            .unwrap();
+        store
+            .save_fills(&[FillRow::new("BTC-USDT", None, &trade)])
+            .await
+            .unwrap();
+        let fills = sqlx::query("SELECT order_id, price FROM fxdx_fills")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].get::<String, _>(0), "7");
+        assert_eq!(fills[0].get::<String, _>(1), "10.5");
+
+        sqlx::query("DELETE FROM fxdx_fills")
+            .execute(&pool)
+            .await
+            .unwrap();
+        for read in equal_fills(["7", "8"]) {
+            store.save_fills(&read).await.unwrap();
+        }
+        let order_ids = sqlx::query("SELECT order_id FROM fxdx_fills ORDER BY order_id")
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get::<String, _>(0))
+            .collect::<Vec<_>>();
+        assert_eq!(order_ids, ["7", "8"]);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::request::NewOrder;
+use crate::response::Symbol;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct Listing {
+    symbols: BTreeMap<String, Symbol>,
+    loaded_at: Option<Instant>,
+}
+
+/// the symbol metadata of `query_symbols`, loaded once and refreshed when older than `max_age`
+///
+/// clones share one listing, so the order checks, rounding and fee estimates of every task
+/// read the same metadata without asking `/symbols` for each order
+#[derive(Debug, Clone)]
+pub struct SymbolRegistry {
+    max_age: Duration,
+    relaxed: bool,
+    listing: Arc<RwLock<Listing>>,
+}
+
+impl SymbolRegistry {
+    pub fn new(max_age: Duration) -> Self {
+        SymbolRegistry {
+            max_age,
+            relaxed: false,
+            listing: Default::default(),
+        }
+    }
+
+    /// let orders on unlisted pairs through `check_order`, see `Environment::relaxed_symbols`
+    pub fn relaxed(mut self, relaxed: bool) -> Self {
+        self.relaxed = relaxed;
+        self
+    }
+
+    /// replace the listing, e.g. with the response of `query_symbols` or a `WarmCache`
+    pub fn load(&self, symbols: Vec<Symbol>) {
+        let mut listing = self.listing.write().unwrap_or_else(|e| e.into_inner());
+        listing.symbols = symbols.into_iter().map(|s| (s.pair(), s)).collect();
+        listing.loaded_at = Some(Instant::now());
+    }
+
+    /// true until the first load and once the listing is older than `max_age`
+    pub fn is_stale(&self) -> bool {
+        let listing = self.listing.read().unwrap_or_else(|e| e.into_inner());
+        listing
+            .loaded_at
+            .is_none_or(|at| at.elapsed() >= self.max_age)
+    }
+
+    /// the metadata of the `BASE-QUOTE` pair
+    pub fn get(&self, pair: &str) -> Option<Symbol> {
+        let listing = self.listing.read().unwrap_or_else(|e| e.into_inner());
+        listing.symbols.get(pair).cloned()
+    }
+
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let listing = self.listing.read().unwrap_or_else(|e| e.into_inner());
+        listing.symbols.values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.listing
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .symbols
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// refuse `order` locally when the exchange would: unknown symbol, market orders where
+    /// they are disabled, an amount below `min_amount` or a value below `min_vol`
+    pub fn check_order(&self, order: &NewOrder) -> Result<(), crate::Error> {
+        let symbol = match self.get(&order.symbol) {
+            Some(symbol) => symbol,
+            None if self.relaxed => return Ok(()),
+            None => {
+                return Err(crate::Error::InvalidRequest(format!(
+                    "unknown symbol {}",
+                    order.symbol
+                )))
+            }
+        };
+        if order.price.is_none() && !symbol.enable_marker_order {
+            return Err(crate::Error::InvalidRequest(format!(
+                "market orders are disabled on {}",
+                order.symbol
+            )));
+        }
+        if order.amount < symbol.min_amount {
+            return Err(crate::Error::InvalidRequest(format!(
+                "amount {} below the minimum {} of {}",
+                order.amount, symbol.min_amount, order.symbol
+            )));
+        }
+        if let Some(price) = &order.price {
+            let volume = price * &order.amount;
+            if volume < symbol.min_vol {
+                return Err(crate::Error::InvalidRequest(format!(
+                    "volume {} below the minimum {} of {}",
+                    volume, symbol.min_vol, order.symbol
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{OrderKind, Side};
+
+    fn btc_usdt() -> Symbol {
+        serde_json::from_str(
+            r#"{"base":1,"quote":0,"base_name":"BTC","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0.001","make_fee":"0.001","min_amount":"0.0001",
+            "min_vol":"10","enable_marker_order":false}"#,
+        )
+        .unwrap()
+    }
+
+    fn order(price: Option<&str>, amount: &str) -> NewOrder {
+        NewOrder::new(
+            Side::Bid,
+            if price.is_some() {
+                OrderKind::Limit
+            } else {
+                OrderKind::Market
+            },
+            String::from("BTC-USDT"),
+            price.map(|p| p.parse().unwrap()),
+            amount.parse().unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = SymbolRegistry::new(Duration::from_secs(60));
+        assert!(registry.is_stale());
+        registry.clone().load(vec![btc_usdt()]);
+        assert!(!registry.is_stale());
+        assert_eq!(registry.get("BTC-USDT").unwrap().base_scale, 4);
+
+        assert!(registry.check_order(&order(Some("100"), "0.1")).is_ok());
+        assert!(registry.check_order(&order(Some("100"), "0.01")).is_err());
+        assert!(registry
+            .check_order(&order(Some("1000000"), "0.00001"))
+            .is_err());
+        assert!(registry.check_order(&order(None, "1")).is_err());
+
+        let mut unknown = order(Some("100"), "1");
+        unknown.symbol = String::from("ETH-USDT");
+        assert!(registry.check_order(&unknown).is_err());
+        assert!(registry.clone().relaxed(true).check_order(&unknown).is_ok());
+        assert_eq!(registry.len(), 1);
+    }
+}
@@ -0,0 +1,362 @@
+//! golden signature vectors, for implementations of the signing contract in other languages
+//!
+//! the vectors are kept in `fixtures/signatures.json`: per request its method, uri, formalized
+//! parameters, body, the signature payload and the hex HMAC-SHA1 signature with the given secret
+//! and timestamp. an implementation checks itself by reproducing every entry of the file, or
+//! from rust with `verify`
+//!
+//! a change to the signing of this crate fails `test_vectors_match_golden_file`; when the change
+//! is intended, `FXDX_BLESS=1 cargo test test_vectors_match_golden_file` rewrites the file
+//!
+//! built for the tests of this crate and, outside of them, with `--features testing`
+
+use crate::encoding::signature_payload;
+use crate::request::{
+    NewOrder, OrderFilter, OrderKind, OrderStatus, Page, PrivPub, Request, Scale, Side,
+};
+use crate::signing::sign_request;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+const GOLDEN: &str = include_str!("../fixtures/signatures.json");
+
+/// everything signed and sent for a request, as one entry of the golden file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signed {
+    pub method: String,
+    pub uri: String,
+    pub formalized: Option<String>,
+    pub body: Option<String>,
+    /// the string the signature is computed over
+    pub payload: String,
+    /// the hex `X-Signature` header
+    pub signature: String,
+}
+
+/// a request with the secret and timestamp it is signed with and what it must sign to
+#[derive(Debug, Clone)]
+pub struct Vector {
+    pub name: &'static str,
+    pub request: Request,
+    pub timestamp: String,
+    pub secret: String,
+    pub expected: Signed,
+}
+
+impl Vector {
+    /// sign the request the way this crate does, with the `/maker` prefix
+    pub fn sign(&self) -> anyhow::Result<Signed> {
+        sign(&self.request, &self.timestamp, &self.secret)
+    }
+}
+
+/// the first part of a signed request that differs from a vector
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("vector {name}: {field} is {actual:?}, expected {expected:?}")]
+pub struct Mismatch {
+    pub name: &'static str,
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// compare what another implementation made of `vector` with the golden entry, field by field
+/// in the order they are derived, so the first mismatch points at the step that went wrong
+pub fn verify(vector: &Vector, signed: &Signed) -> Result<(), Mismatch> {
+    let expected = &vector.expected;
+    let fields = [
+        ("method", Some(&expected.method), Some(&signed.method)),
+        ("uri", Some(&expected.uri), Some(&signed.uri)),
+        (
+            "formalized",
+            expected.formalized.as_ref(),
+            signed.formalized.as_ref(),
+        ),
+        ("body", expected.body.as_ref(), signed.body.as_ref()),
+        ("payload", Some(&expected.payload), Some(&signed.payload)),
+        (
+            "signature",
+            Some(&expected.signature),
+            Some(&signed.signature),
+        ),
+    ];
+    for (field, expected, actual) in fields {
+        if expected != actual {
+            return Err(Mismatch {
+                name: vector.name,
+                field,
+                expected: format!("{:?}", expected),
+                actual: format!("{:?}", actual),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn sign(request: &Request, timestamp: &str, secret: &str) -> anyhow::Result<Signed> {
+    let encoded = request.encode::<PrivPub>()?;
+    let signature = sign_request(
+        secret,
+        timestamp,
+        &encoded.uri,
+        encoded.formalized.as_deref(),
+    )?;
+    Ok(Signed {
+        method: encoded.method.as_str().to_string(),
+        payload: signature_payload(timestamp, &encoded.uri, encoded.formalized.as_deref()),
+        uri: encoded.uri,
+        formalized: encoded.formalized,
+        body: encoded.payload,
+        signature,
+    })
+}
+
+/// one entry of the golden file, `params` is the request as JSON for readers of the file
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    params: serde_json::Value,
+    timestamp: String,
+    secret: String,
+    #[serde(flatten)]
+    signed: Signed,
+}
+
+fn order(side: Side, kind: OrderKind, price: Option<&str>, amount: &str) -> NewOrder {
+    NewOrder::new(
+        side,
+        kind,
+        String::from("BTC-USDT"),
+        price.map(|price| price.parse().unwrap()),
+        amount.parse().unwrap(),
+    )
+    .unwrap()
+}
+
+/// the requests of the vectors with their timestamps and secrets, one per endpoint and the
+/// edge cases of the formalization: optional fields, filters, lists and long secrets
+fn cases() -> Vec<(&'static str, Request, &'static str, &'static str)> {
+    let symbol = || String::from("BTC-USDT");
+    let page = Page::new(2, 50).unwrap();
+    vec![
+        ("balances", Request::Balances, "1650000000", "key"),
+        ("account_info", Request::AccountInfo, "1650000001", "key"),
+        ("symbols", Request::Symbols, "1650000002", "key"),
+        (
+            "depth",
+            Request::Depth {
+                symbol: symbol(),
+                limit: None,
+            },
+            "1650000003",
+            "key",
+        ),
+        (
+            "depth_limit",
+            Request::Depth {
+                symbol: symbol(),
+                limit: Some(20),
+            },
+            "1650000004",
+            "key",
+        ),
+        (
+            "kline",
+            Request::Kline {
+                symbol: symbol(),
+                scale: Scale::Hour,
+            },
+            "1650000005",
+            "key",
+        ),
+        (
+            "trades",
+            Request::Trades {
+                symbol: symbol(),
+                limit: Some(100),
+            },
+            "1650000006",
+            "key",
+        ),
+        (
+            "limit_order",
+            Request::PendingOrder(order(Side::Bid, OrderKind::Limit, Some("100.5"), "2")),
+            "1650000007",
+            "0123456789abcdef",
+        ),
+        (
+            "market_order",
+            Request::PendingOrder(order(Side::Ask, OrderKind::Market, None, "0.001")),
+            "1650000008",
+            "0123456789abcdef",
+        ),
+        (
+            "batch_orders",
+            Request::BatchPendingOrders(vec![
+                order(Side::Bid, OrderKind::PostOnly, Some("99"), "1"),
+                order(Side::Ask, OrderKind::IOC, Some("101.25"), "1.5"),
+            ]),
+            "1650000009",
+            "0123456789abcdef",
+        ),
+        (
+            "cancel_order",
+            Request::CancelOrder {
+                symbol: symbol(),
+                order_id: String::from("1001"),
+            },
+            "1650000010",
+            "0123456789abcdef",
+        ),
+        (
+            "batch_cancel",
+            Request::BatchCancelOrders {
+                symbol: symbol(),
+                order_ids: vec![String::from("1001"), String::from("1002")],
+            },
+            "1650000011",
+            "0123456789abcdef",
+        ),
+        (
+            "order_by_id",
+            Request::OrderById {
+                symbol: symbol(),
+                order_id: String::from("1001"),
+            },
+            "1650000012",
+            "key",
+        ),
+        (
+            "orders_by_page",
+            Request::OrderByPage {
+                symbol: symbol(),
+                page,
+                pending: true,
+                filter: OrderFilter::default(),
+            },
+            "1650000013",
+            "key",
+        ),
+        (
+            "orders_filtered",
+            Request::OrderByPage {
+                symbol: symbol(),
+                page,
+                pending: false,
+                filter: OrderFilter {
+                    status: Some(OrderStatus::Dealed),
+                    side: Some(Side::Ask),
+                    start_time: Some(1649000000),
+                    end_time: Some(1650000000),
+                },
+            },
+            "1650000014",
+            "key",
+        ),
+        (
+            "my_trades",
+            Request::MyTrades {
+                symbol: symbol(),
+                page,
+            },
+            "1650000015",
+            "key",
+        ),
+        (
+            "withdraw",
+            Request::Withdraw {
+                asset: String::from("USDT"),
+                amount: BigDecimal::from(250),
+                address: String::from("0x00000000000000000000000000000000000000ab"),
+                memo: Some(String::from("rent")),
+            },
+            "1650000016",
+            "0123456789abcdef",
+        ),
+        (
+            "deposit_history",
+            Request::DepositHistory {
+                asset: String::from("USDT"),
+                page: Page::first(20).unwrap(),
+            },
+            "1650000017",
+            "key",
+        ),
+        (
+            "withdrawal_history",
+            Request::WithdrawalHistory {
+                asset: String::from("USDT"),
+                page: Page::first(20).unwrap(),
+            },
+            "1650000018",
+            "key",
+        ),
+        // longer than the 64 byte block of SHA-1, hashed before use as the key
+        (
+            "long_secret",
+            Request::Balances,
+            "1650000019",
+            "a secret longer than one block of sha-1, which is sixty four bytes long",
+        ),
+    ]
+}
+
+/// every vector of the golden file with its request, an error when the file does not parse
+pub fn vectors() -> serde_json::Result<Vec<Vector>> {
+    let entries: Vec<Entry> = serde_json::from_str(GOLDEN)?;
+    Ok(cases()
+        .into_iter()
+        .filter_map(|(name, request, _, _)| {
+            let entry = entries.iter().find(|entry| entry.name == name)?;
+            Some(Vector {
+                name,
+                request,
+                timestamp: entry.timestamp.clone(),
+                secret: entry.secret.clone(),
+                expected: entry.signed.clone(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vectors_match_golden_file() {
+        if std::env::var_os("FXDX_BLESS").is_some() {
+            let entries = cases()
+                .into_iter()
+                .map(|(name, request, timestamp, secret)| Entry {
+                    name: name.to_string(),
+                    params: serde_json::to_value(&request).unwrap(),
+                    timestamp: timestamp.to_string(),
+                    secret: secret.to_string(),
+                    signed: sign(&request, timestamp, secret).unwrap(),
+                })
+                .collect::<Vec<_>>();
+            let golden = serde_json::to_string_pretty(&entries).unwrap() + "\n";
+            std::fs::write(
+                concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/signatures.json"),
+                golden,
+            )
+            .unwrap();
+            return;
+        }
+
+        let vectors = vectors().unwrap();
+        assert_eq!(
+            vectors.len(),
+            cases().len(),
+            "a case is not in the golden file"
+        );
+        for vector in &vectors {
+            assert_eq!(verify(vector, &vector.sign().unwrap()), Ok(()));
+        }
+
+        let mut wrong = vectors[0].expected.clone();
+        wrong.formalized = Some(String::from("BTC-USDT"));
+        assert_eq!(verify(&vectors[0], &wrong).unwrap_err().field, "formalized");
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// log2 buckets from 64us up to about a minute, the last bucket takes everything above
+const BUCKETS: usize = 21;
+const FIRST_BOUND_MICROS: u64 = 64;
+
+/// latency histogram with exponential buckets, percentiles resolve to a bucket's upper bound
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    counts: [u64; BUCKETS],
+    count: u64,
+    sum_micros: u64,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl Histogram {
+    fn bound(bucket: usize) -> Duration {
+        Duration::from_micros(FIRST_BOUND_MICROS << bucket)
+    }
+
+    pub fn record(&mut self, sample: Duration) {
+        let micros = sample.as_micros().min(u128::from(u64::MAX)) as u64;
+        let bucket = (0..BUCKETS)
+            .find(|b| sample <= Self::bound(*b))
+            .unwrap_or(BUCKETS - 1);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum_micros = self.sum_micros.saturating_add(micros);
+        self.min = Some(self.min.map_or(sample, |m| m.min(sample)));
+        self.max = Some(self.max.map_or(sample, |m| m.max(sample)));
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| Duration::from_micros(self.sum_micros / self.count))
+    }
+
+    /// the latency `q` (0.0..=1.0) of the samples stay below, never above the largest sample
+    pub fn percentile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::bound(bucket).min(self.max.unwrap_or_default()));
+            }
+        }
+        self.max
+    }
+
+    /// add the samples of `other`
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum_micros = self.sum_micros.saturating_add(other.sum_micros);
+        self.min = match (self.min, other.min) {
+            (Some(min), Some(other)) => Some(min.min(other)),
+            (min, other) => min.or(other),
+        };
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+}
+
+/// running statistics of the signed difference between server and local clock
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClockOffset {
+    pub samples: u64,
+    /// milliseconds the server clock is ahead of the local one, negative when behind
+    pub last_ms: i64,
+    pub min_ms: i64,
+    pub max_ms: i64,
+    sum_ms: i64,
+}
+
+impl ClockOffset {
+    pub fn record(&mut self, offset_ms: i64) {
+        if self.samples == 0 {
+            self.min_ms = offset_ms;
+            self.max_ms = offset_ms;
+        }
+        self.samples += 1;
+        self.last_ms = offset_ms;
+        self.min_ms = self.min_ms.min(offset_ms);
+        self.max_ms = self.max_ms.max(offset_ms);
+        self.sum_ms = self.sum_ms.saturating_add(offset_ms);
+    }
+
+    pub fn mean_ms(&self) -> Option<i64> {
+        (self.samples > 0).then(|| self.sum_ms / self.samples as i64)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimingReport {
+    pub rtt: Histogram,
+    /// round trips of `FxdxClient::ping` alone, always the same query so they compare over time
+    pub ping: Histogram,
+    /// estimated from the `Date` header, which only has second resolution
+    pub clock_offset: ClockOffset,
+}
+
+impl std::fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |d: Option<Duration>| d.map_or(0.0, |d| d.as_secs_f64() * 1000.0);
+        write!(
+            f,
+            "requests={} rtt_p50={:.1}ms rtt_p95={:.1}ms rtt_p99={:.1}ms rtt_max={:.1}ms ping_p50={:.1}ms ping_p99={:.1}ms clock_offset={}ms",
+            self.rtt.count(),
+            ms(self.rtt.percentile(0.5)),
+            ms(self.rtt.percentile(0.95)),
+            ms(self.rtt.percentile(0.99)),
+            ms(self.rtt.max()),
+            ms(self.ping.percentile(0.5)),
+            ms(self.ping.percentile(0.99)),
+            self.clock_offset.last_ms,
+        )
+    }
+}
+
+/// how long one window of `EndpointStats` lasts, the statistics cover the current window and
+/// the one before, so they follow a degrading endpoint within minutes
+pub const STATS_WINDOW: Duration = Duration::from_secs(300);
+
+/// latency and errors of one endpoint over the last one to two `STATS_WINDOW`s, see
+/// `FxdxClient::stats`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EndpointStats {
+    pub rtt: Histogram,
+    pub requests: u64,
+    /// requests without a response or answered with a 5xx status
+    pub errors: u64,
+    /// the last error ever seen, it stays after its window rolled over
+    pub last_error: Option<String>,
+    pub last_error_at: Option<SystemTime>,
+}
+
+impl EndpointStats {
+    pub fn p50(&self) -> Option<Duration> {
+        self.rtt.percentile(0.5)
+    }
+
+    pub fn p95(&self) -> Option<Duration> {
+        self.rtt.percentile(0.95)
+    }
+
+    pub fn p99(&self) -> Option<Duration> {
+        self.rtt.percentile(0.99)
+    }
+
+    /// the share of requests which failed, 0.0 without requests
+    pub fn error_rate(&self) -> f64 {
+        match self.requests {
+            0 => 0.0,
+            requests => self.errors as f64 / requests as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Window {
+    rtt: Histogram,
+    requests: u64,
+    errors: u64,
+}
+
+#[derive(Debug)]
+struct RollingStats {
+    started: Instant,
+    current: Window,
+    previous: Window,
+    last_error: Option<(String, SystemTime)>,
+}
+
+impl RollingStats {
+    fn new(now: Instant) -> Self {
+        RollingStats {
+            started: now,
+            current: Default::default(),
+            previous: Default::default(),
+            last_error: None,
+        }
+    }
+
+    fn roll(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.started);
+        if elapsed < STATS_WINDOW {
+            return;
+        }
+        // a quiet endpoint may skip whole windows, their samples are all outdated
+        self.previous = if elapsed < STATS_WINDOW * 2 {
+            std::mem::take(&mut self.current)
+        } else {
+            self.current = Default::default();
+            Default::default()
+        };
+        self.started = now;
+    }
+
+    fn stats(&self) -> EndpointStats {
+        let mut rtt = self.previous.rtt.clone();
+        rtt.merge(&self.current.rtt);
+        EndpointStats {
+            rtt,
+            requests: self.previous.requests + self.current.requests,
+            errors: self.previous.errors + self.current.errors,
+            last_error: self.last_error.as_ref().map(|(e, _)| e.clone()),
+            last_error_at: self.last_error.as_ref().map(|(_, at)| *at),
+        }
+    }
+}
+
+/// samples of one client session
+#[derive(Debug, Default)]
+pub struct SessionTiming {
+    report: Mutex<TimingReport>,
+    endpoints: Mutex<HashMap<String, RollingStats>>,
+}
+
+impl SessionTiming {
+    /// record a request sent at `sent` which took `rtt` and whose response carried `server_date`
+    pub fn record(&self, sent: SystemTime, rtt: Duration, server_date: Option<SystemTime>) {
+        let mut report = self.report.lock().unwrap_or_else(|e| e.into_inner());
+        report.rtt.record(rtt);
+        if let Some(server) = server_date {
+            // the server stamped the response about halfway through the round trip
+            let local = sent + rtt / 2;
+            let offset = match server.duration_since(local) {
+                Ok(ahead) => ahead.as_millis() as i64,
+                Err(behind) => -(behind.duration().as_millis() as i64),
+            };
+            report.clock_offset.record(offset);
+        }
+    }
+
+    pub fn record_ping(&self, rtt: Duration) {
+        self.report
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .ping
+            .record(rtt);
+    }
+
+    /// record a request to `endpoint` at `now`, `rtt` if a response arrived and `error`
+    /// if it failed
+    pub fn record_endpoint(
+        &self,
+        endpoint: &str,
+        now: Instant,
+        rtt: Option<Duration>,
+        error: Option<String>,
+    ) {
+        let mut endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = endpoints
+            .entry(endpoint.to_string())
+            .or_insert_with(|| RollingStats::new(now));
+        stats.roll(now);
+        stats.current.requests += 1;
+        if let Some(rtt) = rtt {
+            stats.current.rtt.record(rtt);
+        }
+        if let Some(error) = error {
+            stats.current.errors += 1;
+            stats.last_error = Some((error, SystemTime::now()));
+        }
+    }
+
+    /// the statistics of every endpoint a request went to, as of `now`
+    pub fn endpoint_stats(&self, now: Instant) -> HashMap<String, EndpointStats> {
+        let mut endpoints = self.endpoints.lock().unwrap_or_else(|e| e.into_inner());
+        endpoints
+            .iter_mut()
+            .map(|(endpoint, stats)| {
+                stats.roll(now);
+                (endpoint.clone(), stats.stats())
+            })
+            .collect()
+    }
+
+    pub fn report(&self) -> TimingReport {
+        self.report
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn reset(&self) {
+        *self.report.lock().unwrap_or_else(|e| e.into_inner()) = Default::default();
+        self.endpoints
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.min(), Some(Duration::from_millis(1)));
+        assert_eq!(histogram.max(), Some(Duration::from_millis(100)));
+        let p50 = histogram.percentile(0.5).unwrap();
+        assert!(p50 >= Duration::from_millis(50) && p50 <= Duration::from_millis(66));
+        assert_eq!(histogram.percentile(1.0), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_clock_offset() {
+        let timing = SessionTiming::default();
+        let sent = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        timing.record(
+            sent,
+            Duration::from_millis(200),
+            Some(sent + Duration::from_millis(1100)),
+        );
+        timing.record(sent, Duration::from_millis(200), Some(sent));
+        let report = timing.report();
+        assert_eq!(report.clock_offset.max_ms, 1000);
+        assert_eq!(report.clock_offset.last_ms, -100);
+        assert_eq!(report.rtt.count(), 2);
+    }
+
+    #[test]
+    fn test_endpoint_stats_roll() {
+        let timing = SessionTiming::default();
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        timing.record_endpoint("https://a", start, Some(ms(10)), None);
+        timing.record_endpoint("https://a", start, None, Some(String::from("refused")));
+        timing.record_endpoint("https://a", start + STATS_WINDOW, Some(ms(30)), None);
+        timing.record_endpoint("https://b", start, Some(ms(5)), None);
+
+        let stats = timing.endpoint_stats(start + STATS_WINDOW);
+        let a = &stats["https://a"];
+        assert_eq!((a.requests, a.errors), (3, 1));
+        assert_eq!(a.rtt.count(), 2);
+        assert_eq!(a.p99(), Some(ms(30)));
+        assert_eq!(a.last_error.as_deref(), Some("refused"));
+        assert_eq!(stats["https://b"].p50(), Some(ms(5)));
+
+        // the window with the error is gone, the error itself is remembered
+        let a = &timing.endpoint_stats(start + STATS_WINDOW * 2)["https://a"];
+        assert_eq!((a.requests, a.errors, a.rtt.count()), (1, 0, 1));
+        assert!(a.last_error.is_some());
+        let a = &timing.endpoint_stats(start + STATS_WINDOW * 5)["https://a"];
+        assert_eq!((a.requests, a.error_rate()), (0, 0.0));
+    }
+}
@@ -0,0 +1,156 @@
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// how long before its expiry a stored token is no longer reused
+pub const EXPIRY_MARGIN: i64 = 60;
+
+/// the session token of a handshake, its `Debug` output is redacted
+#[derive(Debug, Clone)]
+pub struct StoredToken {
+    /// the address the token was issued to, a store may hold the token of another key
+    pub address: String,
+    pub token: SecretString,
+    /// unix seconds, `None` when the exchange did not say
+    pub expires_at: Option<i64>,
+}
+
+impl StoredToken {
+    /// true if the token is for `address` and does not expire within `EXPIRY_MARGIN` of `now`
+    pub fn is_usable(&self, address: &str, now: i64) -> bool {
+        self.address == address
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at - EXPIRY_MARGIN > now)
+    }
+}
+
+/// where the session token outlives the process, see `FxdxBuilder::token_store`;
+/// implement it over a keyring or a secrets manager to keep the token off the disk
+pub trait TokenStore: Send + Sync {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>>;
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()>;
+
+    fn clear(&self) -> anyhow::Result<()>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenFile {
+    address: String,
+    token: String,
+    expires_at: Option<i64>,
+}
+
+/// the token as JSON in a file only the owner may read
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new<T: Into<PathBuf>>(path: T) -> Self {
+        FileTokenStore { path: path.into() }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>> {
+        let json = match std::fs::read(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let file: TokenFile = serde_json::from_slice(&json)?;
+        Ok(Some(StoredToken {
+            address: file.address,
+            token: file.token.into(),
+            expires_at: file.expires_at,
+        }))
+    }
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(&TokenFile {
+            address: token.address.clone(),
+            token: token.token.expose_secret().to_string(),
+            expires_at: token.expires_at,
+        })?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        std::io::Write::write_all(&mut options.open(&self.path)?, &json)?;
+        Ok(())
+    }
+
+    fn clear(&self) -> anyhow::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// keeps the token for the life of the process only, e.g. to share it between clients
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    token: Mutex<Option<StoredToken>>,
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>> {
+        Ok(self.token.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()> {
+        *self.token.lock().unwrap_or_else(|e| e.into_inner()) = Some(token.clone());
+        Ok(())
+    }
+
+    fn clear(&self) -> anyhow::Result<()> {
+        *self.token.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+}
+
+impl<T: TokenStore + ?Sized> TokenStore for std::sync::Arc<T> {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>> {
+        (**self).load()
+    }
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()> {
+        (**self).save(token)
+    }
+
+    fn clear(&self) -> anyhow::Result<()> {
+        (**self).clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_store() {
+        let path = std::env::temp_dir().join(format!("fxdx-token-{}.json", std::process::id()));
+        let store = FileTokenStore::new(&path);
+        assert!(store.load().unwrap().is_none());
+        store
+            .save(&StoredToken {
+                address: String::from("0xabc"),
+                token: SecretString::from("t-1"),
+                expires_at: Some(1000),
+            })
+            .unwrap();
+        let token = store.load().unwrap().unwrap();
+        assert_eq!(token.token.expose_secret(), "t-1");
+        assert!(token.is_usable("0xabc", 900));
+        assert!(!token.is_usable("0xabc", 950));
+        assert!(!token.is_usable("0xdef", 900));
+        assert!(!format!("{:?}", token).contains("t-1"));
+        store.clear().unwrap();
+        assert!(store.load().unwrap().is_none());
+    }
+}
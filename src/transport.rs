@@ -0,0 +1,226 @@
+use crate::encoding::{Method, SignedRequest};
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+
+/// what came back from the endpoint, whatever the status
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: bytes::Bytes,
+}
+
+impl RawResponse {
+    /// the first header called `name`, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<RawResponse>> + Send + 'a>>;
+
+/// carries signed requests to the exchange, `ReqwestTransport` unless `FxdxBuilder::transport`
+/// sets another one, e.g. a unix socket proxy or a test double
+///
+/// the client signs, rate limits, retries and journals around it, a transport only sends
+pub trait Transport: Send + Sync {
+    /// send `request` to `endpoint` followed by its uri, an `Err` means no response arrived
+    fn execute<'a>(&'a self, endpoint: &'a str, request: SignedRequest) -> TransportFuture<'a>;
+
+    /// true if `error` from `execute` means the request never reached the endpoint, so sending
+    /// it again can not duplicate it
+    fn is_unreachable(&self, error: &anyhow::Error) -> bool {
+        let _ = error;
+        false
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn execute<'a>(&'a self, endpoint: &'a str, request: SignedRequest) -> TransportFuture<'a> {
+        (**self).execute(endpoint, request)
+    }
+
+    fn is_unreachable(&self, error: &anyhow::Error) -> bool {
+        (**self).is_unreachable(error)
+    }
+}
+
+/// the default transport over a `reqwest::Client` built from `connection::ConnectionOptions`
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+    /// gzip bodies of at least this many bytes
+    compress_above: Option<usize>,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        ReqwestTransport {
+            client,
+            compress_above: None,
+        }
+    }
+
+    /// a transport over a client built from `options`, compressing requests as they ask
+    pub fn from_options(options: &crate::connection::ConnectionOptions) -> reqwest::Result<Self> {
+        #[cfg_attr(not(feature = "compression"), allow(unused_mut))]
+        let mut transport = ReqwestTransport::new(options.build()?);
+        #[cfg(feature = "compression")]
+        {
+            transport.compress_above = options.compression.request_above;
+        }
+        Ok(transport)
+    }
+}
+
+/// the gzip of `body`, sent with `Content-Encoding: gzip`; the signature covers the
+/// formalized fields, the gateway checks it after decoding
+#[cfg(feature = "compression")]
+fn gzip(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    use std::io::Write;
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::fast());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
+impl Transport for ReqwestTransport {
+    fn execute<'a>(&'a self, endpoint: &'a str, request: SignedRequest) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let method = reqwest::Method::from_bytes(request.method.as_str().as_bytes())?;
+            let mut builder = self
+                .client
+                .request(method, format!("{}{}", endpoint, request.uri));
+            for (name, value) in request.headers {
+                builder = builder.header(name, value);
+            }
+            match (request.body, self.compress_above) {
+                #[cfg(feature = "compression")]
+                (Some(body), Some(above)) if body.len() >= above => {
+                    builder = builder
+                        .header(reqwest::header::CONTENT_ENCODING, "gzip")
+                        .body(gzip(body.as_bytes())?);
+                }
+                (Some(body), _) => builder = builder.body(body),
+                (None, _) => {}
+            }
+            let resp = builder.send().await?;
+            let status = resp.status().as_u16();
+            let headers = resp
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect();
+            Ok(RawResponse {
+                status,
+                headers,
+                body: resp.bytes().await?,
+            })
+        })
+    }
+
+    fn is_unreachable(&self, error: &anyhow::Error) -> bool {
+        error
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_connect)
+    }
+}
+
+/// the method of a request as the transports see it
+pub(crate) fn method(method: &reqwest::Method) -> Result<Method> {
+    Ok(match *method {
+        reqwest::Method::GET => Method::Get,
+        reqwest::Method::POST => Method::Post,
+        reqwest::Method::PUT => Method::Put,
+        reqwest::Method::PATCH => Method::Patch,
+        reqwest::Method::DELETE => Method::Delete,
+        _ => {
+            return Err(
+                crate::Error::InvalidRequest(format!("unsupported method {}", method)).into(),
+            )
+        }
+    })
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+    use crate::connection::{Compression, ConnectionOptions};
+    use std::io::Read;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_compressed_bodies() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut tcp, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buf = [0u8; 4096];
+            // the head and the whole gzipped body arrive before the reply is sent
+            loop {
+                let n = tcp.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    continue;
+                };
+                let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |length| length.trim().parse().unwrap());
+                if n == 0 || request.len() >= end + 4 + length {
+                    break;
+                }
+            }
+            let body = gzip(br#"{"code":200,"data":[]}"#).unwrap();
+            let mut reply = format!(
+                "HTTP/1.1 200 OK\r\ncontent-encoding: gzip\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            )
+            .into_bytes();
+            reply.extend_from_slice(&body);
+            tcp.write_all(&reply).await.unwrap();
+            request
+        });
+
+        let transport = ReqwestTransport::from_options(&ConnectionOptions {
+            compression: Compression {
+                request_above: Some(64),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        let payload = format!("[{}]", [r#"{"amount":"1"}"#; 10].join(","));
+        let resp = transport
+            .execute(
+                &url,
+                SignedRequest {
+                    method: Method::Post,
+                    uri: String::from("/orders"),
+                    headers: vec![],
+                    body: Some(payload.clone()),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(&resp.body[..], br#"{"code":200,"data":[]}"#);
+
+        let request = server.await.unwrap();
+        let end = request.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+        assert!(head.contains("content-encoding: gzip"));
+        assert!(head.contains("accept-encoding: gzip"));
+        let mut sent = String::new();
+        flate2::read::GzDecoder::new(&request[end + 4..])
+            .read_to_string(&mut sent)
+            .unwrap();
+        assert_eq!(sent, payload);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::orderbook::OrderBook;
+use crate::response::{PriceLevel, Symbol};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedBook {
+    pub symbol: String,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// books and symbol metadata persisted on shutdown and loaded on startup
+///
+/// restored books are stale until the first snapshot lands, which lets a restarted
+/// process resolve symbols and show a book before the exchange answered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WarmCache {
+    /// unix seconds of the capture
+    pub saved_at: i64,
+    pub symbols: Vec<Symbol>,
+    pub books: Vec<CachedBook>,
+}
+
+impl WarmCache {
+    pub fn capture<'a, I>(saved_at: i64, symbols: &[Symbol], books: I) -> Self
+    where
+        I: IntoIterator<Item = &'a OrderBook>,
+    {
+        WarmCache {
+            saved_at,
+            symbols: symbols.to_vec(),
+            books: books
+                .into_iter()
+                .filter(|b| !b.bids.is_empty() || !b.asks.is_empty())
+                .map(|b| CachedBook {
+                    symbol: b.symbol.clone(),
+                    bids: b.bids.clone(),
+                    asks: b.asks.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// write through a temporary file so a crash mid-write never leaves a truncated cache
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// `None` when nothing was persisted yet
+    pub fn load<T: AsRef<Path>>(path: T) -> anyhow::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn symbol(&self, pair: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.pair() == pair)
+    }
+
+    /// the persisted books, all marked stale
+    pub fn books(&self) -> Vec<OrderBook> {
+        self.books
+            .iter()
+            .map(|cached| {
+                let mut book = OrderBook::new(cached.symbol.clone());
+                book.bids = cached.bids.clone();
+                book.asks = cached.asks.clone();
+                book
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::BookValidator;
+    use crate::response::Depth;
+    use bigdecimal::BigDecimal;
+
+    #[test]
+    fn test_round_trip() {
+        let mut book = OrderBook::new(String::from("BTC-USDT"));
+        book.apply_snapshot(
+            1,
+            Depth {
+                depth: 0,
+                seq: None,
+                checksum: None,
+                bids: vec![PriceLevel::new(BigDecimal::from(10), BigDecimal::from(1))],
+                asks: vec![PriceLevel::new(BigDecimal::from(11), BigDecimal::from(1))],
+            },
+            &BookValidator::new(),
+        )
+        .unwrap();
+        let symbol = serde_json::from_str::<Symbol>(
+            r#"{"base":1,"quote":0,"base_name":"BTC","quote_name":"USDT","base_scale":4,
+            "quote_scale":2,"taker_fee":"0.001","make_fee":"0.001","min_amount":"0.0001",
+            "min_vol":"1","enable_marker_order":true}"#,
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!("fxdx-warm-{}.json", std::process::id()));
+        WarmCache::capture(7, &[symbol], [&book])
+            .save(&path)
+            .unwrap();
+        let cache = WarmCache::load(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cache.saved_at, 7);
+        assert_eq!(cache.symbol("BTC-USDT").unwrap().base_scale, 4);
+        let books = cache.books();
+        assert!(books[0].stale);
+        assert_eq!(books[0].best_ask(), Some(&BigDecimal::from(11)));
+        assert!(WarmCache::load(&path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,182 @@
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    /// nothing arrived within `HeartbeatConfig::stale_after`, a reconnect follows
+    Stale,
+    Disconnected,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// how often a ping is sent to keep the connection and the proxies in between alive
+    pub ping_interval: Duration,
+    /// reconnect when no frame at all arrived for this long, pongs included
+    pub stale_after: Duration,
+    pub reconnect_delay: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            ping_interval: Duration::from_secs(15),
+            stale_after: Duration::from_secs(45),
+            reconnect_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// a websocket connection kept alive by pings, answering the server's pings and reconnecting
+/// when it went silent; state changes are published on a `watch` channel
+///
+/// subscriptions are sent again after every reconnect, so the streams resume on their own
+pub struct WsClient {
+    url: String,
+    heartbeat: HeartbeatConfig,
+    state: watch::Sender<ConnectionState>,
+    subscriptions: std::sync::Mutex<Vec<String>>,
+}
+
+impl WsClient {
+    pub fn new(url: String, heartbeat: HeartbeatConfig) -> Self {
+        WsClient {
+            url,
+            heartbeat,
+            state: watch::Sender::new(ConnectionState::Disconnected),
+            subscriptions: Default::default(),
+        }
+    }
+
+    /// send `message` on every connect from the next one on
+    pub fn subscribe(&self, message: String) {
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(message);
+    }
+
+    pub fn state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        self.state.send_if_modified(|current| {
+            let changed = *current != state;
+            *current = state;
+            changed
+        });
+    }
+
+    /// connect and hand every text frame to `on_message`, reconnecting forever
+    pub async fn run<F>(&self, mut on_message: F)
+    where
+        F: FnMut(&str),
+    {
+        loop {
+            if let Err(e) = self.session(&mut on_message).await {
+                log::warn!("fxdx websocket {} failed: {}", self.url, e);
+            }
+            if *self.state.borrow() != ConnectionState::Stale {
+                self.set_state(ConnectionState::Disconnected);
+            }
+            tokio::time::sleep(self.heartbeat.reconnect_delay).await;
+        }
+    }
+
+    /// one connection until it is closed, fails or goes stale
+    async fn session<F>(&self, on_message: &mut F) -> anyhow::Result<()>
+    where
+        F: FnMut(&str),
+    {
+        self.set_state(ConnectionState::Connecting);
+        let (stream, _) = tokio_tungstenite::connect_async(self.url.as_str()).await?;
+        self.set_state(ConnectionState::Connected);
+        let (mut write, mut read) = stream.split();
+        let subscriptions = self
+            .subscriptions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for subscription in subscriptions {
+            write.send(Message::Text(subscription.into())).await?;
+        }
+        let mut ping = tokio::time::interval(self.heartbeat.ping_interval);
+        ping.tick().await;
+        let mut last_seen = Instant::now();
+        loop {
+            tokio::select! {
+                frame = read.next() => {
+                    let frame = match frame {
+                        Some(frame) => frame?,
+                        None => return Ok(()),
+                    };
+                    last_seen = Instant::now();
+                    match frame {
+                        Message::Ping(data) => write.send(Message::Pong(data)).await?,
+                        Message::Text(text) => on_message(text.as_str()),
+                        Message::Close(_) => return Ok(()),
+                        _ => {}
+                    }
+                }
+                _ = ping.tick() => write.send(Message::Ping(Default::default())).await?,
+                _ = tokio::time::sleep_until(last_seen + self.heartbeat.stale_after) => {
+                    self.set_state(ConnectionState::Stale);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_silent_server_goes_stale() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = async {
+            let (tcp, _) = listener.accept().await.unwrap();
+            // accept and then never send anything, pings are not answered either
+            let _ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        };
+        let client = WsClient::new(
+            url,
+            HeartbeatConfig {
+                ping_interval: Duration::from_secs(5),
+                stale_after: Duration::from_millis(100),
+                reconnect_delay: Duration::from_secs(5),
+            },
+        );
+        let mut state = client.state();
+        let watcher = async {
+            let mut seen = vec![];
+            while seen.last() != Some(&ConnectionState::Stale) {
+                state.changed().await.unwrap();
+                seen.push(*state.borrow_and_update());
+            }
+            seen
+        };
+        let seen = tokio::select! {
+            seen = watcher => seen,
+            _ = client.run(|_| {}) => unreachable!(),
+            _ = server => panic!("the client never went stale"),
+        };
+        assert_eq!(
+            seen,
+            vec![
+                ConnectionState::Connecting,
+                ConnectionState::Connected,
+                ConnectionState::Stale
+            ]
+        );
+    }
+}
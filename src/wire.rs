@@ -0,0 +1,234 @@
+use crate::request::{Decimal, NewOrder, Request};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// a field of a JSON request body, named by the wire profile in use
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WireField {
+    OrderType,
+    OrderKind,
+    OrderSymbol,
+    OrderPrice,
+    OrderAmount,
+    TokenNonce,
+    TokenPubkey,
+    TokenSignature,
+}
+
+impl WireField {
+    /// the name the current API version expects
+    pub fn default_name(&self) -> &'static str {
+        match self {
+            WireField::OrderType => "type",
+            WireField::OrderKind => "kind",
+            WireField::OrderSymbol => "symbol",
+            WireField::OrderPrice => "price",
+            WireField::OrderAmount => "amount",
+            WireField::TokenNonce => "nonce",
+            WireField::TokenPubkey => "pubkey",
+            WireField::TokenSignature => "signature",
+        }
+    }
+}
+
+/// the field names of every JSON body the client sends, so an API version with different
+/// names only needs a profile instead of touching the request types
+///
+/// fields without an override keep their `WireField::default_name`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WireProfile {
+    names: BTreeMap<WireField, String>,
+}
+
+impl WireProfile {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn rename(mut self, field: WireField, name: &str) -> Self {
+        self.names.insert(field, name.to_string());
+        self
+    }
+
+    pub fn name(&self, field: WireField) -> &str {
+        self.names
+            .get(&field)
+            .map(String::as_str)
+            .unwrap_or_else(|| field.default_name())
+    }
+
+    /// the JSON body of `req`, `None` for requests which have none; bodies without
+    /// profiled fields are written by `Request::payload`
+    pub fn payload(&self, req: &Request) -> anyhow::Result<Option<String>> {
+        Ok(match req {
+            Request::Token {
+                nonce,
+                pubkey,
+                signature,
+            } => {
+                let mut body = Map::new();
+                body.insert(
+                    self.name(WireField::TokenNonce).into(),
+                    nonce.clone().into(),
+                );
+                body.insert(
+                    self.name(WireField::TokenPubkey).into(),
+                    pubkey.clone().into(),
+                );
+                body.insert(
+                    self.name(WireField::TokenSignature).into(),
+                    signature.clone().into(),
+                );
+                Some(Value::Object(body).to_string())
+            }
+            Request::PendingOrder(order) => Some(serde_json::to_string(&self.order(order))?),
+            Request::BatchPendingOrders(orders) => Some(serde_json::to_string(
+                &orders
+                    .iter()
+                    .map(|order| self.order(order))
+                    .collect::<Vec<_>>(),
+            )?),
+            _ => req.payload()?,
+        })
+    }
+
+    pub fn encode_order(&self, order: &NewOrder) -> anyhow::Result<Value> {
+        Ok(serde_json::to_value(self.order(order))?)
+    }
+
+    fn order<'a>(&'a self, order: &'a NewOrder) -> WireOrder<'a> {
+        WireOrder {
+            profile: self,
+            order,
+        }
+    }
+
+    /// read an order body written with this profile, unknown fields are rejected
+    pub fn decode_order(&self, body: &Value) -> anyhow::Result<NewOrder> {
+        let body = body
+            .as_object()
+            .ok_or_else(|| invalid("order body is no object"))?;
+        let fields = [
+            WireField::OrderType,
+            WireField::OrderKind,
+            WireField::OrderSymbol,
+            WireField::OrderPrice,
+            WireField::OrderAmount,
+        ];
+        if let Some(unknown) = body
+            .keys()
+            .find(|key| !fields.iter().any(|f| self.name(*f) == key.as_str()))
+        {
+            return Err(invalid(&format!("unknown order field {}", unknown)).into());
+        }
+        let string = |field: WireField| {
+            body.get(self.name(field))
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid(&format!("missing order field {}", self.name(field))))
+        };
+        let order = NewOrder {
+            r#type: string(WireField::OrderType)?.to_string(),
+            kind: string(WireField::OrderKind)?.parse()?,
+            symbol: string(WireField::OrderSymbol)?.to_string(),
+            price: match body.get(self.name(WireField::OrderPrice)) {
+                Some(price) => Some(serde_json::from_value(price.clone())?),
+                None => None,
+            },
+            amount: string(WireField::OrderAmount)?.parse()?,
+        };
+        order.validate()?;
+        Ok(order)
+    }
+}
+
+/// an order body with the names of a profile, serialized straight from the order since building
+/// a `Value` first costs more than the rest of encoding and signing the order
+struct WireOrder<'a> {
+    profile: &'a WireProfile,
+    order: &'a NewOrder,
+}
+
+impl Serialize for WireOrder<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (name, order) = (|field| self.profile.name(field), self.order);
+        let mut body = serializer.serialize_map(Some(4 + usize::from(order.price.is_some())))?;
+        body.serialize_entry(name(WireField::OrderType), &order.r#type)?;
+        body.serialize_entry(name(WireField::OrderKind), &order.kind)?;
+        body.serialize_entry(name(WireField::OrderSymbol), &order.symbol)?;
+        if let Some(price) = &order.price {
+            body.serialize_entry(name(WireField::OrderPrice), &Decimal(price))?;
+        }
+        body.serialize_entry(name(WireField::OrderAmount), &Decimal(&order.amount))?;
+        body.end()
+    }
+}
+
+fn invalid(reason: &str) -> crate::Error {
+    crate::Error::InvalidRequest(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{OrderKind, Side};
+    use bigdecimal::BigDecimal;
+
+    fn camel_case() -> WireProfile {
+        WireProfile::new()
+            .rename(WireField::OrderType, "orderType")
+            .rename(WireField::OrderKind, "orderKind")
+            .rename(WireField::OrderSymbol, "market")
+            .rename(WireField::OrderAmount, "quantity")
+    }
+
+    fn order() -> NewOrder {
+        NewOrder::new(
+            Side::Bid,
+            OrderKind::PostOnly,
+            String::from("BTC-USDT"),
+            Some("100.5".parse().unwrap()),
+            BigDecimal::from(2),
+        )
+        .unwrap()
+    }
+
+    fn round_trip(profile: &WireProfile, fixture: &str) {
+        let fixture: Value = serde_json::from_str(fixture).unwrap();
+        let payload = profile
+            .payload(&Request::PendingOrder(order()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&payload).unwrap(), fixture);
+        assert_eq!(profile.decode_order(&fixture).unwrap(), order());
+    }
+
+    #[test]
+    fn test_order_fixtures_round_trip() {
+        round_trip(
+            &WireProfile::new(),
+            include_str!("../fixtures/wire/order_default.json"),
+        );
+        round_trip(
+            &camel_case(),
+            include_str!("../fixtures/wire/order_camel_case.json"),
+        );
+    }
+
+    #[test]
+    fn test_default_profile_matches_serde() {
+        let req = Request::BatchPendingOrders(vec![order()]);
+        assert_eq!(
+            serde_json::from_str::<Value>(&WireProfile::new().payload(&req).unwrap().unwrap())
+                .unwrap(),
+            serde_json::from_str::<Value>(&req.payload().unwrap().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_other_profile() {
+        let fixture: Value =
+            serde_json::from_str(include_str!("../fixtures/wire/order_camel_case.json")).unwrap();
+        assert!(WireProfile::new().decode_order(&fixture).is_err());
+    }
+}